@@ -0,0 +1,28 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// src/lib.rs から C ヘッダーを OUT_DIR/sosu_seisei.h に生成する。ソースツリーには書かないので、
+// 読み取り専用のソースからもビルドできる。include/sosu_seisei.h はリポジトリに含めたもので、
+// 環境変数 SOSU_SEISEI_WRITE_HEADER=1 を付けてビルドしたときだけ上書きする
+
+use std::path::Path;
+
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=SOSU_SEISEI_WRITE_HEADER");
+    let config = cbindgen::Config::from_file(Path::new(&crate_dir).join("cbindgen.toml")).unwrap();
+    let bindings = cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("failed to generate the C header");
+    bindings.write_to_file(Path::new(&out_dir).join("sosu_seisei.h"));
+    if std::env::var("SOSU_SEISEI_WRITE_HEADER").is_ok_and(|v| v == "1") {
+        bindings.write_to_file(Path::new(&crate_dir).join("include/sosu_seisei.h"));
+    }
+}
@@ -0,0 +1,25 @@
+/* Prints the primes in [min, max] and their count.
+   cc examples/primes.c -Iinclude -L../target/release -lsosu_seisei_ffi -o primes */
+#include <inttypes.h>
+#include <stdio.h>
+#include "sosu_seisei.h"
+
+static int32_t print_prime(uint64_t prime, void *user_data) {
+    uint64_t *printed = (uint64_t *)user_data;
+    printf("%" PRIu64 "\n", prime);
+    (*printed)++;
+    return 0;
+}
+
+int main(void) {
+    uint64_t printed = 0, count = 0;
+    if (sosu_generate_range(1, 100, print_prime, &printed) != SOSU_OK) {
+        return 1;
+    }
+    if (sosu_count_range(1, 1000000000, &count) != SOSU_OK) {
+        return 1;
+    }
+    printf("%" PRIu64 " primes printed, %" PRIu64 " primes up to 10^9, 2^61-1 is %s\n",
+           printed, count, sosu_is_prime(2305843009213693951ULL) ? "prime" : "composite");
+    return 0;
+}
@@ -0,0 +1,39 @@
+#ifndef SOSU_SEISEI_H
+#define SOSU_SEISEI_H
+
+/* Generated by cbindgen from ffi/src/lib.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define SOSU_OK 0
+
+#define SOSU_STOPPED 1
+
+#define SOSU_INVALID_RANGE -1
+
+#define SOSU_NULL_POINTER -2
+
+typedef int32_t (*SosuPrimeCallback)(uint64_t prime, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+int32_t sosu_generate_range(uint64_t min,
+                            uint64_t max,
+                            SosuPrimeCallback callback,
+                            void *user_data);
+
+bool sosu_is_prime(uint64_t n);
+
+int32_t sosu_count_range(uint64_t min, uint64_t max, uint64_t *count);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SOSU_SEISEI_H */
@@ -0,0 +1,61 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// C から使うための API。ヘッダー include/sosu_seisei.h はビルド時に cbindgen で生成する
+
+use std::ffi::c_void;
+use sosu_seisei_sieve::config::MAX_PRIME_MAX;
+use sosu_seisei_sieve::primes::{self, Primes};
+
+// 戻り値
+pub const SOSU_OK: i32 = 0;
+// コールバックが 0 以外を返して途中で止めた
+pub const SOSU_STOPPED: i32 = 1;
+// min > max または max が上限 (999999999999999999) を超える
+pub const SOSU_INVALID_RANGE: i32 = -1;
+// callback または count が NULL
+pub const SOSU_NULL_POINTER: i32 = -2;
+
+// 素数ごとに呼ばれる。0 以外を返すと列挙を止める
+pub type SosuPrimeCallback = Option<extern "C" fn(prime: u64, user_data: *mut c_void) -> i32>;
+
+fn valid_range(min: u64, max: u64) -> bool {
+    min <= max && max <= MAX_PRIME_MAX
+}
+
+// [min, max] の素数を小さい順に callback に渡す。user_data はそのまま callback に渡る
+#[no_mangle]
+pub extern "C" fn sosu_generate_range(min: u64, max: u64, callback: SosuPrimeCallback, user_data: *mut c_void) -> i32 {
+    let Some(callback) = callback else {
+        return SOSU_NULL_POINTER;
+    };
+    if !valid_range(min, max) {
+        return SOSU_INVALID_RANGE;
+    }
+    for p in Primes::new(min, max) {
+        if callback(p, user_data) != 0 {
+            return SOSU_STOPPED;
+        }
+    }
+    SOSU_OK
+}
+
+#[no_mangle]
+pub extern "C" fn sosu_is_prime(n: u64) -> bool {
+    primes::is_prime(n)
+}
+
+// [min, max] の素数の個数を *count に書く（並列に篩う）
+#[no_mangle]
+pub extern "C" fn sosu_count_range(min: u64, max: u64, count: Option<&mut u64>) -> i32 {
+    let Some(count) = count else {
+        return SOSU_NULL_POINTER;
+    };
+    if !valid_range(min, max) {
+        return SOSU_INVALID_RANGE;
+    }
+    *count = primes::count_primes(min, max);
+    SOSU_OK
+}
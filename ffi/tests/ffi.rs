@@ -0,0 +1,45 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::ffi::c_void;
+use sosu_seisei_ffi::*;
+
+extern "C" fn collect(prime: u64, user_data: *mut c_void) -> i32 {
+    let primes = unsafe { &mut *(user_data as *mut Vec<u64>) };
+    primes.push(prime);
+    // 5 個で止める
+    (primes.len() >= 5) as i32
+}
+
+#[test]
+fn generate_count_and_errors() {
+    let mut primes: Vec<u64> = Vec::new();
+    let user_data = &mut primes as *mut Vec<u64> as *mut c_void;
+    assert_eq!(sosu_generate_range(0, 10, Some(collect), user_data), SOSU_OK);
+    assert_eq!(primes, vec![2, 3, 5, 7]);
+    primes.clear();
+    assert_eq!(sosu_generate_range(100, 1000, Some(collect), user_data), SOSU_STOPPED);
+    assert_eq!(primes, vec![101, 103, 107, 109, 113]);
+
+    assert_eq!(sosu_generate_range(10, 1, Some(collect), user_data), SOSU_INVALID_RANGE);
+    assert_eq!(sosu_generate_range(0, u64::MAX, Some(collect), user_data), SOSU_INVALID_RANGE);
+    assert_eq!(sosu_generate_range(0, 10, None, user_data), SOSU_NULL_POINTER);
+
+    let mut count = 0;
+    assert_eq!(sosu_count_range(0, 1_000_000, Some(&mut count)), SOSU_OK);
+    assert_eq!(count, 78_498);
+    assert_eq!(sosu_count_range(0, 10, None), SOSU_NULL_POINTER);
+    assert!(sosu_is_prime(1_000_000_007));
+    assert!(!sosu_is_prime(1));
+}
+
+// リポジトリの include/sosu_seisei.h が src/lib.rs から生成したものと同じか
+// （違えば SOSU_SEISEI_WRITE_HEADER=1 cargo build -p sosu-seisei-ffi で更新する）
+#[test]
+fn committed_header_is_up_to_date() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/sosu_seisei.h"));
+    let committed = include_str!("../include/sosu_seisei.h");
+    assert_eq!(committed, generated);
+}
@@ -0,0 +1,65 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// Python モジュール sosu_seisei。generate(min, max) は素数を順に返すイテレータ、
+// is_prime(n) は判定、count(min, max) は個数（計算中は GIL を解放する）
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use sosu_seisei_sieve::config::MAX_PRIME_MAX;
+use sosu_seisei_sieve::primes::{self, Primes};
+
+fn check_range(min: u64, max: u64) -> PyResult<()> {
+    if max > MAX_PRIME_MAX {
+        return Err(PyValueError::new_err(format!("max must be at most {}", MAX_PRIME_MAX)));
+    }
+    if min > max {
+        return Err(PyValueError::new_err("min must not exceed max"));
+    }
+    Ok(())
+}
+
+#[pyclass(module = "sosu_seisei")]
+struct PrimeIterator {
+    inner: Primes,
+}
+
+#[pymethods]
+impl PrimeIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<u64> {
+        slf.inner.next()
+    }
+}
+
+// [min, max] の素数を小さい順に返す（区間ごとに篩うのでメモリは範囲の大きさによらない）
+#[pyfunction]
+fn generate(min: u64, max: u64) -> PyResult<PrimeIterator> {
+    check_range(min, max)?;
+    Ok(PrimeIterator { inner: Primes::new(min, max) })
+}
+
+#[pyfunction]
+fn is_prime(n: u64) -> bool {
+    primes::is_prime(n)
+}
+
+#[pyfunction]
+fn count(py: Python<'_>, min: u64, max: u64) -> PyResult<u64> {
+    check_range(min, max)?;
+    Ok(py.allow_threads(|| primes::count_primes(min, max)))
+}
+
+#[pymodule]
+fn sosu_seisei(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PrimeIterator>()?;
+    m.add_function(wrap_pyfunction!(generate, m)?)?;
+    m.add_function(wrap_pyfunction!(is_prime, m)?)?;
+    m.add_function(wrap_pyfunction!(count, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 篩いながら求める集計値（素数の和、逆数の和、剰余類ごとの個数）。
+// 出力ファイルを読み直さずに実行のまとめに出す。
+// prime_max < 10^18 なので和は u128 に収まる（10^18 以下の素数の和でも 10^34 程度）
+
+use serde::{Deserialize, Serialize};
+use crate::i18n::{format_number, format_u128, tr};
+
+// 剰余類ごとの個数を数える法の上限（まとめに全部の類を並べるため）
+pub const MAX_AGGREGATE_MODULUS: u64 = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Aggregates {
+    pub count: u64,
+    pub sum: u128,
+    // Σ 1/p。Neumaier の補正付きで足す
+    reciprocal_sum: f64,
+    reciprocal_compensation: f64,
+    // modulus が 0 なら数えない。residue_counts[r] は p ≡ r (mod modulus) の個数
+    pub modulus: u64,
+    pub residue_counts: Vec<u64>,
+}
+
+impl Aggregates {
+    pub fn new(modulus: u64) -> Self {
+        Aggregates {
+            count: 0,
+            sum: 0,
+            reciprocal_sum: 0.0,
+            reciprocal_compensation: 0.0,
+            modulus,
+            residue_counts: vec![0; modulus as usize],
+        }
+    }
+
+    // セグメントの素数をまとめて足す（順序は問わない）
+    pub fn add_segment(&mut self, primes: &[u64]) {
+        self.count += primes.len() as u64;
+        self.sum += primes.iter().map(|&p| p as u128).sum::<u128>();
+        for &p in primes {
+            let x = 1.0 / p as f64;
+            let t = self.reciprocal_sum + x;
+            self.reciprocal_compensation += if self.reciprocal_sum.abs() >= x { (self.reciprocal_sum - t) + x } else { (x - t) + self.reciprocal_sum };
+            self.reciprocal_sum = t;
+        }
+        if self.modulus > 0 {
+            for &p in primes {
+                self.residue_counts[(p % self.modulus) as usize] += 1;
+            }
+        }
+    }
+
+    pub fn reciprocal_sum(&self) -> f64 {
+        self.reciprocal_sum + self.reciprocal_compensation
+    }
+
+    // 素数が 1 つ以上ある剰余類 (r, 個数)
+    pub fn nonzero_residues(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.residue_counts.iter().enumerate().filter(|&(_, &n)| n > 0).map(|(r, &n)| (r as u64, n))
+    }
+
+    // ログに出す行
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            tr!("Sum of primes: {}", format_u128(self.sum)),
+            tr!("Sum of reciprocals of primes: {}", format!("{:.15}", self.reciprocal_sum())),
+        ];
+        if self.modulus > 0 {
+            let counts: Vec<String> = self.nonzero_residues().map(|(r, n)| format!("{}: {}", r, format_number(n))).collect();
+            lines.push(tr!("Primes per residue class mod {}: {}", self.modulus, counts.join(", ")));
+        }
+        lines
+    }
+}
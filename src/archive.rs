@@ -0,0 +1,173 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 書き終えた出力ファイルの圧縮（archive_outputs）。分割出力では次のファイルに移るたびに、
+// 書き終えたファイルを 1 つずつ "<名前>.tar.zst" にまとめて元のファイルを消すので、
+// 巨大な実行でもディスクに展開した形で残るのは書いている途中のファイルだけになる。
+// tar は POSIX ustar 形式（8 GiB 以上の大きさは GNU tar と同じ base-256 で書く）
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::UNIX_EPOCH;
+use crate::app::WorkerMessage;
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::output::{PrimeSink, PrimeWriter};
+
+pub const ARCHIVE_EXTENSION: &str = "tar.zst";
+
+const BLOCK: usize = 512;
+
+// path の archive_extension をつけた名前（"primes_1.txt" → "primes_1.txt.tar.zst"）
+pub fn archive_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(ARCHIVE_EXTENSION);
+    path.with_file_name(name)
+}
+
+// ヘッダーの数値欄。8 進で収まらなければ先頭ビットを立てた big endian（base-256）
+fn put_number(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    if value < 1u64 << (3 * digits as u32).min(63) {
+        field[..digits].copy_from_slice(format!("{:0width$o}", value, width = digits).as_bytes());
+        field[digits] = 0;
+    } else {
+        field.fill(0);
+        let bytes = value.to_be_bytes();
+        let len = field.len();
+        field[len - bytes.len()..].copy_from_slice(&bytes);
+        field[0] |= 0x80;
+    }
+}
+
+// 1 つの通常ファイルの ustar ヘッダー
+fn tar_header(name: &str, size: u64, mtime: u64) -> io::Result<[u8; BLOCK]> {
+    if name.len() > 100 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("file name too long for tar: {}", name)));
+    }
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    put_number(&mut header[100..108], 0o644);
+    put_number(&mut header[108..116], 0);
+    put_number(&mut header[116..124], 0);
+    put_number(&mut header[124..136], size);
+    put_number(&mut header[136..148], mtime);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    // チェックサムは自身の欄を空白として数える
+    header[148..156].fill(b' ');
+    let sum: u64 = header.iter().map(|&b| b as u64).sum();
+    header[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+    Ok(header)
+}
+
+// path を archive_path(path) にまとめてから消す。失敗したら作りかけの圧縮ファイルを消し、元のファイルは残す
+pub fn archive_file(path: &Path, level: i32) -> io::Result<PathBuf> {
+    let archive = archive_path(path);
+    let result = write_archive(path, &archive, level);
+    match result {
+        Ok(()) => {
+            fs::remove_file(path)?;
+            Ok(archive)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&archive);
+            Err(e)
+        }
+    }
+}
+
+fn write_archive(path: &Path, archive: &Path, level: i32) -> io::Result<()> {
+    let mut input = File::open(path)?;
+    let metadata = input.metadata()?;
+    let mtime = metadata.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_secs());
+    let name = path.file_name().and_then(|n| n.to_str()).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "file name is not UTF-8"))?;
+    let mut out = zstd::stream::write::Encoder::new(File::create(archive)?, level)?;
+    out.write_all(&tar_header(name, metadata.len(), mtime)?)?;
+    let copied = io::copy(&mut input, &mut out)?;
+    if copied != metadata.len() {
+        return Err(io::Error::other(format!("{} changed while it was archived", path.display())));
+    }
+    // 中身を 512 バイト単位に埋め、終わりの印として空のブロックを 2 つ書く
+    let padding = (BLOCK - (copied % BLOCK as u64) as usize) % BLOCK;
+    out.write_all(&[0u8; 3 * BLOCK][..padding + 2 * BLOCK])?;
+    out.finish()?.sync_all()
+}
+
+// PrimeWriter を包み、書き終えたファイルを .tar.zst にまとめる（archive_outputs が false なら何もしない）
+pub struct ArchiveSink {
+    inner: PrimeWriter,
+    level: Option<i32>,
+    sender: mpsc::Sender<WorkerMessage>,
+    // まとめ終えたファイル（inner.files() の先頭から同じ数だけ置き換える）
+    archives: Vec<PathBuf>,
+}
+
+impl ArchiveSink {
+    pub fn new(inner: PrimeWriter, config: &Config, sender: &mpsc::Sender<WorkerMessage>) -> Self {
+        ArchiveSink { inner, level: config.archive_outputs.then_some(config.zstd_level), sender: sender.clone(), archives: Vec::new() }
+    }
+
+    // 書いたファイルの一覧（まとめたものは圧縮ファイル、最後が現在のファイル）
+    pub fn files(&self) -> Vec<PathBuf> {
+        let files = self.inner.files();
+        self.archives.iter().chain(&files[self.archives.len().min(files.len())..]).cloned().collect()
+    }
+
+    // files() の先頭から何個が書き終わっているか（まとめる場合はまとめ終えたものだけ）
+    pub fn completed(&self) -> usize {
+        match self.level {
+            Some(_) => self.archives.len(),
+            None => self.inner.files().len().saturating_sub(1),
+        }
+    }
+
+    // 先頭から done 個のファイルをまとめる
+    fn archive_completed(&mut self, done: usize) -> io::Result<()> {
+        let Some(level) = self.level else {
+            return Ok(());
+        };
+        while self.archives.len() < done {
+            let path = self.inner.files()[self.archives.len()].clone();
+            let archive = archive_file(&path, level).map_err(|e| io::Error::new(e.kind(), format!("failed to archive {}: {}", path.display(), e)))?;
+            self.sender.send(WorkerMessage::Log(tr!("Archived {} as {}", path.display(), archive.display()))).ok();
+            self.archives.push(archive);
+        }
+        Ok(())
+    }
+}
+
+impl PrimeSink for ArchiveSink {
+    // 分割出力で新しいファイルに移ったら、それより前のファイルは書き終わっている。まとめるのは次の素数を
+    // 書く前にするので、まとめるのに失敗しても素数は書かれておらず、そのまま再試行できる
+    fn write_prime(&mut self, p: u64) -> io::Result<()> {
+        let done = self.inner.files().len().saturating_sub(1);
+        if done > self.archives.len() {
+            self.archive_completed(done)?;
+        }
+        self.inner.write_prime(p)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()?;
+        self.archive_completed(self.inner.files().len())
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.inner.current_path()
+    }
+
+    fn relocate(&mut self, dir: &Path) -> io::Result<()> {
+        self.inner.relocate(dir)
+    }
+
+    fn written_files(&self) -> Vec<PathBuf> {
+        self.files()
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 書き込み専用スレッドを持つ Write。書式化済みのバイト列をブロックにまとめ、上限つきのキューで
+// I/O スレッドへ渡すので、遅いディスク（ネットワークドライブや SMR HDD）でも呼び出し側は
+// キューが一杯になるまで待たされない。
+// 書き込みの失敗はその後の write / flush で返す。失敗した後に受け取ったデータは順番を保って
+// 溜めておき、次の write / flush で書き直すので、PrimeWriter の再試行（ディスク容量不足からの再開）と組み合わせられる
+
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+enum Message {
+    Block(Vec<u8>),
+    // 溜まっているデータをもう一度書く
+    Retry,
+    // ここまでのブロックを書いてフラッシュしたら応答する
+    Sync(mpsc::Sender<()>),
+}
+
+// I/O スレッドで起きた、まだ呼び出し側に返していないエラー
+type SharedError = Arc<Mutex<Option<(io::ErrorKind, String)>>>;
+
+pub struct AsyncWriter<W: Write + Send + 'static> {
+    block: Vec<u8>,
+    block_size: usize,
+    sender: Option<SyncSender<Message>>,
+    handle: Option<JoinHandle<(W, Vec<u8>)>>,
+    error: SharedError,
+    // エラーを返した後、次の呼び出しで書き直しを頼む
+    needs_retry: bool,
+}
+
+// buf を書けるだけ書く。失敗したら書けなかった残りを返す
+fn write_or_keep<W: Write>(inner: &mut W, buf: &[u8]) -> Result<(), (io::Error, Vec<u8>)> {
+    let mut offset = 0;
+    while offset < buf.len() {
+        match inner.write(&buf[offset..]) {
+            Ok(0) => return Err((io::Error::from(io::ErrorKind::WriteZero), buf[offset..].to_vec())),
+            Ok(n) => offset += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err((e, buf[offset..].to_vec())),
+        }
+    }
+    Ok(())
+}
+
+fn io_thread<W: Write>(mut inner: W, receiver: Receiver<Message>, error: SharedError) -> (W, Vec<u8>) {
+    // 書けずに溜まっているデータ（空でなければ失敗中）
+    let mut pending: Vec<u8> = Vec::new();
+    let fail = |e: io::Error| *error.lock().unwrap() = Some((e.kind(), e.to_string()));
+    for message in receiver {
+        match message {
+            Message::Block(block) if !pending.is_empty() => pending.extend_from_slice(&block),
+            Message::Block(block) => {
+                if let Err((e, rest)) = write_or_keep(&mut inner, &block) {
+                    pending = rest;
+                    fail(e);
+                }
+            }
+            Message::Retry => {
+                if !pending.is_empty() {
+                    match write_or_keep(&mut inner, &pending) {
+                        Ok(()) => pending.clear(),
+                        Err((e, rest)) => {
+                            pending = rest;
+                            fail(e);
+                        }
+                    }
+                }
+            }
+            Message::Sync(reply) => {
+                if pending.is_empty() {
+                    if let Err(e) = inner.flush() {
+                        fail(e);
+                    }
+                }
+                reply.send(()).ok();
+            }
+        }
+    }
+    (inner, pending)
+}
+
+impl<W: Write + Send + 'static> AsyncWriter<W> {
+    // block_size バイトずつ、最大 queue_blocks 個のブロックを I/O スレッドへ先に渡す
+    pub fn new(inner: W, block_size: usize, queue_blocks: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(queue_blocks.max(1));
+        let error: SharedError = Arc::new(Mutex::new(None));
+        let thread_error = Arc::clone(&error);
+        let handle = std::thread::spawn(move || io_thread(inner, receiver, thread_error));
+        AsyncWriter {
+            block: Vec::with_capacity(block_size.max(1)),
+            block_size: block_size.max(1),
+            sender: Some(sender),
+            handle: Some(handle),
+            error,
+            needs_retry: false,
+        }
+    }
+
+    fn send(&mut self, message: Message) -> io::Result<()> {
+        let sender = self.sender.as_ref().expect("I/O thread is running");
+        sender.send(message).map_err(|_| io::Error::other("the I/O thread has stopped"))
+    }
+
+    // 前回エラーを返していれば書き直しを頼み、新しいエラーがあれば返す
+    fn check(&mut self) -> io::Result<()> {
+        if self.needs_retry {
+            self.needs_retry = false;
+            self.send(Message::Retry)?;
+        }
+        match self.error.lock().unwrap().take() {
+            Some((kind, message)) => {
+                self.needs_retry = true;
+                Err(io::Error::new(kind, message))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn send_block(&mut self) -> io::Result<()> {
+        if !self.block.is_empty() {
+            let block = std::mem::replace(&mut self.block, Vec::with_capacity(self.block_size));
+            self.send(Message::Block(block))?;
+        }
+        Ok(())
+    }
+
+    // I/O スレッドを止め、中身の Write と、まだ書いていないデータを返す
+    pub fn into_parts(mut self) -> (W, Vec<u8>) {
+        self.sender = None;
+        let (inner, mut unwritten) = self.handle.take().expect("I/O thread is running").join().expect("I/O thread panicked");
+        unwritten.append(&mut self.block);
+        (inner, unwritten)
+    }
+}
+
+impl<W: Write + Send + 'static> Write for AsyncWriter<W> {
+    // エラーを返すときは buf を受け取らない（再試行で二重に書かないように）
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check()?;
+        self.block.extend_from_slice(buf);
+        if self.block.len() >= self.block_size {
+            self.send_block()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_block()?;
+        if self.needs_retry {
+            self.needs_retry = false;
+            self.send(Message::Retry)?;
+        }
+        let (reply, done) = mpsc::channel();
+        self.send(Message::Sync(reply))?;
+        done.recv().map_err(|_| io::Error::other("the I/O thread has stopped"))?;
+        self.check()
+    }
+}
+
+impl<W: Write + Send + 'static> Drop for AsyncWriter<W> {
+    // BufWriter と同じく、残りを書いてから閉じる（エラーは無視する）
+    fn drop(&mut self) {
+        if self.sender.is_some() {
+            let _ = self.send_block();
+            self.sender = None;
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 篩に使う小さい素数（√prime_max 以下）をファイルから読み込む・保存する。
+// 保存するファイルは 1 行目が JSON のヘッダー（limit までのすべての素数、個数、SHA-256）で、
+// 以降 1 行に 1 つずつ素数が並ぶ。ヘッダーのない既存の出力ファイルも読み込めるが、
+// その場合は 2 から最後の素数まで抜けなく並んでいるものとして扱い、上書きはしない
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::app::WorkerMessage;
+use crate::i18n::{format_number, tr};
+use crate::miller_rabin::find_non_prime;
+use crate::output::{push_line, read_primes};
+use crate::sieve::simple_sieve;
+
+// ヘッダー付きのファイルで抜き取り確認する間隔
+const SPOT_CHECK_INTERVAL: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BaseHeader {
+    // limit 以下のすべての素数を含む
+    pub limit: u64,
+    pub count: u64,
+    pub sha256: String,
+}
+
+pub struct BasePrimes {
+    pub limit: u64,
+    pub primes: Vec<u64>,
+    // ヘッダー付き（このアプリが保存したファイル）かどうか
+    pub has_header: bool,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn has_header(path: &Path) -> bool {
+    let mut line = String::new();
+    File::open(path).and_then(|file| BufReader::new(file).read_line(&mut line)).is_ok() && line.trim_start().starts_with('{')
+}
+
+pub fn write_base_primes(path: &Path, limit: u64, primes: &[u64]) -> io::Result<()> {
+    let mut body = Vec::with_capacity(primes.len() * 8);
+    for &p in primes {
+        push_line(&mut body, p);
+    }
+    let header = BaseHeader { limit, count: primes.len() as u64, sha256: to_hex(&Sha256::digest(&body)) };
+    // 途中で止まっても壊れたファイルが残らないよう一時ファイルから名前を変える
+    let temp_path = path.with_extension("tmp");
+    let mut out = BufWriter::new(File::create(&temp_path)?);
+    serde_json::to_writer(&mut out, &header)?;
+    out.write_all(b"\n")?;
+    out.write_all(&body)?;
+    out.flush()?;
+    drop(out);
+    fs::rename(&temp_path, path)
+}
+
+// ファイルを読み、並びと素数性、ヘッダーの個数とハッシュを確かめる。素数性は Miller-Rabin で、
+// ハッシュで中身を確かめられるヘッダー付きのファイルは SPOT_CHECK_INTERVAL 個ごとに、それ以外はすべて確認する
+pub fn read_base_primes(path: &Path) -> Result<BasePrimes, String> {
+    let (limit, primes, has_header) = if has_header(path) {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut lines = BufReader::new(file).lines();
+        let header = lines.next().unwrap_or(Ok(String::new())).map_err(|e| e.to_string())?;
+        let header: BaseHeader = serde_json::from_str(&header).map_err(|e| e.to_string())?;
+        let mut hasher = Sha256::new();
+        let mut primes = Vec::with_capacity(header.count.min(1 << 28) as usize);
+        for line in lines {
+            let line = line.map_err(|e| e.to_string())?;
+            hasher.update(line.as_bytes());
+            hasher.update(b"\n");
+            primes.push(line.trim().parse::<u64>().map_err(|_| tr!("invalid number {}", format!("{:?}", line)))?);
+        }
+        if primes.len() as u64 != header.count || to_hex(&hasher.finalize()) != header.sha256 {
+            return Err(tr!("the primes do not match the count and hash in the header").to_string());
+        }
+        (header.limit, primes, true)
+    } else {
+        let mut primes = Vec::new();
+        read_primes(path, |p| primes.push(p)).map_err(|e| e.to_string())?;
+        (primes.last().copied().unwrap_or(0), primes, false)
+    };
+
+    if limit >= 2 && primes.first() != Some(&2) {
+        return Err(tr!("the primes must start at 2").to_string());
+    }
+    if let Some(w) = primes.windows(2).find(|w| w[0] >= w[1]) {
+        return Err(tr!("{} is out of order", w[1]));
+    }
+    if primes.last().is_some_and(|&p| p > limit) {
+        return Err(tr!("{} exceeds the limit {}", primes[primes.len() - 1], limit));
+    }
+    let step = if has_header { SPOT_CHECK_INTERVAL } else { 1 };
+    if let Some(p) = find_non_prime(&primes, step) {
+        return Err(tr!("{} is not prime", p));
+    }
+    Ok(BasePrimes { limit, primes, has_header })
+}
+
+// root 以下の素数を返す。file が空なら毎回篩い、指定されていればそこから読み込む。
+// 読み込めない・足りない場合は篩い直し、ファイルがないかこのアプリが保存したものなら保存し直す
+pub fn load_small_primes(file: &str, root: u64, sender: &mpsc::Sender<WorkerMessage>) -> Vec<u64> {
+    let file = file.trim();
+    if file.is_empty() {
+        return simple_sieve(root);
+    }
+    let path = Path::new(file);
+    let mut writable = true;
+    if path.exists() {
+        match read_base_primes(path) {
+            Ok(base) if base.limit >= root => {
+                sender.send(WorkerMessage::Log(tr!("Loaded {} base primes up to {} from {}", format_number(base.primes.len() as u64), format_number(base.limit), path.display()))).ok();
+                let mut primes = base.primes;
+                primes.truncate(primes.partition_point(|&p| p <= root));
+                return primes;
+            }
+            Ok(base) => {
+                sender.send(WorkerMessage::Log(tr!("{} only covers primes up to {}; sieving up to {}", path.display(), format_number(base.limit), format_number(root)))).ok();
+                writable = base.has_header;
+            }
+            Err(e) => {
+                sender.send(WorkerMessage::Log(tr!("Ignoring base prime file {}: {}", path.display(), e))).ok();
+                writable = has_header(path);
+            }
+        }
+    }
+
+    let primes = simple_sieve(root);
+    if writable {
+        match write_base_primes(path, root, &primes) {
+            Ok(()) => sender.send(WorkerMessage::Log(tr!("Saved {} base primes to {}", format_number(primes.len() as u64), path.display()))).ok(),
+            Err(e) => sender.send(WorkerMessage::Log(tr!("Failed to save base primes to {}: {}", path.display(), e))).ok(),
+        };
+    }
+    primes
+}
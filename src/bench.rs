@@ -0,0 +1,321 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 性能の測定（sosu-seisei-sieve bench）。決まった範囲を今の性能の設定（セグメントの大きさ、
+// スレッドのピン留め）で篩って素数を数え、1 秒あたりに篩った数を測る。
+// 結果は JSON に書き、同じ種類（--quick かどうか）の前回の結果と比べる。
+// --compare では設定の範囲を篩と Miller-Rabin の両方で求め、時間・増えたメモリ・結果が同じかを比べる
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sysinfo::{ProcessExt, System, SystemExt};
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::i18n::format_number;
+use crate::miller_rabin::verify_prime;
+use crate::pi_report::KNOWN_PI;
+use crate::sieve::{build_thread_pool, integer_sqrt, segment_ranges, segmented_sieve, simple_sieve};
+
+pub const REPORT_FILE_NAME: &str = "bench_report.json";
+pub const HISTORY_FILE: &str = "bench_history.json";
+pub const COMPARE_REPORT_FILE_NAME: &str = "compare_report.json";
+
+// --compare で Miller-Rabin に調べさせる範囲の長さの上限
+pub const MAX_COMPARE_LENGTH: u64 = 1_000_000_000;
+
+// 使用メモリを読む間隔
+const MEMORY_INTERVAL: Duration = Duration::from_millis(10);
+
+// 測る範囲 (low, 長さ)。--quick では長さを 1/100 にする
+const CASES: [(u64, u64); 3] = [
+    (0, 1_000_000_000),
+    (1_000_000_000_000, 10_000_000_000),
+    (1_000_000_000_000_000, 1_000_000_000),
+];
+const QUICK_DIVISOR: u64 = 100;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub name: String,
+    pub low: u64,
+    pub high: u64,
+    pub primes: u64,
+    // 小さい素数の篩を除いた時間
+    pub secs: f64,
+    pub numbers_per_sec: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    pub version: String,
+    pub quick: bool,
+    pub threads: usize,
+    pub segment_size: u64,
+    pub results: Vec<BenchResult>,
+}
+
+// 10 の累乗は 1e9 のように書く
+fn short(n: u64) -> String {
+    match n.checked_ilog10() {
+        Some(k) if 10u64.pow(k) == n && k >= 3 => format!("1e{}", k),
+        _ => n.to_string(),
+    }
+}
+
+// 測る範囲 (名前, low, high)
+pub fn bench_cases(quick: bool) -> Vec<(String, u64, u64)> {
+    CASES
+        .iter()
+        .map(|&(low, length)| {
+            let length = if quick { length / QUICK_DIVISOR } else { length };
+            let name = if low == 0 { format!("pi({})", short(length)) } else { format!("{} at {}", short(length), short(low)) };
+            (name, low, low + length)
+        })
+        .collect()
+}
+
+// 範囲を 1 つ測る。止められたら None
+fn run_case(name: &str, low: u64, high: u64, config: &Config, pool: &rayon::ThreadPool, stop_flag: &Arc<AtomicBool>) -> Option<BenchResult> {
+    let small_primes = simple_sieve(integer_sqrt(high) + 1);
+    let segments = segment_ranges(low, high, config.segment_size.max(1));
+    let start = Instant::now();
+    let primes: u64 = pool.install(|| segments.par_iter().map(|&(l, h)| segmented_sieve(&small_primes, l, h, stop_flag).len() as u64).sum());
+    let secs = start.elapsed().as_secs_f64();
+    if stop_flag.load(Ordering::SeqCst) {
+        return None;
+    }
+    Some(BenchResult { name: name.to_string(), low, high, primes, secs, numbers_per_sec: (high - low + 1) as f64 / secs.max(f64::EPSILON) })
+}
+
+// 範囲を順に測って 1 行ずつ書く。止められたら None
+pub fn run_bench(config: &Config, quick: bool, stop_flag: &Arc<AtomicBool>, out: &mut impl Write) -> Result<Option<BenchReport>, SieveError> {
+    let pool = build_thread_pool(config)?;
+    let mut report = BenchReport {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        quick,
+        threads: pool.current_num_threads(),
+        segment_size: config.segment_size,
+        results: Vec::new(),
+    };
+    let _ = writeln!(out, "sosu-seisei-sieve {} bench: {} threads, segment size {}", report.version, report.threads, format_number(report.segment_size));
+    for (name, low, high) in bench_cases(quick) {
+        let Some(result) = run_case(&name, low, high, config, &pool, stop_flag) else {
+            return Ok(None);
+        };
+        let _ = writeln!(
+            out,
+            "{:<14} {:>14} primes  {:>8.2} s  {:>16}/s",
+            result.name,
+            format_number(result.primes),
+            result.secs,
+            format_number(result.numbers_per_sec.round() as u64)
+        );
+        report.results.push(result);
+    }
+    Ok(Some(report))
+}
+
+// 前回の結果と比べた行と、素数の個数が合わない範囲があるか
+pub fn compare(report: &BenchReport, previous: Option<&BenchReport>) -> (Vec<String>, bool) {
+    let mut lines = Vec::new();
+    let mut mismatch = false;
+    match previous {
+        None => lines.push("No previous results to compare with".to_string()),
+        // 設定が違えば速さの差はその分も含む
+        Some(p) if (p.threads, p.segment_size) != (report.threads, report.segment_size) => lines.push(format!(
+            "The previous run ({}) used {} threads and segment size {}",
+            p.version,
+            p.threads,
+            format_number(p.segment_size)
+        )),
+        Some(_) => {}
+    }
+    for result in &report.results {
+        // 2 以下からの範囲は既知の π(10^k) とも比べる
+        let known = KNOWN_PI.iter().find(|&&(x, _)| result.low == 0 && x == result.high).map(|&(_, pi)| pi);
+        if let Some(pi) = known.filter(|&pi| pi != result.primes) {
+            lines.push(format!("{}: counted {} primes, but the known value is {}", result.name, format_number(result.primes), format_number(pi)));
+            mismatch = true;
+        }
+        let Some((previous, before)) = previous.and_then(|p| Some((p, p.results.iter().find(|r| r.low == result.low && r.high == result.high)?))) else {
+            continue;
+        };
+        if before.primes != result.primes {
+            lines.push(format!("{}: counted {} primes, but the previous run counted {}", result.name, format_number(result.primes), format_number(before.primes)));
+            mismatch = true;
+        }
+        let change = (result.numbers_per_sec / before.numbers_per_sec - 1.0) * 100.0;
+        lines.push(format!("{}: {:+.1}% against {} ({}/s)", result.name, change, previous.version, format_number(before.numbers_per_sec.round() as u64)));
+    }
+    (lines, mismatch)
+}
+
+// 保存してある結果（古い順）
+pub fn load_history(path: &Path) -> Vec<BenchReport> {
+    fs::read_to_string(path).ok().and_then(|text| serde_json::from_str(&text).ok()).unwrap_or_default()
+}
+
+// 同じ種類の最新の結果
+pub fn previous_report(history: &[BenchReport], quick: bool) -> Option<&BenchReport> {
+    history.iter().rev().find(|r| r.quick == quick)
+}
+
+pub fn save_history(path: &Path, history: &[BenchReport]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(history)?)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MethodResult {
+    pub method: String,
+    pub primes: u64,
+    pub secs: f64,
+    // 測り始めてから増えたプロセスの使用メモリの最大（バイト）
+    pub peak_memory: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CompareReport {
+    pub version: String,
+    pub low: u64,
+    pub high: u64,
+    pub threads: usize,
+    pub segment_size: u64,
+    pub results: Vec<MethodResult>,
+    // 2 つの方法で素数の列が同じだったか
+    pub identical: bool,
+    // 同じでなければ、片方だけが素数とした最初の数
+    pub first_difference: Option<u64>,
+}
+
+// このプロセスの使用メモリを別のスレッドで読み、start から増えた分の最大を finish で返す
+struct PeakMemory {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<u64>,
+}
+
+impl PeakMemory {
+    fn start() -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let pid = sysinfo::get_current_pid().ok();
+        let mut sys = System::new();
+        let mut read = move || pid.and_then(|pid| sys.refresh_process(pid).then(|| sys.process(pid).map(|p| p.memory())).flatten()).unwrap_or(0);
+        // 最初の値はこのスレッドで読み、測る処理が始まる前の使用量にする
+        let start = read();
+        let thread = std::thread::spawn(move || {
+            let mut peak = start;
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(MEMORY_INTERVAL) {
+                peak = peak.max(read());
+            }
+            peak.max(read()) - start
+        });
+        PeakMemory { stop, thread }
+    }
+
+    fn finish(self) -> u64 {
+        drop(self.stop);
+        self.thread.join().unwrap_or(0)
+    }
+}
+
+// 片方だけにある最初の数（どちらも昇順）
+pub fn first_difference(a: &[u64], b: &[u64]) -> Option<u64> {
+    match a.iter().zip(b).find(|(x, y)| x != y) {
+        Some((&x, &y)) => Some(x.min(y)),
+        None => a.get(b.len()).or_else(|| b.get(a.len())).copied(),
+    }
+}
+
+// [low, high] の素数をセグメントごとに並列で求めてつなげる。止められたら None
+fn primes_by(low: u64, high: u64, config: &Config, pool: &rayon::ThreadPool, stop_flag: &Arc<AtomicBool>, segment: impl Fn(u64, u64) -> Vec<u64> + Sync) -> Option<Vec<u64>> {
+    let segments = segment_ranges(low, high, config.segment_size.max(1));
+    let parts: Vec<Vec<u64>> = pool.install(|| {
+        segments.par_iter().map(|&(l, h)| if stop_flag.load(Ordering::SeqCst) { Vec::new() } else { segment(l, h) }).collect()
+    });
+    (!stop_flag.load(Ordering::SeqCst)).then(|| parts.concat())
+}
+
+// 設定の prime_min..prime_max を篩と Miller-Rabin（小さい素数の GCD で絞ってから判定する）で求めて比べる。
+// 止められたら None
+pub fn run_compare(config: &Config, stop_flag: &Arc<AtomicBool>, out: &mut impl Write) -> Result<Option<CompareReport>, SieveError> {
+    let low = config.prime_min.parse::<u64>()?;
+    let high = config.prime_max.parse::<u64>()?;
+    if low > high {
+        return Err(SieveError::Config("prime_min must not exceed prime_max".to_string()));
+    }
+    if high - low >= MAX_COMPARE_LENGTH {
+        return Err(SieveError::Config(format!("the range to compare must be at most {} numbers", format_number(MAX_COMPARE_LENGTH))));
+    }
+    let pool = build_thread_pool(config)?;
+    let threads = pool.current_num_threads();
+    let _ = writeln!(
+        out,
+        "sosu-seisei-sieve {} compare: {} to {} ({} numbers), {} threads",
+        env!("CARGO_PKG_VERSION"),
+        format_number(low),
+        format_number(high),
+        format_number(high - low + 1),
+        threads
+    );
+    let small_primes = simple_sieve(integer_sqrt(high) + 1);
+    let sieve = |l, h| segmented_sieve(&small_primes, l, h, stop_flag);
+    let miller_rabin = |l, h| (l..=h).filter(|&n| (n == 2 || n % 2 == 1) && verify_prime(n)).collect();
+    let mut results = Vec::new();
+    let mut outputs = Vec::new();
+    for (method, segment) in [("sieve", &sieve as &(dyn Fn(u64, u64) -> Vec<u64> + Sync)), ("miller-rabin", &miller_rabin)] {
+        let memory = PeakMemory::start();
+        let start = Instant::now();
+        let primes = primes_by(low, high, config, &pool, stop_flag, segment);
+        let secs = start.elapsed().as_secs_f64();
+        let peak_memory = memory.finish();
+        let Some(primes) = primes else {
+            return Ok(None);
+        };
+        let _ = writeln!(
+            out,
+            "{:<14} {:>14} primes  {:>8.2} s  {:>8} MiB",
+            method,
+            format_number(primes.len() as u64),
+            secs,
+            format_number(peak_memory.div_ceil(1024 * 1024))
+        );
+        results.push(MethodResult { method: method.to_string(), primes: primes.len() as u64, secs, peak_memory });
+        outputs.push(primes);
+    }
+    let difference = first_difference(&outputs[0], &outputs[1]);
+    match difference {
+        None => {
+            let _ = writeln!(out, "Identical output: both found the same {} primes", format_number(results[0].primes));
+        }
+        Some(n) => {
+            let _ = writeln!(out, "The outputs differ: only one method found {} prime", format_number(n));
+        }
+    }
+    let (sieve_secs, mr_secs) = (results[0].secs.max(f64::EPSILON), results[1].secs.max(f64::EPSILON));
+    let _ = if sieve_secs <= mr_secs {
+        writeln!(out, "The sieve was {:.1} times as fast on this range", mr_secs / sieve_secs)
+    } else {
+        writeln!(out, "Miller-Rabin was {:.1} times as fast on this range", sieve_secs / mr_secs)
+    };
+    Ok(Some(CompareReport {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        low,
+        high,
+        threads,
+        segment_size: config.segment_size,
+        results,
+        identical: difference.is_none(),
+        first_difference: difference,
+    }))
+}
@@ -0,0 +1,130 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 実行をまたいで再利用するデータのキャッシュディレクトリ（cache_dir）。
+// 篩に使う小さい素数を 2 のべき乗の上限ごとに base_primes_<上限>.txt として保存し、
+// 以前の実行で測った篩の速さ（calibration.json）から所要時間の見積もりを出す
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::app::WorkerMessage;
+use crate::base_primes::{read_base_primes, write_base_primes};
+use crate::config::Config;
+use crate::i18n::{format_number, tr};
+use crate::sieve::simple_sieve;
+
+const BASE_PRIMES_PREFIX: &str = "base_primes_";
+const CALIBRATION_FILE: &str = "calibration.json";
+// これより小さい上限では保存しない（篩い直すほうが速い）
+const MIN_CACHED_LIMIT: u64 = 1 << 16;
+
+// 篩の速さの測定結果。速さは範囲の桁数、スレッド数、セグメントの大きさで変わるので組ごとに持つ
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Calibration {
+    pub threads: usize,
+    pub segment_size: u64,
+    // prime_max の桁数
+    pub digits: u32,
+    pub numbers_per_sec: f64,
+}
+
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    // cache_dir が空なら None
+    pub fn open(config: &Config) -> Option<Self> {
+        let dir = config.cache_dir.trim();
+        (!dir.is_empty()).then(|| DiskCache { dir: PathBuf::from(dir) })
+    }
+
+    pub fn load<T: DeserializeOwned>(&self, name: &str) -> Option<T> {
+        let text = fs::read_to_string(self.dir.join(name)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    pub fn store<T: Serialize>(&self, name: &str, value: &T) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(name);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(value)?)?;
+        fs::rename(&temp_path, &path)
+    }
+
+    // 保存済みの小さい素数の上限（昇順）
+    fn cached_limits(&self) -> Vec<u64> {
+        let mut limits: Vec<u64> = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let name = entry.file_name().to_str()?.to_string();
+                        name.strip_prefix(BASE_PRIMES_PREFIX)?.strip_suffix(".txt")?.parse().ok()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        limits.sort_unstable();
+        limits
+    }
+
+    fn base_primes_path(&self, limit: u64) -> PathBuf {
+        self.dir.join(format!("{}{}.txt", BASE_PRIMES_PREFIX, limit))
+    }
+
+    // root 以下の素数。root 以上の上限で保存したものがあれば使い、なければ上限を 2 のべき乗に
+    // 切り上げて篩い、保存する（近い root の実行で同じファイルを使い回せるように）
+    pub fn base_primes(&self, root: u64, sender: &mpsc::Sender<WorkerMessage>) -> Vec<u64> {
+        if root < MIN_CACHED_LIMIT {
+            return simple_sieve(root);
+        }
+        for cached in self.cached_limits().into_iter().filter(|&l| l >= root) {
+            let path = self.base_primes_path(cached);
+            match read_base_primes(&path) {
+                Ok(base) if base.limit >= root => {
+                    sender.send(WorkerMessage::Log(tr!("Loaded {} base primes up to {} from {}", format_number(base.primes.len() as u64), format_number(base.limit), path.display()))).ok();
+                    let mut primes = base.primes;
+                    primes.truncate(primes.partition_point(|&p| p <= root));
+                    return primes;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    sender.send(WorkerMessage::Log(tr!("Ignoring base prime file {}: {}", path.display(), e))).ok();
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+
+        let limit = root.next_power_of_two();
+        let mut primes = simple_sieve(limit);
+        let path = self.base_primes_path(limit);
+        match fs::create_dir_all(&self.dir).and_then(|_| write_base_primes(&path, limit, &primes)) {
+            Ok(()) => sender.send(WorkerMessage::Log(tr!("Saved {} base primes to {}", format_number(primes.len() as u64), path.display()))).ok(),
+            Err(e) => sender.send(WorkerMessage::Log(tr!("Failed to save base primes to {}: {}", path.display(), e))).ok(),
+        };
+        primes.truncate(primes.partition_point(|&p| p <= root));
+        primes
+    }
+
+    pub fn calibration(&self, threads: usize, segment_size: u64, prime_max: u64) -> Option<Calibration> {
+        let digits = prime_max.checked_ilog10().unwrap_or(0) + 1;
+        self.load::<Vec<Calibration>>(CALIBRATION_FILE)?
+            .into_iter()
+            .find(|c| c.threads == threads && c.segment_size == segment_size && c.digits == digits)
+    }
+
+    // 測定結果を保存する（同じ組の古い結果は置き換える）
+    pub fn store_calibration(&self, threads: usize, segment_size: u64, prime_max: u64, numbers_per_sec: f64) -> std::io::Result<()> {
+        let digits = prime_max.checked_ilog10().unwrap_or(0) + 1;
+        let mut all: Vec<Calibration> = self.load(CALIBRATION_FILE).unwrap_or_default();
+        all.retain(|c| !(c.threads == threads && c.segment_size == segment_size && c.digits == digits));
+        all.push(Calibration { threads, segment_size, digits, numbers_per_sec });
+        self.store(CALIBRATION_FILE, &all)
+    }
+}
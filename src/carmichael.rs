@@ -0,0 +1,127 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// カーマイケル数の探索モード。篩で合成数と分かっている奇数のうち、底 2 の
+// フェルマーテストを通過したものを素因数分解して Korselt の判定法で確かめる
+
+use std::fs::{create_dir_all, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use rayon::prelude::*;
+use crate::app::{ProgressPhase, WorkerMessage};
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::factor::{factorize, format_factors, Factorization};
+use crate::miller_rabin::pow_mod;
+use crate::sieve::{build_thread_pool, format_eta, integer_sqrt, parallel_segments, segment_ranges, segmented_sieve_in, simple_sieve};
+use crate::i18n::tr;
+
+pub const OUTPUT_FILE_NAME: &str = "carmichael_numbers.txt";
+
+// Korselt の判定法: n は平方因子を持たず、素因数が 2 個以上で、n の各素因数 p について (p - 1) | (n - 1)
+pub fn is_korselt(n: u64, factors: &[(u64, u32)]) -> bool {
+    factors.len() >= 2 && factors.iter().all(|&(p, e)| e == 1 && (n - 1).is_multiple_of(p - 1))
+}
+
+// [low, high] のカーマイケル数と素因数分解
+pub fn carmichael_numbers_in(small_primes: &[u64], low: u64, high: u64, stop_flag: &Arc<AtomicBool>) -> Vec<(u64, Factorization)> {
+    let mut composite = vec![0u8; (high - low + 1) as usize];
+    segmented_sieve_in(&mut composite, small_primes, low, high, stop_flag);
+    let mut found = Vec::new();
+    for (i, &flag) in composite.iter().enumerate() {
+        let n = low + i as u64;
+        // カーマイケル数は奇数
+        if flag == 0 || n.is_multiple_of(2) || n < 9 {
+            continue;
+        }
+        if i & 0xFFFF == 0 && stop_flag.load(Ordering::SeqCst) {
+            break;
+        }
+        // カーマイケル数はすべての互いに素な底でフェルマーテストを通過するので、先に底 2 で絞り込む
+        if pow_mod(2, n - 1, n) != 1 {
+            continue;
+        }
+        let factors = factorize(n);
+        if is_korselt(n, &factors) {
+            found.push((n, factors));
+        }
+    }
+    found
+}
+
+pub fn run_carmichael(config: Config, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    sender.send(WorkerMessage::Log(tr!("Searching Carmichael numbers").to_string())).ok();
+
+    let prime_min = config.prime_min.parse::<u64>()?;
+    let prime_max = config.prime_max.parse::<u64>()?;
+    if prime_min > prime_max {
+        return Err(SieveError::Config("prime_min must not exceed prime_max".to_string()));
+    }
+    if config.segment_size == 0 {
+        return Err(SieveError::Config("segment_size must be greater than 0".to_string()));
+    }
+    if !config.output_dir.is_empty() {
+        create_dir_all(&config.output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(&config.output_dir), source })?;
+    }
+    let path = Path::new(&config.output_dir).join(OUTPUT_FILE_NAME);
+    let file = File::create(&path).map_err(|source| SieveError::OpenFile { path: path.clone(), source })?;
+    let mut out = BufWriter::with_capacity(config.writer_buffer_size, file);
+    writeln!(out, "n\tfactors").map_err(SieveError::write(&path))?;
+
+    let pool = build_thread_pool(&config)?;
+    let small_primes = simple_sieve(integer_sqrt(prime_max) + 1);
+    let segments = segment_ranges(prime_min, prime_max, config.segment_size);
+    let total_range = prime_max - prime_min + 1;
+    let start_time = Instant::now();
+    let mut processed = 0u64;
+    // 素因数の個数ごとの個数（添字は素因数の個数）
+    let mut by_factor_count: Vec<u64> = Vec::new();
+
+    for batch in segments.chunks(parallel_segments(&config, &pool)) {
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        let results: Vec<Vec<(u64, Factorization)>> = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|&(low, high)| carmichael_numbers_in(&small_primes, low, high, &stop_flag))
+                .collect()
+        });
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+
+        for (n, factors) in results.into_iter().flatten() {
+            let factors_str = format_factors(&factors);
+            writeln!(out, "{}\t{}", n, factors_str).map_err(SieveError::write(&path))?;
+            sender.send(WorkerMessage::Log(tr!("Carmichael number: {} = {}", n, factors_str))).ok();
+            if by_factor_count.len() <= factors.len() {
+                by_factor_count.resize(factors.len() + 1, 0);
+            }
+            by_factor_count[factors.len()] += 1;
+        }
+
+        processed += batch.iter().map(|&(low, high)| high - low + 1).sum::<u64>();
+        sender.send(WorkerMessage::Progress { current: processed, total: total_range, phase: ProgressPhase::Testing }).ok();
+        sender.send(WorkerMessage::Eta(format_eta(start_time, processed as f64 / total_range as f64))).ok();
+    }
+    out.flush().map_err(SieveError::write(&path))?;
+
+    for (k, &count) in by_factor_count.iter().enumerate().filter(|(_, &c)| c > 0) {
+        sender.send(WorkerMessage::Log(tr!("{} prime factors: {}", k, count))).ok();
+    }
+    let total: u64 = by_factor_count.iter().sum();
+    sender.send(WorkerMessage::Eta(tr!("0 hour 0 min 0 sec").to_string())).ok();
+    sender.send(WorkerMessage::Log(tr!("Finished Carmichael search. {} numbers written to {}", total, path.display()))).ok();
+    sender.send(WorkerMessage::Finished { found: total, files: vec![path] }).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
@@ -0,0 +1,471 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// コマンドラインからの実行。引数があれば GUI を開かずに settings.txt の設定で 1 回だけ実行する。
+// --progress-json では進捗を 1 行 1 個の JSON（NDJSON）で標準エラーに出し、ラッパーや CI から
+// ログの文章（言語設定で変わる）を解析せずに進捗を表示できるようにする。
+// 結果は終了コードで区別でき、--summary-json で個数・所要時間・出力ファイルを JSON に書き出す。
+// 設定は環境変数（SOSU_<設定名>）と --set name=value で上書きできる。
+// bench では決まった範囲を篩う速さを測り、前回の結果と比べる。bench --compare は篩と Miller-Rabin を
+// 同じ範囲で比べる（bench.rs）
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::aggregates::Aggregates;
+use crate::app::{run_worker, ProgressPhase, WorkerMessage};
+use crate::bench::{compare, load_history, previous_report, run_bench, run_compare, save_history, COMPARE_REPORT_FILE_NAME, HISTORY_FILE, REPORT_FILE_NAME};
+use crate::controller::{Controller, RunInputs};
+use crate::config::{apply_env_overrides, apply_override, load_or_create_config, Config, OverwriteMode, RunMode};
+use crate::error::SieveError;
+use crate::i18n::{set_language, tr};
+use crate::output::existing_outputs;
+use crate::paths::{app_dir, enter_work_dir, work_dir};
+use crate::power::KeepAwake;
+
+pub const USAGE: &str = "\
+Usage: sosu-seisei-sieve [--portable] [--cli] [--progress-json] [--summary-json <file>] [--set <name>=<value>]...
+       sosu-seisei-sieve [--portable] bench [--quick | --compare] [--report <file>] [--set <name>=<value>]...
+
+Without --cli, --progress-json, --summary-json or --set the settings window is opened.
+  --portable             Keep settings.txt and the working folder beside the executable
+  --cli                  Run once with settings.txt without opening the window
+  --progress-json        Like --cli, and write progress events to stderr as NDJSON
+  --summary-json <file>  Like --cli, and write the result of the run to <file> as JSON
+  --set <name>=<value>   Like --cli, and override a setting for this run (e.g. --set prime_max=1000000)
+  --help                 Show this help
+
+bench measures how fast the current performance settings sieve pi(1e9), 1e10 numbers at 1e12 and
+1e9 numbers at 1e15, compares the result with the previous bench run and writes a JSON report.
+  --quick                Measure ranges 100 times smaller
+  --compare              Instead find the primes from prime_min to prime_max (at most 1e9 numbers) both
+                         with the sieve and with Miller-Rabin, and compare time, memory and output
+  --report <file>        Write the report to <file> (default bench_report.json, or compare_report.json)
+
+In command-line mode settings can also be overridden with SOSU_<NAME> environment variables
+(e.g. SOSU_PRIME_MAX=1000000); --set takes precedence over them, and both over settings.txt.
+
+Exit codes: 0 success, 1 other failure, 2 invalid settings, 3 interrupted, 4 I/O error";
+
+// 終了コード
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_INVALID_CONFIG: i32 = 2;
+pub const EXIT_INTERRUPTED: i32 = 3;
+pub const EXIT_IO_ERROR: i32 = 4;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CliOptions {
+    pub progress_json: bool,
+    pub summary_json: Option<PathBuf>,
+    // --set で上書きする設定（名前, 値）。環境変数より優先する
+    pub overrides: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BenchOptions {
+    pub quick: bool,
+    // 篩と Miller-Rabin を比べる
+    pub compare: bool,
+    // None なら起動したフォルダの bench_report.json（--compare では compare_report.json）
+    pub report: Option<PathBuf>,
+    pub overrides: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args {
+    // 設定と作業フォルダを実行ファイルの隣に置く
+    pub portable: bool,
+    // None なら GUI を開く
+    pub cli: Option<CliOptions>,
+    // bench を実行する（cli より優先）
+    pub bench: Option<BenchOptions>,
+}
+
+// 不明な引数や --help は Err で使い方を返す
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut portable = false;
+    let mut options = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--portable" => portable = true,
+            "bench" if options.is_none() => return Ok(Args { portable, cli: None, bench: Some(parse_bench_args(args)?) }),
+            "--cli" => {
+                options.get_or_insert_with(CliOptions::default);
+            }
+            "--progress-json" => options.get_or_insert_with(CliOptions::default).progress_json = true,
+            "--summary-json" => {
+                let path = args.next().ok_or_else(|| format!("--summary-json needs a file name\n\n{}", USAGE))?;
+                options.get_or_insert_with(CliOptions::default).summary_json = Some(PathBuf::from(path));
+            }
+            "--set" => {
+                let setting = args.next().ok_or_else(|| format!("--set needs <name>=<value>\n\n{}", USAGE))?;
+                options.get_or_insert_with(CliOptions::default).overrides.push(parse_setting(&setting)?);
+            }
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ => {
+                if let Some(path) = arg.strip_prefix("--summary-json=") {
+                    options.get_or_insert_with(CliOptions::default).summary_json = Some(PathBuf::from(path));
+                } else if let Some(setting) = arg.strip_prefix("--set=") {
+                    options.get_or_insert_with(CliOptions::default).overrides.push(parse_setting(setting)?);
+                } else {
+                    return Err(format!("unknown option: {}\n\n{}", arg, USAGE));
+                }
+            }
+        }
+    }
+    Ok(Args { portable, cli: options, bench: None })
+}
+
+fn parse_bench_args(args: impl IntoIterator<Item = String>) -> Result<BenchOptions, String> {
+    let mut options = BenchOptions::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quick" => options.quick = true,
+            "--compare" => options.compare = true,
+            "--report" => options.report = Some(PathBuf::from(args.next().ok_or_else(|| format!("--report needs a file name\n\n{}", USAGE))?)),
+            "--set" => {
+                let setting = args.next().ok_or_else(|| format!("--set needs <name>=<value>\n\n{}", USAGE))?;
+                options.overrides.push(parse_setting(&setting)?);
+            }
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ => match (arg.strip_prefix("--report="), arg.strip_prefix("--set=")) {
+                (Some(path), _) => options.report = Some(PathBuf::from(path)),
+                (_, Some(setting)) => options.overrides.push(parse_setting(setting)?),
+                _ => return Err(format!("unknown bench option: {}\n\n{}", arg, USAGE)),
+            },
+        }
+    }
+    if options.quick && options.compare {
+        return Err(format!("--quick and --compare cannot be combined\n\n{}", USAGE));
+    }
+    Ok(options)
+}
+
+fn parse_setting(setting: &str) -> Result<(String, String), String> {
+    match setting.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.to_string())),
+        _ => Err(format!("--set needs <name>=<value>, got {:?}\n\n{}", setting, USAGE)),
+    }
+}
+
+// settings.txt の設定に環境変数、--set の順で上書きを重ねる
+pub fn layer_overrides(config: &Config, env: impl IntoIterator<Item = (String, String)>, options: &CliOptions) -> Result<Config, String> {
+    let mut config = apply_env_overrides(config, env)?;
+    for (name, value) in &options.overrides {
+        config = apply_override(&config, name, value).map_err(|e| format!("--set {}", e))?;
+    }
+    Ok(config)
+}
+
+// エラーの種類ごとの終了コード
+pub fn exit_code(error: &SieveError) -> i32 {
+    match error {
+        SieveError::Parse(_) | SieveError::Config(_) | SieveError::WorkUnit(_) => EXIT_INVALID_CONFIG,
+        SieveError::CreateDir { .. }
+        | SieveError::OpenFile { .. }
+        | SieveError::Write { .. }
+        | SieveError::SegmentBuffer(_)
+        | SieveError::Network { .. }
+        | SieveError::Database(_) => EXIT_IO_ERROR,
+        SieveError::ThreadPool(_) | SieveError::SelfTest(_) | SieveError::Miscount(_) => EXIT_FAILURE,
+    }
+}
+
+// --summary-json で書く実行結果
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunReport {
+    pub exit_code: i32,
+    pub mode: RunMode,
+    pub prime_min: String,
+    pub prime_max: String,
+    // 見つけた素数の個数（結果を数えないモードや失敗した実行では None）
+    pub found: Option<u64>,
+    pub nth_prime: Option<u64>,
+    pub duration_secs: f64,
+    pub files: Vec<String>,
+    pub error: Option<String>,
+    // aggregates を有効にした Generate の集計値
+    #[serde(default)]
+    pub aggregates: Option<Aggregates>,
+}
+
+// --progress-json の 1 行。"type" で種類を区別する
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event<'a> {
+    Progress { current: u64, total: u64, phase: ProgressPhase },
+    SegmentDone { low: u64, high: u64, primes: u64 },
+    Segments { done: u64, total: u64 },
+    Eta { eta: &'a str },
+    Log { message: &'a str },
+    NthPrime { n: u64, prime: u64 },
+    DiskFull { path: &'a str },
+    Warning { message: &'a str },
+    Error { message: &'a str },
+    Finished { found: u64, files: Vec<String> },
+    Stopped,
+    Done,
+}
+
+// WorkerMessage を NDJSON の 1 行にする。素数ごとの通知や GUI 用の統計は出さない
+pub fn progress_event(message: &WorkerMessage) -> Option<String> {
+    let event = match message {
+        WorkerMessage::Progress { current, total, phase } => Event::Progress { current: *current, total: *total, phase: *phase },
+        WorkerMessage::SegmentDone { low, high, primes } => Event::SegmentDone { low: *low, high: *high, primes: *primes },
+        WorkerMessage::Segments { done, total } => Event::Segments { done: *done, total: *total },
+        WorkerMessage::Eta(eta) => Event::Eta { eta },
+        WorkerMessage::Log(message) => Event::Log { message: message.trim_end() },
+        WorkerMessage::NthPrime { n, prime } => Event::NthPrime { n: *n, prime: *prime },
+        WorkerMessage::DiskFull(path) => Event::DiskFull { path },
+        WorkerMessage::Warning(message) => Event::Warning { message },
+        WorkerMessage::Error(message) => Event::Error { message },
+        WorkerMessage::Finished { found, files } => Event::Finished { found: *found, files: files.iter().map(|f| f.display().to_string()).collect() },
+        WorkerMessage::Stopped => Event::Stopped,
+        WorkerMessage::Done => Event::Done,
+        _ => return None,
+    };
+    serde_json::to_string(&event).ok()
+}
+
+// stop_flag を立てると中断する（終了コード 3）
+pub fn run_headless(config: Config, options: &CliOptions, stop_flag: Arc<AtomicBool>, out: &mut impl Write, err: &mut impl Write) -> io::Result<RunReport> {
+    set_language(config.language);
+    let mut report = RunReport {
+        exit_code: EXIT_SUCCESS,
+        mode: config.mode.clone(),
+        prime_min: config.prime_min.clone(),
+        prime_max: config.prime_max.clone(),
+        found: None,
+        nth_prime: None,
+        duration_secs: 0.0,
+        files: Vec::new(),
+        error: None,
+        aggregates: None,
+    };
+    // GUI の Run と同じ検証をする
+    let mut config = match Controller::validate_inputs(&RunInputs::from_config(&config), config.clone()) {
+        Ok(config) => config,
+        Err(errors) => {
+            let message = errors.join("; ");
+            match progress_event(&WorkerMessage::Error(message.clone())) {
+                Some(line) if options.progress_json => writeln!(err, "{}", line)?,
+                _ => writeln!(err, "{}", tr!("Error: {}", message))?,
+            }
+            report.exit_code = EXIT_INVALID_CONFIG;
+            report.error = Some(message);
+            return Ok(report);
+        }
+    };
+    // 確認の画面がないので、既存の出力があれば新しい版の名前で書く
+    if config.overwrite_mode == OverwriteMode::Ask
+        && !existing_outputs(Path::new(&config.output_dir), &config.output_format, config.zstd_seekable, config.split_count).is_empty()
+    {
+        config.overwrite_mode = OverwriteMode::Version;
+    }
+    let _keep_awake = if config.keep_awake { KeepAwake::acquire().ok() } else { None };
+    let started = Instant::now();
+    let (sender, receiver) = mpsc::channel();
+    // 再開の指示は送らない（ディスク容量不足では停止する）
+    let (_command_sender, commands) = mpsc::channel();
+    let worker_stop = Arc::clone(&stop_flag);
+    let worker = std::thread::spawn(move || {
+        let result = run_worker(config, sender.clone(), commands, worker_stop);
+        if let Err(e) = &result {
+            sender.send(WorkerMessage::Error(e.to_string())).ok();
+        }
+        sender.send(WorkerMessage::Done).ok();
+        result
+    });
+
+    let mut disk_full = None;
+    let mut stopped = false;
+    for message in receiver {
+        if options.progress_json {
+            if let Some(line) = progress_event(&message) {
+                writeln!(err, "{}", line)?;
+            }
+        }
+        match message {
+            WorkerMessage::Log(text) if !options.progress_json => writeln!(out, "{}", text.trim_end())?,
+            WorkerMessage::Warning(text) if !options.progress_json => writeln!(err, "{}", tr!("Warning: {}", text))?,
+            WorkerMessage::Error(text) => {
+                if !options.progress_json {
+                    writeln!(err, "{}", tr!("Error: {}", text))?;
+                }
+                report.error.get_or_insert(text);
+            }
+            WorkerMessage::DiskFull(path) => {
+                disk_full = Some(path);
+                stop_flag.store(true, Ordering::SeqCst);
+            }
+            WorkerMessage::NthPrime { prime, .. } => report.nth_prime = Some(prime),
+            WorkerMessage::Aggregates(aggregates) => report.aggregates = Some(aggregates),
+            WorkerMessage::Finished { found, files } => {
+                report.found = Some(found);
+                report.files = files.iter().map(|f| f.display().to_string()).collect();
+            }
+            WorkerMessage::Stopped => stopped = true,
+            WorkerMessage::Done => break,
+            _ => {}
+        }
+    }
+    report.duration_secs = started.elapsed().as_secs_f64();
+    report.exit_code = match worker.join() {
+        Ok(Err(e)) => exit_code(&e),
+        Err(_) => EXIT_FAILURE,
+        Ok(Ok(())) if disk_full.is_some() => EXIT_IO_ERROR,
+        Ok(Ok(())) if stopped => EXIT_INTERRUPTED,
+        Ok(Ok(())) if report.error.is_some() => EXIT_FAILURE,
+        Ok(Ok(())) => EXIT_SUCCESS,
+    };
+    if let Some(path) = disk_full {
+        report.error.get_or_insert(format!("disk full: {}", path));
+    }
+    Ok(report)
+}
+
+// Ctrl+C（と SIGTERM）で stop_flag を立てる。2 回目の Ctrl+C ではすぐに終了する
+#[cfg(unix)]
+fn watch_interrupts(stop_flag: &Arc<AtomicBool>) {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+    use signal_hook::flag;
+    for signal in [SIGINT, SIGTERM] {
+        let _ = flag::register_conditional_shutdown(signal, 130, Arc::clone(stop_flag));
+        let _ = flag::register(signal, Arc::clone(stop_flag));
+    }
+}
+
+// Windows では Ctrl+C でそのまま終了する
+#[cfg(not(unix))]
+fn watch_interrupts(_stop_flag: &Arc<AtomicBool>) {}
+
+// 終了コードを返す
+pub fn run(mut options: CliOptions) -> i32 {
+    let config = match load_or_create_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load settings: {}", e);
+            return EXIT_INVALID_CONFIG;
+        }
+    };
+    let config = match layer_overrides(&config, std::env::vars(), &options) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid setting override: {}", e);
+            return EXIT_INVALID_CONFIG;
+        }
+    };
+    // --summary-json の相対パスは起動したフォルダから見た位置のままにする
+    if let Some(path) = &options.summary_json {
+        options.summary_json = std::path::absolute(path).ok();
+    }
+    if let Err(e) = enter_work_dir(&config) {
+        eprintln!("Failed to enter the working folder {}: {}", work_dir(&config).display(), e);
+        return EXIT_IO_ERROR;
+    }
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    watch_interrupts(&stop_flag);
+    let report = match run_headless(config, &options, stop_flag, &mut io::stdout().lock(), &mut io::stderr().lock()) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_IO_ERROR;
+        }
+    };
+    if let Some(path) = &options.summary_json {
+        let written = serde_json::to_string_pretty(&report).map_err(io::Error::from).and_then(|json| fs::write(path, json));
+        if let Err(e) = written {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            return EXIT_IO_ERROR;
+        }
+    }
+    report.exit_code
+}
+
+// bench の終了コードを返す。測った個数が既知の値や前回と合わなければ失敗にする
+pub fn bench(options: BenchOptions) -> i32 {
+    let config = match load_or_create_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load settings: {}", e);
+            return EXIT_INVALID_CONFIG;
+        }
+    };
+    let overrides = CliOptions { overrides: options.overrides.clone(), ..CliOptions::default() };
+    let config = match layer_overrides(&config, std::env::vars(), &overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid setting override: {}", e);
+            return EXIT_INVALID_CONFIG;
+        }
+    };
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    watch_interrupts(&stop_flag);
+    if options.compare {
+        return compare_methods(&config, options.report, &stop_flag);
+    }
+    let report = match run_bench(&config, options.quick, &stop_flag, &mut io::stdout().lock()) {
+        Ok(Some(report)) => report,
+        Ok(None) => return EXIT_INTERRUPTED,
+        Err(e) => {
+            eprintln!("{}", e);
+            return exit_code(&e);
+        }
+    };
+    let history_path = app_dir().join(HISTORY_FILE);
+    let mut history = load_history(&history_path);
+    let (lines, mismatch) = compare(&report, previous_report(&history, options.quick));
+    for line in lines {
+        println!("{}", line);
+    }
+    let report_path = options.report.unwrap_or_else(|| PathBuf::from(REPORT_FILE_NAME));
+    let written = serde_json::to_string_pretty(&report).map_err(io::Error::from).and_then(|json| fs::write(&report_path, json));
+    if let Err(e) = written {
+        eprintln!("Failed to write {}: {}", report_path.display(), e);
+        return EXIT_IO_ERROR;
+    }
+    println!("Report written to {}", report_path.display());
+    // 個数が合わない結果は次の比較に使わない
+    if mismatch {
+        return EXIT_FAILURE;
+    }
+    history.push(report);
+    if let Err(e) = save_history(&history_path, &history) {
+        eprintln!("Failed to write {}: {}", history_path.display(), e);
+        return EXIT_IO_ERROR;
+    }
+    EXIT_SUCCESS
+}
+
+// bench --compare の終了コードを返す。2 つの方法の結果が違えば失敗にする
+fn compare_methods(config: &Config, report_path: Option<PathBuf>, stop_flag: &Arc<AtomicBool>) -> i32 {
+    let report = match run_compare(config, stop_flag, &mut io::stdout().lock()) {
+        Ok(Some(report)) => report,
+        Ok(None) => return EXIT_INTERRUPTED,
+        Err(e) => {
+            eprintln!("{}", e);
+            return exit_code(&e);
+        }
+    };
+    let report_path = report_path.unwrap_or_else(|| PathBuf::from(COMPARE_REPORT_FILE_NAME));
+    let written = serde_json::to_string_pretty(&report).map_err(io::Error::from).and_then(|json| fs::write(&report_path, json));
+    if let Err(e) = written {
+        eprintln!("Failed to write {}: {}", report_path.display(), e);
+        return EXIT_IO_ERROR;
+    }
+    println!("Report written to {}", report_path.display());
+    if report.identical {
+        EXIT_SUCCESS
+    } else {
+        EXIT_FAILURE
+    }
+}
@@ -0,0 +1,629 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 実行の制御（入力の検証、ワーカーの起動、ワーカーからのメッセージの反映）。
+// 画面の描画からは切り離してあり、GUI とコマンドラインの両方から使う
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::aggregates::{Aggregates, MAX_AGGREGATE_MODULUS};
+use crate::app::{run_worker, PhaseProgress, WorkerCommand, WorkerMessage};
+use crate::config::{validate_range, Config, OverwriteMode, RangeErrors, RunMode, MAX_PRIME_MAX};
+use crate::error::SieveError;
+use crate::filter::DigitExpr;
+use crate::gaps::GapReport;
+use crate::i18n::{strip_separators, tr};
+use crate::monitor::{ResourceMonitor, CPU_WINDOW};
+use crate::pi_report::PiReport;
+use crate::power::KeepAwake;
+use crate::pseudoprime::MAX_BASES;
+use crate::script::FilterScript;
+
+// 終了時にワーカーが止まるのを待つ時間
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 数として読む入力欄の内容（検証前）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunInputs {
+    pub prime_min: String,
+    pub prime_max: String,
+    pub split_count: String,
+    pub residue_modulus: String,
+    // カンマ区切り
+    pub residues: String,
+    pub race_modulus: String,
+    pub race_a: String,
+    pub race_b: String,
+    pub aggregate_modulus: String,
+    pub digit_filter: String,
+    pub filter_script: String,
+    pub pseudoprime_bases: String,
+    pub work_unit_size: String,
+    pub nth_count: String,
+    pub sink_batch_size: String,
+    pub segment_size: String,
+    pub chunk_size: String,
+    pub writer_buffer_size: String,
+    pub parallel_segments: String,
+}
+
+impl RunInputs {
+    pub fn from_config(config: &Config) -> Self {
+        let list = |values: &[u64]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ");
+        RunInputs {
+            prime_min: config.prime_min.clone(),
+            prime_max: config.prime_max.clone(),
+            split_count: config.split_count.to_string(),
+            residue_modulus: config.residue_modulus.to_string(),
+            residues: list(&config.residues),
+            race_modulus: config.race_modulus.to_string(),
+            race_a: config.race_a.to_string(),
+            race_b: config.race_b.to_string(),
+            aggregate_modulus: config.aggregate_modulus.to_string(),
+            digit_filter: config.digit_filter.clone(),
+            filter_script: config.filter_script.clone(),
+            pseudoprime_bases: list(&config.pseudoprime_bases),
+            work_unit_size: config.work_unit_size.to_string(),
+            nth_count: config.nth_count.to_string(),
+            sink_batch_size: config.sink_batch_size.to_string(),
+            segment_size: config.segment_size.to_string(),
+            chunk_size: config.chunk_size.to_string(),
+            writer_buffer_size: config.writer_buffer_size.to_string(),
+            parallel_segments: config.parallel_segments.to_string(),
+        }
+    }
+}
+
+// 完了した実行のまとめ
+pub struct RunSummary {
+    pub mode: RunMode,
+    pub prime_min: u64,
+    pub prime_max: u64,
+    // 見つけた個数（何を数えるかはモードによる）
+    pub found: u64,
+    pub duration: Duration,
+    pub files: Vec<PathBuf>,
+    // N 番目の素数モードの結果 (n, n 番目の素数)
+    pub nth_prime: Option<(u64, u64)>,
+    pub aggregates: Option<Aggregates>,
+}
+
+// ジョブの番号（登録した順）
+pub type JobId = u64;
+
+// ジョブごとの進捗
+#[derive(Debug, Clone)]
+pub struct JobProgress {
+    pub fraction: f32,
+    pub eta: String,
+    // 開始時刻（経過時間の表示用）
+    pub started: Option<Instant>,
+    pub current: u64,
+    pub total: u64,
+    // 篩い終えたセグメントの数と総数（Generate のみ）
+    pub segments: Option<(u64, u64)>,
+    // 今の段階とその速さ
+    pub phase: Option<PhaseProgress>,
+}
+
+impl Default for JobProgress {
+    fn default() -> Self {
+        JobProgress { fraction: 0.0, eta: "N/A".to_string(), started: None, current: 0, total: 0, segments: None, phase: None }
+    }
+}
+
+// 登録したワーカー 1 つ。終わった後も、次のジョブを登録するまで進捗を残す
+pub struct WorkerHandle {
+    // 終わったら None
+    pub receiver: Option<mpsc::Receiver<WorkerMessage>>,
+    pub command_sender: Option<mpsc::Sender<WorkerCommand>>,
+    pub stop_flag: Arc<AtomicBool>,
+    pub progress: JobProgress,
+    // ワーカーのスレッド（終了時に待つ）
+    pub thread: Option<JoinHandle<()>>,
+}
+
+impl WorkerHandle {
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+}
+
+// ワーカーのスレッドに渡すもの
+pub struct WorkerEnds {
+    pub sender: mpsc::Sender<WorkerMessage>,
+    pub commands: mpsc::Receiver<WorkerCommand>,
+    pub stop_flag: Arc<AtomicBool>,
+}
+
+// 登録したワーカーと、受け取ったメッセージから組み立てた状態
+pub struct Controller {
+    pub workers: BTreeMap<JobId, WorkerHandle>,
+    next_job: JobId,
+    // 進捗の表示と停止の対象にするジョブ（最後に登録したもの）
+    pub foreground: Option<JobId>,
+    pub log: String,
+    pub error: Option<String>,
+    pub warning: Option<String>,
+    // ディスクの空きを待っているジョブと書いていたファイル
+    pub disk_full: Option<(JobId, String)>,
+    // 実行中の Generate などにメモリ使用量を送る（ツールは購読しない）
+    pub monitor: ResourceMonitor,
+    // 最後に start_run で始めた実行の設定（まとめの表示用）
+    pub run_config: Config,
+
+    // Finished で受け取った結果（Done でまとめにする）
+    pub finished: Option<(u64, Vec<PathBuf>)>,
+    pub summary: Option<RunSummary>,
+    pub mem_usage: u64,
+    // 直近 CPU_WINDOW のコアごとの使用率と受け取った時刻
+    pub cpu_history: VecDeque<(Instant, Vec<f32>)>,
+    pub temperature: Option<f32>,
+    pub interval_counts: Vec<(u64, u64, u64)>,
+    pub gap_report: Option<GapReport>,
+    pub pi_table: Option<PiReport>,
+    pub race_samples: Vec<(u64, i64)>,
+    // 実行中に受け取った N 番目の素数と集計値（まとめの表示用）
+    pub nth_prime: Option<(u64, u64)>,
+    pub run_aggregates: Option<Aggregates>,
+    // 実行中に書き出した素数の個数と最後の素数（停止の確認用）
+    pub written_primes: u64,
+    pub last_written: Option<u64>,
+}
+
+impl Controller {
+    pub fn new(config: &Config) -> Self {
+        Controller {
+            workers: BTreeMap::new(),
+            next_job: 1,
+            foreground: None,
+            log: String::new(),
+            error: None,
+            warning: None,
+            disk_full: None,
+            monitor: ResourceMonitor::new(),
+            run_config: config.clone(),
+            finished: None,
+            summary: None,
+            mem_usage: 0,
+            cpu_history: VecDeque::new(),
+            temperature: None,
+            interval_counts: Vec::new(),
+            gap_report: None,
+            pi_table: None,
+            race_samples: Vec::new(),
+            nth_prime: None,
+            run_aggregates: None,
+            written_primes: 0,
+            last_written: None,
+        }
+    }
+
+    // 動いているワーカーがあるか
+    pub fn is_running(&self) -> bool {
+        self.workers.values().any(WorkerHandle::is_running)
+    }
+
+    // 表示するジョブの進捗（まだ何も実行していなければ空）
+    pub fn progress(&self) -> JobProgress {
+        self.foreground.and_then(|id| self.workers.get(&id)).map(|w| w.progress.clone()).unwrap_or_default()
+    }
+
+    // 新しいジョブを登録して表示の対象にする。終わったジョブの記録はここで消す
+    pub fn register(&mut self, eta: &str) -> (JobId, WorkerEnds) {
+        self.workers.retain(|_, worker| worker.is_running());
+        let id = self.next_job;
+        self.next_job += 1;
+        let (sender, receiver) = mpsc::channel();
+        let (command_sender, commands) = mpsc::channel();
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let progress = JobProgress { eta: eta.to_string(), started: Some(Instant::now()), ..JobProgress::default() };
+        self.workers.insert(id, WorkerHandle { receiver: Some(receiver), command_sender: Some(command_sender), stop_flag: stop_flag.clone(), progress, thread: None });
+        self.foreground = Some(id);
+        (id, WorkerEnds { sender, commands, stop_flag })
+    }
+
+    pub fn log_line(&mut self, line: &str) {
+        self.log.push_str(line);
+        if !line.ends_with('\n') {
+            self.log.push('\n');
+        }
+    }
+
+    // 範囲の入力を検証する。N 番目の素数モードでは prime_max を使わないので上限として扱う
+    pub fn validate_range(inputs: &RunInputs, mode: &RunMode) -> Result<(u64, u64, u64), RangeErrors> {
+        let prime_max = if *mode == RunMode::NthPrime { MAX_PRIME_MAX.to_string() } else { inputs.prime_max.clone() };
+        validate_range(&inputs.prime_min, &prime_max, &inputs.split_count)
+    }
+
+    // 入力欄の内容を検証して config に反映する。config には数として読まない入力（チェックボックスや選択肢、
+    // 文字列の欄）を反映しておく。誤りがあれば、範囲の誤りか、それ以外の誤りをすべて返す
+    pub fn validate_inputs(inputs: &RunInputs, mut config: Config) -> Result<Config, Vec<String>> {
+        let (prime_min, prime_max, split_count) = Self::validate_range(inputs, &config.mode).map_err(|e| e.messages())?;
+        let mut errors = Vec::new();
+
+        if config.prime_index && config.overwrite_mode == OverwriteMode::Append {
+            errors.push(tr!("numbering primes cannot be combined with appending to existing files."));
+        }
+
+        let residue_modulus = match strip_separators(&inputs.residue_modulus).parse::<u64>() {
+            Ok(v) => v,
+            Err(_) => {
+                errors.push(tr!("residue modulus is not a valid u64 integer."));
+                0
+            }
+        };
+        let residues = match parse_list(&inputs.residues) {
+            Ok(v) => v,
+            Err(_) => {
+                errors.push(tr!("residues must be a comma separated list of u64 integers."));
+                Vec::new()
+            }
+        };
+        if residue_modulus > 0 && residues.is_empty() {
+            errors.push(tr!("at least one residue is required when the residue modulus is set."));
+        }
+        if residues.iter().any(|&r| residue_modulus > 0 && r >= residue_modulus) {
+            errors.push(tr!("each residue must be less than the residue modulus."));
+        }
+
+        let parse_race = |input: &str| strip_separators(input).parse::<u64>().ok();
+        let (race_modulus, race_a, race_b) = match (parse_race(&inputs.race_modulus), parse_race(&inputs.race_a), parse_race(&inputs.race_b)) {
+            (Some(m), Some(a), Some(b)) => (m, a, b),
+            _ => {
+                errors.push(tr!("prime race modulus and classes must be valid u64 integers."));
+                (0, 0, 0)
+            }
+        };
+        if race_modulus > 0 && race_a % race_modulus == race_b % race_modulus {
+            errors.push(tr!("the two prime race classes must differ."));
+        }
+        let aggregate_modulus = match strip_separators(&inputs.aggregate_modulus).parse::<u64>() {
+            Ok(m) if m <= MAX_AGGREGATE_MODULUS => m,
+            _ => {
+                errors.push(tr!("aggregate modulus must be an integer from 0 to 1000."));
+                0
+            }
+        };
+
+        let pseudoprime_bases = match parse_list(&inputs.pseudoprime_bases) {
+            Ok(v) => v,
+            Err(_) => {
+                errors.push(tr!("Miller-Rabin bases must be a comma separated list of u64 integers."));
+                Vec::new()
+            }
+        };
+        if config.mode == RunMode::Pseudoprime
+            && (pseudoprime_bases.is_empty() || pseudoprime_bases.len() > MAX_BASES || pseudoprime_bases.contains(&0) || pseudoprime_bases.contains(&1))
+        {
+            errors.push(tr!("between 1 and 16 Miller-Rabin bases (each at least 2) are required."));
+        }
+
+        let work_unit_size = match strip_separators(&inputs.work_unit_size).parse::<u64>() {
+            Ok(v) if v > 0 => v,
+            _ => {
+                errors.push(tr!("work unit size must be a positive u64 integer."));
+                0
+            }
+        };
+
+        let nth_count = match strip_separators(&inputs.nth_count).parse::<u64>() {
+            Ok(v) if v > 0 => v,
+            _ if config.mode != RunMode::NthPrime => config.nth_count,
+            _ => {
+                errors.push(tr!("the number of primes must be a positive u64 integer."));
+                0
+            }
+        };
+
+        let digit_filter = inputs.digit_filter.trim().to_string();
+        if !digit_filter.is_empty() && DigitExpr::parse(&digit_filter).is_err() {
+            errors.push(tr!("digit filter expression is invalid."));
+        }
+
+        let sink_batch_size = match strip_separators(&inputs.sink_batch_size).parse::<usize>() {
+            Ok(v) if v > 0 => v,
+            _ => {
+                errors.push(tr!("sink batch size must be a positive integer."));
+                0
+            }
+        };
+
+        let segment_size = match strip_separators(&inputs.segment_size).parse::<u64>() {
+            Ok(v) if v > 0 => v,
+            _ => {
+                errors.push(tr!("segment size must be a positive integer."));
+                0
+            }
+        };
+        let chunk_size = match strip_separators(&inputs.chunk_size).parse::<usize>() {
+            Ok(v) if v > 0 => v,
+            _ => {
+                errors.push(tr!("chunk size must be a positive integer."));
+                0
+            }
+        };
+        let writer_buffer_size = match strip_separators(&inputs.writer_buffer_size).parse::<usize>() {
+            Ok(v) if v > 0 => v,
+            _ => {
+                errors.push(tr!("writer buffer size must be a positive integer."));
+                0
+            }
+        };
+        let parallel_segments = match strip_separators(&inputs.parallel_segments).parse::<usize>() {
+            Ok(v) => v,
+            Err(_) => {
+                errors.push(tr!("parallel segments must be a non-negative integer."));
+                0
+            }
+        };
+
+        let filter_script = inputs.filter_script.trim().to_string();
+        if !filter_script.is_empty() && FilterScript::compile(&filter_script).is_err() {
+            errors.push(tr!("filter script is invalid."));
+        }
+
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(str::to_string).collect());
+        }
+        config.prime_min = prime_min.to_string();
+        if config.mode != RunMode::NthPrime {
+            config.prime_max = prime_max.to_string();
+        }
+        config.split_count = split_count;
+        config.nth_count = nth_count;
+        config.residue_modulus = residue_modulus;
+        config.residues = residues;
+        config.race_modulus = race_modulus;
+        config.race_a = race_a;
+        config.race_b = race_b;
+        config.aggregate_modulus = aggregate_modulus;
+        config.digit_filter = digit_filter;
+        config.filter_script = filter_script;
+        config.work_unit_size = work_unit_size;
+        config.sink_batch_size = sink_batch_size;
+        config.segment_size = segment_size;
+        config.chunk_size = chunk_size;
+        config.writer_buffer_size = writer_buffer_size;
+        config.parallel_segments = parallel_segments;
+        if !pseudoprime_bases.is_empty() {
+            config.pseudoprime_bases = pseudoprime_bases;
+        }
+        for text in [
+            &mut config.coordinator_listen,
+            &mut config.coordinator_address,
+            &mut config.coordinator_token,
+            &mut config.s3_bucket,
+            &mut config.s3_prefix,
+            &mut config.s3_endpoint,
+            &mut config.s3_region,
+            &mut config.db_url,
+            &mut config.sink_script,
+            &mut config.db_table,
+            &mut config.base_primes_file,
+            &mut config.cache_dir,
+            &mut config.work_dir,
+        ] {
+            *text = text.trim().to_string();
+        }
+        Ok(config)
+    }
+
+    // ワーカースレッドを起動する（config は検証済みであること）
+    pub fn start_run(&mut self, config: Config) -> JobId {
+        self.finished = None;
+        self.summary = None;
+        self.nth_prime = None;
+        self.run_aggregates = None;
+        self.written_primes = 0;
+        self.last_written = None;
+        self.interval_counts.clear();
+        self.gap_report = None;
+        self.pi_table = None;
+        self.race_samples.clear();
+        self.run_config = config.clone();
+
+        let (id, WorkerEnds { sender, commands, stop_flag }) = self.register(tr!("Calculating..."));
+        self.monitor.subscribe(id, sender.clone());
+        let thread = std::thread::spawn(move || {
+            // 実行中のみスリープを抑止（ガードはこのスレッドで保持）
+            let _keep_awake = if config.keep_awake {
+                match KeepAwake::acquire() {
+                    Ok(guard) => Some(guard),
+                    Err(e) => {
+                        let _ = sender.send(WorkerMessage::Log(tr!("Keep awake unavailable: {}", e)));
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            if let Err(e) = run_worker(config, sender.clone(), commands, stop_flag) {
+                let _ = sender.send(WorkerMessage::Error(e.to_string()));
+            }
+            let _ = sender.send(WorkerMessage::Done);
+        });
+        self.attach(id, thread);
+        id
+    }
+
+    // 素数の生成以外のツール（ギャップ解析、特殊形の判定など）を別スレッドで実行する
+    pub fn spawn_tool(&mut self, job: impl FnOnce(mpsc::Sender<WorkerMessage>, Arc<AtomicBool>) -> Result<(), SieveError> + Send + 'static) -> JobId {
+        self.error = None;
+        self.warning = None;
+
+        // ツールは再開の指示を受けない
+        let (id, WorkerEnds { sender, stop_flag, .. }) = self.register("N/A");
+        let thread = std::thread::spawn(move || {
+            if let Err(e) = job(sender.clone(), stop_flag) {
+                let _ = sender.send(WorkerMessage::Error(e.to_string()));
+            }
+            let _ = sender.send(WorkerMessage::Done);
+        });
+        self.attach(id, thread);
+        id
+    }
+
+    fn attach(&mut self, job: JobId, thread: JoinHandle<()>) {
+        if let Some(worker) = self.workers.get_mut(&job) {
+            worker.thread = Some(thread);
+        }
+    }
+
+    // 表示しているジョブを止める
+    pub fn stop(&self) {
+        if let Some(worker) = self.foreground.and_then(|id| self.workers.get(&id)) {
+            worker.stop_flag.store(true, Ordering::SeqCst);
+        }
+    }
+
+    // すべてのジョブを止め、スレッドが終わるのを timeout まで待つ。止まったジョブは停止した出力を
+    // 書き終えている（Append で続けられる）。timeout までに終わらなかったスレッドの数を返す
+    pub fn shutdown(&mut self, timeout: Duration) -> usize {
+        for worker in self.workers.values() {
+            worker.stop_flag.store(true, Ordering::SeqCst);
+        }
+        let deadline = Instant::now() + timeout;
+        while self.workers.values().any(|w| w.thread.as_ref().is_some_and(|t| !t.is_finished())) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let mut left = 0;
+        for worker in self.workers.values_mut() {
+            match worker.thread.take() {
+                Some(thread) if thread.is_finished() => {
+                    let _ = thread.join();
+                }
+                // 待ちきれなかったスレッドは切り離す
+                Some(_) => left += 1,
+                None => {}
+            }
+        }
+        for (job, message) in self.pending_messages() {
+            self.handle_message(job, message);
+        }
+        self.monitor.stop();
+        left
+    }
+
+    // ディスクの空きを待っているワーカーに再開を指示する
+    pub fn resume(&mut self, command: WorkerCommand) {
+        if let Some((id, _)) = self.disk_full.take() {
+            if let Some(command_sender) = self.workers.get(&id).and_then(|w| w.command_sender.as_ref()) {
+                let _ = command_sender.send(command);
+            }
+        }
+    }
+
+    // 届いているメッセージをすべて受け取る（待たない）
+    pub fn pending_messages(&self) -> Vec<(JobId, WorkerMessage)> {
+        self.workers
+            .iter()
+            .filter_map(|(&id, worker)| worker.receiver.as_ref().map(|receiver| (id, receiver)))
+            .flat_map(|(id, receiver)| receiver.try_iter().map(move |message| (id, message)))
+            .collect()
+    }
+
+    // メッセージを状態に反映する。画面の部品に渡すもの（螺旋の素数、表ビューアの索引）はそのまま返す
+    pub fn handle_message(&mut self, job: JobId, message: WorkerMessage) -> Option<WorkerMessage> {
+        let Some(progress) = self.workers.get_mut(&job).map(|w| &mut w.progress) else {
+            return Some(message);
+        };
+        match message {
+            WorkerMessage::Log(msg) => self.log_line(&msg),
+            WorkerMessage::Progress { current, total, phase } => {
+                progress.fraction = current as f32 / total as f32;
+                progress.current = current;
+                progress.total = total;
+                progress.phase = Some(PhaseProgress::record(progress.phase, phase, current));
+            }
+            WorkerMessage::SegmentDone { .. } => {}
+            WorkerMessage::Segments { done, total } => progress.segments = Some((done, total)),
+            WorkerMessage::Eta(eta) => progress.eta = eta,
+            WorkerMessage::MemUsage(mem_usage) => self.mem_usage = mem_usage,
+            WorkerMessage::Temperature(temperature) => self.temperature = Some(temperature),
+            WorkerMessage::CpuUsage(loads) => {
+                let now = Instant::now();
+                while self.cpu_history.front().is_some_and(|(at, _)| now.duration_since(*at) > CPU_WINDOW) {
+                    self.cpu_history.pop_front();
+                }
+                self.cpu_history.push_back((now, loads));
+            }
+            WorkerMessage::FoundPrimeIndex(p, count) => {
+                self.written_primes = count;
+                self.last_written = Some(p);
+            }
+            WorkerMessage::IntervalCount { start, end, count } => self.interval_counts.push((start, end, count)),
+            WorkerMessage::PiReport(report) => self.pi_table = Some(report),
+            WorkerMessage::GapReport(report) => self.gap_report = Some(report),
+            WorkerMessage::RaceSample { x, difference } => self.race_samples.push((x, difference)),
+            message @ (WorkerMessage::SpiralPrimes { .. } | WorkerMessage::PrimeIndex(_)) => return Some(message),
+            WorkerMessage::DiskFull(path) => self.disk_full = Some((job, path)),
+            WorkerMessage::Error(msg) => {
+                self.log_line(&tr!("Error: {}", msg));
+                self.error = Some(msg);
+            }
+            WorkerMessage::Warning(msg) => {
+                self.log_line(&tr!("Warning: {}", msg));
+                self.warning = Some(msg);
+            }
+            WorkerMessage::NthPrime { n, prime } => self.nth_prime = Some((n, prime)),
+            WorkerMessage::Aggregates(aggregates) => self.run_aggregates = Some(aggregates),
+            WorkerMessage::Finished { found, files } => self.finished = Some((found, files)),
+            WorkerMessage::Done => {
+                let started = progress.started;
+                self.finish(job);
+                if let (Some((found, files)), Some(started)) = (self.finished.take(), started) {
+                    let config = &self.run_config;
+                    let prime_max = match self.nth_prime {
+                        Some((_, prime)) => prime,
+                        None => strip_separators(&config.prime_max).parse().unwrap_or(0),
+                    };
+                    self.summary = Some(RunSummary {
+                        mode: config.mode.clone(),
+                        prime_min: strip_separators(&config.prime_min).parse().unwrap_or(0),
+                        prime_max,
+                        found,
+                        duration: started.elapsed(),
+                        files,
+                        nth_prime: self.nth_prime,
+                        aggregates: self.run_aggregates.take(),
+                    });
+                }
+            }
+            WorkerMessage::Stopped => {
+                self.finish(job);
+                self.log_line(tr!("Process stopped by user."));
+            }
+        }
+        None
+    }
+
+    // Done か Stopped でジョブを終える（同じ回に届いた残りのメッセージは受け取り済み）
+    fn finish(&mut self, job: JobId) {
+        if let Some(worker) = self.workers.get_mut(&job) {
+            worker.receiver = None;
+            worker.command_sender = None;
+        }
+        self.monitor.unsubscribe(job);
+        if self.disk_full.as_ref().is_some_and(|(id, _)| *id == job) {
+            self.disk_full = None;
+        }
+    }
+}
+
+// 画面を閉じずに Controller が捨てられたときも、ワーカーを切り離したままにしない
+impl Drop for Controller {
+    fn drop(&mut self) {
+        self.shutdown(SHUTDOWN_TIMEOUT);
+    }
+}
+
+fn parse_list(input: &str) -> Result<Vec<u64>, std::num::ParseIntError> {
+    input.split(',').map(str::trim).filter(|v| !v.is_empty()).map(str::parse::<u64>).collect()
+}
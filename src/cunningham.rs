@@ -0,0 +1,173 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// カニンガム鎖の探索モード。[prime_min, prime_max] の素数から始まる
+// 第 1 種 (p, 2p+1, 4p+3, ...) と第 2 種 (p, 2p-1, 4p-3, ...) の鎖を探す。
+// 鎖の 2 番目以降は範囲外になりうるので Miller-Rabin で判定する
+
+use std::fs::{create_dir_all, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use rayon::prelude::*;
+use crate::app::{ProgressPhase, WorkerMessage};
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::miller_rabin::is_prime;
+use crate::sieve::{build_thread_pool, format_eta, integer_sqrt, parallel_segments, segment_ranges, segmented_sieve, simple_sieve};
+use crate::i18n::{tr, translate};
+
+pub const OUTPUT_FILE_NAME: &str = "cunningham_chains.txt";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChainKind {
+    First,
+    Second,
+}
+
+impl ChainKind {
+    fn next(self, p: u64) -> Option<u64> {
+        match self {
+            ChainKind::First => p.checked_mul(2)?.checked_add(1),
+            ChainKind::Second => p.checked_mul(2)?.checked_sub(1),
+        }
+    }
+
+    // 鎖の 1 つ前の項（整数にならない場合は None）
+    fn prev(self, p: u64) -> Option<u64> {
+        match self {
+            ChainKind::First => (p % 2 == 1).then(|| (p - 1) / 2),
+            ChainKind::Second => (p % 2 == 1).then(|| p / 2 + 1),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ChainKind::First => "first",
+            ChainKind::Second => "second",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chain {
+    pub kind: ChainKind,
+    pub members: Vec<u64>,
+    // u64 を超えたため途中で打ち切った
+    pub truncated: bool,
+}
+
+// p から始まる鎖（p の前の項が素数なら p は始点ではないので None）
+pub fn chain_from(p: u64, kind: ChainKind, min_length: usize) -> Option<Chain> {
+    if kind.prev(p).is_some_and(|q| q != p && is_prime(q)) {
+        return None;
+    }
+    let mut members = vec![p];
+    let mut truncated = false;
+    loop {
+        let last = *members.last().unwrap();
+        match kind.next(last) {
+            Some(next) if is_prime(next) => members.push(next),
+            Some(_) => break,
+            None => {
+                truncated = true;
+                break;
+            }
+        }
+    }
+    (members.len() >= min_length).then_some(Chain { kind, members, truncated })
+}
+
+pub fn run_cunningham(config: Config, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let min_length = config.cunningham_min_length.max(2) as usize;
+    sender.send(WorkerMessage::Log(tr!("Searching Cunningham chains of length >= {}", min_length))).ok();
+
+    let prime_min = config.prime_min.parse::<u64>()?;
+    let prime_max = config.prime_max.parse::<u64>()?;
+    if prime_min > prime_max {
+        return Err(SieveError::Config("prime_min must not exceed prime_max".to_string()));
+    }
+    if config.segment_size == 0 {
+        return Err(SieveError::Config("segment_size must be greater than 0".to_string()));
+    }
+    if !config.output_dir.is_empty() {
+        create_dir_all(&config.output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(&config.output_dir), source })?;
+    }
+    let path = Path::new(&config.output_dir).join(OUTPUT_FILE_NAME);
+    let file = File::create(&path).map_err(|source| SieveError::OpenFile { path: path.clone(), source })?;
+    let mut out = BufWriter::with_capacity(config.writer_buffer_size, file);
+    writeln!(out, "kind\tlength\tchain").map_err(SieveError::write(&path))?;
+
+    let pool = build_thread_pool(&config)?;
+    let small_primes = simple_sieve(integer_sqrt(prime_max) + 1);
+    let segments = segment_ranges(prime_min, prime_max, config.segment_size);
+    let total_range = prime_max - prime_min + 1;
+    let start_time = Instant::now();
+    let mut processed = 0u64;
+    // 長さごとの鎖の個数（添字は長さ）
+    let mut by_length: Vec<[u64; 2]> = Vec::new();
+    let mut longest: Option<Chain> = None;
+
+    for batch in segments.chunks(parallel_segments(&config, &pool)) {
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        let results: Vec<Vec<Chain>> = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|&(low, high)| {
+                    let mut chains = Vec::new();
+                    for p in segmented_sieve(&small_primes, low, high, &stop_flag) {
+                        for kind in [ChainKind::First, ChainKind::Second] {
+                            chains.extend(chain_from(p, kind, min_length));
+                        }
+                    }
+                    chains
+                })
+                .collect()
+        });
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+
+        for chain in results.into_iter().flatten() {
+            let members = chain.members.iter().map(|m| m.to_string()).collect::<Vec<_>>().join(", ");
+            let suffix = if chain.truncated { ", ..." } else { "" };
+            writeln!(out, "{}\t{}\t{}{}", chain.kind.name(), chain.members.len(), members, suffix).map_err(SieveError::write(&path))?;
+
+            let len = chain.members.len();
+            if by_length.len() <= len {
+                by_length.resize(len + 1, [0; 2]);
+            }
+            by_length[len][chain.kind as usize] += 1;
+            if longest.as_ref().is_none_or(|l| len > l.members.len()) {
+                sender.send(WorkerMessage::Log(tr!(
+                    "Longest chain so far ({} kind, length {}): {}{}", translate(chain.kind.name()), len, members, suffix))).ok();
+                longest = Some(chain);
+            }
+        }
+
+        processed += batch.iter().map(|&(low, high)| high - low + 1).sum::<u64>();
+        sender.send(WorkerMessage::Progress { current: processed, total: total_range, phase: ProgressPhase::Sieving }).ok();
+        sender.send(WorkerMessage::Eta(format_eta(start_time, processed as f64 / total_range as f64))).ok();
+    }
+    out.flush().map_err(SieveError::write(&path))?;
+
+    for (len, counts) in by_length.iter().enumerate().filter(|(_, c)| c[0] + c[1] > 0) {
+        sender.send(WorkerMessage::Log(tr!(
+            "Length {}: {} of the first kind, {} of the second kind", len, counts[0], counts[1]))).ok();
+    }
+    let total: u64 = by_length.iter().map(|c| c[0] + c[1]).sum();
+    sender.send(WorkerMessage::Eta(tr!("0 hour 0 min 0 sec").to_string())).ok();
+    sender.send(WorkerMessage::Log(tr!("Finished Cunningham chain search. {} chains written to {}", total, path.display()))).ok();
+    sender.send(WorkerMessage::Finished { found: total, files: vec![path] }).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
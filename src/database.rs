@@ -0,0 +1,213 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 素数を外部データベースへ一括登録する sink。ファイル出力と並行して db_batch_size 件ずつ送る。
+// PostgreSQL は COPY ... FROM STDIN、MySQL は複数行の INSERT を使う
+// （LOAD DATA LOCAL INFILE はサーバー側で無効にされていることが多いため）
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use mysql::prelude::Queryable;
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::output::{push_decimal, push_line, PrimeSink};
+
+// MySQL の INSERT 1 文あたりの行数（max_allowed_packet を超えないように）
+const MYSQL_ROWS_PER_INSERT: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DbKind {
+    Postgres,
+    MySql,
+}
+
+impl DbKind {
+    pub fn from_url(url: &str) -> Result<Self, SieveError> {
+        let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase()).unwrap_or_default();
+        match scheme.as_str() {
+            "postgres" | "postgresql" => Ok(DbKind::Postgres),
+            "mysql" => Ok(DbKind::MySql),
+            _ => Err(SieveError::Config("db_url must start with postgres://, postgresql:// or mysql://".to_string())),
+        }
+    }
+}
+
+// テーブル名は SQL に埋め込むので英数字と _ と .（スキーマ名の区切り）に限る
+pub fn validate_table_name(table: &str) -> Result<(), SieveError> {
+    let valid = !table.is_empty()
+        && table.split('.').all(|part| {
+            part.bytes().next().is_some_and(|b| b.is_ascii_alphabetic() || b == b'_')
+                && part.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(SieveError::Config(format!("db_table {:?} must consist of letters, digits and underscores", table)))
+    }
+}
+
+// ログ表示用にパスワードを伏せた URL
+pub fn redact_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    match authority.rsplit_once('@') {
+        Some((userinfo, host)) => {
+            let user = userinfo.split_once(':').map_or(userinfo, |(user, _)| user);
+            format!("{}://{}:***@{}{}", scheme, user, host, path)
+        }
+        None => url.to_string(),
+    }
+}
+
+pub fn create_table_sql(table: &str) -> String {
+    format!("CREATE TABLE IF NOT EXISTS {} (p BIGINT NOT NULL)", table)
+}
+
+pub fn mysql_insert_sql(table: &str, primes: &[u64]) -> String {
+    let mut sql = format!("INSERT INTO {} (p) VALUES ", table).into_bytes();
+    for (i, &p) in primes.iter().enumerate() {
+        if i > 0 {
+            sql.push(b',');
+        }
+        sql.push(b'(');
+        push_decimal(&mut sql, p);
+        sql.push(b')');
+    }
+    // UTF-8 の文字列に ASCII を足しただけなので失敗しない
+    String::from_utf8(sql).expect("SQL is ASCII")
+}
+
+enum Connection {
+    Postgres(Box<postgres::Client>),
+    MySql(Box<mysql::Conn>),
+}
+
+fn db_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::other(format!("database: {}", e))
+}
+
+impl Connection {
+    fn open(url: &str) -> Result<Self, String> {
+        match DbKind::from_url(url).map_err(|e| e.to_string())? {
+            DbKind::Postgres => postgres::Client::connect(url, postgres::NoTls)
+                .map(|client| Connection::Postgres(Box::new(client)))
+                .map_err(|e| e.to_string()),
+            DbKind::MySql => mysql::Opts::from_url(url)
+                .map_err(|e| e.to_string())
+                .and_then(|opts| mysql::Conn::new(opts).map_err(|e| e.to_string()))
+                .map(|conn| Connection::MySql(Box::new(conn))),
+        }
+    }
+
+    fn execute(&mut self, sql: &str) -> io::Result<()> {
+        match self {
+            Connection::Postgres(client) => client.batch_execute(sql).map_err(db_error),
+            Connection::MySql(conn) => conn.query_drop(sql).map_err(db_error),
+        }
+    }
+
+    fn insert(&mut self, table: &str, primes: &[u64]) -> io::Result<()> {
+        match self {
+            Connection::Postgres(client) => {
+                let mut text = Vec::with_capacity(primes.len() * 12);
+                for &p in primes {
+                    push_line(&mut text, p);
+                }
+                let mut copy = client.copy_in(&format!("COPY {} (p) FROM STDIN", table)).map_err(db_error)?;
+                copy.write_all(&text)?;
+                copy.finish().map_err(db_error)?;
+                Ok(())
+            }
+            Connection::MySql(conn) => {
+                // バッチ全体を 1 トランザクションにして、失敗したバッチは丸ごと再送できるようにする
+                let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(db_error)?;
+                for chunk in primes.chunks(MYSQL_ROWS_PER_INSERT) {
+                    tx.query_drop(mysql_insert_sql(table, chunk)).map_err(db_error)?;
+                }
+                tx.commit().map_err(db_error)
+            }
+        }
+    }
+}
+
+// 内側の sink に書きつつ、素数を db_batch_size 件ずつデータベースへ送る（db_url が空なら何もしない）
+pub struct DbSink<S> {
+    inner: S,
+    connection: Option<Connection>,
+    table: String,
+    batch_size: usize,
+    batch: Vec<u64>,
+}
+
+impl<S: PrimeSink> DbSink<S> {
+    pub fn new(inner: S, config: &Config) -> Result<Self, SieveError> {
+        let url = config.db_url.trim();
+        let table = config.db_table.trim().to_string();
+        let connection = if url.is_empty() {
+            None
+        } else {
+            DbKind::from_url(url)?;
+            validate_table_name(&table)?;
+            let mut connection = Connection::open(url)
+                .map_err(|e| SieveError::Database(format!("{}: {}", redact_url(url), e)))?;
+            connection.execute(&create_table_sql(&table))
+                .map_err(|e| SieveError::Database(format!("{}: {}", table, e)))?;
+            Some(connection)
+        };
+        let batch_size = config.db_batch_size.max(1);
+        Ok(DbSink { inner, connection, table, batch_size, batch: Vec::new() })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    // 溜まった素数を送る。失敗した場合はバッチを残すので、そのまま再試行できる
+    fn flush_batch(&mut self) -> io::Result<()> {
+        if let Some(connection) = &mut self.connection {
+            if !self.batch.is_empty() {
+                connection.insert(&self.table, &self.batch)?;
+                self.batch.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: PrimeSink> PrimeSink for DbSink<S> {
+    fn write_prime(&mut self, p: u64) -> io::Result<()> {
+        // 満杯のバッチは次の素数の前に送る（送信に失敗しても p を二重に書かないように）
+        if self.batch.len() >= self.batch_size {
+            self.flush_batch()?;
+        }
+        if self.connection.is_some() && p > i64::MAX as u64 {
+            return Err(db_error(format!("{} does not fit in BIGINT", p)));
+        }
+        self.inner.write_prime(p)?;
+        if self.connection.is_some() {
+            self.batch.push(p);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush_batch()?;
+        self.inner.finish()
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.inner.current_path()
+    }
+
+    fn relocate(&mut self, dir: &Path) -> io::Result<()> {
+        self.inner.relocate(dir)
+    }
+
+    fn written_files(&self) -> Vec<PathBuf> {
+        self.inner.written_files()
+    }
+}
@@ -0,0 +1,351 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 複数台での素数生成。コーディネーターは [prime_min, prime_max] を作業単位に分けて
+// TCP で接続してきたワーカーに配り、返ってきた素数を作業単位の順に出力ファイルへ書く。
+// 通信は 1 行 1 メッセージの JSON。切断したワーカーの作業単位は別のワーカーに配り直す。
+// ワーカーは Ready で coordinator_token を送り、一致しなければ切断する（ループバック以外で待ち受けるなら必須）。
+// 返ってきた素数は作業単位ファイルの結果と同じく確かめてから書く
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::fs::create_dir_all;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::app::{ProgressPhase, WorkerCommand, WorkerMessage};
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::i18n::{format_number, tr};
+use crate::metadata::{plan_output, record_output, Fingerprint, OutputPlan};
+use crate::miller_rabin::verify_prime;
+use crate::output::PrimeSink;
+use crate::sieve::{build_thread_pool, format_eta, integer_sqrt, open_output, parse_filters, retry_on_disk_full, segment_ranges, segmented_sieve, simple_sieve};
+use crate::work_units::SPOT_CHECK_INTERVAL;
+
+// 停止フラグや新しい接続を確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// コーディネーターからワーカーへ
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ToWorker {
+    Unit { id: u64, low: u64, high: u64 },
+    Finish,
+}
+
+// ワーカーからコーディネーターへ
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ToCoordinator {
+    Ready {
+        threads: usize,
+        #[serde(default)]
+        token: String,
+    },
+    Done { id: u64, primes: Vec<u64> },
+}
+
+fn send_line<T: Serialize>(out: &mut impl Write, message: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, message)?;
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+// 1 行読む。読み取りタイムアウトの間に停止フラグを確認し、停止されたら None
+fn read_line<T: for<'de> Deserialize<'de>>(input: &mut impl BufRead, stop_flag: &AtomicBool) -> io::Result<Option<T>> {
+    let mut line = String::new();
+    loop {
+        if stop_flag.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        // タイムアウトで中断しても読んだぶんは line に残るので続きから読む
+        match input.read_line(&mut line) {
+            Ok(0) => return Err(io::Error::new(ErrorKind::UnexpectedEof, "connection closed")),
+            Ok(_) if line.ends_with('\n') => {
+                return serde_json::from_str(&line).map(Some).map_err(|e| io::Error::new(ErrorKind::InvalidData, e));
+            }
+            Ok(_) => {}
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// 待ち受けるアドレスがすべてループバックか（そうでなければ coordinator_token が要る）
+fn is_loopback(address: &str) -> bool {
+    address.to_socket_addrs().is_ok_and(|mut addrs| addrs.all(|addr| addr.ip().is_loopback()))
+}
+
+// ワーカーが返した素数を確かめる。作業単位の範囲内で狭義に増加していることと、SPOT_CHECK_INTERVAL 個ごとに
+// verify_prime で素数であることを見る（work_units の結果ファイルと同じ）
+fn check_primes(primes: &[u64], low: u64, high: u64) -> io::Result<()> {
+    let mut previous = None;
+    for (i, &p) in primes.iter().enumerate() {
+        if p < low || p > high || previous.is_some_and(|q| q >= p) {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("{} is out of order or outside [{}, {}]", p, low, high)));
+        }
+        if i.is_multiple_of(SPOT_CHECK_INTERVAL) && !verify_prime(p) {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("{} is not prime", p)));
+        }
+        previous = Some(p);
+    }
+    Ok(())
+}
+
+// 配布待ちの作業単位とすべて終わったかどうか
+struct WorkQueue {
+    units: Vec<(u64, u64)>,
+    pending: Mutex<VecDeque<u64>>,
+    complete: AtomicBool,
+}
+
+pub fn run_coordinator(config: Config, sender: mpsc::Sender<WorkerMessage>, commands: mpsc::Receiver<WorkerCommand>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let prime_min = config.prime_min.parse::<u64>()?;
+    let prime_max = config.prime_max.parse::<u64>()?;
+    if prime_min > prime_max {
+        return Err(SieveError::Config("prime_min must not exceed prime_max".to_string()));
+    }
+    if config.work_unit_size == 0 {
+        return Err(SieveError::Config("work_unit_size must be greater than 0".to_string()));
+    }
+    let filters = parse_filters(&config)?;
+    let plan = plan_output(&config, prime_min, prime_max, &sender);
+    let prime_min = match &plan {
+        OutputPlan::Continue { start, .. } => *start,
+        OutputPlan::Covered => {
+            sender.send(WorkerMessage::Finished { found: 0, files: Vec::new() }).ok();
+            sender.send(WorkerMessage::Done).ok();
+            return Ok(());
+        }
+        OutputPlan::Fresh | OutputPlan::Untracked => prime_min,
+    };
+    let token = config.coordinator_token.trim().to_string();
+    if token.is_empty() && !is_loopback(config.coordinator_listen.trim()) {
+        return Err(SieveError::Config("coordinator_token must be set when coordinator_listen is not a loopback address".to_string()));
+    }
+    let listener = TcpListener::bind(config.coordinator_listen.trim())
+        .map_err(|source| SieveError::Network { addr: config.coordinator_listen.clone(), source })?;
+    listener.set_nonblocking(true)
+        .map_err(|source| SieveError::Network { addr: config.coordinator_listen.clone(), source })?;
+    if let Ok(addr) = listener.local_addr() {
+        sender.send(WorkerMessage::Log(tr!("Coordinator listening on {}", addr))).ok();
+    }
+
+    if !config.output_dir.is_empty() {
+        create_dir_all(&config.output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(&config.output_dir), source })?;
+    }
+    let mut out = open_output(&config, filters, &sender)?;
+
+    let units = segment_ranges(prime_min, prime_max, config.work_unit_size);
+    sender.send(WorkerMessage::Log(tr!("{} work units of up to {} numbers", format_number(units.len() as u64), format_number(config.work_unit_size)))).ok();
+    let queue = Arc::new(WorkQueue {
+        pending: Mutex::new((0..units.len() as u64).collect()),
+        units,
+        complete: AtomicBool::new(false),
+    });
+    let (result_sender, results) = mpsc::channel::<(u64, Vec<u64>)>();
+
+    let total_range = prime_max - prime_min + 1;
+    let start_time = Instant::now();
+    let mut processed = 0u64;
+    let mut found_count = 0u64;
+    let mut fingerprint = Fingerprint::default();
+    // 順番待ちの結果（作業単位の番号順に書く）
+    let mut completed: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+    let mut next_id = 0u64;
+    let result = 'run: loop {
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            break Ok(());
+        }
+        if next_id == queue.units.len() as u64 {
+            break Ok(());
+        }
+        loop {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    sender.send(WorkerMessage::Log(tr!("Worker connected: {}", peer))).ok();
+                    let queue = Arc::clone(&queue);
+                    let result_sender = result_sender.clone();
+                    let sender = sender.clone();
+                    let stop_flag = Arc::clone(&stop_flag);
+                    let token = token.clone();
+                    std::thread::spawn(move || serve_worker(stream, peer, &token, &queue, &result_sender, &sender, &stop_flag));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    sender.send(WorkerMessage::Log(tr!("Failed to accept a worker: {}", e))).ok();
+                    break;
+                }
+            }
+        }
+        let Ok((id, primes)) = results.recv_timeout(POLL_INTERVAL) else {
+            continue;
+        };
+        completed.insert(id, primes);
+
+        // 次に書く番の作業単位がそろっていれば書く
+        while let Some(primes) = completed.remove(&next_id) {
+            for &p in &primes {
+                match retry_on_disk_full(&mut out, &sender, &commands, &stop_flag, |out| out.write_prime(p)) {
+                    Ok(true) => {}
+                    // 停止された（Stopped は送信済み）
+                    Ok(false) => break 'run Ok(()),
+                    Err(e) => break 'run Err(e),
+                }
+                found_count += 1;
+                fingerprint.add(p);
+                sender.send(WorkerMessage::FoundPrimeIndex(p, found_count)).ok();
+            }
+            let (low, high) = queue.units[next_id as usize];
+            processed += high - low + 1;
+            next_id += 1;
+        }
+        sender.send(WorkerMessage::Progress { current: processed, total: total_range, phase: ProgressPhase::Sieving }).ok();
+        sender.send(WorkerMessage::Eta(format_eta(start_time, processed as f64 / total_range as f64))).ok();
+    };
+    // 接続中のワーカーに終了を伝える
+    queue.complete.store(true, Ordering::SeqCst);
+    result?;
+    if stop_flag.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    if !retry_on_disk_full(&mut out, &sender, &commands, &stop_flag, |out| out.finish())? {
+        return Ok(());
+    }
+    record_output(plan, &config, &[(prime_min, prime_max)], found_count, fingerprint, &out.written_files(), &sender);
+    sender.send(WorkerMessage::Eta(tr!("0 hour 0 min 0 sec").to_string())).ok();
+    sender.send(WorkerMessage::Log(tr!("Finished distributed generation. Total primes found: {}", format_number(found_count)))).ok();
+    sender.send(WorkerMessage::Finished { found: found_count, files: out.written_files() }).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
+
+// 1 台のワーカーとのやり取り。作業単位を 1 つずつ渡し、確かめた結果をコーディネーターに回す
+fn serve_worker(
+    stream: TcpStream,
+    peer: SocketAddr,
+    token: &str,
+    queue: &WorkQueue,
+    results: &mpsc::Sender<(u64, Vec<u64>)>,
+    sender: &mpsc::Sender<WorkerMessage>,
+    stop_flag: &AtomicBool,
+) {
+    let mut current = None;
+    let outcome = (|| -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        let mut input = BufReader::new(stream.try_clone()?);
+        let mut output = BufWriter::new(stream);
+        let Some(ToCoordinator::Ready { threads, token: sent }) = read_line(&mut input, stop_flag)? else {
+            return Ok(());
+        };
+        if sent != token {
+            return Err(io::Error::new(ErrorKind::PermissionDenied, "wrong coordinator_token"));
+        }
+        sender.send(WorkerMessage::Log(tr!("Worker {} is ready ({} threads)", peer, threads))).ok();
+        loop {
+            if stop_flag.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            let next = queue.pending.lock().unwrap().pop_front();
+            let Some(id) = next else {
+                // 配り終えた。他のワーカーが切断したら配り直しがあるので終わるまで待つ
+                if queue.complete.load(Ordering::SeqCst) {
+                    return send_line(&mut output, &ToWorker::Finish);
+                }
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            };
+            current = Some(id);
+            let (low, high) = queue.units[id as usize];
+            send_line(&mut output, &ToWorker::Unit { id, low, high })?;
+            match read_line(&mut input, stop_flag)? {
+                Some(ToCoordinator::Done { id: done, primes }) if done == id => {
+                    check_primes(&primes, low, high)?;
+                    current = None;
+                    results.send((id, primes)).ok();
+                }
+                Some(_) => return Err(io::Error::new(ErrorKind::InvalidData, "unexpected message")),
+                None => return Ok(()),
+            }
+        }
+    })();
+    if let Err(e) = outcome {
+        sender.send(WorkerMessage::Log(tr!("Worker {} disconnected: {}", peer, e))).ok();
+    }
+    // 終わっていない作業単位は配り直す
+    if let Some(id) = current {
+        queue.pending.lock().unwrap().push_front(id);
+    }
+}
+
+// ワーカーモード。コーディネーターに接続し、受け取った作業単位を篩って素数を返す
+pub fn run_distributed_worker(config: Config, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    if config.segment_size == 0 {
+        return Err(SieveError::Config("segment_size must be greater than 0".to_string()));
+    }
+    let addr = config.coordinator_address.trim().to_string();
+    let network = |source| SieveError::Network { addr: addr.clone(), source };
+    sender.send(WorkerMessage::Log(tr!("Connecting to coordinator {}", addr))).ok();
+    let stream = TcpStream::connect(&addr).map_err(network)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL)).map_err(network)?;
+    let mut input = BufReader::new(stream.try_clone().map_err(network)?);
+    let mut output = BufWriter::new(stream);
+
+    let pool = build_thread_pool(&config)?;
+    send_line(&mut output, &ToCoordinator::Ready { threads: pool.current_num_threads(), token: config.coordinator_token.trim().to_string() }).map_err(network)?;
+
+    let mut small_primes: Vec<u64> = Vec::new();
+    let mut sieve_limit = 0u64;
+    let mut units_done = 0u64;
+    let mut found_count = 0u64;
+    loop {
+        let message = match read_line(&mut input, &stop_flag).map_err(network)? {
+            Some(message) => message,
+            None => {
+                sender.send(WorkerMessage::Stopped).ok();
+                return Ok(());
+            }
+        };
+        let (id, low, high) = match message {
+            ToWorker::Unit { id, low, high } if low <= high => (id, low, high),
+            ToWorker::Unit { .. } => return Err(network(io::Error::new(ErrorKind::InvalidData, "empty work unit"))),
+            ToWorker::Finish => break,
+        };
+        // 篩に使う小さい素数は必要になった範囲まで作り直す
+        let root = integer_sqrt(high) + 1;
+        if root > sieve_limit {
+            small_primes = simple_sieve(root);
+            sieve_limit = root;
+        }
+        let segments = segment_ranges(low, high, config.segment_size);
+        let primes: Vec<u64> = pool.install(|| {
+            segments
+                .par_iter()
+                .map(|&(low, high)| segmented_sieve(&small_primes, low, high, &stop_flag))
+                .collect::<Vec<_>>()
+                .concat()
+        });
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        units_done += 1;
+        found_count += primes.len() as u64;
+        sender.send(WorkerMessage::Log(tr!("Work unit {} [{}, {}]: {} primes", id, format_number(low), format_number(high), format_number(primes.len() as u64)))).ok();
+        send_line(&mut output, &ToCoordinator::Done { id, primes }).map_err(network)?;
+    }
+
+    sender.send(WorkerMessage::Log(tr!("Coordinator finished. {} work units, {} primes sent", format_number(units_done), format_number(found_count)))).ok();
+    sender.send(WorkerMessage::Finished { found: found_count, files: Vec::new() }).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
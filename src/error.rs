@@ -0,0 +1,55 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SieveError {
+    #[error("Invalid number in settings: {0}")]
+    Parse(#[from] std::num::ParseIntError),
+
+    #[error("Invalid settings: {0}")]
+    Config(String),
+
+    #[error("Failed to create output directory {path}: {source}")]
+    CreateDir { path: PathBuf, source: io::Error },
+
+    #[error("Failed to open output file {path}: {source}")]
+    OpenFile { path: PathBuf, source: io::Error },
+
+    #[error("Failed to write output file {path}: {source}")]
+    Write { path: PathBuf, source: io::Error },
+
+    #[error("Failed to allocate segment buffer: {0}")]
+    SegmentBuffer(io::Error),
+
+    #[error("Network error ({addr}): {source}")]
+    Network { addr: String, source: io::Error },
+
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Invalid work unit: {0}")]
+    WorkUnit(String),
+
+    // 既知の π(10^k) と数えた個数が違う（篩・設定・小さい素数のキャッシュのどれかがおかしい）
+    #[error("Prime count check failed: {0}")]
+    Miscount(String),
+
+    #[error("Self test failed: {0}")]
+    SelfTest(String),
+
+    #[error("Failed to start worker threads: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+}
+
+impl SieveError {
+    // map_err 用のヘルパー
+    pub fn write(path: &Path) -> impl Fn(io::Error) -> SieveError + '_ {
+        move |source| SieveError::Write { path: path.to_path_buf(), source }
+    }
+}
@@ -0,0 +1,488 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 素因数分解。u64 は小さな素数で試し割りしてから、残りを Pollard の rho 法
+// （Brent の変形）で分解する。u64 を超える数は rho 法で見つからない因数を
+// 楕円曲線法 (ECM) で探す。u128 に収まる余因数の rho 法は BigUint を使わない
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, ToPrimitive, Zero};
+use crate::miller_rabin::{is_bpsw_probable_prime, is_prime, mul_mod, Montgomery128};
+use crate::sieve::simple_sieve;
+use crate::i18n::tr;
+
+// 試し割りする素数の上限
+const TRIAL_DIVISION_LIMIT: u64 = 1000;
+
+// (素因数, 指数) の組。素因数の昇順
+pub type Factorization = Vec<(u64, u32)>;
+
+// n を割り切れるだけ p で割り、指数を factors に追加する
+fn divide_out(factors: &mut Factorization, n: &mut u64, p: u64) {
+    let mut e = 0;
+    while n.is_multiple_of(p) {
+        *n /= p;
+        e += 1;
+    }
+    if e > 0 {
+        factors.push((p, e));
+    }
+}
+
+// n <= 1 は空
+pub fn factorize(n: u64) -> Factorization {
+    let mut factors = Vec::new();
+    let mut n = n;
+    if n <= 1 {
+        return factors;
+    }
+    divide_out(&mut factors, &mut n, 2);
+    let mut p = 3;
+    while p <= TRIAL_DIVISION_LIMIT && p * p <= n {
+        divide_out(&mut factors, &mut n, p);
+        p += 2;
+    }
+    if n > 1 {
+        // 残りは試し割りの上限より大きな素因数だけからなる
+        let mut stack = vec![n];
+        let mut large = Vec::new();
+        while let Some(m) = stack.pop() {
+            if is_prime(m) {
+                large.push(m);
+            } else {
+                let d = pollard_rho(m);
+                stack.push(d);
+                stack.push(m / d);
+            }
+        }
+        large.sort_unstable();
+        for q in large {
+            match factors.last_mut() {
+                Some((last, e)) if *last == q => *e += 1,
+                _ => factors.push((q, 1)),
+            }
+        }
+    }
+    factors
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// 合成数 n の非自明な約数を 1 つ返す（n は奇数で素数でないこと）
+fn pollard_rho(n: u64) -> u64 {
+    // 平方数は rho 法では見つけにくい
+    let root = (n as f64).sqrt() as u64;
+    for r in root.saturating_sub(1)..=root + 1 {
+        if r > 1 && r.checked_mul(r) == Some(n) {
+            return r;
+        }
+    }
+    for c in 1.. {
+        let f = |x: u64| (mul_mod(x, x, n) + c) % n;
+        let (mut x, mut y, mut q, mut g) = (2u64, 2u64, 1u64, 1u64);
+        let mut ys = 2u64;
+        let mut r = 1u64;
+        const BATCH: u64 = 128;
+        while g == 1 {
+            x = y;
+            for _ in 0..r {
+                y = f(y);
+            }
+            let mut k = 0;
+            while k < r && g == 1 {
+                ys = y;
+                for _ in 0..BATCH.min(r - k) {
+                    y = f(y);
+                    q = mul_mod(q, x.abs_diff(y), n);
+                }
+                g = gcd(q, n);
+                k += BATCH;
+            }
+            r *= 2;
+        }
+        if g == n {
+            // まとめて掛けた中に n の倍数があった場合は 1 つずつやり直す
+            loop {
+                ys = f(ys);
+                g = gcd(x.abs_diff(ys), n);
+                if g > 1 {
+                    break;
+                }
+            }
+        }
+        if g != n {
+            return g;
+        }
+    }
+    unreachable!()
+}
+
+// 素因数分解を "3 * 11 * 17" や "2^3 * 5" の形に
+pub fn format_factors(factors: &[(u64, u32)]) -> String {
+    factors
+        .iter()
+        .map(|&(p, e)| if e == 1 { p.to_string() } else { format!("{}^{}", p, e) })
+        .collect::<Vec<_>>()
+        .join(" * ")
+}
+
+// u64 を超える数の素因数
+#[derive(Debug, Clone, PartialEq)]
+pub struct BigFactor {
+    pub value: BigUint,
+    pub exponent: u32,
+    // false の場合は分解できなかった合成数
+    pub prime: bool,
+}
+
+// rho 法の反復回数の上限（これで見つからなければ ECM に進む）
+const BIG_RHO_ITERATIONS: u64 = 200_000;
+
+// ECM の段階 (B1, 曲線の数)。概ね 15, 20, 25, 30 桁の因数向け
+const ECM_LEVELS: [(u64, u32); 4] = [(2_000, 25), (11_000, 90), (50_000, 300), (250_000, 700)];
+
+// 停止された場合は None。status には段階の切り替わりを知らせる
+pub fn factorize_big(n: &BigUint, stop_flag: &AtomicBool, mut status: impl FnMut(String)) -> Option<Vec<BigFactor>> {
+    let mut factors: Vec<BigFactor> = Vec::new();
+    let mut add = |value: BigUint, exponent: u32, prime: bool| {
+        match factors.iter_mut().find(|f| f.value == value) {
+            Some(f) => f.exponent += exponent,
+            None => factors.push(BigFactor { value, exponent, prime }),
+        }
+    };
+    let mut stack = vec![n.clone()];
+    while let Some(m) = stack.pop() {
+        if m.is_one() || m.is_zero() {
+            continue;
+        }
+        if let Some(small) = m.to_u64() {
+            for (p, e) in factorize(small) {
+                add(BigUint::from(p), e, true);
+            }
+            continue;
+        }
+        // 小さな素因数を先に取り除く
+        if let Some(p) = [2].into_iter().chain((3u32..TRIAL_DIVISION_LIMIT as u32).step_by(2)).find(|&p| (&m % p).is_zero()) {
+            stack.push(BigUint::from(p));
+            stack.push(m / p);
+            continue;
+        }
+        if is_bpsw_probable_prime(&m) {
+            add(m, 1, true);
+            continue;
+        }
+        let root = m.sqrt();
+        if &root * &root == m {
+            stack.push(root.clone());
+            stack.push(root);
+            continue;
+        }
+        if stop_flag.load(Ordering::SeqCst) {
+            return None;
+        }
+        status(tr!("Pollard rho on a {}-digit cofactor", m.to_string().len()));
+        let mut divisor = match m.to_u128() {
+            Some(small) => pollard_rho_u128(small, BIG_RHO_ITERATIONS, stop_flag).map(BigUint::from),
+            None => pollard_rho_big(&m, BIG_RHO_ITERATIONS, stop_flag),
+        };
+        for (level, &(b1, curves)) in ECM_LEVELS.iter().enumerate() {
+            if divisor.is_some() {
+                break;
+            }
+            if stop_flag.load(Ordering::SeqCst) {
+                return None;
+            }
+            status(tr!("ECM level {} (B1 = {}, {} curves) on a {}-digit cofactor", level + 1, b1, curves, m.to_string().len()));
+            divisor = ecm(&m, b1, curves, stop_flag);
+        }
+        match divisor {
+            Some(d) => {
+                stack.push(&m / &d);
+                stack.push(d);
+            }
+            None if stop_flag.load(Ordering::SeqCst) => return None,
+            None => add(m, 1, false),
+        }
+    }
+    factors.sort_by(|a, b| a.value.cmp(&b.value));
+    Some(factors)
+}
+
+fn pollard_rho_big(n: &BigUint, max_iterations: u64, stop_flag: &AtomicBool) -> Option<BigUint> {
+    for c in 1u32..=3 {
+        let f = |x: &BigUint| (x * x + c) % n;
+        let mut y = BigUint::from(2u32);
+        let mut q = BigUint::one();
+        let mut iterations = 0u64;
+        let mut r = 1u64;
+        'outer: while iterations < max_iterations {
+            let x = y.clone();
+            for _ in 0..r {
+                y = f(&y);
+            }
+            let mut k = 0;
+            while k < r {
+                let mut ys = y.clone();
+                let steps = 128.min(r - k);
+                for _ in 0..steps {
+                    y = f(&y);
+                    q = q * if x > y { &x - &y } else { &y - &x } % n;
+                }
+                iterations += steps;
+                let g = q.gcd(n);
+                if g.is_one() {
+                    k += 128;
+                    continue;
+                }
+                if &g == n {
+                    // まとめて掛けた中に n の倍数があった場合は 1 つずつやり直す
+                    loop {
+                        ys = f(&ys);
+                        let g = (if x > ys { &x - &ys } else { &ys - &x }).gcd(n);
+                        if !g.is_one() {
+                            if &g == n {
+                                break 'outer;
+                            }
+                            return Some(g);
+                        }
+                    }
+                }
+                return Some(g);
+            }
+            if stop_flag.load(Ordering::SeqCst) {
+                return None;
+            }
+            r *= 2;
+        }
+    }
+    None
+}
+
+fn gcd_u128(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// pollard_rho_big と同じ手順を u128 のモンゴメリ表現で行う（n は奇数の合成数）。
+// 値に R を掛けたままでも n との GCD は変わらない
+fn pollard_rho_u128(n: u128, max_iterations: u64, stop_flag: &AtomicBool) -> Option<u128> {
+    let mont = Montgomery128::new(n);
+    for c in 1u32..=3 {
+        let c = mont.enter(c as u128);
+        let f = |x: u128| mont.add(mont.mul(x, x), c);
+        let mut y = mont.enter(2);
+        let mut q = mont.enter(1);
+        let mut iterations = 0u64;
+        let mut r = 1u64;
+        'outer: while iterations < max_iterations {
+            let x = y;
+            for _ in 0..r {
+                y = f(y);
+            }
+            let mut k = 0;
+            while k < r {
+                let mut ys = y;
+                let steps = 128.min(r - k);
+                for _ in 0..steps {
+                    y = f(y);
+                    q = mont.mul(q, x.abs_diff(y));
+                }
+                iterations += steps;
+                let g = gcd_u128(q, n);
+                if g == 1 {
+                    k += 128;
+                    continue;
+                }
+                if g == n {
+                    // まとめて掛けた中に n の倍数があった場合は 1 つずつやり直す
+                    loop {
+                        ys = f(ys);
+                        let g = gcd_u128(x.abs_diff(ys), n);
+                        if g != 1 {
+                            if g == n {
+                                break 'outer;
+                            }
+                            return Some(g);
+                        }
+                    }
+                }
+                return Some(g);
+            }
+            if stop_flag.load(Ordering::SeqCst) {
+                return None;
+            }
+            r *= 2;
+        }
+    }
+    None
+}
+
+// モンゴメリ型曲線 By^2 = x^3 + Ax^2 + x 上の点の射影 x 座標 (X : Z)
+#[derive(Clone)]
+struct Point {
+    x: BigUint,
+    z: BigUint,
+}
+
+struct Curve<'a> {
+    n: &'a BigUint,
+    // (A + 2) / 4
+    a24: BigUint,
+}
+
+impl Curve<'_> {
+    fn add_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let s = a + b;
+        if &s >= self.n { s - self.n } else { s }
+    }
+
+    fn sub_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        if a >= b { a - b } else { self.n - b + a }
+    }
+
+    fn mul_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a * b % self.n
+    }
+
+    fn double(&self, p: &Point) -> Point {
+        let s = self.add_mod(&p.x, &p.z);
+        let d = self.sub_mod(&p.x, &p.z);
+        let t1 = self.mul_mod(&s, &s);
+        let t2 = self.mul_mod(&d, &d);
+        let t3 = self.sub_mod(&t1, &t2);
+        let z = self.mul_mod(&t3, &self.add_mod(&t2, &self.mul_mod(&self.a24, &t3)));
+        Point { x: self.mul_mod(&t1, &t2), z }
+    }
+
+    // p + q（diff = p - q）
+    fn add(&self, p: &Point, q: &Point, diff: &Point) -> Point {
+        let u = self.mul_mod(&self.sub_mod(&p.x, &p.z), &self.add_mod(&q.x, &q.z));
+        let v = self.mul_mod(&self.add_mod(&p.x, &p.z), &self.sub_mod(&q.x, &q.z));
+        let s = self.add_mod(&u, &v);
+        let d = self.sub_mod(&u, &v);
+        Point { x: self.mul_mod(&diff.z, &self.mul_mod(&s, &s)), z: self.mul_mod(&diff.x, &self.mul_mod(&d, &d)) }
+    }
+
+    // モンゴメリのはしご
+    fn multiply(&self, k: u64, p: &Point) -> Point {
+        if k == 1 {
+            return p.clone();
+        }
+        let mut r0 = p.clone();
+        let mut r1 = self.double(p);
+        for i in (0..63 - k.leading_zeros()).rev() {
+            if k >> i & 1 == 1 {
+                r0 = self.add(&r1, &r0, p);
+                r1 = self.double(&r1);
+            } else {
+                r1 = self.add(&r1, &r0, p);
+                r0 = self.double(&r0);
+            }
+        }
+        r0
+    }
+}
+
+// 第 2 段階の刻み幅
+const ECM_STAGE2_D: u64 = 210;
+
+// Lenstra の楕円曲線法。曲線は鈴木のパラメータ付け (sigma = 6, 7, ...)。
+// 第 1 段階は B1 以下の素数べき、第 2 段階は B1 < q <= 50 B1 の素数 q を 1 つだけ含む位数を扱う
+pub fn ecm(n: &BigUint, b1: u64, curves: u32, stop_flag: &AtomicBool) -> Option<BigUint> {
+    // 第 2 段階の開始位置を (m - 1) D >= D にするため
+    let b1 = b1.max(2 * ECM_STAGE2_D);
+    let b2 = b1 * 50;
+    let primes = simple_sieve(b2);
+    let split = primes.partition_point(|&p| p <= b1);
+    let nontrivial = |g: BigUint| (!g.is_one() && &g != n).then_some(g);
+
+    for sigma in 6..6 + curves as u64 {
+        if stop_flag.load(Ordering::SeqCst) {
+            return None;
+        }
+        // u = sigma^2 - 5, v = 4 sigma, x0 = u^3, z0 = v^3, (A + 2) / 4 = (v - u)^3 (3u + v) / (16 u^3 v)
+        let sigma = BigUint::from(sigma);
+        let u = (&sigma * &sigma - 5u32) % n;
+        let v = (&sigma * 4u32) % n;
+        let u3 = u.modpow(&BigUint::from(3u32), n);
+        let v3 = v.modpow(&BigUint::from(3u32), n);
+        let diff = if v >= u { &v - &u } else { n - &u + &v };
+        let numerator = diff.modpow(&BigUint::from(3u32), n) * ((&u * 3u32 + &v) % n) % n;
+        let denominator = (&u3 * &v * 16u32) % n;
+        let Some(inverse) = denominator.modinv(n) else {
+            // 逆元がない場合は n と共通因数を持つ
+            if let Some(g) = nontrivial(denominator.gcd(n)) {
+                return Some(g);
+            }
+            continue;
+        };
+        let curve = Curve { n, a24: numerator * inverse % n };
+
+        // 第 1 段階
+        let mut q = Point { x: u3, z: v3 };
+        for &p in &primes[..split] {
+            let mut power = p;
+            while power <= b1 / p {
+                power *= p;
+            }
+            q = curve.multiply(power, &q);
+        }
+        if let Some(g) = nontrivial(q.z.gcd(n)) {
+            return Some(g);
+        }
+        if stop_flag.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        // 第 2 段階: 素数 q = mD ± j について x(mD Q) = x(j Q) かどうかをまとめて調べる
+        let half = ECM_STAGE2_D / 2;
+        let mut baby: Vec<Option<Point>> = vec![None; half as usize + 1];
+        let q2 = curve.double(&q);
+        let (mut prev, mut cur) = (q.clone(), curve.add(&q2, &q, &q));
+        baby[1] = Some(q.clone());
+        let mut j = 3;
+        while j <= half {
+            baby[j as usize] = Some(cur.clone());
+            let next = curve.add(&cur, &q2, &prev);
+            prev = cur;
+            cur = next;
+            j += 2;
+        }
+        let step = curve.multiply(ECM_STAGE2_D, &q);
+        let mut m = b1 / ECM_STAGE2_D;
+        let mut giant_prev = curve.multiply((m - 1) * ECM_STAGE2_D, &q);
+        let mut giant = curve.multiply(m * ECM_STAGE2_D, &q);
+        let mut acc = BigUint::one();
+        let mut index = split;
+        while index < primes.len() {
+            let center = m * ECM_STAGE2_D;
+            while index < primes.len() && primes[index] <= center + half {
+                let j = primes[index].abs_diff(center) as usize;
+                if let Some(b) = &baby[j] {
+                    let t = curve.sub_mod(&curve.mul_mod(&giant.x, &b.z), &curve.mul_mod(&b.x, &giant.z));
+                    acc = curve.mul_mod(&acc, &t);
+                }
+                index += 1;
+            }
+            let next = curve.add(&giant, &step, &giant_prev);
+            giant_prev = giant;
+            giant = next;
+            m += 1;
+        }
+        if let Some(g) = nontrivial(acc.gcd(n)) {
+            return Some(g);
+        }
+    }
+    None
+}
@@ -0,0 +1,417 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 出力先の手前に挟むフィルタ。条件を満たす素数だけを内側の sink に渡す
+
+use crate::output::PrimeSink;
+use crate::script::FilterScript;
+use crate::i18n::tr;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// p mod modulus が residues のいずれかに一致する素数だけを通す。
+// modulus = 0 の場合は何もしない（すべて通す）
+pub struct ResidueFilter<S> {
+    inner: S,
+    modulus: u64,
+    selected: Vec<bool>,
+    counts: Vec<u64>,
+    written: u64,
+}
+
+impl<S: PrimeSink> ResidueFilter<S> {
+    pub fn new(inner: S, modulus: u64, residues: &[u64]) -> Self {
+        let mut selected = vec![false; modulus as usize];
+        for &r in residues {
+            if modulus > 0 {
+                selected[(r % modulus) as usize] = true;
+            }
+        }
+        ResidueFilter { inner, modulus, selected, counts: vec![0; modulus as usize], written: 0 }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.modulus > 0
+    }
+
+    // 内側の sink に渡した素数の個数
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    // 各剰余類の素数の個数（フィルタで落としたものも含む）。添字が剰余
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    // 終了時のログ用。modulus が大きい場合は選択した剰余類のみ
+    pub fn summary(&self) -> String {
+        let classes = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|&(r, &n)| self.selected[r] || (self.modulus <= 64 && n > 0))
+            .map(|(r, &n)| format!("{}{}: {}", if self.selected[r] { "*" } else { "" }, r, n))
+            .collect::<Vec<_>>();
+        tr!("Primes per residue class mod {} (* = written): {}", self.modulus, classes.join(", "))
+    }
+}
+
+impl<S: PrimeSink> PrimeSink for ResidueFilter<S> {
+    fn write_prime(&mut self, p: u64) -> io::Result<()> {
+        if self.modulus == 0 {
+            self.inner.write_prime(p)?;
+            self.written += 1;
+            return Ok(());
+        }
+        let r = (p % self.modulus) as usize;
+        if self.selected[r] {
+            // 書き込みに失敗した場合は再試行されるので、成功してから数える
+            self.inner.write_prime(p)?;
+            self.written += 1;
+        }
+        self.counts[r] += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.inner.current_path()
+    }
+
+    fn relocate(&mut self, dir: &Path) -> io::Result<()> {
+        self.inner.relocate(dir)
+    }
+
+    fn written_files(&self) -> Vec<PathBuf> {
+        self.inner.written_files()
+    }
+}
+
+// 10 進表記の性質による条件式。例: "palindrome & digit_sum % 3 == 2", "repunit | near_repdigit"
+// 演算子は ! (否定), & (かつ), | (または) と括弧。比較は ==, !=, <, <=, >, >=
+#[derive(Debug, Clone, PartialEq)]
+pub enum DigitExpr {
+    Palindrome,
+    // すべての桁が 1
+    Repunit,
+    // 1 桁を除いてすべて同じ数字（例: 11111111211）
+    NearRepdigit,
+    // 各桁の和（modulus があればその剰余）を比較する
+    DigitSum { modulus: Option<u64>, op: CmpOp, value: u64 },
+    // 桁数を比較する
+    Digits { op: CmpOp, value: u64 },
+    Not(Box<DigitExpr>),
+    And(Box<DigitExpr>, Box<DigitExpr>),
+    Or(Box<DigitExpr>, Box<DigitExpr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    pub const ALL: [CmpOp; 6] = [CmpOp::Eq, CmpOp::Ne, CmpOp::Lt, CmpOp::Le, CmpOp::Gt, CmpOp::Ge];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        }
+    }
+
+    fn apply(self, a: u64, b: u64) -> bool {
+        match self {
+            CmpOp::Eq => a == b,
+            CmpOp::Ne => a != b,
+            CmpOp::Lt => a < b,
+            CmpOp::Le => a <= b,
+            CmpOp::Gt => a > b,
+            CmpOp::Ge => a >= b,
+        }
+    }
+}
+
+impl DigitExpr {
+    pub fn parse(input: &str) -> Result<DigitExpr, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(t) => Err(format!("unexpected '{}'", t)),
+        }
+    }
+
+    pub fn matches(&self, p: u64) -> bool {
+        let mut buf = [0u8; 20];
+        let digits = decimal_digits(p, &mut buf);
+        self.eval(digits)
+    }
+
+    // digits は上位桁から並んだ各桁の値
+    fn eval(&self, digits: &[u8]) -> bool {
+        match self {
+            DigitExpr::Palindrome => digits.iter().eq(digits.iter().rev()),
+            DigitExpr::Repunit => digits.iter().all(|&d| d == 1),
+            DigitExpr::NearRepdigit => {
+                // 最初の 3 桁のうち 2 つ以上に現れる数字が「同じ数字」
+                let common = if digits.len() < 3 {
+                    return false;
+                } else if digits[0] == digits[1] || digits[0] == digits[2] {
+                    digits[0]
+                } else {
+                    digits[1]
+                };
+                digits.iter().filter(|&&d| d != common).count() == 1
+            }
+            DigitExpr::DigitSum { modulus, op, value } => {
+                let sum = digits.iter().map(|&d| d as u64).sum::<u64>();
+                let sum = match modulus {
+                    Some(m) => sum % m,
+                    None => sum,
+                };
+                op.apply(sum, *value)
+            }
+            DigitExpr::Digits { op, value } => op.apply(digits.len() as u64, *value),
+            DigitExpr::Not(e) => !e.eval(digits),
+            DigitExpr::And(a, b) => a.eval(digits) && b.eval(digits),
+            DigitExpr::Or(a, b) => a.eval(digits) || b.eval(digits),
+        }
+    }
+}
+
+fn decimal_digits(mut n: u64, buf: &mut [u8; 20]) -> &[u8] {
+    let mut i = buf.len();
+    loop {
+        i -= 1;
+        buf[i] = (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    &buf[i..]
+}
+
+fn tokenize(input: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(chars[start..i].iter().collect());
+        } else if matches!(c, '=' | '!' | '<' | '>') && chars.get(i + 1) == Some(&'=') {
+            tokens.push(format!("{}=", c));
+            i += 2;
+        } else if matches!(c, '!' | '<' | '>' | '&' | '|' | '(' | ')' | '%') {
+            tokens.push(c.to_string());
+            i += 1;
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("unexpected end of expression")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn or(&mut self) -> Result<DigitExpr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some("|") {
+            self.pos += 1;
+            expr = DigitExpr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<DigitExpr, String> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some("&") {
+            self.pos += 1;
+            expr = DigitExpr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<DigitExpr, String> {
+        let token = self.next()?;
+        match token.as_str() {
+            "!" => Ok(DigitExpr::Not(Box::new(self.unary()?))),
+            "(" => {
+                let expr = self.or()?;
+                match self.next()?.as_str() {
+                    ")" => Ok(expr),
+                    t => Err(format!("expected ')' but found '{}'", t)),
+                }
+            }
+            "palindrome" => Ok(DigitExpr::Palindrome),
+            "repunit" => Ok(DigitExpr::Repunit),
+            "near_repdigit" => Ok(DigitExpr::NearRepdigit),
+            "digit_sum" => {
+                let modulus = if self.peek() == Some("%") {
+                    self.pos += 1;
+                    match self.number()? {
+                        0 => return Err("digit_sum modulus must be greater than 0".to_string()),
+                        m => Some(m),
+                    }
+                } else {
+                    None
+                };
+                let op = self.cmp_op()?;
+                Ok(DigitExpr::DigitSum { modulus, op, value: self.number()? })
+            }
+            "digits" => {
+                let op = self.cmp_op()?;
+                Ok(DigitExpr::Digits { op, value: self.number()? })
+            }
+            t => Err(format!("unknown condition '{}'", t)),
+        }
+    }
+
+    fn cmp_op(&mut self) -> Result<CmpOp, String> {
+        let token = self.next()?;
+        CmpOp::ALL
+            .into_iter()
+            .find(|op| op.symbol() == token)
+            .ok_or_else(|| format!("expected a comparison but found '{}'", token))
+    }
+
+    fn number(&mut self) -> Result<u64, String> {
+        let token = self.next()?;
+        token.parse().map_err(|_| format!("expected a number but found '{}'", token))
+    }
+}
+
+// 条件式を満たす素数だけを通す
+pub struct DigitFilter<S> {
+    inner: S,
+    expr: Option<DigitExpr>,
+}
+
+impl<S: PrimeSink> DigitFilter<S> {
+    // expr = None の場合は何もしない（すべて通す）
+    pub fn new(inner: S, expr: Option<DigitExpr>) -> Self {
+        DigitFilter { inner, expr }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.expr.is_some()
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: PrimeSink> PrimeSink for DigitFilter<S> {
+    fn write_prime(&mut self, p: u64) -> io::Result<()> {
+        match &self.expr {
+            Some(expr) if !expr.matches(p) => Ok(()),
+            _ => self.inner.write_prime(p),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.inner.current_path()
+    }
+
+    fn relocate(&mut self, dir: &Path) -> io::Result<()> {
+        self.inner.relocate(dir)
+    }
+
+    fn written_files(&self) -> Vec<PathBuf> {
+        self.inner.written_files()
+    }
+}
+
+// スクリプトの条件式を満たす素数だけを通す
+pub struct ScriptFilter<S> {
+    inner: S,
+    script: Option<FilterScript>,
+}
+
+impl<S: PrimeSink> ScriptFilter<S> {
+    // script = None の場合は何もしない（すべて通す）
+    pub fn new(inner: S, script: Option<FilterScript>) -> Self {
+        ScriptFilter { inner, script }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.script.is_some()
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: PrimeSink> PrimeSink for ScriptFilter<S> {
+    fn write_prime(&mut self, p: u64) -> io::Result<()> {
+        if let Some(script) = &mut self.script {
+            let matched = script
+                .matches(p)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("filter_script: {}", e)))?;
+            if !matched {
+                return Ok(());
+            }
+        }
+        self.inner.write_prime(p)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.inner.current_path()
+    }
+
+    fn relocate(&mut self, dir: &Path) -> io::Result<()> {
+        self.inner.relocate(dir)
+    }
+
+    fn written_files(&self) -> Vec<PathBuf> {
+        self.inner.written_files()
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 日本語フォントの読み込み。embed-font フィーチャーでは assets/NotoSansJP-Regular.ttf を
+// バイナリに埋め込む。それ以外は実行ファイルの隣の assets/、カレントディレクトリの assets/、
+// OS 標準の日本語フォントの順に探す
+
+use std::path::PathBuf;
+use eframe::egui;
+
+const FONT_NAME: &str = "japanese";
+
+// assets/ に置くフォントのファイル名（先にあるものを優先）
+const ASSET_FONTS: [&str; 2] = ["NotoSansJP-Regular.ttf", "NotoSansJP-Black.ttf"];
+
+// OS 標準の日本語フォント
+#[cfg(windows)]
+const SYSTEM_FONTS: &[&str] = &[
+    "C:\\Windows\\Fonts\\YuGothM.ttc",
+    "C:\\Windows\\Fonts\\meiryo.ttc",
+    "C:\\Windows\\Fonts\\msgothic.ttc",
+];
+#[cfg(target_os = "macos")]
+const SYSTEM_FONTS: &[&str] = &[
+    "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/Library/Fonts/Arial Unicode.ttf",
+];
+#[cfg(not(any(windows, target_os = "macos")))]
+const SYSTEM_FONTS: &[&str] = &[
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/fonts-japanese-gothic.ttf",
+    "/usr/share/fonts/opentype/ipafont-gothic/ipagp.ttf",
+];
+
+#[cfg(feature = "embed-font")]
+static EMBEDDED_FONT: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/NotoSansJP-Regular.ttf"));
+
+// 探すフォントファイルの候補（優先順）
+pub fn font_candidates() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(|p| p.join("assets"))) {
+        dirs.push(dir);
+    }
+    dirs.push(PathBuf::from("assets"));
+    let mut candidates: Vec<PathBuf> = dirs
+        .iter()
+        .flat_map(|dir| ASSET_FONTS.iter().map(move |name| dir.join(name)))
+        .collect();
+    candidates.extend(SYSTEM_FONTS.iter().map(PathBuf::from));
+    candidates
+}
+
+// 日本語フォントを既定フォントの後ろ（欧文は既定フォントのまま）に追加する。
+// 読み込んだフォントの出どころを返し、見つからなければ None
+pub fn install_japanese_font(ctx: &egui::Context) -> Option<String> {
+    let (data, source) = load_font_data()?;
+    let mut fonts = egui::FontDefinitions::default();
+    fonts.font_data.insert(FONT_NAME.to_string(), egui::FontData::from_owned(data));
+    for family in [egui::FontFamily::Proportional, egui::FontFamily::Monospace] {
+        fonts.families.entry(family).or_default().push(FONT_NAME.to_string());
+    }
+    ctx.set_fonts(fonts);
+    Some(source)
+}
+
+#[cfg(feature = "embed-font")]
+fn load_font_data() -> Option<(Vec<u8>, String)> {
+    Some((EMBEDDED_FONT.to_vec(), "embedded NotoSansJP-Regular.ttf".to_string()))
+}
+
+#[cfg(not(feature = "embed-font"))]
+fn load_font_data() -> Option<(Vec<u8>, String)> {
+    font_candidates()
+        .into_iter()
+        .find_map(|path| std::fs::read(&path).ok().map(|data| (data, path.display().to_string())))
+}
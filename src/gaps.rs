@@ -0,0 +1,175 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 素数ギャップの解析。素数を昇順に push していき、最大ギャップの更新記録と
+// 区間ごとの平均・中央値・最大ギャップを集計する
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub const REPORT_FILE_NAME: &str = "gap_report.txt";
+
+// p の次の素数が p + gap で、それまでのどのギャップよりも大きい
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordGap {
+    pub gap: u64,
+    pub after: u64,
+}
+
+// 区間 [start, end] に上側の素数が入るギャップの統計
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IntervalGaps {
+    pub start: u64,
+    pub end: u64,
+    pub count: u64,
+    pub mean: f64,
+    pub median: u64,
+    pub max: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GapReport {
+    pub prime_count: u64,
+    pub first_prime: Option<u64>,
+    pub last_prime: Option<u64>,
+    pub records: Vec<RecordGap>,
+    pub intervals: Vec<IntervalGaps>,
+}
+
+pub struct GapAnalyzer {
+    range_start: u64,
+    interval: u64,
+    prev: Option<u64>,
+    report: GapReport,
+    // 現在の区間
+    interval_index: u64,
+    histogram: Vec<u64>, // ギャップ値ごとの出現数（中央値用）
+    sum: u64,
+    count: u64,
+    max: u64,
+}
+
+impl GapAnalyzer {
+    // interval = 0 の場合は範囲全体を 1 区間として扱う
+    pub fn new(range_start: u64, interval: u64) -> Self {
+        GapAnalyzer {
+            range_start,
+            interval,
+            prev: None,
+            report: GapReport::default(),
+            interval_index: 0,
+            histogram: Vec::new(),
+            sum: 0,
+            count: 0,
+            max: 0,
+        }
+    }
+
+    pub fn push(&mut self, p: u64) {
+        self.report.prime_count += 1;
+        self.report.last_prime = Some(p);
+        let prev = match self.prev.replace(p) {
+            Some(prev) => prev,
+            None => {
+                self.report.first_prime = Some(p);
+                return;
+            }
+        };
+        let gap = p - prev;
+
+        if self.report.records.last().is_none_or(|r| gap > r.gap) {
+            self.report.records.push(RecordGap { gap, after: prev });
+        }
+
+        if let Some(index) = p.saturating_sub(self.range_start).checked_div(self.interval) {
+            if index != self.interval_index {
+                self.close_interval(prev);
+                self.interval_index = index;
+            }
+        }
+        let slot = gap as usize;
+        if self.histogram.len() <= slot {
+            self.histogram.resize(slot + 1, 0);
+        }
+        self.histogram[slot] += 1;
+        self.sum += gap;
+        self.count += 1;
+        self.max = self.max.max(gap);
+    }
+
+    // 現在の区間を確定する。last はその区間に含まれる最後の値
+    fn close_interval(&mut self, last: u64) {
+        if self.count == 0 {
+            return;
+        }
+        let (start, end) = if self.interval > 0 {
+            let start = self.range_start + self.interval_index * self.interval;
+            (start, start.saturating_add(self.interval - 1))
+        } else {
+            (self.range_start, last)
+        };
+        let mut median = 0;
+        let mut seen = 0;
+        for (gap, &n) in self.histogram.iter().enumerate() {
+            seen += n;
+            if seen * 2 >= self.count {
+                median = gap as u64;
+                break;
+            }
+        }
+        self.report.intervals.push(IntervalGaps {
+            start,
+            end,
+            count: self.count,
+            mean: self.sum as f64 / self.count as f64,
+            median,
+            max: self.max,
+        });
+        self.histogram.clear();
+        self.sum = 0;
+        self.count = 0;
+        self.max = 0;
+    }
+
+    pub fn finish(mut self) -> GapReport {
+        if let Some(last) = self.prev {
+            self.close_interval(last);
+        }
+        self.report
+    }
+}
+
+impl GapReport {
+    pub fn max_gap(&self) -> Option<&RecordGap> {
+        self.records.last()
+    }
+
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        writeln!(w, "# Prime gap report")?;
+        match (self.first_prime, self.last_prime) {
+            (Some(first), Some(last)) => writeln!(w, "primes: {} ({} .. {})", self.prime_count, first, last)?,
+            _ => writeln!(w, "primes: 0")?,
+        }
+        if let Some(max) = self.max_gap() {
+            writeln!(w, "max gap: {} (after {})", max.gap, max.after)?;
+        }
+        writeln!(w)?;
+        writeln!(w, "## Record gaps")?;
+        writeln!(w, "gap\tafter\tnext")?;
+        for r in &self.records {
+            writeln!(w, "{}\t{}\t{}", r.gap, r.after, r.after + r.gap)?;
+        }
+        writeln!(w)?;
+        writeln!(w, "## Intervals")?;
+        writeln!(w, "start\tend\tgaps\tmean\tmedian\tmax")?;
+        for i in &self.intervals {
+            writeln!(w, "{}\t{}\t{}\t{:.3}\t{}\t{}", i.start, i.end, i.count, i.mean, i.median, i.max)?;
+        }
+        w.flush()
+    }
+}
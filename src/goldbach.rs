@@ -0,0 +1,248 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// ゴールドバッハ予想の検証モード。[prime_min, prime_max] の各偶数 n について
+// n = p + q（p <= q, 共に素数）となる最小の p を探し、見つからない n を報告する
+
+use std::fs::{create_dir_all, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use bitvec::prelude::*;
+use rayon::prelude::*;
+use crate::app::{ProgressPhase, WorkerMessage};
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::sieve::{build_thread_pool, format_eta, integer_sqrt, segment_ranges, segmented_sieve, simple_sieve};
+use crate::i18n::tr;
+
+pub const REPORT_FILE_NAME: &str = "goldbach_report.txt";
+
+// 1 チャンクあたりの偶数の個数
+const CHUNK_EVENS: u64 = 1 << 16;
+
+// limit 以下の素数の判定表（奇数のみ保持。ビット i が 2i+1 に対応）
+pub struct PrimeBits {
+    bits: BitVec<u64, Lsb0>,
+    limit: u64,
+}
+
+impl PrimeBits {
+    // セグメント篩で作る。停止された場合は None
+    pub fn sieve(limit: u64, segment_size: u64, pool: &rayon::ThreadPool, stop_flag: &Arc<AtomicBool>) -> Option<Self> {
+        let mut bits = bitvec![u64, Lsb0; 0; (limit / 2 + 1) as usize];
+        let small_primes = simple_sieve(integer_sqrt(limit) + 1);
+        let segments = segment_ranges(0, limit, segment_size);
+        for batch in segments.chunks(pool.current_num_threads().max(1)) {
+            let results: Vec<Vec<u64>> = pool.install(|| {
+                batch.par_iter().map(|&(low, high)| segmented_sieve(&small_primes, low, high, stop_flag)).collect()
+            });
+            if stop_flag.load(Ordering::SeqCst) {
+                return None;
+            }
+            for p in results.into_iter().flatten().filter(|&p| p != 2) {
+                bits.set((p / 2) as usize, true);
+            }
+        }
+        Some(PrimeBits { bits, limit })
+    }
+
+    pub fn is_prime(&self, n: u64) -> bool {
+        debug_assert!(n <= self.limit);
+        n == 2 || (n & 1 == 1 && self.bits[(n / 2) as usize])
+    }
+
+    // n = p + (n - p) となる最小の素数 p
+    pub fn min_partition(&self, n: u64) -> Option<u64> {
+        if n == 4 {
+            return Some(2);
+        }
+        (3..=n / 2).step_by(2).find(|&p| self.is_prime(p) && self.is_prime(n - p))
+    }
+
+    // p <= n - p となる分割の個数
+    pub fn partition_count(&self, n: u64) -> u64 {
+        if n == 4 {
+            return 1;
+        }
+        (3..=n / 2).step_by(2).filter(|&p| self.is_prime(p) && self.is_prime(n - p)).count() as u64
+    }
+}
+
+// 偶数 n の集計（チャンク単位で作り、昇順に結合する）
+#[derive(Default)]
+struct Summary {
+    evens: u64,
+    failures: Vec<u64>,
+    // 最小の p がそれまでのどの n よりも大きくなった (n, p)
+    records: Vec<(u64, u64)>,
+    sum_min_p: u64,
+    // (r(n), n)
+    min_count: Option<(u64, u64)>,
+    max_count: Option<(u64, u64)>,
+    sum_count: u64,
+}
+
+impl Summary {
+    fn check(bits: &PrimeBits, low: u64, high: u64, count_partitions: bool, stop_flag: &AtomicBool) -> Summary {
+        let mut s = Summary::default();
+        for n in (low..=high).step_by(2) {
+            if n & 0xFFFF == 0 && stop_flag.load(Ordering::SeqCst) {
+                break;
+            }
+            s.evens += 1;
+            match bits.min_partition(n) {
+                Some(p) => {
+                    if s.records.last().is_none_or(|&(_, best)| p > best) {
+                        s.records.push((n, p));
+                    }
+                    s.sum_min_p += p;
+                }
+                None => s.failures.push(n),
+            }
+            if count_partitions {
+                let r = bits.partition_count(n);
+                s.sum_count += r;
+                if s.min_count.is_none_or(|(min, _)| r < min) {
+                    s.min_count = Some((r, n));
+                }
+                if s.max_count.is_none_or(|(max, _)| r > max) {
+                    s.max_count = Some((r, n));
+                }
+            }
+        }
+        s
+    }
+
+    // self の後ろに続く範囲の集計を結合する
+    fn merge(&mut self, other: Summary) {
+        self.evens += other.evens;
+        self.failures.extend(other.failures);
+        for (n, p) in other.records {
+            if self.records.last().is_none_or(|&(_, best)| p > best) {
+                self.records.push((n, p));
+            }
+        }
+        self.sum_min_p += other.sum_min_p;
+        self.sum_count += other.sum_count;
+        if let Some((r, n)) = other.min_count {
+            if self.min_count.is_none_or(|(min, _)| r < min) {
+                self.min_count = Some((r, n));
+            }
+        }
+        if let Some((r, n)) = other.max_count {
+            if self.max_count.is_none_or(|(max, _)| r > max) {
+                self.max_count = Some((r, n));
+            }
+        }
+    }
+
+    fn write_to(&self, path: &Path, low: u64, high: u64, count_partitions: bool) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        writeln!(w, "# Goldbach check")?;
+        writeln!(w, "range: {} .. {} ({} even numbers)", low, high, self.evens)?;
+        writeln!(w, "without partition: {}", self.failures.len())?;
+        for n in &self.failures {
+            writeln!(w, "{}", n)?;
+        }
+        let checked = self.evens - self.failures.len() as u64;
+        if let Some(&(n, p)) = self.records.last() {
+            writeln!(w, "largest minimal prime: {} ({} = {} + {})", p, n, p, n - p)?;
+            writeln!(w, "mean minimal prime: {:.3}", self.sum_min_p as f64 / checked as f64)?;
+        }
+        if count_partitions {
+            if let (Some((min, min_n)), Some((max, max_n))) = (self.min_count, self.max_count) {
+                writeln!(w, "partition count: min {} (n = {}), max {} (n = {}), mean {:.3}",
+                    min, min_n, max, max_n, self.sum_count as f64 / self.evens as f64)?;
+            }
+        }
+        writeln!(w)?;
+        writeln!(w, "## Record minimal primes")?;
+        writeln!(w, "n\tp\tn-p")?;
+        for &(n, p) in &self.records {
+            writeln!(w, "{}\t{}\t{}", n, p, n - p)?;
+        }
+        w.flush()
+    }
+}
+
+pub fn run_goldbach(config: Config, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    sender.send(WorkerMessage::Log(tr!("Running Goldbach check").to_string())).ok();
+
+    let min = config.prime_min.parse::<u64>()?;
+    let max = config.prime_max.parse::<u64>()?;
+    if min > max {
+        return Err(SieveError::Config("prime_min must not exceed prime_max".to_string()));
+    }
+    if config.segment_size == 0 {
+        return Err(SieveError::Config("segment_size must be greater than 0".to_string()));
+    }
+    // 4 以上の偶数のみ対象
+    let low = min.max(4).next_multiple_of(2);
+    let high = max - max % 2;
+    if low > high {
+        return Err(SieveError::Config("the range contains no even numbers >= 4".to_string()));
+    }
+
+    if !config.output_dir.is_empty() {
+        create_dir_all(&config.output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(&config.output_dir), source })?;
+    }
+
+    let pool = build_thread_pool(&config)?;
+    let start_time = Instant::now();
+    sender.send(WorkerMessage::Log(tr!("Sieving primes up to {}", high))).ok();
+    let bits = match PrimeBits::sieve(high, config.segment_size, &pool, &stop_flag) {
+        Some(bits) => bits,
+        None => {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+    };
+
+    let chunks = segment_ranges(low / 2, high / 2, CHUNK_EVENS);
+    let total_evens = high / 2 - low / 2 + 1;
+    let mut summary = Summary::default();
+    for batch in chunks.chunks(pool.current_num_threads().max(1)) {
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        let results: Vec<Summary> = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|&(a, b)| Summary::check(&bits, a * 2, b * 2, config.goldbach_count_partitions, &stop_flag))
+                .collect()
+        });
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        for s in results {
+            for n in &s.failures {
+                sender.send(WorkerMessage::Log(tr!("No Goldbach partition found for {}", n))).ok();
+            }
+            summary.merge(s);
+        }
+        sender.send(WorkerMessage::Progress { current: summary.evens, total: total_evens, phase: ProgressPhase::Testing }).ok();
+        sender.send(WorkerMessage::Eta(format_eta(start_time, summary.evens as f64 / total_evens as f64))).ok();
+    }
+
+    let path = Path::new(&config.output_dir).join(REPORT_FILE_NAME);
+    summary.write_to(&path, low, high, config.goldbach_count_partitions).map_err(SieveError::write(&path))?;
+
+    sender.send(WorkerMessage::Eta(tr!("0 hour 0 min 0 sec").to_string())).ok();
+    if let Some(&(n, p)) = summary.records.last() {
+        sender.send(WorkerMessage::Log(tr!("Largest minimal prime: {} ({} = {} + {})", p, n, p, n - p))).ok();
+    }
+    sender.send(WorkerMessage::Log(tr!(
+        "Finished Goldbach check. {} even numbers checked, {} without partition. Report: {}",
+        summary.evens, summary.failures.len(), path.display()))).ok();
+    sender.send(WorkerMessage::Finished { found: summary.failures.len() as u64, files: vec![path] }).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
@@ -0,0 +1,101 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// GUI の状態（ウィンドウの位置と大きさ、開いていたタブ、ログの表示、入力欄の内容）。
+// settings.txt とは別に gui_state.json に保存し、次に起動したときに戻す。
+// 入力欄は実行しなくても残るように、その時点の内容を設定の形で持つ
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use eframe::egui;
+use crate::app::{SpecialFormsState, Tab};
+use crate::config::Config;
+use crate::paths::gui_state_path;
+
+// 実行中などに落ちても失わないよう、この間隔で変わっていれば保存する（終了時にも保存する）
+pub const AUTO_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+// これより小さいウィンドウの大きさは戻さない（壊れた値の代わり）
+const MIN_WINDOW_SIDE: f32 = 200.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WindowGeometry {
+    // 外枠の左上（論理ピクセル）。分からなければ None
+    pub position: Option<[f32; 2]>,
+    // 内側の大きさ（論理ピクセル）
+    pub size: [f32; 2],
+    pub maximized: bool,
+}
+
+impl WindowGeometry {
+    // 起動時のウィンドウに位置と大きさを反映する
+    pub fn apply(&self, mut builder: egui::ViewportBuilder) -> egui::ViewportBuilder {
+        let [width, height] = self.size;
+        if width.is_finite() && height.is_finite() && width >= MIN_WINDOW_SIDE && height >= MIN_WINDOW_SIDE {
+            builder = builder.with_inner_size([width, height]);
+        }
+        if let Some([x, y]) = self.position.filter(|p| p.iter().all(|v| v.is_finite())) {
+            builder = builder.with_position([x, y]);
+        }
+        builder.with_maximized(self.maximized)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct GuiState {
+    pub window: Option<WindowGeometry>,
+    pub tab: Tab,
+    pub show_log: bool,
+    // Sieve タブの入力欄（検証前の内容）
+    pub inputs: Option<Config>,
+    pub special_forms: Option<SpecialFormsState>,
+    pub check_input: String,
+    pub check_certificate: bool,
+    pub viewer_file: String,
+}
+
+impl Default for GuiState {
+    fn default() -> Self {
+        GuiState {
+            window: None,
+            tab: Tab::Sieve,
+            show_log: true,
+            inputs: None,
+            special_forms: None,
+            check_input: String::new(),
+            check_certificate: true,
+            viewer_file: String::new(),
+        }
+    }
+}
+
+impl GuiState {
+    // 読めなければ（初回や壊れている場合）既定の状態
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&gui_state_path())
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        self.save_to(&gui_state_path())
+    }
+}
@@ -0,0 +1,636 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// UI とログの文字列の翻訳。英語の文字列をキーとして日本語の表を引く。
+// 表にない文字列は英語のまま表示する。引数は {} (順番どおり) または {0}, {1}, ... (位置指定)
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+use crate::config::Language;
+
+// ワーカースレッドからも参照するので設定とは別に持つ
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        1 => Language::Japanese,
+        _ => Language::English,
+    }
+}
+
+// 現在の言語での text
+pub fn translate(text: &'static str) -> &'static str {
+    match language() {
+        Language::English => text,
+        Language::Japanese => japanese(text).unwrap_or(text),
+    }
+}
+
+pub fn japanese(text: &str) -> Option<&'static str> {
+    static TABLE: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+    TABLE.get_or_init(|| JAPANESE.iter().copied().collect()).get(text).copied()
+}
+
+// tr したうえで引数を埋め込む
+pub fn tr_format(template: &'static str, args: &[&dyn Display]) -> String {
+    format_template(translate(template), args)
+}
+
+pub fn format_template(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len() + 16);
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}') {
+            Some(close) if after[..close].bytes().all(|b| b.is_ascii_digit()) => {
+                let index = if close == 0 {
+                    next += 1;
+                    next - 1
+                } else {
+                    after[..close].parse().unwrap_or(usize::MAX)
+                };
+                if let Some(arg) = args.get(index) {
+                    out.push_str(&arg.to_string());
+                }
+                rest = &after[close + 1..];
+            }
+            _ => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// 3 桁ごとにカンマで区切った数（日本語でも同じ）
+pub fn format_number(n: u64) -> String {
+    group_digits(n.to_string())
+}
+
+// 素数の和など u64 に収まらない数
+pub fn format_u128(n: u128) -> String {
+    group_digits(n.to_string())
+}
+
+fn group_digits(digits: String) -> String {
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// 入力欄の数から桁区切り（, _ ' 空白）を取り除く（1_000_000 や 1,000,000 を受け付ける）
+pub fn strip_separators(input: &str) -> String {
+    input.chars().filter(|c| !matches!(c, ',' | '_' | '\'') && !c.is_whitespace()).collect()
+}
+
+// tr!("text") は &'static str、tr!("text {}", arg) は String
+macro_rules! tr {
+    ($text:literal) => {
+        $crate::i18n::translate($text)
+    };
+    ($text:literal, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::tr_format($text, &[$(&$arg as &dyn std::fmt::Display),+])
+    };
+}
+pub(crate) use tr;
+
+// 英語 → 日本語
+const JAPANESE: &[(&str, &str)] = &[
+    ("Calculating...", "計算中..."),
+    ("Keep awake unavailable: {}", "スリープ防止を有効にできません: {}"),
+    ("Special Forms", "特殊形"),
+    ("Primality tools for numbers of special form. Results are shown in the log and appended to special_forms.txt in the output directory.", "特殊な形の数の素数判定ツールです。結果はログに表示され、出力ディレクトリの special_forms.txt に追記されます。"),
+    ("Proth numbers k*2^n+1", "プロス数 k*2^n+1"),
+    ("Fermat number factors", "フェルマー数の因数"),
+    ("Primorial p#±1", "素数階乗 p#±1"),
+    ("Factorial n!±1", "階乗 n!±1"),
+    ("k (odd):", "k（奇数）:"),
+    ("n from:", "n の下限:"),
+    ("n to:", "n の上限:"),
+    ("m from:", "m の下限:"),
+    ("m to:", "m の上限:"),
+    ("max k:", "k の上限:"),
+    ("p up to:", "p の上限:"),
+    ("n up to:", "n の上限:"),
+    ("Start", "開始"),
+    ("Number Check", "素数判定"),
+    ("Tests a single number. Composite numbers are factored (Pollard rho, then ECM for numbers above 2^64); for 64-bit primes a Pratt certificate can be written to pratt_<n>.txt in the output directory.", "1 つの数を判定します。合成数は素因数分解します（Pollard rho、2^64 を超える数は ECM も使用）。64 ビットの素数については出力ディレクトリの pratt_<n>.txt に Pratt 証明書を書き出せます。"),
+    ("Emit Pratt certificate (provable result)", "Pratt 証明書を出力する（証明付きの結果）"),
+    ("Check", "判定"),
+    ("n is not a valid non-negative integer.", "n が有効な非負整数ではありません。"),
+    ("Spiral", "スパイラル"),
+    ("Ulam spiral", "ウラムの螺旋"),
+    ("Sacks spiral", "サックスの螺旋"),
+    ("Sieve the range", "範囲を篩う"),
+    ("Output file", "出力ファイル"),
+    ("Center (first number):", "中心（最初の数）:"),
+    ("side", "一辺"),
+    ("File:", "ファイル:"),
+    ("Browse", "参照"),
+    ("Render", "描画"),
+    ("Reset view", "表示をリセット"),
+    ("Drag to pan, scroll to zoom.", "ドラッグで移動、スクロールで拡大縮小します。"),
+    ("The spiral center is not a valid u64 integer.", "スパイラルの中心が有効な u64 整数ではありません。"),
+    ("Table Viewer", "テーブルビューア"),
+    ("Opens a generated output file page by page. An index (<file>.idx) is built on first open and reused afterwards.", "生成した出力ファイルをページ単位で表示します。初回に索引 (<file>.idx) を作成し、以降は再利用します。"),
+    ("Open", "開く"),
+    ("Click Render to draw the spiral", "「描画」を押すとスパイラルを描きます"),
+    (" (prime)", "（素数）"),
+    ("[{}, {}) zoom {}x", "[{}, {}) 倍率 {}x"),
+    ("Failed to read {}: {}", "{} を読み込めませんでした: {}"),
+    ("⏮ First", "⏮ 最初"),
+    ("◀ Prev", "◀ 前へ"),
+    ("Next ▶", "次へ ▶"),
+    ("Last ⏭", "最後 ⏭"),
+    ("Page {} / {} ({} primes)", "{} / {} ページ（素数 {} 個）"),
+    ("Go to index:", "番号へ移動:"),
+    ("Go", "移動"),
+    ("Index must be between 1 and {}.", "番号は 1 から {} の間で指定してください。"),
+    ("Find value:", "値を検索:"),
+    ("Find", "検索"),
+    ("First prime >= {} is #{}", "{} 以上の最初の素数は {} 番目です"),
+    ("All primes in the file are less than {}.", "ファイル内の素数はすべて {} 未満です。"),
+    ("Search failed: {}", "検索に失敗しました: {}"),
+    ("Value is not a valid u64 integer.", "値が有効な u64 整数ではありません。"),
+    ("Prime", "素数"),
+    ("Gap", "ギャップ"),
+    ("{} is not a valid integer.", "{} が有効な整数ではありません。"),
+    ("n from", "n の下限"),
+    ("n to", "n の上限"),
+    ("m from", "m の下限"),
+    ("m to", "m の上限"),
+    ("max k", "k の上限"),
+    ("p up to", "p の上限"),
+    ("n up to", "n の上限"),
+    ("Error: {}", "エラー: {}"),
+    ("Process stopped by user.", "ユーザーにより処理が停止されました。"),
+    ("Failed to create tray icon: {}", "トレイアイコンを作成できませんでした: {}"),
+    ("Language:", "言語:"),
+    ("No Japanese font found. Japanese text may not be displayed (see README: embed-font).", "日本語フォントが見つかりません。日本語が表示されない場合があります（README の embed-font を参照）。"),
+    ("Appearance", "表示"),
+    ("Theme:", "配色:"),
+    ("System", "システム設定に従う"),
+    ("Light", "ライト"),
+    ("Dark", "ダーク"),
+    ("UI scale", "UI 倍率"),
+    ("Monospace log", "ログを等幅フォントで表示"),
+    ("Shortcut: Enter", "ショートカット: Enter"),
+    ("Shortcut: Enter / Ctrl+Enter", "ショートカット: Enter / Ctrl+Enter"),
+    ("Shortcut: Ctrl+{}", "ショートカット: Ctrl+{}"),
+    ("Shortcut: Esc / Ctrl+C", "ショートカット: Esc / Ctrl+C"),
+    ("Shortcut: Ctrl+O", "ショートカット: Ctrl+O"),
+    ("Elapsed: {}", "経過時間: {}"),
+    ("Primes found:", "見つかった素数:"),
+    ("Even numbers without partition:", "分割のない偶数:"),
+    ("Chains found:", "見つかった鎖:"),
+    ("Mersenne primes found:", "見つかったメルセンヌ素数:"),
+    ("Pseudoprimes found:", "見つかった強擬素数:"),
+    ("Carmichael numbers found:", "見つかったカーマイケル数:"),
+    ("Run summary", "実行結果"),
+    ("Range:", "範囲:"),
+    ("Duration:", "所要時間:"),
+    ("Throughput:", "処理速度:"),
+    ("{} numbers/s", "{} 個/秒"),
+    ("Output files:", "出力ファイル:"),
+    ("Open folder", "フォルダを開く"),
+    ("Compress with zstd (seekable)", "zstd で圧縮する（seekable 形式）"),
+    ("Writes .zst files made of independent frames with a seek table, so the Table Viewer can still jump to any position", "独立したフレームとシークテーブルからなる .zst ファイルを書くので、テーブルビューアで任意の位置へ移動できます"),
+    ("level", "レベル"),
+    ("8-byte little-endian integers, the layout of a primesieve uint64 array", "8 バイトのリトルエンディアン整数（primesieve の uint64 配列と同じ並び）"),
+    ("Compare with primesieve output...", "primesieve の出力と比較..."),
+    ("Import primesieve output...", "primesieve の出力を取り込む..."),
+    ("Comparing {} file(s) with {}", "{} 個のファイルを {} と比較しています"),
+    ("The outputs have no range in common", "出力に共通する範囲がありません"),
+    ("Compared [{}, {}]: {} primes match", "[{}, {}] を比較しました: {} 個の素数が一致"),
+    ("The outputs agree", "出力は一致しています"),
+    ("{} primes only in our output: {}", "こちらの出力にだけある素数 {} 個: {}"),
+    ("{} primes only in the primesieve output: {}", "primesieve の出力にだけある素数 {} 個: {}"),
+    ("Note: the output filters may have removed primes from our output", "注意: 出力のフィルタにより、こちらの出力から素数が除かれている可能性があります"),
+    ("Importing {}", "{} を取り込んでいます"),
+    ("Imported {} primes into {}", "{} 個の素数を {} に取り込みました"),
+    ("Write output files on a separate I/O thread", "出力ファイルを別の I/O スレッドで書き込む"),
+    ("Keeps slow disks such as network drives from stalling the run", "ネットワークドライブなど遅いディスクで処理が止まらないようにします"),
+    ("number base", "基数"),
+    ("Non-decimal output cannot be read back by the viewer, gap analysis or spiral.", "10 進以外の出力はビューア、ギャップ解析、螺旋表示では読み込めません。"),
+    ("Interval statistics, gap analysis, prime races and the pi(x) report are skipped in descending order", "降順では区間ごとの統計、ギャップ解析、素数競争、π(x) のレポートは行いません"),
+    ("Descending order (largest prime first)", "降順（大きい素数から）"),
+    ("Searching for the first {} primes from {}", "{} 個の素数を {} から小さい順に探します"),
+    ("The {}-th prime is {}", "{} 番目の素数は {} です"),
+    ("The {}-th prime from {} is {}", "{} 番目の素数（{} から数えて）は {} です"),
+    ("N-th prime", "N 番目の素数"),
+    ("Primes from prime_min are sieved until N have been found; prime_max is not used.\nThe primes are written with the output settings below.", "prime_min から N 個の素数が見つかるまで篩います。prime_max は使いません。\n素数は下の出力設定で書き出します。"),
+    ("Number of primes (N):", "素数の個数 (N):"),
+    ("the number of primes must be a positive u64 integer.", "素数の個数は正の u64 整数で指定してください。"),
+    ("Warning: {} lists missing files ({}); its range is not treated as covered", "警告: {} に記録されたファイルが見つかりません（{}）。その範囲は篩い終えたものとして扱いません"),
+    ("Warning: {} already covers {} with other filters", "警告: {} は別のフィルタで {} を含んでいます"),
+    ("Warning: {} duplicates the output recorded in {}", "警告: {} は {} に記録された出力と重複しています"),
+    ("Warning: {} overlaps the output recorded in {}", "警告: {} が {} に記録された出力と重なっています"),
+    ("Other outputs already cover [{}, {}]", "ほかの出力がすでに [{}, {}] を含んでいます"),
+    ("Skipping ranges covered by other outputs; sieving {}", "ほかの出力が含む範囲を飛ばし、{} を篩います"),
+    ("Interval statistics, gap analysis, prime races and the pi(x) report are skipped because the range has gaps", "範囲が途切れているため、区間ごとの統計、ギャップ解析、素数競争、π(x) のレポートは行いません"),
+    ("Range already generated", "生成済みの範囲"),
+    ("Other outputs in the folder already cover parts of this range:", "フォルダ内のほかの出力がこの範囲の一部をすでに含んでいます:"),
+    ("Nothing is left to sieve.", "篩う範囲は残っていません。"),
+    ("Remaining: {}", "残り: {}"),
+    ("Skip covered ranges", "含まれる範囲を飛ばす"),
+    ("Generate everything", "すべて生成"),
+    ("Run cancelled: the range overlaps other outputs.", "実行を中止しました: 範囲がほかの出力と重なっています。"),
+    ("Skip ranges covered by other outputs", "ほかの出力が含む範囲を飛ばす"),
+    ("Uses the .meta.json files in the output folder", "出力フォルダの .meta.json ファイルを使います"),
+    ("{} was written with other format or filter settings; appending without updating it", "{} は別の形式またはフィルタ設定で書かれているため、更新せずに追記します"),
+    ("The existing output already covers [{}, {}]", "既存の出力は [{}, {}] をすでに含んでいます"),
+    ("Continuing the existing output from {} ([{}, {}] already written)", "既存の出力の続きを {} から篩います（[{}, {}] は書き込み済み）"),
+    ("Warning: [{}, {}] starts before the end of the existing output; the appended primes will not be in order", "警告: [{}, {}] は既存の出力の終わりより前から始まるため、追記した素数は昇順になりません"),
+    ("Failed to write {}: {}", "{} を書き込めませんでした: {}"),
+    ("Estimated sieving time from previous runs: {}", "以前の実行から見積もった篩の時間: {}"),
+    ("Failed to save calibration data: {}", "測定結果を保存できませんでした: {}"),
+    ("Cache directory:", "キャッシュディレクトリ:"),
+    ("Base primes and sieving speed measurements are kept here for later runs.\nEmpty disables the cache.", "基底素数と篩の速さの測定結果をここに保存して次回以降に使います。\n空ならキャッシュしません。"),
+    ("Working folder:", "作業フォルダ:"),
+    ("Relative paths (output, cache, base primes file) are relative to this folder.\nEmpty uses the folder the program was started in (beside the program in portable mode).", "相対パス（出力先、キャッシュ、基底素数ファイル）はこのフォルダから見た位置になります。\n空なら起動したフォルダ（ポータブルモードでは実行ファイルのフォルダ）を使います。"),
+    ("Settings file: {}", "設定ファイル: {}"),
+    ("Failed to watch {}: {}", "{} を監視できませんでした: {}"),
+    ("Failed to reload settings: {}", "設定を読み込み直せませんでした: {}"),
+    ("Reloaded {} (changed outside the program)", "{} を読み込み直しました（外部で変更されました）"),
+    ("Failed to enter the working folder {}: {}", "作業フォルダ {} に移動できませんでした: {}"),
+    ("the primes must start at 2", "素数が 2 から始まっていません"),
+    ("{} is out of order", "{} の順序が正しくありません"),
+    ("{} exceeds the limit {}", "{} が上限 {} を超えています"),
+    ("Loaded {} base primes up to {} from {}", "{} 個の基底素数（{} まで）を {} から読み込みました"),
+    ("{} only covers primes up to {}; sieving up to {}", "{} には {} までの素数しかないため、{} まで篩います"),
+    ("Ignoring base prime file {}: {}", "基底素数ファイル {} を使いません: {}"),
+    ("Saved {} base primes to {}", "{} 個の基底素数を {} に保存しました"),
+    ("Failed to save base primes to {}: {}", "基底素数を {} に保存できませんでした: {}"),
+    ("Base primes file:", "基底素数ファイル:"),
+    ("The primes up to sqrt(prime_max) are read from this file and saved to it\nwhen it is missing or too short. Empty computes them on every run.", "sqrt(prime_max) までの素数をこのファイルから読み込み、\nファイルがないか足りない場合は保存します。空なら毎回計算します。"),
+    ("Inserting primes into {} ({})", "素数を {} に登録します（{}）"),
+    ("Passing primes to {}", "素数を {} に渡します"),
+    ("Sending primes to {}", "素数を {} に送ります"),
+    ("Stopped sending primes to {}: {}", "{} への素数の送信をやめました: {}"),
+    ("Send to another program", "ほかのプログラムへ送る"),
+    ("Socket / pipe:", "ソケット / パイプ:"),
+    ("The written primes are sent one per line to the Unix domain socket\n(named pipe on Windows) that the other program is listening on.\nStart that program first. An empty path disables it.", "書き出した素数を 1 行に 1 つずつ、ほかのプログラムが待ち受けている\nUnix ドメインソケット（Windows では名前付きパイプ）に送ります。\n先にそのプログラムを起動してください。パスが空なら送りません。"),
+    ("Insert into database", "データベースに登録"),
+    ("Connection URL:", "接続 URL:"),
+    ("Table:", "テーブル:"),
+    ("Primes are also inserted into the table (created if missing) in batches,\nwith COPY on PostgreSQL and multi-row INSERT on MySQL. An empty URL disables it.", "素数をテーブル（なければ作成）にもまとめて登録します。\nPostgreSQL では COPY、MySQL では複数行の INSERT を使います。URL が空なら登録しません。"),
+    ("Uploaded {} to {}", "{} を {} にアップロードしました"),
+    ("Failed to upload {}: {}", "{} のアップロードに失敗しました: {}"),
+    ("Upload to S3", "S3 へアップロード"),
+    ("Bucket:", "バケット:"),
+    ("Key prefix:", "キーの接頭辞:"),
+    ("Endpoint:", "エンドポイント:"),
+    ("Region:", "リージョン:"),
+    ("Path-style URLs (MinIO etc.)", "パス形式の URL（MinIO など）"),
+    ("Each finished output file is uploaded while the next one is written.\nAn empty bucket disables uploading. Credentials are read from\nAWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY.", "書き終えた出力ファイルは次のファイルを書いている間にアップロードされます。\nバケットが空ならアップロードしません。認証情報は\nAWS_ACCESS_KEY_ID と AWS_SECRET_ACCESS_KEY から読みます。"),
+    ("{} is already at {}, skipped", "{} はすでに {} にあるので送りません"),
+    ("Deleted the local copy of {}", "手元の {} を削除しました"),
+    ("Failed to delete {}: {}", "{} を削除できませんでした: {}"),
+    ("Upload to WebDAV", "WebDAV へアップロード"),
+    ("Folder URL:", "フォルダーの URL:"),
+    ("Files already on the server with the same SHA-256 are skipped.\nAn empty URL disables uploading. Credentials are read from\nWEBDAV_USERNAME and WEBDAV_PASSWORD.", "サーバーに同じ SHA-256 のファイルがすでにあれば送りません。\nURL が空ならアップロードしません。認証情報は\nWEBDAV_USERNAME と WEBDAV_PASSWORD から読みます。"),
+    ("Upload to SFTP", "SFTP へアップロード"),
+    ("The server's host key must be in ~/.ssh/known_hosts. The password or key\nis read from SFTP_PASSWORD or SFTP_PRIVATE_KEY (otherwise ssh-agent is used).\nFiles already on the server with the same SHA-256 are skipped.", "サーバーのホスト鍵が ~/.ssh/known_hosts にある必要があります。パスワードか鍵は\nSFTP_PASSWORD か SFTP_PRIVATE_KEY から読みます（なければ ssh-agent を使います）。\nサーバーに同じ SHA-256 のファイルがすでにあれば送りません。"),
+    ("Delete local files after uploading", "アップロードしたら手元のファイルを削除"),
+    ("A file is deleted only after every destination has received it", "すべてのアップロード先に送れたファイルだけを削除します"),
+    ("Token:", "合言葉:"),
+    ("Workers must send the same token. Required unless the coordinator listens only on 127.0.0.1", "ワーカーは同じ合言葉を送る必要があります。コーディネーターが 127.0.0.1 だけで待ち受けるとき以外は必須です"),
+    ("Export Work Unit Files", "作業単位ファイルを書き出す"),
+    ("Stitch Result Files...", "結果ファイルを結合..."),
+    ("Process Work Unit Files...", "作業単位ファイルを処理..."),
+    ("No *.{} files in {}", "{1} に *.{0} ファイルがありません"),
+    ("Exported {} work unit files to {}", "作業単位ファイル {} 個を {} に書き出しました"),
+    ("Hand out the unit_*.unit.json files and keep {} private.", "unit_*.unit.json ファイルを配布し、{} は手元から出さないでください。"),
+    ("Processed {} work units. Send the unit_*.result.txt files back to the coordinator.", "作業単位 {} 個を処理しました。unit_*.result.txt ファイルをコーディネーターに返してください。"),
+    ("belongs to project {}", "プロジェクト {} のファイルです"),
+    ("invalid signature for work unit {}", "作業単位 {} の署名が正しくありません"),
+    ("invalid number {}", "不正な数 {}"),
+    ("{} is out of order or outside [{}, {}]", "{} の順序が不正か、[{}, {}] の範囲外です"),
+    ("{} is not prime", "{} は素数ではありません"),
+    ("the primes do not match the count and hash in the header", "素数がヘッダーの個数とハッシュに一致しません"),
+    ("Work unit {} appears in both {} and {}; using the first", "作業単位 {} が {} と {} の両方にあります。前者を使います"),
+    ("{} of {} work units are missing: {}{}", "作業単位 {1} 個のうち {0} 個がありません: {2}{3}"),
+    ("Stitched {} work units [{}, {}]. Total primes: {}", "作業単位 {} 個 [{}, {}] を結合しました。素数の合計: {}"),
+    ("work unit size must be a positive u64 integer.", "作業単位の大きさは正の u64 整数で指定してください。"),
+    ("Distributed: coordinator", "分散: コーディネーター"),
+    ("Distributed: worker", "分散: ワーカー"),
+    ("[prime_min, prime_max] is split into work units that connected workers sieve.\nThe primes are written here in order, with the output settings below.", "[prime_min, prime_max] を作業単位に分け、接続したワーカーが篩います。\n素数は下の出力設定でこのマシンに順番どおり書き込みます。"),
+    ("Listen on:", "待ち受けアドレス:"),
+    ("Work unit size:", "作業単位の大きさ:"),
+    ("Connects to a coordinator and sieves the work units it sends until the run is finished.\nThe range and output settings of this instance are not used.", "コーディネーターに接続し、送られてくる作業単位を実行が終わるまで篩います。\nこのインスタンスの範囲と出力設定は使いません。"),
+    ("Coordinator:", "コーディネーター:"),
+    ("Primes sent:", "送った素数:"),
+    ("Coordinator listening on {}", "コーディネーターは {} で待ち受けています"),
+    ("{} work units of up to {} numbers", "作業単位 {} 個（1 個あたり最大 {} 個の数）"),
+    ("Worker connected: {}", "ワーカーが接続しました: {}"),
+    ("Failed to accept a worker: {}", "ワーカーの接続を受け付けられませんでした: {}"),
+    ("Finished distributed generation. Total primes found: {}", "分散生成が完了しました。見つかった素数: {}"),
+    ("Worker {} is ready ({} threads)", "ワーカー {} の準備ができました（{} スレッド）"),
+    ("Worker {} disconnected: {}", "ワーカー {} が切断しました: {}"),
+    ("Connecting to coordinator {}", "コーディネーター {} に接続しています"),
+    ("Work unit {} [{}, {}]: {} primes", "作業単位 {} [{}, {}]: 素数 {} 個"),
+    ("Coordinator finished. {} work units, {} primes sent", "コーディネーターが終了しました。作業単位 {} 個、素数 {} 個を送りました"),
+    ("Streaming progress on ws://{}", "ws://{} で進捗を配信しています"),
+    ("Failed to start the WebSocket server on {}:{}: {}", "WebSocket サーバーを {}:{} で開始できませんでした: {}"),
+    ("Open file", "ファイルを開く"),
+    ("Open Folder", "フォルダを開く"),
+    ("Failed to open {}: {}", "{} を開けませんでした: {}"),
+    ("Failed to save settings: {}", "設定を保存できませんでした: {}"),
+    ("Run", "実行"),
+    ("residue modulus is not a valid u64 integer.", "剰余の法が有効な u64 整数ではありません。"),
+    ("residues must be a comma separated list of u64 integers.", "剰余は u64 整数のカンマ区切りで指定してください。"),
+    ("at least one residue is required when the residue modulus is set.", "剰余の法を指定した場合は剰余を 1 つ以上指定してください。"),
+    ("each residue must be less than the residue modulus.", "各剰余は剰余の法より小さくしてください。"),
+    ("prime race modulus and classes must be valid u64 integers.", "素数レースの法と剰余類は有効な u64 整数で指定してください。"),
+    ("the two prime race classes must differ.", "素数レースの 2 つの剰余類は異なる値にしてください。"),
+    ("Miller-Rabin bases must be a comma separated list of u64 integers.", "Miller-Rabin の底は u64 整数のカンマ区切りで指定してください。"),
+    ("between 1 and 16 Miller-Rabin bases (each at least 2) are required.", "Miller-Rabin の底は 1 個以上 16 個以下（各 2 以上）で指定してください。"),
+    ("digit filter expression is invalid.", "桁フィルタの式が正しくありません。"),
+    ("filter script is invalid.", "フィルタスクリプトが正しくありません。"),
+    ("Enter a number.", "数を入力してください。"),
+    ("Only the digits 0-9 are allowed.", "0-9 の数字だけを入力してください。"),
+    ("Too large for a 64-bit integer (max {}).", "64 ビット整数に収まりません（最大 {}）。"),
+    ("Must be at most {}.", "{} 以下にしてください。"),
+    ("Must not exceed prime_max.", "prime_max 以下にしてください。"),
+    ("Output files already exist", "出力ファイルが既に存在します"),
+    ("{} existing file(s) would be affected:", "既存の {} 個のファイルが影響を受けます:"),
+    ("... and {} more", "... ほか {} 個"),
+    ("Overwrite", "上書き"),
+    ("Auto-version", "自動で別名"),
+    ("Append", "追記"),
+    ("Cancel", "キャンセル"),
+    ("Run cancelled: output files already exist.", "出力ファイルが既に存在するため実行を取り消しました。"),
+    ("Dismiss", "閉じる"),
+    ("Disk full while writing {}. Free space or change output directory, then resume.", "{} の書き込み中にディスクがいっぱいになりました。空き容量を確保するか出力ディレクトリを変更して再開してください。"),
+    ("Resume", "再開"),
+    ("Change Directory & Resume", "ディレクトリを変更して再開"),
+    ("Log", "ログ"),
+    ("No logs yet", "ログはまだありません"),
+    ("Sieve", "篩"),
+    ("Settings", "設定"),
+    ("Mode:", "モード:"),
+    ("Generate primes", "素数の生成"),
+    ("Goldbach check", "ゴールドバッハ予想の検証"),
+    ("Cunningham chains", "カニンガム鎖"),
+    ("Mersenne (Lucas-Lehmer)", "メルセンヌ数 (Lucas-Lehmer)"),
+    ("Strong pseudoprimes", "強擬素数"),
+    ("Carmichael numbers", "カーマイケル数"),
+    ("Every even number in [prime_min, prime_max] is checked for a Goldbach partition.", "[prime_min, prime_max] のすべての偶数について、2 つの素数の和で表せるかを確認します。"),
+    ("Count all partitions (slow)", "すべての分割を数える（低速）"),
+    ("Chains starting at a prime in [prime_min, prime_max] are written to cunningham_chains.txt.", "[prime_min, prime_max] の素数から始まる鎖を cunningham_chains.txt に書き出します。"),
+    ("minimum length", "最小の長さ"),
+    ("Each prime p in [prime_min, prime_max] is used as an exponent and 2^p - 1 is tested.\nThe progress bar shows the current candidate. Mersenne primes go to mersenne_primes.txt.", "[prime_min, prime_max] の各素数 p を指数として 2^p - 1 を判定します。\n進捗バーは現在の候補の進み具合です。メルセンヌ素数は mersenne_primes.txt に書き出します。"),
+    ("Carmichael numbers in [prime_min, prime_max] and their factorizations are written to carmichael_numbers.txt.", "[prime_min, prime_max] のカーマイケル数とその素因数分解を carmichael_numbers.txt に書き出します。"),
+    ("Odd composites in [prime_min, prime_max] passing Miller-Rabin for every base are written to pseudoprimes.txt.", "[prime_min, prime_max] の奇数の合成数のうち、すべての底で Miller-Rabin を通過するものを pseudoprimes.txt に書き出します。"),
+    ("Bases:", "底:"),
+    ("0 means no splitting. If a number is specified, the output primes file\nwill be split into multiple files every specified number of primes.", "0 は分割しません。数を指定すると、出力する素数ファイルを\n指定した個数ごとに複数のファイルに分割します。"),
+    ("Number the primes across files", "ファイルをまたいで素数に通し番号をつける"),
+    ("Archive finished files as .tar.zst", "書き終えたファイルを .tar.zst にまとめる"),
+    ("Compresses each output file into primes_1.txt.tar.zst as soon as it is finished and deletes the original, so only the file being written takes its full size on disk", "出力ファイルを書き終えるたびに primes_1.txt.tar.zst に圧縮して元のファイルを消すので、ディスクを元の大きさで使うのは書いている途中のファイルだけになります"),
+    ("Archived {} as {}", "{} を {} にまとめました"),
+    ("Writes index:prime records and names split files after their first index (primes_from_idx_1000001.txt)", "番号:素数 の形で書き、分割ファイルの名前を最初の番号にします（primes_from_idx_1000001.txt）"),
+    ("numbering primes cannot be combined with appending to existing files.", "通し番号は既存のファイルへの追記と併用できません。"),
+    ("Residue filter (p mod m = r):", "剰余フィルタ (p mod m = r):"),
+    ("m = 0 writes all primes. Otherwise only primes whose remainder mod m\nis one of the listed residues are written.", "m = 0 ならすべての素数を書き出します。それ以外は m で割った余りが\n指定した剰余のいずれかである素数だけを書き出します。"),
+    ("Digit filter:", "桁フィルタ:"),
+    ("Compose digit filter", "桁フィルタを組み立てる"),
+    ("Filter script (rhai):", "フィルタスクリプト（rhai）:"),
+    ("Output script", "出力スクリプト"),
+    ("Script file:", "スクリプトファイル:"),
+    ("Batch size:", "バッチサイズ:"),
+    ("The rhai script defines fn on_batch(primes), which receives the written primes\nin batches, and optionally fn on_finish(). It can use append_file(path, text),\nhttp_post(url, body) and log(text). An empty file name disables it.", "rhai スクリプトで fn on_batch(primes) を定義すると、書き出した素数をバッチごとに\n受け取ります（fn on_finish() は省略可）。append_file(path, text)、\nhttp_post(url, body)、log(text) が使えます。ファイル名が空なら無効です。"),
+    ("sink batch size must be a positive integer.", "出力スクリプトのバッチサイズは正の整数で指定してください。"),
+    ("Only primes for which the script returns true are written.\nFunctions: digit_sum, digits, reverse_digits, palindrome, is_prime.", "スクリプトが true を返した素数だけを書き出します。\n関数: digit_sum, digits, reverse_digits, palindrome, is_prime"),
+    ("Prime race (a mod m vs b mod m):", "素数レース (a mod m 対 b mod m):"),
+    ("m = 0 disables the race. The running difference is plotted while sieving.", "m = 0 でレースを無効にします。篩の実行中に差の推移をグラフに描きます。"),
+    ("Output Format:", "出力形式:"),
+    ("Output Directory:", "出力ディレクトリ:"),
+    ("Select Folder", "フォルダを選択"),
+    ("If output files exist:", "出力ファイルが存在する場合:"),
+    ("Ask", "確認する"),
+    ("Keep awake (prevent system sleep while running)", "スリープ防止（実行中にシステムをスリープさせない）"),
+    ("Minimize to system tray", "システムトレイに最小化"),
+    ("Background mode (low priority)", "バックグラウンドモード（低優先度）"),
+    ("idle %", "休止 %"),
+    ("Pin worker threads to CPU cores (NUMA)", "ワーカースレッドを CPU コアに固定 (NUMA)"),
+    ("Segment Buffer:", "セグメントバッファ:"),
+    ("Advanced", "詳細設定"),
+    ("Choose sizes automatically", "サイズを自動で決める"),
+    ("Picks the sizes from the free memory and CPU count when the run starts", "実行開始時に空きメモリと CPU 数からサイズを決めます"),
+    ("Size the writer buffer for the output drive", "出力先に合わせて書き込みバッファを決める"),
+    ("Times a few writes to the output folder when the run starts and picks the writer buffer, queued blocks and flush interval for an SSD, hard disk or network share", "実行開始時に出力フォルダへ少し書き込んで時間を測り、SSD・ハードディスク・ネットワーク共有に合った書き込みバッファ、先に渡すブロック数、書き出す間隔を選びます"),
+    ("Segment size:", "セグメントサイズ:"),
+    ("Chunk size:", "チャンクサイズ:"),
+    ("Writer buffer (bytes):", "書き込みバッファ (バイト):"),
+    ("Each worker thread sieves segment size numbers at a time and needs about\nthat many bytes. Larger writer buffers mean fewer writes to the output files.", "各ワーカースレッドは一度にセグメントサイズ個の数を篩い、ほぼ同じバイト数の\nメモリを使います。書き込みバッファが大きいほど出力ファイルへの書き込み回数が減ります。"),
+    ("segment size must be a positive integer.", "セグメントサイズは正の整数で指定してください。"),
+    ("chunk size must be a positive integer.", "チャンクサイズは正の整数で指定してください。"),
+    ("writer buffer size must be a positive integer.", "書き込みバッファのサイズは正の整数で指定してください。"),
+    ("Parallel segments:", "並列セグメント数:"),
+    ("0 uses one segment per worker thread", "0 ならワーカースレッドごとに 1 セグメント"),
+    ("parallel segments must be a non-negative integer.", "並列セグメント数は 0 以上の整数で指定してください。"),
+    ("Memory footprint:", "メモリ使用量:"),
+    ("Custom", "カスタム"),
+    ("Low", "少"),
+    ("Medium", "中"),
+    ("High", "多"),
+    ("Estimated peak memory: {} MB (sieving {} MB, primes {} MB, writer {} MB)", "最大メモリの見積もり: {} MB（篩 {} MB、素数 {} MB、書き込み {} MB）"),
+    ("Auto tuning: segment size {}, chunk size {}, writer buffer {} bytes", "自動設定: セグメントサイズ {}、チャンクサイズ {}、書き込みバッファ {} バイト"),
+    ("SSD", "SSD"),
+    ("hard disk", "ハードディスク"),
+    ("network share", "ネットワーク共有"),
+    ("Output drive looks like a {} ({} ms per sync, {} MiB/s): writer buffer {} bytes, {} queued blocks, {}", "出力先は {} のようです（同期 1 回 {} ms、{} MiB/s）: 書き込みバッファ {} バイト、先に渡すブロック {} 個、{}"),
+    ("flushed when the buffer is full", "バッファが埋まったときに書き出し"),
+    ("flushed every {} ms", "{} ms ごとに書き出し"),
+    ("Could not measure the output drive ({}); keeping the writer buffer settings", "出力先の書き込みを測れませんでした（{}）。書き込みバッファの設定はそのままにします"),
+    ("Gap analysis (write gap_report.txt)", "ギャップ解析（gap_report.txt を書き出す）"),
+    ("Analyze Existing Output", "既存の出力を解析"),
+    ("Progress / System", "進捗 / システム"),
+    ("Processed: {}/{}", "処理済み: {}/{}"),
+    ("Processed: N/A", "処理済み: N/A"),
+    ("ETA: {}", "残り時間: {}"),
+    ("Memory Usage: {} KB / {} KB", "メモリ使用量: {} KB / {} KB"),
+    ("Prime race ({} vs {} mod {}): difference {}", "素数レース（{} 対 {} mod {}）: 差 {}"),
+    ("Palindrome", "回文数"),
+    ("Repunit (all digits 1)", "レピュニット（すべての桁が 1）"),
+    ("Near-repdigit (all digits equal but one)", "ほぼゾロ目（1 桁以外がすべて同じ数字）"),
+    ("Digit sum", "各桁の和"),
+    ("Match all", "すべてに一致"),
+    ("Match any", "いずれかに一致"),
+    ("(no filter)", "（フィルタなし）"),
+    ("Use this filter", "このフィルタを使う"),
+    ("Primes analyzed: {}", "解析した素数: {}"),
+    ("Max gap: {} ({} - {})", "最大ギャップ: {}（{} - {}）"),
+    ("Record gaps ({})", "記録的なギャップ（{}）"),
+    ("After", "直前の素数"),
+    ("Next", "次の素数"),
+    ("Gaps per interval ({})", "区間ごとのギャップ（{}）"),
+    ("Interval", "区間"),
+    ("Gaps", "個数"),
+    ("Mean", "平均"),
+    ("Median", "中央値"),
+    ("Max", "最大"),
+    ("Searching Carmichael numbers", "カーマイケル数を探索しています"),
+    ("Carmichael number: {} = {}", "カーマイケル数: {} = {}"),
+    ("{} prime factors: {}", "素因数 {} 個: {}"),
+    ("0 hour 0 min 0 sec", "0 時間 0 分 0 秒"),
+    ("Finished Carmichael search. {} numbers written to {}", "カーマイケル数の探索が完了しました。{} 個を {} に書き出しました"),
+    ("Searching Cunningham chains of length >= {}", "長さ {} 以上のカニンガム鎖を探索しています"),
+    ("Longest chain so far ({} kind, length {}): {}{}", "これまでの最長の鎖（{}、長さ {}）: {}{}"),
+    ("Length {}: {} of the first kind, {} of the second kind", "長さ {}: 第 1 種 {} 個、第 2 種 {} 個"),
+    ("Finished Cunningham chain search. {} chains written to {}", "カニンガム鎖の探索が完了しました。{} 個の鎖を {} に書き出しました"),
+    ("Pollard rho on a {}-digit cofactor", "{} 桁の余因数に Pollard rho を適用しています"),
+    ("ECM level {} (B1 = {}, {} curves) on a {}-digit cofactor", "ECM レベル {}（B1 = {}、曲線 {} 本）を {} 桁の余因数に適用しています"),
+    ("Primes per residue class mod {} (* = written): {}", "mod {} の剰余類ごとの素数の個数（* = 書き出し対象）: {}"),
+    ("Running Goldbach check", "ゴールドバッハ予想を検証しています"),
+    ("Sieving primes up to {}", "{} までの素数を篩っています"),
+    ("No Goldbach partition found for {}", "{} のゴールドバッハ分割が見つかりません"),
+    ("Largest minimal prime: {} ({} = {} + {})", "最小の素数の最大値: {}（{} = {} + {}）"),
+    ("Finished Goldbach check. {} even numbers checked, {} without partition. Report: {}", "ゴールドバッハ予想の検証が完了しました。偶数 {} 個を確認し、分割のないものは {} 個でした。レポート: {}"),
+    ("Running Lucas-Lehmer test on Mersenne numbers 2^p - 1", "メルセンヌ数 2^p - 1 に Lucas-Lehmer テストを実行しています"),
+    ("{} prime exponents in range", "範囲内の素数の指数: {} 個"),
+    ("{}: composite, factor {}", "{}: 合成数、因数 {}"),
+    ("{}: PRIME ({} s)", "{}: 素数（{} 秒）"),
+    ("{}: composite ({} s)", "{}: 合成数（{} 秒）"),
+    ("Finished Lucas-Lehmer. {} exponents tested ({} eliminated by trial factoring). Mersenne primes: {}", "Lucas-Lehmer テストが完了しました。指数 {} 個を判定しました（試し割りで除外 {} 個）。メルセンヌ素数: {}"),
+    ("{} is neither prime nor composite", "{} は素数でも合成数でもありません"),
+    ("{} is composite: {}", "{} は合成数です: {}"),
+    ("{} is prime (deterministic Miller-Rabin)", "{} は素数です（決定的 Miller-Rabin）"),
+    ("{} is prime (proved by Pratt certificate)", "{} は素数です（Pratt 証明書で証明）"),
+    ("Certificate written to {}", "証明書を {} に書き出しました"),
+    ("{} is composite, factoring...", "{} は合成数です。素因数分解しています..."),
+    ("{} = {} ({} s)", "{} = {}（{} 秒）"),
+    ("Some cofactors could not be factored (no factor found by Pollard rho or ECM).", "一部の余因数は分解できませんでした（Pollard rho と ECM で因数が見つかりませんでした）。"),
+    ("Searching strong pseudoprimes to bases {}", "底 {} の強擬素数を探索しています"),
+    ("Strong pseudoprime: {}", "強擬素数: {}"),
+    ("Base {}: strong liar for {} of {} odd composites", "底 {0}: 奇数の合成数 {2} 個のうち {1} 個で strong liar"),
+    ("Finished strong pseudoprime search. {} pseudoprimes written to {}", "強擬素数の探索が完了しました。{} 個を {} に書き出しました"),
+    ("Prime race: {} mod {} takes the lead over {} mod {} at {}", "素数レース: {4} で {0} mod {1} が {2} mod {3} を逆転しました"),
+    ("Prime race mod {0}: {1} mod {0} = {2}, {3} mod {0} = {4}, lead changes: {5}, {1} mod {0} ahead for {6}% of the race", "素数レース mod {0}: {1} mod {0} = {2}、{3} mod {0} = {4}、首位交代: {5} 回、{1} mod {0} がリードしていた割合: {6}%"),
+    ("Running old method (Sieve) with parallelization", "並列化した篩 (old method) を実行しています"),
+    ("Background mode: lowered worker priority (throttle {}%)", "バックグラウンドモード: ワーカーの優先度を下げました（休止 {}%）"),
+    ("Background mode: failed to lower priority: {}", "バックグラウンドモード: 優先度を下げられませんでした: {}"),
+    ("Pinned {} worker threads to CPU cores", "{} 個のワーカースレッドを CPU コアに固定しました"),
+    ("Segment buffers: {}", "セグメントバッファ: {}"),
+    ("Primes written after filters: {}", "フィルタ後に書き出した素数: {}"),
+    ("Finished old method. Total primes found: {}", "篩が完了しました。見つかった素数の総数: {}"),
+    ("Sum of primes: {}", "素数の和: {}"),
+    ("Warning: {}", "警告: {}"),
+    ("{} is prime (deterministic Miller-Rabin with the first 13 prime bases). Pratt certificates are limited to 64-bit numbers.", "{} は素数です（最初の 13 個の素数を底にした決定的な Miller-Rabin）。Pratt 証明書は 64 ビットの数に限られます。"),
+    ("{} is a probable prime (BPSW and {} random Miller-Rabin rounds). Pratt certificates are limited to 64-bit numbers.", "{} は確率的素数です（BPSW とランダムな底の Miller-Rabin {} 回）。Pratt 証明書は 64 ビットの数に限られます。"),
+    ("Probabilistic result: numbers of 3.3×10^24 and above are tested with BPSW and {} random Miller-Rabin rounds, which is not a proof. Raise the number of random rounds to lower the chance of error.", "確率的な結果です: 3.3×10^24 以上の数は BPSW とランダムな底の Miller-Rabin {} 回で判定しており、証明ではありません。ランダムな底の回数を増やすと誤る確率が下がります。"),
+    ("extra random Miller-Rabin rounds above 3.3×10^24", "3.3×10^24 以上で追加するランダムな底の Miller-Rabin の回数"),
+    ("Below 3.3×10^24 the test is deterministic. Above it, each round lowers the chance that a composite passes by at least 4 times.", "3.3×10^24 未満の判定は決定的です。それ以上では、1 回ごとに合成数が通る確率が 4 分の 1 以下になります。"),
+    ("Sieving in {} segments", "{} 個のセグメントで篩います"),
+    ("Segment: {} / {}", "セグメント: {} / {}"),
+    ("Writing {} primes", "{} 個の素数を書き込みます"),
+    ("Sieving", "篩"),
+    ("Testing", "判定"),
+    ("Writing", "書き込み"),
+    ("Verifying", "検証"),
+    ("Reading", "読み込み"),
+    ("Speed: {}/s", "速さ: {}/秒"),
+    ("Self Test", "自己診断"),
+    ("Checks known prime counts and compares the sieve, Miller-Rabin and output paths using the current performance settings. Takes a few seconds.", "既知の素数の個数を確かめ、今の性能の設定で篩・Miller-Rabin・出力の各経路の結果を比べます。数秒かかります。"),
+    ("Known pi(x) up to 10^7", "10^7 までの既知の π(x)"),
+    ("Simple sieve against Miller-Rabin", "単純な篩と Miller-Rabin の比較"),
+    ("Segmented sieve against Miller-Rabin near 10^12 and 10^15", "10^12 と 10^15 の近くでの区分篩と Miller-Rabin の比較"),
+    ("Generate with the current performance settings", "今の性能の設定での生成"),
+    ("Generate, descending and N-th prime outputs are identical", "生成・降順・N 番目の素数の出力の一致"),
+    ("Primality tests above 64 bits", "64 ビットを超える数の素数判定"),
+    ("PASS {} ({} s)", "合格 {}（{} 秒）"),
+    ("FAIL {}: {}", "不合格 {}: {}"),
+    ("Self test passed: all {} checks", "自己診断に合格しました（{} 項目すべて）"),
+    ("Check counts against known pi(10^k)", "既知の π(10^k) と個数を照合"),
+    ("When the range starts at 2 or below, the number of primes found is compared with the known values of pi(10), pi(100), ... as sieving passes them, and the run stops at the first mismatch.", "範囲が 2 以下から始まるとき、篩が π(10)、π(100)、... を過ぎるたびに見つけた素数の個数を既知の値と比べ、違えばすぐに止めます。"),
+    ("Known pi(10^k) checks are skipped because the range does not start at 2 or below in ascending order", "範囲が 2 以下から昇順に始まらないので、既知の π(10^k) との照合は行いません"),
+    ("pi({}) = {} matches the known value", "π({}) = {} は既知の値と一致しました"),
+    ("pi({}) = {}, but the known value is {}", "π({}) = {} ですが、既知の値は {} です"),
+    ("Fingerprint of the {} primes in {}: {}", "{} 個の素数の指紋（{}）: {}"),
+    ("{} background jobs did not stop within {} seconds", "{} 個のバックグラウンドのジョブが {} 秒以内に止まりませんでした"),
+    ("CPU usage per core (last {} s)", "コアごとの CPU 使用率（直近 {} 秒）"),
+    ("CPU {}: {}%", "CPU {}: {}%"),
+    ("°C: sieve fewer segments at once above this CPU temperature (0 = off)", "℃: CPU の温度がこれを超えたら同時に篩うセグメントを減らす（0 で無効）"),
+    ("Only where the system reports CPU temperatures", "OS が CPU の温度を報告する環境でのみ有効です"),
+    ("CPU temperature: {} °C", "CPU の温度: {} ℃"),
+    ("CPU temperature is not available on this system; the temperature limit is ignored", "この環境では CPU の温度を読めないため、温度の上限は使いません"),
+    ("CPU at {} °C: sieving {} of {} segments at once", "CPU {} ℃: {} / {} 個のセグメントを同時に篩います"),
+    ("Hide", "隠す"),
+    ("Show", "表示"),
+    ("Failed to save the window state: {}", "ウィンドウの状態を保存できませんでした: {}"),
+    ("Failed to close the output after stopping: {}", "停止後に出力を閉じられませんでした: {}"),
+    ("Stopped after writing {} primes. Descending output cannot be continued.", "{} 個の素数を書いたところで停止しました。降順の出力は続きから再開できません。"),
+    ("Stopped after writing {} primes up to {}.", "{1} までの {0} 個の素数を書いたところで停止しました。"),
+    ("Stopped after writing {} primes up to {}. Run again with Append to continue from {}.", "{1} までの {0} 個の素数を書いたところで停止しました。Append で実行すると {2} から続けます。"),
+    ("Stopped after writing {} primes.", "{} 個の素数を書いたところで停止しました。"),
+    ("Stop the run?", "実行を停止しますか？"),
+    ("Stop", "停止"),
+    ("Keep running", "続ける"),
+    ("Completed: {} / {} ({}%)", "完了: {} / {}（{}%）"),
+    ("The primes of the finished segments are kept in memory until the whole range is sieved.\nStopping now discards them; nothing is written.", "篩い終えたセグメントの素数は、範囲全体を篩い終えるまでメモリに保持しています。\nいま停止すると破棄され、何も書き出されません。"),
+    ("No resume checkpoint will be saved.", "再開用のチェックポイントは保存されません。"),
+    ("{} primes up to {} have been written. Stopping closes the output files and keeps them.", "{1} までの {0} 個の素数を書き出しました。停止すると出力ファイルを閉じて残します。"),
+    ("Descending output cannot be continued, so no resume checkpoint will be saved.", "降順の出力は続きから再開できないため、再開用のチェックポイントは保存されません。"),
+    ("The written range is saved as a checkpoint: running again with Append continues from {}.", "書き出した範囲をチェックポイントとして保存します。Append で実行すると {} から続けます。"),
+    ("{} primes have been written. Stopping closes the output files and keeps them.", "{} 個の素数を書き出しました。停止すると出力ファイルを閉じて残します。"),
+    ("This mode does not save a resume checkpoint.", "このモードは再開用のチェックポイントを保存しません。"),
+    ("Dock", "戻す"),
+    ("Pop out", "切り離す"),
+    ("Show progress in a separate window that stays on top", "進捗を常に手前に表示される別のウィンドウに表示します"),
+    ("Progress is shown in a separate window.", "進捗は別のウィンドウに表示しています。"),
+    ("Progress", "進捗"),
+    ("Adaptive segments: segment size {}", "適応的なセグメント: セグメントサイズ {}"),
+    ("Grow segments as the run progresses", "篩い進むにつれてセグメントを大きくする"),
+    ("Higher segments hold fewer primes; segments grow up to 8 times the segment size so that each batch takes about as long as the first", "上の方のセグメントほど素数が少ないので、各バッチが最初のバッチと同じくらいの時間になるよう、セグメントをセグメントサイズの 8 倍まで大きくします"),
+    ("Counting the primes below {} for the pi(x) report", "π(x) のレポートのため {} 未満の素数を数えています"),
+    ("pi(x) report written to {}. pi({}) = {}, pi - li = {}, pi - R = {}, pi - x/ln x = {}", "π(x) のレポートを {} に書き出しました。π({}) = {}、π - li = {}、π - R = {}、π - x/ln x = {}"),
+    ("pi(x) vs li(x) report (write pi_report.txt)", "π(x) と li(x) の比較（pi_report.txt に書き出す）"),
+    ("pi(x) and its approximations", "π(x) と近似式"),
+    ("Error terms: pi - li (blue), pi - R (green), pi - x/ln x (orange)", "誤差項: π - li（青）、π - R（緑）、π - x/ln x（橙）"),
+    ("Table ({} rows)", "表（{} 行）"),
+    ("Sum of reciprocals of primes: {}", "素数の逆数の和: {}"),
+    ("Primes per residue class mod {}: {}", "{} を法とする剰余類ごとの素数の個数: {}"),
+    ("Sum of primes:", "素数の和:"),
+    ("Sum of reciprocals:", "逆数の和:"),
+    ("Per residue class mod {}:", "剰余類ごと (mod {}):"),
+    ("aggregate modulus must be an integer from 0 to 1000.", "集計の法は 0 から 1000 の整数にしてください。"),
+    ("Aggregates (sum of primes and of reciprocals)", "集計（素数の和と逆数の和）"),
+    ("count per residue mod", "剰余類ごとに数える法"),
+    ("Computed while sieving and shown in the run summary (0 = no residue counts).", "篩いながら求めて実行のまとめに表示します（0 なら剰余類ごとには数えません）。"),
+    ("Analyzing gaps in {} file(s)", "{} 個のファイルのギャップを解析しています"),
+    ("Gap report written to {}. Max gap: {} after {}", "ギャップレポートを {0} に書き出しました。最大ギャップ: {2} の後の {1}"),
+    ("Gap report written to {}", "ギャップレポートを {} に書き出しました"),
+    ("Paused: {} ({})", "一時停止: {}（{}）"),
+    ("Output moved to {}. Files already completed remain in {}", "出力先を {} に変更しました。完了済みのファイルは {} に残っています"),
+    ("Failed to switch output directory: {}", "出力ディレクトリを切り替えられませんでした: {}"),
+    ("Resuming...", "再開しています..."),
+    ("{} hour {} min {} sec", "{} 時間 {} 分 {} 秒"),
+    ("Results appended to {}", "結果を {} に追記しました"),
+    ("Reading primes from {}", "{} から素数を読み込んでいます"),
+    ("{} primes in [{}, {})", "[{1}, {2}) の素数: {0} 個"),
+    ("Building index for {}", "{} の索引を作成しています"),
+    ("Index saved to {}", "索引を {} に保存しました"),
+    ("Failed to save index {}: {}", "索引 {} を保存できませんでした: {}"),
+    ("{} primes in {}", "{1} の素数: {0} 個"),
+    ("Fixed-width records in {}; searching the file directly", "{} はレコードの長さがそろっているので、索引を作らずに直接探索します"),
+    ("One zero-padded prime per line; every record has the same length, so the file can be binary searched", "1 行に 1 つ、ゼロ埋めした素数。どのレコードも同じ長さなので、ファイルをそのまま二分探索できます"),
+    ("first", "第 1 種"),
+    ("second", "第 2 種"),
+];
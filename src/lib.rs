@@ -4,5 +4,58 @@
 // See LICENSE file in the project root directory for more information.
 
 pub mod config;
+pub mod i18n;
+pub mod fonts;
+pub mod error;
 pub mod app;
+pub mod controller;
+pub mod monitor;
 pub mod sieve;
+pub mod primes;
+pub mod output;
+pub mod filter;
+pub mod script;
+pub mod gaps;
+pub mod goldbach;
+pub mod cunningham;
+pub mod miller_rabin;
+pub mod lucas_lehmer;
+pub mod pseudoprime;
+pub mod factor;
+pub mod carmichael;
+pub mod pratt;
+pub mod number_check;
+pub mod spiral;
+pub mod viewer;
+pub mod special_forms;
+pub mod race;
+pub mod segment_buffer;
+pub mod storage;
+pub mod power;
+pub mod taskbar;
+pub mod shell;
+pub mod websocket;
+pub mod distributed;
+pub mod work_units;
+pub mod archive;
+pub mod s3;
+pub mod webdav;
+pub mod sftp;
+pub mod database;
+pub mod pipe;
+pub mod base_primes;
+pub mod cache;
+pub mod metadata;
+pub mod nth_prime;
+pub mod async_writer;
+pub mod seekable;
+pub mod primesieve;
+pub mod cli;
+pub mod bench;
+pub mod paths;
+pub mod settings_watch;
+pub mod memory;
+pub mod aggregates;
+pub mod pi_report;
+pub mod gui_state;
+pub mod self_test;
@@ -0,0 +1,147 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// メルセンヌ数 2^p - 1 の判定モード。[prime_min, prime_max] の素数 p を指数として、
+// 小さな因数の試し割りで候補を絞ってから Lucas-Lehmer テストを行う
+
+use std::fs::{create_dir_all, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use num_bigint::BigUint;
+use num_traits::One;
+use crate::app::{ProgressPhase, WorkerMessage};
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::miller_rabin::pow_mod;
+use crate::sieve::{format_eta, simple_sieve};
+use crate::i18n::tr;
+
+pub const OUTPUT_FILE_NAME: &str = "mersenne_primes.txt";
+
+// 試し割りする因数 2kp+1 の k の上限
+const TRIAL_FACTOR_MAX_K: u64 = 10_000;
+
+// 指数の上限（これより大きい p のテストは現実的な時間で終わらない）
+const MAX_EXPONENT: u64 = 100_000_000;
+
+// 進捗を送る間隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+// 2^p - 1 の因数は q = 2kp + 1 (q ≡ ±1 mod 8) の形に限られる
+pub fn small_factor(p: u64, max_k: u64) -> Option<u64> {
+    (1..=max_k)
+        .map_while(|k| k.checked_mul(2 * p)?.checked_add(1).filter(|&q| q < 1 << 62))
+        // 小さな p では 2^p - 1 自身を因数と見なさない
+        .take_while(|&q| p >= 62 || q < (1 << p) - 1)
+        .filter(|q| q % 8 == 1 || q % 8 == 7)
+        .find(|&q| pow_mod(2, p, q) == 1)
+}
+
+// Lucas-Lehmer テスト。停止された場合は None。
+// on_iteration(i) は 2^10 回ごとに呼ばれる
+pub fn lucas_lehmer(p: u64, stop_flag: &AtomicBool, mut on_iteration: impl FnMut(u64)) -> Option<bool> {
+    if p == 2 {
+        return Some(true);
+    }
+    let m = (BigUint::one() << p) - 1u32;
+    let mut s = BigUint::from(4u32);
+    for i in 0..p - 2 {
+        if i & 0x3FF == 0 {
+            if stop_flag.load(Ordering::SeqCst) {
+                return None;
+            }
+            on_iteration(i);
+        }
+        // s^2 - 2 mod (2^p - 1)。2^p ≡ 1 を使って除算を避ける
+        s = &s * &s;
+        s = (&s & &m) + (&s >> p);
+        if s >= m {
+            s -= &m;
+        }
+        if s < BigUint::from(2u32) {
+            s += &m;
+        }
+        s -= 2u32;
+    }
+    Some(s.bits() == 0 || s == m)
+}
+
+pub fn run_lucas_lehmer(config: Config, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    sender.send(WorkerMessage::Log(tr!("Running Lucas-Lehmer test on Mersenne numbers 2^p - 1").to_string())).ok();
+
+    let prime_min = config.prime_min.parse::<u64>()?;
+    let prime_max = config.prime_max.parse::<u64>()?;
+    if prime_min > prime_max {
+        return Err(SieveError::Config("prime_min must not exceed prime_max".to_string()));
+    }
+    if prime_max > MAX_EXPONENT {
+        return Err(SieveError::Config(format!("exponents above {} are not supported", MAX_EXPONENT)));
+    }
+    if !config.output_dir.is_empty() {
+        create_dir_all(&config.output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(&config.output_dir), source })?;
+    }
+    let path = Path::new(&config.output_dir).join(OUTPUT_FILE_NAME);
+    let file = File::create(&path).map_err(|source| SieveError::OpenFile { path: path.clone(), source })?;
+    let mut out = BufWriter::new(file);
+
+    let exponents: Vec<u64> = simple_sieve(prime_max).into_iter().filter(|&p| p >= prime_min).collect();
+    sender.send(WorkerMessage::Log(tr!("{} prime exponents in range", exponents.len()))).ok();
+
+    let mut found = Vec::new();
+    let mut factored = 0u64;
+    for (index, &p) in exponents.iter().enumerate() {
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        let label = format!("M{} ({}/{})", p, index + 1, exponents.len());
+        if let Some(q) = small_factor(p, TRIAL_FACTOR_MAX_K) {
+            factored += 1;
+            sender.send(WorkerMessage::Log(tr!("{}: composite, factor {}", label, q))).ok();
+            continue;
+        }
+
+        // 候補ごとに進捗バーを 0 から進める
+        let start_time = Instant::now();
+        let mut last_sent = start_time;
+        let total = p.saturating_sub(2).max(1);
+        sender.send(WorkerMessage::Progress { current: 0, total, phase: ProgressPhase::Testing }).ok();
+        let result = lucas_lehmer(p, &stop_flag, |i| {
+            if last_sent.elapsed() >= PROGRESS_INTERVAL {
+                last_sent = Instant::now();
+                sender.send(WorkerMessage::Progress { current: i, total, phase: ProgressPhase::Testing }).ok();
+                sender.send(WorkerMessage::Eta(format!("{} - {}", label, format_eta(start_time, i as f64 / total as f64)))).ok();
+            }
+        });
+        let is_prime = match result {
+            Some(is_prime) => is_prime,
+            None => {
+                sender.send(WorkerMessage::Stopped).ok();
+                return Ok(());
+            }
+        };
+        sender.send(WorkerMessage::Progress { current: total, total, phase: ProgressPhase::Testing }).ok();
+        let elapsed = start_time.elapsed().as_secs_f64();
+        if is_prime {
+            found.push(p);
+            writeln!(out, "{}", p).and_then(|_| out.flush()).map_err(SieveError::write(&path))?;
+            sender.send(WorkerMessage::Log(tr!("{}: PRIME ({} s)", label, format!("{:.2}", elapsed)))).ok();
+        } else {
+            sender.send(WorkerMessage::Log(tr!("{}: composite ({} s)", label, format!("{:.2}", elapsed)))).ok();
+        }
+    }
+
+    sender.send(WorkerMessage::Eta(tr!("0 hour 0 min 0 sec").to_string())).ok();
+    sender.send(WorkerMessage::Log(tr!(
+        "Finished Lucas-Lehmer. {} exponents tested ({} eliminated by trial factoring). Mersenne primes: {}",
+        exponents.len(), factored, format!("{:?}", found)))).ok();
+    sender.send(WorkerMessage::Finished { found: found.len() as u64, files: vec![path] }).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
@@ -3,8 +3,35 @@
 // This software is released under the MIT License.
 // See LICENSE file in the project root directory for more information.
 
+use eframe::egui;
+use sosu_seisei_sieve::{cli, paths};
+use sosu_seisei_sieve::gui_state::GuiState;
+
 fn main() {
-    let options = eframe::NativeOptions::default();
+    let args = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) if message == cli::USAGE => {
+            println!("{}", message);
+            std::process::exit(cli::EXIT_SUCCESS);
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(cli::EXIT_INVALID_CONFIG);
+        }
+    };
+    paths::init(args.portable);
+    if let Some(options) = args.bench {
+        std::process::exit(cli::bench(options));
+    }
+    // --cli などがあればウィンドウを開かずに実行する
+    if let Some(options) = args.cli {
+        std::process::exit(cli::run(options));
+    }
+    // 前回のウィンドウの位置と大きさで開く
+    let mut options = eframe::NativeOptions::default();
+    if let Some(window) = GuiState::load().window {
+        options.viewport = window.apply(egui::ViewportBuilder::default());
+    }
     let _ = eframe::run_native(
         "Sosu-Seisei Settings",
         options,
@@ -0,0 +1,95 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// メモリ使用量のプリセットと、実行中に必要なメモリの見積もり
+
+use crate::config::{Config, RunMode};
+use crate::sieve::MAX_SEGMENT_GROWTH;
+
+const MIB: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryPreset {
+    Low,
+    Medium,
+    High,
+}
+
+impl MemoryPreset {
+    pub const ALL: [MemoryPreset; 3] = [MemoryPreset::Low, MemoryPreset::Medium, MemoryPreset::High];
+
+    // (segment_size, writer_buffer_size, parallel_segments)。Medium は既定値と同じ
+    fn sizes(self) -> (u64, usize, usize) {
+        match self {
+            MemoryPreset::Low => (1_000_000, MIB, 2),
+            MemoryPreset::Medium => (10_000_000, 8 * MIB, 0),
+            MemoryPreset::High => (32_000_000, 32 * MIB, 0),
+        }
+    }
+
+    pub fn apply(self, config: &Config) -> Config {
+        let (segment_size, writer_buffer_size, parallel_segments) = self.sizes();
+        Config { segment_size, writer_buffer_size, parallel_segments, ..config.clone() }
+    }
+
+    // 設定がいずれかのプリセットと一致すればそれを返す
+    pub fn matching(config: &Config) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.sizes() == (config.segment_size, config.writer_buffer_size, config.parallel_segments))
+    }
+}
+
+// 見積もり（バイト）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    // 同時に篩うセグメントと篩に使う小さい素数
+    pub sieving: u64,
+    // 書き出す前に並べ替えるために保持する素数（Generate のみ）
+    pub primes: u64,
+    // 書き込みバッファ（async_writer では待ち行列のブロックも含む）
+    pub writer: u64,
+}
+
+impl MemoryEstimate {
+    pub fn total(&self) -> u64 {
+        self.sieving + self.primes + self.writer
+    }
+}
+
+// x 以下の素数の数のおおよその値（x / (ln x - 1)。この式が増加しない小さい x は数える）
+fn approx_prime_count(x: u64) -> u64 {
+    const SMALL: [u64; 8] = [0, 0, 1, 2, 2, 3, 3, 4];
+    if x < 8 {
+        return SMALL[x as usize];
+    }
+    let x = x as f64;
+    (x / (x.ln() - 1.0)) as u64
+}
+
+// [prime_min, prime_max] を threads 個のワーカーで処理するときに必要なメモリのおおよその最大値
+pub fn estimate_peak_memory(config: &Config, prime_min: u64, prime_max: u64, threads: usize) -> MemoryEstimate {
+    let window = match config.parallel_segments {
+        0 => threads.max(1),
+        n => n,
+    } as u64;
+    // 適応的なセグメントは最大まで大きくなるとみなす
+    let growth = if config.adaptive_segments && config.mode == RunMode::Generate { MAX_SEGMENT_GROWTH } else { 1 };
+    let segment = config.segment_size.saturating_mul(growth).min(prime_max.saturating_sub(prime_min) + 1);
+    // セグメントは 1 数 1 バイト、見つかった素数は 1 個 8 バイト
+    let segment_primes = approx_prime_count(prime_min.saturating_add(segment)).saturating_sub(approx_prime_count(prime_min));
+    let small_primes = approx_prime_count((prime_max as f64).sqrt() as u64 + 1);
+    let sieving = window * (segment + segment_primes * 8) + small_primes * 8;
+
+    // 蓄積する Vec は容量を倍々に増やすので、最大で 2 のべき乗に切り上げた分を確保する
+    let primes = if config.mode == RunMode::Generate {
+        let count = approx_prime_count(prime_max).saturating_sub(approx_prime_count(prime_min.saturating_sub(1)));
+        count.next_power_of_two() * 8
+    } else {
+        0
+    };
+
+    let blocks = if config.async_writer { 1 + config.writer_queue_blocks } else { 1 };
+    let writer = (config.writer_buffer_size * blocks) as u64;
+    MemoryEstimate { sieving, primes, writer }
+}
@@ -0,0 +1,394 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 出力ファイルの組（primes.txt、または primes_1.txt, primes_2.txt, ...）ごとのメタデータ。
+// 出力ディレクトリに <基本名>.meta.json として置き、どの範囲を篩った結果か、
+// どのフィルタを通したかを記録する。追記モードではこれを見て続きの範囲から篩い、
+// 実行前には出力ディレクトリのほかの組との範囲の重なりを調べる。素数の指紋も記録するので、
+// 別のマシンで篩った同じ範囲の出力をファイルを比べずに照合できる
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use crate::app::WorkerMessage;
+use crate::config::{Config, OutputFormat, OverwriteMode, RunMode};
+use crate::error::SieveError;
+use crate::i18n::{format_number, tr};
+use crate::output::{existing_outputs, is_binary, BASE_NAME, ZSTD_EXTENSION};
+use xxhash_rust::xxh3::xxh3_64;
+
+pub const META_SUFFIX: &str = ".meta.json";
+
+// 素数の列の指紋。各素数（8 バイトのリトルエンディアン）の xxh3 を 2^64 を法として足し合わせる。
+// 和は順序によらないので、降順の出力や追記で続けた出力も、同じ素数なら同じ値になる
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fingerprint(pub u64);
+
+impl Fingerprint {
+    pub fn of(primes: &[u64]) -> Self {
+        let mut fingerprint = Fingerprint::default();
+        for &p in primes {
+            fingerprint.add(p);
+        }
+        fingerprint
+    }
+
+    pub fn add(&mut self, p: u64) {
+        self.0 = self.0.wrapping_add(xxh3_64(&p.to_le_bytes()));
+    }
+
+    pub fn combine(self, other: Fingerprint) -> Self {
+        Fingerprint(self.0.wrapping_add(other.0))
+    }
+
+    pub fn hex(self) -> String {
+        format!("{:016x}", self.0)
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        u64::from_str_radix(text, 16).ok().map(Fingerprint)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutputMeta {
+    // 篩い終えた範囲（昇順、重なりなし）
+    pub ranges: Vec<(u64, u64)>,
+    // 範囲内で見つかった素数の数（フィルタ前）
+    pub primes_found: u64,
+    pub output_format: OutputFormat,
+    #[serde(default = "default_radix")]
+    pub output_radix: u32,
+    pub split_count: u64,
+    pub residue_modulus: u64,
+    pub residues: Vec<u64>,
+    pub digit_filter: String,
+    #[serde(default)]
+    pub filter_script: String,
+    #[serde(default)]
+    pub descending: bool,
+    #[serde(default)]
+    pub zstd_seekable: bool,
+    // ranges 内の素数（フィルタ前）の Fingerprint（16 進）。指紋のない版で書いた範囲を含めば None
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    // 出力ファイル名（書いた順）
+    pub files: Vec<String>,
+}
+
+fn default_radix() -> u32 {
+    10
+}
+
+impl OutputMeta {
+    pub fn new(config: &Config) -> Self {
+        OutputMeta {
+            ranges: Vec::new(),
+            primes_found: 0,
+            output_format: config.output_format.clone(),
+            output_radix: config.output_radix,
+            split_count: config.split_count,
+            residue_modulus: config.residue_modulus,
+            residues: config.residues.clone(),
+            digit_filter: config.digit_filter.trim().to_string(),
+            filter_script: config.filter_script.trim().to_string(),
+            // 降順は Generate のみ
+            descending: config.descending && config.mode == RunMode::Generate,
+            zstd_seekable: config.zstd_seekable,
+            fingerprint: Some(Fingerprint::default().hex()),
+            files: Vec::new(),
+        }
+    }
+
+    // 同じ形式・同じフィルタで書いた出力か（続きを書いてよいか）。降順の出力は続きを書くと順序が崩れるので対象外
+    pub fn matches(&self, config: &Config) -> bool {
+        let descending = config.descending && config.mode == RunMode::Generate;
+        self.output_format == config.output_format
+            && self.output_radix == config.output_radix
+            && self.zstd_seekable == config.zstd_seekable
+            && self.split_count == config.split_count
+            && !self.descending
+            && !descending
+            && self.same_filters(config)
+    }
+
+    // 同じフィルタを通した出力か（形式が違っても同じ素数を含む）
+    pub fn same_filters(&self, config: &Config) -> bool {
+        let other = OutputMeta::new(config);
+        self.residue_modulus == other.residue_modulus
+            && (self.residue_modulus == 0 || self.residues == other.residues)
+            && self.digit_filter == other.digit_filter
+            && self.filter_script == other.filter_script
+    }
+
+    // 範囲を加え、隣接・重なる範囲をまとめる
+    pub fn add_range(&mut self, low: u64, high: u64) {
+        self.ranges.push((low, high));
+        self.ranges.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(self.ranges.len());
+        for &(low, high) in &self.ranges {
+            match merged.last_mut() {
+                Some(last) if low <= last.1.saturating_add(1) => last.1 = last.1.max(high),
+                _ => merged.push((low, high)),
+            }
+        }
+        self.ranges = merged;
+    }
+
+    pub fn add_files(&mut self, files: &[PathBuf]) {
+        for file in files {
+            if let Some(name) = file.file_name().and_then(|n| n.to_str()) {
+                if !self.files.iter().any(|f| f == name) {
+                    self.files.push(name.to_string());
+                }
+            }
+        }
+    }
+
+    // prime_min からの範囲を追記するときの篩い始め。篩い終えた範囲は飛ばし、x + 1（x は最後の範囲の終わり）と
+    // prime_min の大きいほうを返す。prime_min が最後の範囲より前なら、追記すると昇順でなくなるので None
+    pub fn continuation(&self, prime_min: u64) -> Option<u64> {
+        match self.ranges.last() {
+            Some(&(low, _)) if prime_min < low => None,
+            Some(&(_, high)) => Some(prime_min.max(high.saturating_add(1))),
+            None => Some(prime_min),
+        }
+    }
+}
+
+// 出力ファイルの組のメタデータのパス（"primes (2)_3.txt" -> "primes (2).meta.json"）
+pub fn meta_path(file: &Path, split_count: u64) -> PathBuf {
+    // "primes.txt.zst" は "primes.txt" と同じ名前にする
+    let uncompressed = if file.extension().is_some_and(|e| e == ZSTD_EXTENSION) { file.with_extension("") } else { file.to_path_buf() };
+    let stem = uncompressed.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let base = if split_count > 0 {
+        // prime_index の "primes_from_idx_1001.txt" も "primes.meta.json"
+        let base = stem.rsplit_once('_').map_or(stem, |(base, _)| base);
+        base.strip_suffix("_from_idx").unwrap_or(base)
+    } else {
+        stem
+    };
+    file.with_file_name(format!("{}{}", base, META_SUFFIX))
+}
+
+pub fn read_meta(path: &Path) -> io::Result<OutputMeta> {
+    let text = fs::read_to_string(path)?;
+    serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// file を含む組のメタデータ（分割しない組と分割した組の名前を探し、files に file があるもの）
+pub fn meta_for_file(file: &Path) -> Option<OutputMeta> {
+    let name = file.file_name()?.to_str()?;
+    [0, 1].into_iter().find_map(|split_count| read_meta(&meta_path(file, split_count)).ok().filter(|meta| meta.files.iter().any(|f| f == name)))
+}
+
+// ギャップ解析やスパイラルは出力を 10 進のテキスト（Text / CSV / JSON）として読むので、それ以外で書いたファイルは断る。
+// 形式と基数はメタデータから決め、メタデータがなければ assumed（出力の設定など）を使う
+pub fn require_decimal_text(file: &Path, assumed: Option<(&OutputFormat, u32)>) -> Result<(), SieveError> {
+    let meta = meta_for_file(file);
+    let (format, radix) = match (&meta, assumed) {
+        (Some(meta), _) => (Some(&meta.output_format), meta.output_radix),
+        (None, Some((format, radix))) => (Some(format), radix),
+        (None, None) => (None, 10),
+    };
+    let format = if is_binary(file) { Some(&OutputFormat::Binary) } else { format };
+    if let Some(format @ (OutputFormat::Binary | OutputFormat::FixedWidth)) = format {
+        return Err(SieveError::Config(format!("{} was written in the {:?} format; only decimal Text, CSV or JSON output can be read", file.display(), format)));
+    }
+    if radix != 10 {
+        return Err(SieveError::Config(format!("{} was written in base {}; only decimal Text, CSV or JSON output can be read", file.display(), radix)));
+    }
+    Ok(())
+}
+
+pub fn write_meta(path: &Path, meta: &OutputMeta) -> io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, serde_json::to_string_pretty(meta)?)?;
+    fs::rename(&temp_path, path)
+}
+
+// 実行前に決める、既存の出力とメタデータの扱い
+pub enum OutputPlan {
+    // 新しい出力。終わったらメタデータを新しく書く
+    Fresh,
+    // 既存の出力の続きを start から篩って追記し、meta を更新する
+    Continue { start: u64, meta: OutputMeta },
+    // 既存の出力が [prime_min, prime_max] をすでに含む
+    Covered,
+    // メタデータがないか設定の合わない出力への追記。範囲がわからないのでメタデータは書かない
+    Untracked,
+}
+
+pub fn plan_output(config: &Config, prime_min: u64, prime_max: u64, sender: &mpsc::Sender<WorkerMessage>) -> OutputPlan {
+    if config.overwrite_mode != OverwriteMode::Append {
+        return OutputPlan::Fresh;
+    }
+    let existing = existing_outputs(Path::new(&config.output_dir), &config.output_format, config.zstd_seekable, config.split_count);
+    let Some(first) = existing.first() else {
+        return OutputPlan::Fresh;
+    };
+    let path = meta_path(first, config.split_count);
+    let Ok(meta) = read_meta(&path) else {
+        return OutputPlan::Untracked;
+    };
+    if !meta.matches(config) {
+        sender.send(WorkerMessage::Log(tr!("{} was written with other format or filter settings; appending without updating it", path.display()))).ok();
+        return OutputPlan::Untracked;
+    }
+    match meta.continuation(prime_min) {
+        Some(start) if start > prime_max => {
+            sender.send(WorkerMessage::Log(tr!("The existing output already covers [{}, {}]", format_number(prime_min), format_number(prime_max)))).ok();
+            OutputPlan::Covered
+        }
+        Some(start) => {
+            if start > prime_min {
+                sender.send(WorkerMessage::Log(tr!("Continuing the existing output from {} ([{}, {}] already written)", format_number(start), format_number(meta.ranges[0].0), format_number(start - 1)))).ok();
+            }
+            OutputPlan::Continue { start, meta }
+        }
+        None => {
+            sender.send(WorkerMessage::Log(tr!("Warning: [{}, {}] starts before the end of the existing output; the appended primes will not be in order", format_number(prime_min), format_number(prime_max)))).ok();
+            OutputPlan::Untracked
+        }
+    }
+}
+
+// 実行が終わったら出力のメタデータに篩った範囲を書く（Untracked と Covered では何もしない）
+pub fn record_output(plan: OutputPlan, config: &Config, ranges: &[(u64, u64)], primes_found: u64, fingerprint: Fingerprint, files: &[PathBuf], sender: &mpsc::Sender<WorkerMessage>) {
+    let mut meta = match plan {
+        OutputPlan::Fresh => OutputMeta::new(config),
+        OutputPlan::Continue { meta, .. } => meta,
+        OutputPlan::Covered | OutputPlan::Untracked => return,
+    };
+    let Some(first) = files.first() else {
+        return;
+    };
+    for &(low, high) in ranges {
+        meta.add_range(low, high);
+    }
+    meta.primes_found += primes_found;
+    meta.fingerprint = meta.fingerprint.as_deref().and_then(Fingerprint::parse).map(|f| f.combine(fingerprint).hex());
+    meta.add_files(files);
+    let path = meta_path(first, config.split_count);
+    if let Err(e) = write_meta(&path, &meta) {
+        sender.send(WorkerMessage::Log(tr!("Failed to write {}: {}", path.display(), e))).ok();
+        return;
+    }
+    if let Some(fingerprint) = &meta.fingerprint {
+        sender.send(WorkerMessage::Log(tr!("Fingerprint of the {} primes in {}: {}", format_number(meta.primes_found), path.display(), fingerprint))).ok();
+    }
+}
+
+// 出力ディレクトリにあるほかの出力ファイルの組のうち、範囲が [low, high] と重なるもの
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlap {
+    pub meta_path: PathBuf,
+    // 重なる部分（昇順）
+    pub ranges: Vec<(u64, u64)>,
+    pub same_filters: bool,
+    // メタデータにあるのに見つからないファイル
+    pub missing_files: Vec<String>,
+}
+
+impl Overlap {
+    // この組の範囲を篩い終えたものとして飛ばしてよいか
+    pub fn is_reusable(&self) -> bool {
+        self.same_filters && self.missing_files.is_empty()
+    }
+}
+
+fn output_dir(config: &Config) -> &Path {
+    Path::new(if config.output_dir.is_empty() { "." } else { &config.output_dir })
+}
+
+pub fn find_overlaps(config: &Config, low: u64, high: u64) -> Vec<Overlap> {
+    let dir = output_dir(config);
+    // 上書き・追記する組は重なりとして扱わない（Version では新しい組に書くのですべて対象）
+    let own = (config.overwrite_mode != OverwriteMode::Version).then(|| dir.join(format!("{}{}", BASE_NAME, META_SUFFIX)));
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(META_SUFFIX)))
+            .filter(|p| own.as_ref() != Some(p))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let meta = read_meta(&path).ok()?;
+            let ranges: Vec<(u64, u64)> = meta
+                .ranges
+                .iter()
+                .map(|&(l, h)| (l.max(low), h.min(high)))
+                .filter(|&(l, h)| l <= h)
+                .collect();
+            if ranges.is_empty() {
+                return None;
+            }
+            let missing_files = meta.files.iter().filter(|f| !path.with_file_name(f).exists()).cloned().collect();
+            Some(Overlap { meta_path: path, ranges, same_filters: meta.same_filters(config), missing_files })
+        })
+        .collect()
+}
+
+// [low, high] のうち、飛ばしてよい組のどれにも含まれない範囲（昇順）
+pub fn uncovered_ranges(low: u64, high: u64, overlaps: &[Overlap]) -> Vec<(u64, u64)> {
+    let mut covered: Vec<(u64, u64)> = overlaps.iter().filter(|o| o.is_reusable()).flat_map(|o| o.ranges.iter().copied()).collect();
+    covered.sort_unstable();
+    let mut uncovered = Vec::new();
+    let mut next = low;
+    for (l, h) in covered {
+        if l > next {
+            uncovered.push((next, l - 1));
+        }
+        if h >= high {
+            return uncovered;
+        }
+        next = next.max(h + 1);
+    }
+    uncovered.push((next, high));
+    uncovered
+}
+
+pub fn format_ranges(ranges: &[(u64, u64)]) -> String {
+    ranges
+        .iter()
+        .map(|&(l, h)| format!("[{}, {}]", format_number(l), format_number(h)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// 実行前にほかの出力との重なりを警告し、篩う範囲を返す。skip_covered_ranges なら重なる部分を除く
+pub fn ranges_to_sieve(config: &Config, low: u64, high: u64, sender: &mpsc::Sender<WorkerMessage>) -> Vec<(u64, u64)> {
+    let overlaps = find_overlaps(config, low, high);
+    for overlap in &overlaps {
+        let path = overlap.meta_path.display();
+        let message = if !overlap.missing_files.is_empty() {
+            tr!("Warning: {} lists missing files ({}); its range is not treated as covered", path, overlap.missing_files.join(", "))
+        } else if !overlap.same_filters {
+            tr!("Warning: {} already covers {} with other filters", path, format_ranges(&overlap.ranges))
+        } else if overlap.ranges == [(low, high)] {
+            tr!("Warning: {} duplicates the output recorded in {}", format_ranges(&overlap.ranges), path)
+        } else {
+            tr!("Warning: {} overlaps the output recorded in {}", format_ranges(&overlap.ranges), path)
+        };
+        sender.send(WorkerMessage::Log(message)).ok();
+    }
+    if !config.skip_covered_ranges {
+        return vec![(low, high)];
+    }
+    let ranges = uncovered_ranges(low, high, &overlaps);
+    if ranges.is_empty() {
+        sender.send(WorkerMessage::Log(tr!("Other outputs already cover [{}, {}]", format_number(low), format_number(high)))).ok();
+    } else if ranges != [(low, high)] {
+        sender.send(WorkerMessage::Log(tr!("Skipping ranges covered by other outputs; sieving {}", format_ranges(&ranges)))).ok();
+    }
+    ranges
+}
@@ -0,0 +1,652 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// u64 の範囲で決定的な Miller-Rabin 判定（篩の範囲外の数の判定用）と、
+// 特殊形の素数判定で使う BigUint 版のべき乗剰余・強擬素数判定。
+// u128 に収まる数は BigUint を使わずモンゴメリ乗算で判定する
+
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+use rayon::prelude::*;
+
+// 最初の 12 個の素数を底にすれば 2^64 未満で誤判定しない
+const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+// u128 用には 41 も加えた最初の 13 個の素数を底にする（12 個では 3.2 × 10^23 の強擬素数を通してしまう）
+const BASES_U128: [u64; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+// n がこれ未満なら BASES の先頭の個数の底で足りる（それぞれの上限は最小の強擬素数）
+const BASE_COUNTS: [(u64, usize); 8] = [
+    (2_047, 1),
+    (1_373_653, 2),
+    (25_326_001, 3),
+    (3_215_031_751, 4),
+    (2_152_302_898_747, 5),
+    (3_474_749_660_383, 6),
+    (341_550_071_728_321, 7),
+    (3_825_123_056_546_413_051, 9),
+];
+
+// 3 から 53 までの素数の積（u64 に収まる）。これと互いに素でない数は小さい因数を持つ
+const SMALL_PRIMES_PRODUCT: u64 = 3 * 5 * 7 * 11 * 13 * 17 * 19 * 23 * 29 * 31 * 37 * 41 * 43 * 47 * 53;
+
+pub fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 * b as u128) % m as u128) as u64
+}
+
+// 奇数の法 m でのモンゴメリ乗算（R = 2^64）。u128 の剰余（除算）を使わないので、
+// べき乗剰余が u128 の % より 2 倍ほど速い
+#[derive(Debug, Clone, Copy)]
+struct Montgomery {
+    m: u64,
+    // m^-1 mod 2^64
+    m_inv: u64,
+    // R^2 mod m
+    r2: u64,
+}
+
+impl Montgomery {
+    // m は 3 以上の奇数
+    fn new(m: u64) -> Self {
+        debug_assert!(m % 2 == 1 && m > 1);
+        // ニュートン法で 2^64 を法とする逆数を求める（1 回ごとに正しいビット数が倍になる）
+        let mut m_inv = m;
+        for _ in 0..5 {
+            m_inv = m_inv.wrapping_mul(2u64.wrapping_sub(m.wrapping_mul(m_inv)));
+        }
+        let r = ((1u128 << 64) % m as u128) as u64;
+        Montgomery { m, m_inv, r2: mul_mod(r, r, m) }
+    }
+
+    // t·R^-1 mod m（t < m·2^64）
+    fn reduce(&self, t: u128) -> u64 {
+        let u = (t as u64).wrapping_mul(self.m_inv);
+        let (high, um_high) = ((t >> 64) as u64, ((u as u128 * self.m as u128) >> 64) as u64);
+        // 下位 64 ビットは等しいので、上位だけ引けばよい
+        if high >= um_high { high - um_high } else { high.wrapping_sub(um_high).wrapping_add(self.m) }
+    }
+
+    // a をモンゴメリ表現 a·R mod m にする
+    fn enter(&self, a: u64) -> u64 {
+        self.mul(a % self.m, self.r2)
+    }
+
+    // モンゴメリ表現から通常の値に戻す
+    fn leave(&self, a: u64) -> u64 {
+        self.reduce(a as u128)
+    }
+
+    // モンゴメリ表現どうしの積
+    fn mul(&self, a: u64, b: u64) -> u64 {
+        self.reduce(a as u128 * b as u128)
+    }
+
+    // モンゴメリ表現の base の exp 乗
+    fn pow(&self, mut base: u64, mut exp: u64) -> u64 {
+        let mut result = self.enter(1);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = self.mul(result, base);
+            }
+            base = self.mul(base, base);
+            exp >>= 1;
+        }
+        result
+    }
+}
+
+pub fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    if m % 2 == 1 && m > 1 {
+        let mont = Montgomery::new(m);
+        return mont.leave(mont.pow(mont.enter(base), exp));
+    }
+    let mut result = 1 % m;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+// n - 1 = d * 2^s として、底 a で n が強擬素数かどうか
+pub fn is_strong_probable_prime(n: u64, a: u64) -> bool {
+    if n % 2 == 1 && n > 1 {
+        return is_strong_probable_prime_mont(&Montgomery::new(n), a);
+    }
+    let a = a % n;
+    if a == 0 {
+        return true;
+    }
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    let mut x = pow_mod(a, d, n);
+    if x == 1 || x == n - 1 {
+        return true;
+    }
+    for _ in 1..s {
+        x = mul_mod(x, x, n);
+        if x == n - 1 {
+            return true;
+        }
+    }
+    false
+}
+
+// 奇数 n の判定。モンゴメリ表現のまま 1 と n - 1 と比べる
+fn is_strong_probable_prime_mont(mont: &Montgomery, a: u64) -> bool {
+    let n = mont.m;
+    let a = a % n;
+    if a == 0 {
+        return true;
+    }
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    let (one, minus_one) = (mont.enter(1), mont.enter(n - 1));
+    let mut x = mont.pow(mont.enter(a), d);
+    if x == one || x == minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = mont.mul(x, x);
+        if x == minus_one {
+            return true;
+        }
+    }
+    false
+}
+
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for &p in &BASES {
+        if n == p {
+            return true;
+        }
+        if n.is_multiple_of(p) {
+            return false;
+        }
+    }
+    // ここまでで n は 37 より大きい奇数
+    let mont = Montgomery::new(n);
+    BASES.iter().all(|&a| is_strong_probable_prime_mont(&mont, a))
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// ファイルの素数を確かめるときの判定（結果は is_prime と同じ）。小さい因数は積との GCD 1 回で除き、
+// 底は n の大きさに足りる個数だけ使うので、素数が並んでいる普通の場合に速い
+pub fn verify_prime(n: u64) -> bool {
+    if n <= 53 {
+        return is_prime(n);
+    }
+    if n.is_multiple_of(2) || gcd(SMALL_PRIMES_PRODUCT, n) != 1 {
+        return false;
+    }
+    let count = BASE_COUNTS.iter().find(|&&(limit, _)| n < limit).map_or(BASES.len(), |&(_, count)| count);
+    let mont = Montgomery::new(n);
+    BASES[..count].iter().all(|&a| is_strong_probable_prime_mont(&mont, a))
+}
+
+// numbers を step 個ごとに並列で確かめ、素数でないものがあれば 1 つ返す
+pub fn find_non_prime(numbers: &[u64], step: usize) -> Option<u64> {
+    numbers.par_iter().step_by(step).find_any(|&&n| !verify_prime(n)).copied()
+}
+
+// 以下は u64 に収まらない数（BigUint）用
+
+// 試し割りに使う小さな素数
+const SMALL_PRIMES: [u32; 25] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97];
+
+// 最初の 13 個の素数（BASES_U128）を底にした判定が誤らない上限（3.3 × 10^24、Sorenson と Webster による）。
+// これ以上の数には証明された固定の底の組がないので、判定は確率的になる
+pub const DETERMINISTIC_LIMIT: u128 = 3_317_044_064_679_887_385_961_981;
+
+pub fn is_strong_probable_prime_big(n: &BigUint, a: u64) -> bool {
+    is_strong_probable_prime_big_base(n, &BigUint::from(a))
+}
+
+fn is_strong_probable_prime_big_base(n: &BigUint, a: &BigUint) -> bool {
+    let one = BigUint::one();
+    let n_minus_one = n - &one;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+    let a = a % n;
+    if a.is_zero() {
+        return true;
+    }
+    let mut x = a.modpow(&d, n);
+    if x == one || x == n_minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = &x * &x % n;
+        if x == n_minus_one {
+            return true;
+        }
+    }
+    false
+}
+
+// u64 に収まる場合は決定的判定、それ以外は小さな素数の底による確率的判定
+pub fn is_probable_prime_big(n: &BigUint) -> bool {
+    if let Some(n) = n.to_u64() {
+        return is_prime(n);
+    }
+    if let Some(n) = n.to_u128() {
+        return is_probable_prime_u128(n);
+    }
+    if SMALL_PRIMES.iter().any(|&p| (n % p).is_zero()) {
+        return false;
+    }
+    BASES.iter().all(|&a| is_strong_probable_prime_big(n, a))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BigPrimality {
+    Composite,
+    // 決定的な判定で素数
+    Prime,
+    // DETERMINISTIC_LIMIT 以上で、BPSW とランダムな底の判定をすべて通った
+    ProbablePrime,
+}
+
+// DETERMINISTIC_LIMIT 未満は決定的に判定する。それ以上は BPSW に加えて、
+// ランダムな底の Miller-Rabin を random_rounds 回行う（1 回ごとに誤る確率は 1/4 以下）
+pub fn check_prime_big(n: &BigUint, random_rounds: u32) -> BigPrimality {
+    // DETERMINISTIC_LIMIT 未満はすべて u128 に収まる
+    if let Some(n) = n.to_u128() {
+        return check_prime_u128(n, random_rounds);
+    }
+    if !is_bpsw_probable_prime(n) {
+        return BigPrimality::Composite;
+    }
+    let bases = n - 3u32;
+    let mut bytes = vec![0u8; n.bits().div_ceil(8) as usize + 8];
+    for _ in 0..random_rounds {
+        // 乱数が得られなければ 0 埋め（底 2）になるが、BPSW ですでに確かめている
+        let _ = getrandom::getrandom(&mut bytes);
+        let a = BigUint::from_bytes_le(&bytes) % &bases + 2u32;
+        if !is_strong_probable_prime_big_base(n, &a) {
+            return BigPrimality::Composite;
+        }
+    }
+    BigPrimality::ProbablePrime
+}
+
+// ヤコビ記号 (a / n)。n は奇数
+pub fn jacobi(a: u64, n: &BigUint) -> i32 {
+    // (a / n) = (a mod n / n) で、a が小さいので相互法則で (n mod a / a) に帰着させる
+    let mut a = a;
+    let mut sign = 1;
+    while a.is_multiple_of(2) && a > 0 {
+        a /= 2;
+        // (2 / n) = -1 ⇔ n ≡ 3, 5 mod 8
+        let r = (n % 8u32).to_u32().unwrap_or(0);
+        if r == 3 || r == 5 {
+            sign = -sign;
+        }
+    }
+    if a == 0 {
+        return 0;
+    }
+    if a == 1 {
+        return sign;
+    }
+    let n_mod_a = (n % a).to_u64().unwrap_or(0);
+    let n_mod_4 = (n % 4u32).to_u32().unwrap_or(0);
+    if a % 4 == 3 && n_mod_4 == 3 {
+        sign = -sign;
+    }
+    sign * jacobi_u128(n_mod_a as u128, a as u128)
+}
+
+fn jacobi_u128(mut a: u128, mut n: u128) -> i32 {
+    let mut sign = 1;
+    a %= n;
+    while a != 0 {
+        while a.is_multiple_of(2) {
+            a /= 2;
+            if n % 8 == 3 || n % 8 == 5 {
+                sign = -sign;
+            }
+        }
+        std::mem::swap(&mut a, &mut n);
+        if a % 4 == 3 && n % 4 == 3 {
+            sign = -sign;
+        }
+        a %= n;
+    }
+    if n == 1 { sign } else { 0 }
+}
+
+// BPSW 判定（底 2 の強擬素数判定 + 強 Lucas 擬素数判定）。反例は知られていない
+pub fn is_bpsw_probable_prime(n: &BigUint) -> bool {
+    if let Some(n) = n.to_u64() {
+        return is_prime(n);
+    }
+    if let Some(n) = n.to_u128() {
+        return is_bpsw_probable_prime_u128(n);
+    }
+    if SMALL_PRIMES.iter().any(|&p| (n % p).is_zero()) {
+        return false;
+    }
+    if !is_strong_probable_prime_big(n, 2) {
+        return false;
+    }
+    // 平方数には条件を満たす D が存在しない
+    let root = n.sqrt();
+    if &root * &root == *n {
+        return false;
+    }
+    is_strong_lucas_probable_prime(n)
+}
+
+// Selfridge の方法で D = 5, -7, 9, -11, ... から (D / n) = -1 となるものを選び、
+// P = 1, Q = (1 - D) / 4 の Lucas 数列で判定する
+fn is_strong_lucas_probable_prime(n: &BigUint) -> bool {
+    let n_mod_4 = (n % 4u32).to_u32().unwrap_or(0);
+    let mut d: i64 = 5;
+    loop {
+        let j = jacobi(d.unsigned_abs(), n);
+        // (-1 / n) = 1 ⇔ n ≡ 1 mod 4
+        let j = if d < 0 && n_mod_4 == 3 { -j } else { j };
+        if j == -1 {
+            break;
+        }
+        if j == 0 && BigUint::from(d.unsigned_abs()) != *n {
+            return false;
+        }
+        d = if d > 0 { -(d + 2) } else { -d + 2 };
+    }
+    let q = (1 - d) / 4;
+    // 負の数は n を法として正の代表元にする
+    let to_mod = |v: i64| {
+        let r = BigUint::from(v.unsigned_abs()) % n;
+        if v < 0 && !r.is_zero() { n - r } else { r }
+    };
+    let d_mod = to_mod(d);
+    let q_mod = to_mod(q);
+    let half = |x: BigUint| if x.bit(0) { (x + n) >> 1 } else { x >> 1 };
+    let sub_mod = |a: &BigUint, b: &BigUint| if a >= b { a - b } else { n - (b - a) };
+
+    let n_plus_one: BigUint = n + 1u32;
+    let s = n_plus_one.trailing_zeros().unwrap_or(0);
+    let k = &n_plus_one >> s;
+
+    // U_1 = 1, V_1 = P = 1, Q^1
+    let mut u = BigUint::one();
+    let mut v = BigUint::one();
+    let mut qk = q_mod.clone();
+    for i in (0..k.bits() - 1).rev() {
+        // 2 倍: U_2k = U_k V_k, V_2k = V_k^2 - 2Q^k
+        u = &u * &v % n;
+        v = sub_mod(&(&v * &v % n), &(&qk * 2u32 % n));
+        qk = &qk * &qk % n;
+        if k.bit(i) {
+            // 1 進める: U_k+1 = (U_k + V_k) / 2, V_k+1 = (D U_k + V_k) / 2
+            let new_u = half((&u + &v) % n);
+            v = half((&d_mod * &u + &v) % n);
+            u = new_u;
+            qk = &qk * &q_mod % n;
+        }
+    }
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..s {
+        v = sub_mod(&(&v * &v % n), &(&qk * 2u32 % n));
+        if v.is_zero() {
+            return true;
+        }
+        qk = &qk * &qk % n;
+    }
+    false
+}
+
+// 以下は u64 を超えて u128 に収まる数用。BigUint を使わず 128 ビットのモンゴメリ乗算で計算する
+
+// a·b（256 ビット）を (上位, 下位) で返す
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    let (a_high, a_low) = (a >> 64, a & u64::MAX as u128);
+    let (b_high, b_low) = (b >> 64, b & u64::MAX as u128);
+    let (middle, middle_carry) = (a_low * b_high).overflowing_add(a_high * b_low);
+    let (low, low_carry) = (a_low * b_low).overflowing_add(middle << 64);
+    let high = a_high * b_high + (middle >> 64) + ((middle_carry as u128) << 64) + low_carry as u128;
+    (high, low)
+}
+
+fn add_mod_u128(a: u128, b: u128, m: u128) -> u128 {
+    let (sum, overflow) = a.overflowing_add(b);
+    if overflow || sum >= m { sum.wrapping_sub(m) } else { sum }
+}
+
+// 奇数の法 m での 128 ビットのモンゴメリ乗算（R = 2^128）
+#[derive(Debug, Clone, Copy)]
+pub struct Montgomery128 {
+    m: u128,
+    // m^-1 mod 2^128
+    m_inv: u128,
+    // R^2 mod m
+    r2: u128,
+}
+
+impl Montgomery128 {
+    // m は 3 以上の奇数
+    pub fn new(m: u128) -> Self {
+        debug_assert!(m % 2 == 1 && m > 1);
+        let mut m_inv = m;
+        for _ in 0..6 {
+            m_inv = m_inv.wrapping_mul(2u128.wrapping_sub(m.wrapping_mul(m_inv)));
+        }
+        // R mod m を 128 回 2 倍すると R^2 mod m
+        let mut r2 = (u128::MAX % m + 1) % m;
+        for _ in 0..128 {
+            r2 = add_mod_u128(r2, r2, m);
+        }
+        Montgomery128 { m, m_inv, r2 }
+    }
+
+    fn reduce(&self, (high, low): (u128, u128)) -> u128 {
+        let u = low.wrapping_mul(self.m_inv);
+        let um_high = mul_wide(u, self.m).0;
+        if high >= um_high { high - um_high } else { high.wrapping_sub(um_high).wrapping_add(self.m) }
+    }
+
+    // a をモンゴメリ表現にする
+    pub fn enter(&self, a: u128) -> u128 {
+        self.mul(a % self.m, self.r2)
+    }
+
+    // モンゴメリ表現から通常の値に戻す
+    pub fn leave(&self, a: u128) -> u128 {
+        self.reduce((0, a))
+    }
+
+    pub fn mul(&self, a: u128, b: u128) -> u128 {
+        self.reduce(mul_wide(a, b))
+    }
+
+    // 和・差・半分はモンゴメリ表現のままでよい
+    pub fn add(&self, a: u128, b: u128) -> u128 {
+        add_mod_u128(a, b, self.m)
+    }
+
+    pub fn sub(&self, a: u128, b: u128) -> u128 {
+        if a >= b { a - b } else { self.m - (b - a) }
+    }
+
+    fn half(&self, a: u128) -> u128 {
+        if a & 1 == 0 { a >> 1 } else { (a >> 1) + (self.m >> 1) + 1 }
+    }
+
+    pub fn pow(&self, mut base: u128, mut exp: u128) -> u128 {
+        let mut result = self.enter(1);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = self.mul(result, base);
+            }
+            base = self.mul(base, base);
+            exp >>= 1;
+        }
+        result
+    }
+}
+
+pub fn pow_mod_u128(base: u128, exp: u128, m: u128) -> u128 {
+    if m % 2 == 1 && m > 1 {
+        let mont = Montgomery128::new(m);
+        return mont.leave(mont.pow(mont.enter(base), exp));
+    }
+    // 偶数の法（ここでは使わない）は BigUint で
+    BigUint::from(base).modpow(&BigUint::from(exp), &BigUint::from(m)).to_u128().unwrap_or(0)
+}
+
+// 奇数 n > 1 について、底 a で強擬素数かどうか
+fn is_strong_probable_prime_u128(mont: &Montgomery128, a: u128) -> bool {
+    let n = mont.m;
+    let a = a % n;
+    if a == 0 {
+        return true;
+    }
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    let (one, minus_one) = (mont.enter(1), mont.enter(n - 1));
+    let mut x = mont.pow(mont.enter(a), d);
+    if x == one || x == minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = mont.mul(x, x);
+        if x == minus_one {
+            return true;
+        }
+    }
+    false
+}
+
+// 小さな素数で割り切れるかどうかを調べる。決着すれば Some
+fn trial_divide_u128(n: u128) -> Option<bool> {
+    if n < 2 {
+        return Some(false);
+    }
+    for &p in &SMALL_PRIMES {
+        if n == p as u128 {
+            return Some(true);
+        }
+        if n.is_multiple_of(p as u128) {
+            return Some(false);
+        }
+    }
+    None
+}
+
+// 最初の 13 個の素数（BASES_U128）を底にした判定（DETERMINISTIC_LIMIT 未満なら決定的）
+pub fn is_probable_prime_u128(n: u128) -> bool {
+    if let Some(result) = trial_divide_u128(n) {
+        return result;
+    }
+    let mont = Montgomery128::new(n);
+    BASES_U128.iter().all(|&a| is_strong_probable_prime_u128(&mont, a as u128))
+}
+
+// u128 の BPSW 判定
+pub fn is_bpsw_probable_prime_u128(n: u128) -> bool {
+    if let Some(result) = trial_divide_u128(n) {
+        return result;
+    }
+    let mont = Montgomery128::new(n);
+    if !is_strong_probable_prime_u128(&mont, 2) {
+        return false;
+    }
+    let root = n.isqrt();
+    if root * root == n {
+        return false;
+    }
+    is_strong_lucas_probable_prime_u128(&mont)
+}
+
+// is_strong_lucas_probable_prime と同じ判定をモンゴメリ表現で行う
+fn is_strong_lucas_probable_prime_u128(mont: &Montgomery128) -> bool {
+    let n = mont.m;
+    let mut d: i64 = 5;
+    loop {
+        let j = jacobi_u128(d.unsigned_abs() as u128, n);
+        let j = if d < 0 && n % 4 == 3 { -j } else { j };
+        if j == -1 {
+            break;
+        }
+        if j == 0 && d.unsigned_abs() as u128 != n {
+            return false;
+        }
+        d = if d > 0 { -(d + 2) } else { -d + 2 };
+    }
+    let q = (1 - d) / 4;
+    let to_mont = |v: i64| {
+        let r = mont.enter(v.unsigned_abs() as u128);
+        if v < 0 { mont.sub(0, r) } else { r }
+    };
+    let d_mont = to_mont(d);
+    let q_mont = to_mont(q);
+
+    // u128::MAX は 3 で割り切れるので、n + 1 はあふれない
+    let s = (n + 1).trailing_zeros();
+    let k = (n + 1) >> s;
+
+    let mut u = mont.enter(1);
+    let mut v = u;
+    let mut qk = q_mont;
+    for i in (0..127 - k.leading_zeros()).rev() {
+        u = mont.mul(u, v);
+        v = mont.sub(mont.mul(v, v), mont.add(qk, qk));
+        qk = mont.mul(qk, qk);
+        if k >> i & 1 == 1 {
+            let new_u = mont.half(mont.add(u, v));
+            v = mont.half(mont.add(mont.mul(d_mont, u), v));
+            u = new_u;
+            qk = mont.mul(qk, q_mont);
+        }
+    }
+    if u == 0 || v == 0 {
+        return true;
+    }
+    for _ in 1..s {
+        v = mont.sub(mont.mul(v, v), mont.add(qk, qk));
+        if v == 0 {
+            return true;
+        }
+        qk = mont.mul(qk, qk);
+    }
+    false
+}
+
+// check_prime_big の u128 版
+pub fn check_prime_u128(n: u128, random_rounds: u32) -> BigPrimality {
+    if n < DETERMINISTIC_LIMIT {
+        return if is_probable_prime_u128(n) { BigPrimality::Prime } else { BigPrimality::Composite };
+    }
+    if !is_bpsw_probable_prime_u128(n) {
+        return BigPrimality::Composite;
+    }
+    let mont = Montgomery128::new(n);
+    let mut bytes = [0u8; 16];
+    for _ in 0..random_rounds {
+        let _ = getrandom::getrandom(&mut bytes);
+        let a = u128::from_le_bytes(bytes) % (n - 3) + 2;
+        if !is_strong_probable_prime_u128(&mont, a) {
+            return BigPrimality::Composite;
+        }
+    }
+    BigPrimality::ProbablePrime
+}
@@ -0,0 +1,174 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// メモリ使用量、コアごとの CPU 使用率、CPU の温度の監視。購読しているジョブにだけ一定間隔で送る。
+// スレッドは最初の購読で起動し、最後の購読が外れたところで止めて待つ
+
+use std::collections::BTreeMap;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use sysinfo::{ComponentExt, CpuExt, System, SystemExt};
+use crate::app::WorkerMessage;
+
+// 使用量を送る間隔
+const INTERVAL: Duration = Duration::from_millis(500);
+
+// CPU 使用率の履歴として表示する長さ
+pub const CPU_WINDOW: Duration = Duration::from_secs(60);
+
+// 温度センサーを読み直す間隔
+const SENSOR_INTERVAL: Duration = Duration::from_secs(2);
+
+// 温度を下げたあと元の並列数に戻すまでに下がるべき温度（℃）
+const HYSTERESIS: f32 = 5.0;
+
+type Subscribers = Arc<Mutex<BTreeMap<u64, mpsc::Sender<WorkerMessage>>>>;
+
+#[derive(Default)]
+pub struct ResourceMonitor {
+    subscribers: Subscribers,
+    // 監視スレッドと、捨てると止まる送り側
+    thread: Option<(mpsc::Sender<()>, JoinHandle<()>)>,
+}
+
+impl ResourceMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    // id のジョブに使用量を送る。動いていなければスレッドを起動する
+    pub fn subscribe(&mut self, id: u64, sender: mpsc::Sender<WorkerMessage>) {
+        self.subscribers.lock().unwrap().insert(id, sender);
+        if self.thread.is_none() {
+            let (stop, stopped) = mpsc::channel();
+            let subscribers = Arc::clone(&self.subscribers);
+            self.thread = Some((stop, std::thread::spawn(move || monitor(subscribers, stopped))));
+        }
+    }
+
+    // 購読が残っていなければスレッドを止める
+    pub fn unsubscribe(&mut self, id: u64) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.remove(&id);
+        if subscribers.is_empty() {
+            drop(subscribers);
+            self.stop();
+        }
+    }
+
+    // 購読をすべて外してスレッドを止める
+    pub fn stop(&mut self) {
+        self.subscribers.lock().unwrap().clear();
+        if let Some((stop, thread)) = self.thread.take() {
+            drop(stop);
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ResourceMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn monitor(subscribers: Subscribers, stopped: mpsc::Receiver<()>) {
+    let mut sys = System::new();
+    // 使用率は前回の更新との差から求めるので、最初に 1 回更新しておく
+    sys.refresh_cpu();
+    let mut sensor = TemperatureSensor::new();
+    while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(INTERVAL) {
+        sys.refresh_memory();
+        sys.refresh_cpu();
+        let mem_usage = sys.used_memory();
+        let loads: Vec<f32> = sys.cpus().iter().map(|cpu| cpu.cpu_usage()).collect();
+        let temperature = sensor.as_mut().and_then(TemperatureSensor::read);
+        // 受け取る側がなくなったジョブは外す
+        subscribers.lock().unwrap().retain(|_, sender| {
+            sender.send(WorkerMessage::MemUsage(mem_usage)).is_ok()
+                && sender.send(WorkerMessage::CpuUsage(loads.clone())).is_ok()
+                && temperature.is_none_or(|t| sender.send(WorkerMessage::Temperature(t)).is_ok())
+        });
+    }
+}
+
+// CPU の温度センサー。読み直しは SENSOR_INTERVAL ごと
+pub struct TemperatureSensor {
+    sys: System,
+    read_at: Option<Instant>,
+    temperature: Option<f32>,
+}
+
+impl TemperatureSensor {
+    // 温度を読めるセンサーがなければ None
+    pub fn new() -> Option<Self> {
+        let mut sys = System::new();
+        sys.refresh_components_list();
+        let mut sensor = TemperatureSensor { sys, read_at: None, temperature: None };
+        sensor.read().map(|_| sensor)
+    }
+
+    // 最後に読んだ CPU の温度（℃）
+    pub fn read(&mut self) -> Option<f32> {
+        if self.read_at.is_none_or(|at| at.elapsed() >= SENSOR_INTERVAL) {
+            self.sys.refresh_components();
+            self.temperature = cpu_temperature(&self.sys);
+            self.read_at = Some(Instant::now());
+        }
+        self.temperature
+    }
+}
+
+// CPU のセンサーらしい名前のものの最高値。見分けられなければすべての最高値
+fn cpu_temperature(sys: &System) -> Option<f32> {
+    let highest = |cpu_only: bool| {
+        sys.components()
+            .iter()
+            .filter(|c| !cpu_only || is_cpu_sensor(c.label()))
+            .map(|c| c.temperature())
+            .filter(|t| t.is_finite() && *t > 0.0)
+            .reduce(f32::max)
+    };
+    highest(true).or_else(|| highest(false))
+}
+
+pub fn is_cpu_sensor(label: &str) -> bool {
+    let label = label.to_ascii_lowercase();
+    ["cpu", "package", "core", "tctl", "tdie", "k10temp", "coretemp"].iter().any(|name| label.contains(name))
+}
+
+// 温度に応じて並列に篩うセグメントの数を決める。上限を超えたら半分にし、
+// 上限より HYSTERESIS 下がったら倍に戻す（full を超えない）
+pub struct ThermalThrottle {
+    limit: f32,
+    full: usize,
+    pub current: usize,
+}
+
+impl ThermalThrottle {
+    pub fn new(limit: u8, full: usize) -> Self {
+        ThermalThrottle { limit: limit as f32, full, current: full }
+    }
+
+    // 並列数を変えたら新しい値を返す
+    pub fn update(&mut self, temperature: f32) -> Option<usize> {
+        let next = if temperature > self.limit {
+            (self.current / 2).max(1)
+        } else if temperature <= self.limit - HYSTERESIS {
+            (self.current * 2).min(self.full)
+        } else {
+            self.current
+        };
+        (next != self.current).then(|| {
+            self.current = next;
+            next
+        })
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// N 番目の素数モード。上限の代わりに個数 nth_count を指定し、prime_min 以上の素数を
+// 小さい順に nth_count 個見つけるまでセグメントを継ぎ足して篩う。素数は出力の設定に従って書き、
+// 最後に見つけた素数（prime_min が 2 以下なら N 番目の素数そのもの）を報告する
+
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use rayon::prelude::*;
+use crate::app::{ProgressPhase, WorkerCommand, WorkerMessage};
+use crate::config::{Config, OverwriteMode};
+use crate::error::SieveError;
+use crate::i18n::{format_number, tr};
+use crate::metadata::{record_output, Fingerprint, OutputPlan};
+use crate::output::PrimeSink;
+use crate::sieve::{build_thread_pool, close_stopped_output, format_eta, integer_sqrt, open_output, parallel_segments, parse_filters, retry_on_disk_full, segmented_sieve, simple_sieve, OutputSink};
+
+// 篩に使う小さい素数の上限の最大値（√u64::MAX）
+const MAX_SMALL_LIMIT: u64 = 1 << 32;
+
+// low から始めて、count 個までの長さ segment_size のセグメント。u64::MAX に達したら打ち切る
+pub fn next_segments(low: u64, segment_size: u64, count: usize) -> Vec<(u64, u64)> {
+    let mut segments = Vec::with_capacity(count);
+    let mut low = low;
+    while segments.len() < count {
+        let high = low.saturating_add(segment_size - 1);
+        segments.push((low, high));
+        if high == u64::MAX {
+            break;
+        }
+        low = high + 1;
+    }
+    segments
+}
+
+pub fn run_nth_prime(config: Config, sender: mpsc::Sender<WorkerMessage>, commands: mpsc::Receiver<WorkerCommand>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let start = config.prime_min.parse::<u64>()?;
+    let n = config.nth_count;
+    if n == 0 {
+        return Err(SieveError::Config("nth_count must be greater than 0".to_string()));
+    }
+    if config.segment_size == 0 {
+        return Err(SieveError::Config("segment_size must be greater than 0".to_string()));
+    }
+    let filters = parse_filters(&config)?;
+    sender.send(WorkerMessage::Log(tr!("Searching for the first {} primes from {}", format_number(n), format_number(start)))).ok();
+
+    if !config.output_dir.is_empty() {
+        create_dir_all(&config.output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(&config.output_dir), source })?;
+    }
+    let pool = build_thread_pool(&config)?;
+    let batch_size = parallel_segments(&config, &pool);
+    let mut out = open_output(&config, filters, &sender)?;
+
+    let start_time = Instant::now();
+    // small_primes は small_limit 以下のすべての素数。セグメントが伸びたら上限を倍にして篩い直す
+    let mut small_primes: Vec<u64> = Vec::new();
+    let mut small_limit = 0u64;
+    let mut next = Some(start);
+    let mut found = 0u64;
+    let mut fingerprint = Fingerprint::default();
+    let mut last = None;
+    while found < n {
+        let Some(low) = next else {
+            return Err(SieveError::Config(format!("there are fewer than {} primes from {} below 2^64", n, start)));
+        };
+        if stop_flag.load(Ordering::SeqCst) {
+            stop(&mut out, found, &sender);
+            return Ok(());
+        }
+        let batch = next_segments(low, config.segment_size, batch_size);
+        let batch_high = batch[batch.len() - 1].1;
+        next = batch_high.checked_add(1);
+
+        let root = integer_sqrt(batch_high) + 1;
+        if root > small_limit {
+            small_limit = root.max(small_limit.saturating_mul(2)).min(MAX_SMALL_LIMIT);
+            small_primes = simple_sieve(small_limit);
+        }
+        let results: Vec<Vec<u64>> = pool.install(|| {
+            batch.par_iter().map(|&(low, high)| segmented_sieve(&small_primes, low, high, &stop_flag)).collect()
+        });
+        if stop_flag.load(Ordering::SeqCst) {
+            stop(&mut out, found, &sender);
+            return Ok(());
+        }
+
+        for (&(low, high), primes) in batch.iter().zip(&results) {
+            sender.send(WorkerMessage::SegmentDone { low, high, primes: primes.len() as u64 }).ok();
+        }
+        // セグメントは昇順なので、そのまま書けば小さい順になる
+        for &p in results.iter().flatten().take((n - found) as usize) {
+            if !retry_on_disk_full(&mut out, &sender, &commands, &stop_flag, |out| out.write_prime(p))? {
+                return Ok(());
+            }
+            found += 1;
+            fingerprint.add(p);
+            last = Some(p);
+            sender.send(WorkerMessage::FoundPrimeIndex(p, found)).ok();
+        }
+        sender.send(WorkerMessage::Progress { current: found, total: n, phase: ProgressPhase::Sieving }).ok();
+        sender.send(WorkerMessage::Eta(format_eta(start_time, found as f64 / n as f64))).ok();
+    }
+
+    if !retry_on_disk_full(&mut out, &sender, &commands, &stop_flag, |out| out.finish())? {
+        return Ok(());
+    }
+    let last = last.unwrap_or(start);
+    // 追記では既存の出力の範囲とつながるとは限らないのでメタデータは更新しない
+    let plan = if config.overwrite_mode == OverwriteMode::Append { OutputPlan::Untracked } else { OutputPlan::Fresh };
+    record_output(plan, &config, &[(start, last)], found, fingerprint, &out.written_files(), &sender);
+
+    let message = if start <= 2 {
+        tr!("The {}-th prime is {}", format_number(n), format_number(last))
+    } else {
+        tr!("The {}-th prime from {} is {}", format_number(n), format_number(start), format_number(last))
+    };
+    sender.send(WorkerMessage::Log(message)).ok();
+    sender.send(WorkerMessage::NthPrime { n, prime: last }).ok();
+    sender.send(WorkerMessage::Finished { found, files: out.written_files() }).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
+
+// 停止時は書いた素数を残して出力ファイルを閉じる
+fn stop(out: &mut OutputSink, found: u64, sender: &mpsc::Sender<WorkerMessage>) {
+    if found > 0 && close_stopped_output(out, sender) {
+        sender.send(WorkerMessage::Log(tr!("Stopped after writing {} primes.", format_number(found)))).ok();
+    }
+    sender.send(WorkerMessage::Stopped).ok();
+}
@@ -0,0 +1,104 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 1 つの数の素数判定ツール。素数なら（指定があれば）Pratt 証明書を出力し、
+// 合成数なら素因数分解を表示する。u64 を超える数は 3.3 × 10^24 未満なら 13 個の底で決定的に、
+// それ以上は BPSW とランダムな底の Miller-Rabin で確率的に判定し、ECM も使って分解する
+
+use std::fs::{create_dir_all, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use crate::app::WorkerMessage;
+use crate::error::SieveError;
+use crate::factor::{factorize, factorize_big, format_factors};
+use crate::miller_rabin::{check_prime_big, is_prime, BigPrimality};
+use crate::pratt::pratt_certificate;
+use crate::i18n::tr;
+
+// 3.3 × 10^24 以上で素数と判定したときの警告
+pub fn probabilistic_warning(random_rounds: u32) -> String {
+    tr!("Probabilistic result: numbers of 3.3×10^24 and above are tested with BPSW and {} random Miller-Rabin rounds, which is not a proof. Raise the number of random rounds to lower the chance of error.", random_rounds)
+}
+
+// 証明書のファイル名
+pub fn certificate_file_name(n: u64) -> String {
+    format!("pratt_{}.txt", n)
+}
+
+pub fn run_number_check(n: BigUint, certificate: bool, random_rounds: u32, output_dir: &str, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let Some(n) = n.to_u64() else {
+        return check_big(&n, random_rounds, &sender, &stop_flag);
+    };
+    if n < 2 {
+        sender.send(WorkerMessage::Log(tr!("{} is neither prime nor composite", n))).ok();
+    } else if !is_prime(n) {
+        sender.send(WorkerMessage::Log(tr!("{} is composite: {}", n, format_factors(&factorize(n))))).ok();
+    } else if !certificate {
+        sender.send(WorkerMessage::Log(tr!("{} is prime (deterministic Miller-Rabin)", n))).ok();
+    } else {
+        let cert = pratt_certificate(n)
+            .filter(|cert| cert.verify())
+            .ok_or_else(|| SieveError::Config(format!("failed to build a Pratt certificate for {}", n)))?;
+        if !output_dir.is_empty() {
+            create_dir_all(output_dir)
+                .map_err(|source| SieveError::CreateDir { path: PathBuf::from(output_dir), source })?;
+        }
+        let path = Path::new(output_dir).join(certificate_file_name(n));
+        let text = cert.to_text();
+        File::create(&path)
+            .map_err(|source| SieveError::OpenFile { path: path.clone(), source })?
+            .write_all(text.as_bytes())
+            .map_err(SieveError::write(&path))?;
+        sender.send(WorkerMessage::Log(tr!("{} is prime (proved by Pratt certificate)", n))).ok();
+        sender.send(WorkerMessage::Log(text)).ok();
+        sender.send(WorkerMessage::Log(tr!("Certificate written to {}", path.display()))).ok();
+    }
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
+
+fn check_big(n: &BigUint, random_rounds: u32, sender: &mpsc::Sender<WorkerMessage>, stop_flag: &AtomicBool) -> Result<(), SieveError> {
+    match check_prime_big(n, random_rounds) {
+        BigPrimality::Prime => {
+            sender.send(WorkerMessage::Log(tr!("{} is prime (deterministic Miller-Rabin with the first 13 prime bases). Pratt certificates are limited to 64-bit numbers.", n))).ok();
+            sender.send(WorkerMessage::Done).ok();
+            return Ok(());
+        }
+        BigPrimality::ProbablePrime => {
+            sender.send(WorkerMessage::Log(tr!("{} is a probable prime (BPSW and {} random Miller-Rabin rounds). Pratt certificates are limited to 64-bit numbers.", n, random_rounds))).ok();
+            sender.send(WorkerMessage::Warning(probabilistic_warning(random_rounds))).ok();
+            sender.send(WorkerMessage::Done).ok();
+            return Ok(());
+        }
+        BigPrimality::Composite => {}
+    }
+    sender.send(WorkerMessage::Log(tr!("{} is composite, factoring...", n))).ok();
+    let start_time = Instant::now();
+    let Some(factors) = factorize_big(n, stop_flag, |status| {
+        sender.send(WorkerMessage::Log(status)).ok();
+    }) else {
+        sender.send(WorkerMessage::Stopped).ok();
+        return Ok(());
+    };
+    let text = factors
+        .iter()
+        .map(|f| {
+            let value = if f.prime { f.value.to_string() } else { format!("({} composite)", f.value) };
+            if f.exponent == 1 { value } else { format!("{}^{}", value, f.exponent) }
+        })
+        .collect::<Vec<_>>()
+        .join(" * ");
+    sender.send(WorkerMessage::Log(tr!("{} = {} ({} s)", n, text, format!("{:.2}", start_time.elapsed().as_secs_f64())))).ok();
+    if factors.iter().any(|f| !f.prime) {
+        sender.send(WorkerMessage::Log(tr!("Some cofactors could not be factored (no factor found by Pollard rho or ECM).").to_string())).ok();
+    }
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
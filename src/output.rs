@@ -0,0 +1,903 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 素数の出力先（sink）とファイル形式ごとの書式。
+// PrimeWriter の各操作は失敗した場合にそのまま再試行できるようにしてある（ディスク容量不足からの再開用）。
+
+use crate::async_writer::AsyncWriter;
+use crate::config::{Config, OutputFormat, OverwriteMode};
+use crate::seekable::{SeekTable, SeekableReader, SeekableWriter};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// 素数を順に受け取る出力先
+pub trait PrimeSink {
+    fn write_prime(&mut self, p: u64) -> io::Result<()>;
+    fn finish(&mut self) -> io::Result<()>;
+
+    // ディスク容量不足からの再開用。ファイルに書かない sink は実装しなくてよい
+    fn current_path(&self) -> Option<&Path> {
+        None
+    }
+    fn relocate(&mut self, _dir: &Path) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "this output cannot be relocated"))
+    }
+    // これまでに書いたファイル（実行後のまとめ表示用）
+    fn written_files(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+// 追記再開時に既存ファイルをどう扱うか
+pub struct Reopen {
+    // この長さまで切り詰めてから追記する（末尾の epilogue を取り除く）
+    pub truncate_to: u64,
+    pub has_records: bool,
+    pub write_prologue: bool,
+}
+
+// 1 ファイルぶんの書式。分割出力でも各ファイルが prologue .. epilogue で完結する
+pub trait RecordFormat {
+    fn extension(&self) -> &'static str;
+    fn prologue(&self, _buf: &mut Vec<u8>) {}
+    // first はファイル内で最初のレコードかどうか。radix は数の基数（2〜36）
+    fn record(&self, p: u64, radix: u32, first: bool, buf: &mut Vec<u8>);
+    fn epilogue(&self, _buf: &mut Vec<u8>) {}
+    // len は既存ファイルの長さ、tail はその末尾（最大 TAIL_LEN バイト）
+    fn reopen(&self, len: u64, _tail: &[u8]) -> io::Result<Reopen> {
+        Ok(Reopen { truncate_to: len, has_records: len > 0, write_prologue: false })
+    }
+}
+
+const TAIL_LEN: u64 = 64;
+
+const BINARY_RECORD_LEN: u64 = 8;
+
+pub const MIN_RADIX: u32 = 2;
+pub const MAX_RADIX: u32 = 36;
+
+// p を radix 進（a〜z は小文字）で buf に追加する。format! を通さず下の桁から組み立てる
+pub fn push_radix(buf: &mut Vec<u8>, p: u64, radix: u32) {
+    const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    debug_assert!((MIN_RADIX..=MAX_RADIX).contains(&radix));
+    // u64 は 2 進で最大 64 桁
+    let mut digits = [0u8; 64];
+    let mut i = digits.len();
+    let mut n = p;
+    let radix = radix as u64;
+    loop {
+        i -= 1;
+        digits[i] = DIGITS[(n % radix) as usize];
+        n /= radix;
+        if n == 0 {
+            break;
+        }
+    }
+    buf.extend_from_slice(&digits[i..]);
+}
+
+// p を 10 進で buf に追加する。書き込みの大半を占めるので write! の書式処理を通さず itoa を使う
+pub fn push_decimal(buf: &mut Vec<u8>, p: u64) {
+    buf.extend_from_slice(itoa::Buffer::new().format(p).as_bytes());
+}
+
+// p を 10 進で 1 行に書く
+pub fn push_line(buf: &mut Vec<u8>, p: u64) {
+    push_decimal(buf, p);
+    buf.push(b'\n');
+}
+
+fn push_number(buf: &mut Vec<u8>, p: u64, radix: u32) {
+    if radix == 10 {
+        push_decimal(buf, p);
+    } else {
+        push_radix(buf, p, radix);
+    }
+}
+
+// radix 進で u64 の最大値を書いたときの桁数（10 進は 20 桁）
+pub fn fixed_width(radix: u32) -> usize {
+    let mut digits = Vec::new();
+    push_radix(&mut digits, u64::MAX, radix);
+    digits.len()
+}
+
+// FixedWidth の 1 件。width 桁になるよう先頭を 0 で埋める
+fn push_padded(buf: &mut Vec<u8>, p: u64, radix: u32, width: usize) {
+    let start = buf.len();
+    push_number(buf, p, radix);
+    let zeros = width.saturating_sub(buf.len() - start);
+    buf.splice(start..start, std::iter::repeat_n(b'0', zeros));
+}
+
+impl RecordFormat for OutputFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Text | OutputFormat::FixedWidth => "txt",
+            OutputFormat::CSV  => "csv",
+            OutputFormat::JSON => "json",
+            OutputFormat::Binary => "bin",
+        }
+    }
+
+    fn prologue(&self, buf: &mut Vec<u8>) {
+        if let OutputFormat::JSON = self {
+            buf.push(b'[');
+        }
+    }
+
+    fn record(&self, p: u64, radix: u32, first: bool, buf: &mut Vec<u8>) {
+        match self {
+            OutputFormat::Text => {
+                push_number(buf, p, radix);
+                buf.push(b'\n');
+            }
+            OutputFormat::CSV => {
+                push_number(buf, p, radix);
+                buf.push(b',');
+            }
+            OutputFormat::JSON => {
+                if !first {
+                    buf.push(b',');
+                }
+                // JSON の数値は 10 進のみなので、それ以外は文字列にする
+                if radix == 10 {
+                    push_number(buf, p, radix);
+                } else {
+                    buf.push(b'"');
+                    push_radix(buf, p, radix);
+                    buf.push(b'"');
+                }
+            }
+            OutputFormat::FixedWidth => {
+                push_padded(buf, p, radix, fixed_width(radix));
+                buf.push(b'\n');
+            }
+            // 基数は使わない
+            OutputFormat::Binary => buf.extend_from_slice(&p.to_le_bytes()),
+        }
+    }
+
+    fn epilogue(&self, buf: &mut Vec<u8>) {
+        if let OutputFormat::JSON = self {
+            buf.push(b']');
+        }
+    }
+
+    fn reopen(&self, len: u64, tail: &[u8]) -> io::Result<Reopen> {
+        match self {
+            OutputFormat::JSON => {
+                let trimmed = tail.trim_ascii_end();
+                if trimmed.is_empty() && len <= TAIL_LEN {
+                    return Ok(Reopen { truncate_to: 0, has_records: false, write_prologue: true });
+                }
+                match trimmed.strip_suffix(b"]") {
+                    Some(body) => {
+                        let truncate_to = len - (tail.len() - body.len()) as u64;
+                        let has_records = !(len <= TAIL_LEN && body.trim_ascii() == b"[");
+                        Ok(Reopen { truncate_to, has_records, write_prologue: false })
+                    }
+                    None => Err(io::Error::new(io::ErrorKind::InvalidData, "existing file is not a JSON array")),
+                }
+            }
+            // 書きかけのレコードがあれば取り除く
+            OutputFormat::Binary => {
+                let truncate_to = len - len % BINARY_RECORD_LEN;
+                Ok(Reopen { truncate_to, has_records: truncate_to > 0, write_prologue: false })
+            }
+            _ => Ok(Reopen { truncate_to: len, has_records: len > 0, write_prologue: false }),
+        }
+    }
+}
+
+// 出力ファイルを zstd seekable 形式で圧縮するときの設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZstdOptions {
+    pub level: i32,
+    // 1 フレームの圧縮前のバイト数
+    pub frame_size: usize,
+}
+
+impl ZstdOptions {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        config.zstd_seekable.then_some(ZstdOptions { level: config.zstd_level, frame_size: config.zstd_frame_size })
+    }
+}
+
+// 圧縮した出力ファイルの拡張子（"primes.txt.zst"）
+pub const ZSTD_EXTENSION: &str = "zst";
+
+// 出力ファイル本体。そのまま書くか、zstd seekable 形式で圧縮して書く
+enum FileSink {
+    Plain(File),
+    Seekable(SeekableWriter<File>),
+}
+
+impl FileSink {
+    // 書き込み先のファイルを差し替え、元のファイルを返す（圧縮の状態は引き継ぐ）
+    fn replace_file(&mut self, file: File) -> File {
+        match self {
+            FileSink::Plain(inner) => std::mem::replace(inner, file),
+            FileSink::Seekable(writer) => std::mem::replace(writer.get_mut(), file),
+        }
+    }
+}
+
+impl Write for FileSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            FileSink::Plain(file) => file.write(buf),
+            FileSink::Seekable(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            FileSink::Plain(file) => file.flush(),
+            FileSink::Seekable(writer) => writer.flush(),
+        }
+    }
+}
+
+// 出力ファイルへの書き込み。呼び出し側のスレッドで書くか、I/O スレッドに任せる
+enum FileOutput {
+    Buffered(BufWriter<FileSink>),
+    Async(AsyncWriter<FileSink>),
+}
+
+impl FileOutput {
+    // ファイル本体と、まだ書いていないデータ
+    fn into_parts(self) -> (FileSink, Vec<u8>) {
+        match self {
+            FileOutput::Buffered(writer) => {
+                let (file, buffered) = writer.into_parts();
+                (file, buffered.unwrap_or_default())
+            }
+            FileOutput::Async(writer) => writer.into_parts(),
+        }
+    }
+}
+
+impl Write for FileOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            FileOutput::Buffered(writer) => writer.write(buf),
+            FileOutput::Async(writer) => writer.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            FileOutput::Buffered(writer) => writer.write_all(buf),
+            FileOutput::Async(writer) => writer.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            FileOutput::Buffered(writer) => writer.flush(),
+            FileOutput::Async(writer) => writer.flush(),
+        }
+    }
+}
+
+// ファイルへ書き込む sink（split_count によるファイル分割を含む）
+pub struct PrimeWriter {
+    format: OutputFormat,
+    zstd: Option<ZstdOptions>,
+    dir: PathBuf,
+    base_name: String,
+    split_count: u64,
+    buffer_size: usize,
+    radix: u32,
+    // I/O スレッドへ先に渡すブロックの数（0 なら呼び出し側のスレッドで書く）
+    queue_blocks: usize,
+    // バッファが埋まらなくてもこの間隔でファイルへ書き出す（None ならバッファが埋まったときだけ）
+    flush_interval: Option<Duration>,
+    last_flush: Instant,
+    // 前に間隔を確かめてから書いた件数
+    records_since_check: u32,
+    writer: Option<FileOutput>,
+    path: PathBuf,
+    // 開いたファイルの一覧（最後が現在のファイル）
+    files: Vec<PathBuf>,
+    file_index: usize,
+    count_in_file: u64,
+    // 現在のファイルの epilogue を書き終えたか（ファイル切り替えの再試行用）
+    closed: bool,
+    // 次に書く素数の通し番号（prime_index のときだけ）
+    next_index: Option<u64>,
+    item: Vec<u8>,
+}
+
+impl PrimeWriter {
+    // 既存ファイルの扱いは mode に従う（Ask は確認済みとして Overwrite 扱い）
+    // zstd を指定すると各ファイルを zstd seekable 形式で圧縮する（拡張子に ".zst" がつく）
+    pub fn create(format: OutputFormat, zstd: Option<ZstdOptions>, dir: &Path, split_count: u64, buffer_size: usize, mode: &OverwriteMode) -> io::Result<Self> {
+        let mut out = PrimeWriter {
+            format,
+            zstd,
+            dir: dir.to_path_buf(),
+            base_name: BASE_NAME.to_string(),
+            split_count,
+            buffer_size,
+            radix: 10,
+            queue_blocks: 0,
+            flush_interval: None,
+            last_flush: Instant::now(),
+            records_since_check: 0,
+            writer: None,
+            path: PathBuf::new(),
+            files: Vec::new(),
+            file_index: 1,
+            count_in_file: 0,
+            closed: false,
+            next_index: None,
+            item: Vec::with_capacity(32),
+        };
+        match mode {
+            OverwriteMode::Ask | OverwriteMode::Overwrite => out.open_file(1)?,
+            OverwriteMode::Version => {
+                out.base_name = versioned_base_name(dir, &out.format, zstd.is_some(), split_count);
+                out.open_file(1)?
+            }
+            OverwriteMode::Append => {
+                if split_count > 0 {
+                    // 分割出力では既存の最大番号の次から新しいファイルを作る
+                    let next = existing_outputs(dir, &out.format, zstd.is_some(), split_count)
+                        .iter()
+                        .filter_map(|p| split_index(p, BASE_NAME, &out.format, zstd.is_some()))
+                        .max()
+                        .unwrap_or(0) + 1;
+                    out.open_file(next)?
+                } else {
+                    out.open_append()?
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    // 数を書く基数（既定は 10 進）。MIN_RADIX〜MAX_RADIX
+    pub fn with_radix(mut self, radix: u32) -> Self {
+        self.radix = radix;
+        self
+    }
+
+    // 各レコードの前に 1 から始まる通し番号を書き（"1000001:15485867"、JSON は番号をキーにしたオブジェクト、
+    // Binary はレコードを変えない）、分割ファイルの名前を最初の番号にする（"primes_from_idx_1000001.txt"）。
+    // 何も書かないうちに呼ぶこと（create で開いたファイルを開き直す）
+    pub fn with_prime_index(mut self) -> io::Result<Self> {
+        drop(self.writer.take());
+        fs::remove_file(&self.path)?;
+        self.files.clear();
+        self.next_index = Some(1);
+        self.open_file(self.file_index)?;
+        Ok(self)
+    }
+
+    // ファイルへの書き込みを I/O スレッドに任せる（buffer_size バイトのブロックを最大 queue_blocks 個先に渡す）
+    pub fn with_io_thread(mut self, queue_blocks: usize) -> io::Result<Self> {
+        self.queue_blocks = queue_blocks.max(1);
+        if let Some(writer) = self.writer.take() {
+            let (sink, buffered) = writer.into_parts();
+            let mut writer = self.wrap(sink, self.buffer_size);
+            writer.write_all(&buffered)?;
+            self.writer = Some(writer);
+        }
+        Ok(self)
+    }
+
+    // バッファが埋まるのを待たず、interval ごとにファイルへ書き出す（0 なら何もしない）。
+    // 時刻は FLUSH_CHECK_RECORDS 件ごとにだけ見る
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = (!interval.is_zero()).then_some(interval);
+        self
+    }
+
+    fn wrap(&self, sink: FileSink, capacity: usize) -> FileOutput {
+        if self.queue_blocks > 0 {
+            FileOutput::Async(AsyncWriter::new(sink, self.buffer_size, self.queue_blocks))
+        } else {
+            FileOutput::Buffered(BufWriter::with_capacity(capacity, sink))
+        }
+    }
+
+    fn sink(&self, file: File) -> FileSink {
+        match self.zstd {
+            Some(zstd) => FileSink::Seekable(SeekableWriter::new(file, zstd.level, zstd.frame_size)),
+            None => FileSink::Plain(file),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // 開いたファイルの一覧（最後が現在のファイル）
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    fn file_name(&self, index: usize) -> String {
+        if self.next_index.is_some() && self.split_count > 0 {
+            let first_index = (index as u64 - 1) * self.split_count + 1;
+            return indexed_file_name(&self.base_name, &self.format, self.zstd.is_some(), first_index);
+        }
+        file_name(&self.base_name, &self.format, self.zstd.is_some(), self.split_count, index)
+    }
+
+    // prime_index の JSON は配列ではなくオブジェクトにする
+    fn prologue(&self, buf: &mut Vec<u8>) {
+        match (&self.format, self.next_index) {
+            (OutputFormat::JSON, Some(_)) => buf.push(b'{'),
+            (format, _) => format.prologue(buf),
+        }
+    }
+
+    fn epilogue(&self, buf: &mut Vec<u8>) {
+        match (&self.format, self.next_index) {
+            (OutputFormat::JSON, Some(_)) => buf.push(b'}'),
+            (format, _) => format.epilogue(buf),
+        }
+    }
+
+    fn open_file(&mut self, index: usize) -> io::Result<()> {
+        let path = self.dir.join(self.file_name(index));
+        let file = OpenOptions::new().create(true).truncate(true).write(true).open(&path)?;
+        let mut writer = self.wrap(self.sink(file), self.buffer_size);
+        let mut prologue = Vec::new();
+        self.prologue(&mut prologue);
+        writer.write_all(&prologue)?;
+        self.writer = Some(writer);
+        self.files.push(path.clone());
+        self.path = path;
+        self.file_index = index;
+        self.count_in_file = 0;
+        self.closed = false;
+        Ok(())
+    }
+
+    // 単一ファイルへの追記。epilogue があれば取り除いてから続ける
+    fn open_append(&mut self) -> io::Result<()> {
+        let path = self.dir.join(self.file_name(1));
+        let mut file = OpenOptions::new().create(true).read(true).write(true).truncate(false).open(&path)?;
+        let with_path = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+        let (sink, reopen) = match self.zstd {
+            // 末尾のフレームを展開して圧縮前のデータに戻し、その中で epilogue を取り除く
+            Some(zstd) => {
+                let (mut seekable, len) = SeekableWriter::append(file, zstd.level, zstd.frame_size, TAIL_LEN as usize).map_err(with_path)?;
+                let frame = seekable.frame_mut();
+                let frame_start = len - frame.len() as u64;
+                let reopen = self.format.reopen(len, &frame[frame.len().saturating_sub(TAIL_LEN as usize)..]).map_err(with_path)?;
+                frame.truncate(reopen.truncate_to.saturating_sub(frame_start) as usize);
+                (FileSink::Seekable(seekable), reopen)
+            }
+            None => {
+                let len = file.metadata()?.len();
+                let mut tail = Vec::new();
+                file.seek(SeekFrom::Start(len.saturating_sub(TAIL_LEN)))?;
+                file.read_to_end(&mut tail)?;
+                let reopen = self.format.reopen(len, &tail).map_err(with_path)?;
+                file.set_len(reopen.truncate_to)?;
+                file.seek(SeekFrom::End(0))?;
+                (FileSink::Plain(file), reopen)
+            }
+        };
+
+        let mut writer = self.wrap(sink, self.buffer_size);
+        if reopen.write_prologue {
+            let mut prologue = Vec::new();
+            self.prologue(&mut prologue);
+            writer.write_all(&prologue)?;
+        }
+        self.writer = Some(writer);
+        self.files.push(path.clone());
+        self.path = path;
+        self.file_index = 1;
+        // 分割なしなので count_in_file はレコードの有無（先頭判定）にだけ使う
+        self.count_in_file = reopen.has_records as u64;
+        self.closed = false;
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut FileOutput {
+        self.writer.as_mut().expect("output file is not open")
+    }
+
+    fn close_current(&mut self) -> io::Result<()> {
+        if !self.closed {
+            let mut epilogue = Vec::new();
+            self.epilogue(&mut epilogue);
+            self.writer().write_all(&epilogue)?;
+            self.closed = true;
+        }
+        self.writer().flush()
+    }
+}
+
+impl PrimeSink for PrimeWriter {
+    // 分割ファイルの切り替えは次の素数を書く直前に行う（最後に空のファイルが残らないように）。
+    // 1 件ぶんを一度の write_all で書くので、失敗しても途中まで書かれることはない
+    fn write_prime(&mut self, p: u64) -> io::Result<()> {
+        if self.split_count > 0 && self.count_in_file >= self.split_count {
+            self.close_current()?;
+            self.open_file(self.file_index + 1)?;
+        }
+        // 書き出しもレコードを書く前に行うので、失敗して再試行しても同じ素数を 2 度書かない
+        if let Some(interval) = self.flush_interval {
+            if self.records_since_check >= FLUSH_CHECK_RECORDS {
+                if self.last_flush.elapsed() >= interval {
+                    self.writer().flush()?;
+                    self.last_flush = Instant::now();
+                }
+                self.records_since_check = 0;
+            }
+            self.records_since_check += 1;
+        }
+        let mut item = std::mem::take(&mut self.item);
+        item.clear();
+        let first = self.count_in_file == 0;
+        match (&self.format, self.next_index) {
+            (OutputFormat::Binary, _) | (_, None) => self.format.record(p, self.radix, first, &mut item),
+            (OutputFormat::JSON, Some(index)) => {
+                if !first {
+                    item.push(b',');
+                }
+                item.push(b'"');
+                push_decimal(&mut item, index);
+                item.extend_from_slice(b"\":");
+                self.format.record(p, self.radix, true, &mut item);
+            }
+            (format, Some(index)) => {
+                // FixedWidth では番号もゼロ埋めして、どの行も同じ長さにする
+                if *format == OutputFormat::FixedWidth {
+                    push_padded(&mut item, index, 10, fixed_width(10));
+                } else {
+                    push_decimal(&mut item, index);
+                }
+                item.push(b':');
+                format.record(p, self.radix, first, &mut item);
+            }
+        }
+        let result = self.writer().write_all(&item);
+        self.item = item;
+        result?;
+        self.count_in_file += 1;
+        if let Some(index) = self.next_index.as_mut() {
+            *index += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.close_current()
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    fn written_files(&self) -> Vec<PathBuf> {
+        self.files.clone()
+    }
+
+    // 書き込み中のファイルを別ディレクトリへ移し、以降のファイルもそこへ出力する。
+    // 未フラッシュのバッファ内容も引き継ぐ。
+    fn relocate(&mut self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let new_path = dir.join(self.file_name(self.file_index));
+        let (mut sink, buffered) = match self.writer.take() {
+            Some(writer) => writer.into_parts(),
+            None => return Err(io::Error::other("output file is not open")),
+        };
+
+        // 圧縮時はシークテーブルの上書きのために戻ることがあるので、追記モードではなく末尾へ移動して書く
+        let result = fs::copy(&self.path, &new_path).and_then(|_| {
+            let mut file = OpenOptions::new().write(true).open(&new_path)?;
+            file.seek(SeekFrom::End(0))?;
+            Ok(file)
+        });
+        match result {
+            Ok(file) => {
+                drop(sink.replace_file(file));
+                let _ = fs::remove_file(&self.path);
+                // 未フラッシュ分は新しいファイルへの最初の書き込みとして扱う
+                let mut writer = self.wrap(sink, self.buffer_size.max(buffered.len() + 1));
+                writer.write_all(&buffered)?;
+                self.writer = Some(writer);
+                if let Some(last) = self.files.last_mut() {
+                    *last = new_path.clone();
+                }
+                self.path = new_path;
+                self.dir = dir.to_path_buf();
+                Ok(())
+            }
+            Err(e) => {
+                // 元のファイルで書き込みを続けられるよう戻す
+                let _ = fs::remove_file(&new_path);
+                // 容量に余裕を持たせ、未フラッシュ分をバッファへ戻すだけにする（ここでは I/O は発生しない）
+                let mut writer = self.wrap(sink, self.buffer_size.max(buffered.len() + 1));
+                writer.write_all(&buffered)?;
+                self.writer = Some(writer);
+                Err(e)
+            }
+        }
+    }
+}
+
+pub(crate) const BASE_NAME: &str = "primes";
+
+// flush_interval を確かめる間隔（件数）
+pub const FLUSH_CHECK_RECORDS: u32 = 1024;
+
+fn extension(format: &OutputFormat, compressed: bool) -> String {
+    if compressed {
+        format!("{}.{}", format.extension(), ZSTD_EXTENSION)
+    } else {
+        format.extension().to_string()
+    }
+}
+
+fn file_name(base_name: &str, format: &OutputFormat, compressed: bool, split_count: u64, index: usize) -> String {
+    if split_count > 0 {
+        format!("{}_{}.{}", base_name, index, extension(format, compressed))
+    } else {
+        format!("{}.{}", base_name, extension(format, compressed))
+    }
+}
+
+// prime_index の分割ファイルの名前（最初の素数の番号を入れる）
+fn indexed_file_name(base_name: &str, format: &OutputFormat, compressed: bool, first_index: u64) -> String {
+    format!("{}_from_idx_{}.{}", base_name, first_index, extension(format, compressed))
+}
+
+// "primes_12.txt" -> Some(12)（prefix は "primes_"）
+fn name_number(path: &Path, prefix: &str, format: &OutputFormat, compressed: bool) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.strip_suffix(extension(format, compressed).as_str())?.strip_suffix('.')?;
+    let number = stem.strip_prefix(prefix)?;
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+fn split_index(path: &Path, base_name: &str, format: &OutputFormat, compressed: bool) -> Option<usize> {
+    name_number(path, &format!("{}_", base_name), format, compressed).and_then(|i| usize::try_from(i).ok())
+}
+
+// "primes_from_idx_1000001.txt" -> Some(1000001)
+fn first_index(path: &Path, base_name: &str, format: &OutputFormat, compressed: bool) -> Option<u64> {
+    name_number(path, &format!("{}_from_idx_", base_name), format, compressed)
+}
+
+// 今回の設定で上書きされうる既存の出力ファイル
+pub fn existing_outputs(dir: &Path, format: &OutputFormat, compressed: bool, split_count: u64) -> Vec<PathBuf> {
+    existing_outputs_for(dir, BASE_NAME, format, compressed, split_count)
+}
+
+fn existing_outputs_for(dir: &Path, base_name: &str, format: &OutputFormat, compressed: bool, split_count: u64) -> Vec<PathBuf> {
+    if split_count == 0 {
+        let path = dir.join(file_name(base_name, format, compressed, 0, 1));
+        return if path.exists() { vec![path] } else { Vec::new() };
+    }
+    // 番号の名前（prime_index）のファイルも最初の番号の順に含める
+    let mut found: Vec<(u64, PathBuf)> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter_map(|p| {
+                let key = split_index(&p, base_name, format, compressed).map(|i| i as u64).or_else(|| first_index(&p, base_name, format, compressed));
+                key.map(|key| (key, p))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    found.sort();
+    found.into_iter().map(|(_, p)| p).collect()
+}
+
+// 既存の出力ファイルから素数を順に読み出す。Text / CSV / JSON のいずれも数字以外を区切りとして扱い、
+// 拡張子が .bin のファイルは Binary 形式として読む
+pub fn read_primes(path: &Path, mut f: impl FnMut(u64)) -> io::Result<()> {
+    scan_primes(path, 0, |_, p| {
+        f(p);
+        true
+    })
+}
+
+fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == ZSTD_EXTENSION)
+}
+
+// Binary 形式のファイルか（"primes.bin"、"primes.bin.zst"）
+pub fn is_binary(path: &Path) -> bool {
+    let path = if is_compressed(path) { path.with_extension("") } else { path.to_path_buf() };
+    path.extension().is_some_and(|e| e == OutputFormat::Binary.extension())
+}
+
+// 出力ファイルを（圧縮されていれば展開して）offset バイト目から読む。
+// zstd seekable 形式なら offset を含むフレームから展開し、シークテーブルのない zstd は先頭から展開して読み飛ばす
+pub fn open_content(path: &Path, offset: u64) -> io::Result<Box<dyn Read>> {
+    let mut file = File::open(path)?;
+    if !is_compressed(path) {
+        file.seek(SeekFrom::Start(offset))?;
+        return Ok(Box::new(file));
+    }
+    if let Some(mut reader) = SeekableReader::new(file)? {
+        reader.seek(SeekFrom::Start(offset))?;
+        return Ok(Box::new(reader));
+    }
+    let mut decoder = zstd::Decoder::new(File::open(path)?)?;
+    io::copy(&mut (&mut decoder).take(offset), &mut io::sink())?;
+    Ok(Box::new(decoder))
+}
+
+// すべてのレコードが同じ長さのファイルなら、その長さ（Binary は 8、FixedWidth は改行を含む 1 行）。
+// Text は先頭の行がゼロ埋めされていて、末尾の行と同じ長さで、全体がその倍数のときだけ FixedWidth とみなす。
+// シークテーブルのない zstd は途中から読めないので None
+pub fn fixed_record_len(path: &Path) -> io::Result<Option<u64>> {
+    if is_compressed(path) && SeekTable::read(&mut File::open(path)?)?.is_none() {
+        return Ok(None);
+    }
+    let len = content_len(path)?;
+    if is_binary(path) {
+        return Ok((len > 0 && len % BINARY_RECORD_LEN == 0).then_some(BINARY_RECORD_LEN));
+    }
+    // 番号付きでも 20 + 1 + 20 + 1 バイトに収まる
+    const MAX_LINE: u64 = 128;
+    let mut first = Vec::new();
+    io::BufReader::new(open_content(path, 0)?.take(MAX_LINE)).read_until(b'\n', &mut first)?;
+    let record_len = first.len() as u64;
+    let is_record = |line: &[u8]| line.len() as u64 == record_len && line.iter().position(|&b| b == b'\n') == Some(line.len() - 1);
+    if first.first() != Some(&b'0') || !is_record(&first) || len % record_len != 0 {
+        return Ok(None);
+    }
+    let mut last = Vec::new();
+    open_content(path, len - record_len)?.take(record_len).read_to_end(&mut last)?;
+    Ok(is_record(&last).then_some(record_len))
+}
+
+// 展開後の長さ（シークテーブルのない zstd はファイルの長さ）
+pub fn content_len(path: &Path) -> io::Result<u64> {
+    if is_compressed(path) {
+        if let Some(table) = SeekTable::read(&mut File::open(path)?)? {
+            return Ok(table.decompressed_len());
+        }
+    }
+    Ok(fs::metadata(path)?.len())
+}
+
+// offset バイト目から素数を読み、各素数の先頭のバイト位置と値を f に渡す。f が false を返したら終わる。
+// 圧縮されたファイルでは位置は展開後のもの
+pub fn scan_primes(path: &Path, offset: u64, mut f: impl FnMut(u64, u64) -> bool) -> io::Result<()> {
+    let mut reader = io::BufReader::new(open_content(path, offset)?);
+    if is_binary(path) {
+        let mut record = [0u8; BINARY_RECORD_LEN as usize];
+        let mut pos = offset;
+        loop {
+            let mut filled = 0;
+            while filled < record.len() {
+                match reader.read(&mut record[filled..]) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if filled == 0 {
+                return Ok(());
+            }
+            if filled < record.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("truncated record in {}", path.display())));
+            }
+            if !f(pos, u64::from_le_bytes(record)) {
+                return Ok(());
+            }
+            pos += BINARY_RECORD_LEN;
+        }
+    }
+    let mut buf = [0u8; 64 * 1024];
+    let mut pos = offset;
+    let mut numbers = TextNumbers::default();
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        for (i, &b) in buf[..n].iter().enumerate() {
+            let number = numbers.push(b, pos + i as u64).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("number too large in {}", path.display()))
+            })?;
+            if let Some((start, v)) = number {
+                if !f(start, v) {
+                    return Ok(());
+                }
+            }
+        }
+        pos += n as u64;
+    }
+    if let Some((start, v)) = numbers.finish() {
+        f(start, v);
+    }
+    Ok(())
+}
+
+// テキストの出力から 10 進の数を 1 バイトずつ取り出す。数字以外は区切りとして扱い、
+// 直後に ':' が続く数（JSON では '":'）は prime_index の番号なので読み飛ばす
+#[derive(Default)]
+pub(crate) struct TextNumbers {
+    // 読み途中の数とその先頭位置
+    value: Option<(u64, u64)>,
+    // '"' で終わった数（次が ':' なら番号）
+    quoted: Option<(u64, u64)>,
+}
+
+pub(crate) struct NumberTooLarge;
+
+impl TextNumbers {
+    // pos は b の位置。読み終えた数があれば (先頭位置, 値) を返す
+    pub(crate) fn push(&mut self, b: u8, pos: u64) -> Result<Option<(u64, u64)>, NumberTooLarge> {
+        if let Some(quoted) = self.quoted.take() {
+            if b == b':' {
+                return Ok(None);
+            }
+            // 数の直後なので、b で終わる数はない
+            self.push(b, pos)?;
+            return Ok(Some(quoted));
+        }
+        if b.is_ascii_digit() {
+            let (start, v) = self.value.unwrap_or((pos, 0));
+            let v = v.checked_mul(10).and_then(|v| v.checked_add((b - b'0') as u64)).ok_or(NumberTooLarge)?;
+            self.value = Some((start, v));
+            return Ok(None);
+        }
+        Ok(match (self.value.take(), b) {
+            (Some(_), b':') => None,
+            (Some(value), b'"') => {
+                self.quoted = Some(value);
+                None
+            }
+            (value, _) => value,
+        })
+    }
+
+    pub(crate) fn finish(&mut self) -> Option<(u64, u64)> {
+        self.value.take().or(self.quoted.take())
+    }
+}
+
+// "primes (2)", "primes (3)", ... のうち既存ファイルと衝突しない最初の名前
+fn versioned_base_name(dir: &Path, format: &OutputFormat, compressed: bool, split_count: u64) -> String {
+    if existing_outputs_for(dir, BASE_NAME, format, compressed, split_count).is_empty() {
+        return BASE_NAME.to_string();
+    }
+    let mut version = 2;
+    loop {
+        let base_name = format!("{} ({})", BASE_NAME, version);
+        if existing_outputs_for(dir, &base_name, format, compressed, split_count).is_empty() {
+            return base_name;
+        }
+        version += 1;
+    }
+}
+
+pub fn is_disk_full(e: &io::Error) -> bool {
+    if e.kind() == io::ErrorKind::StorageFull {
+        return true;
+    }
+    match e.raw_os_error() {
+        // ERROR_HANDLE_DISK_FULL / ERROR_DISK_FULL
+        #[cfg(windows)]
+        Some(39) | Some(112) => true,
+        // ENOSPC
+        #[cfg(unix)]
+        Some(28) => true,
+        _ => false,
+    }
+}
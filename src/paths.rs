@@ -0,0 +1,96 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 設定ファイルと作業フォルダの場所。
+// settings.txt と gui_state.json はアプリケーションデータのフォルダ（Windows は %APPDATA%\sosu-seisei、
+// Linux は ~/.config/sosu-seisei、macOS は ~/Library/Application Support/sosu-seisei）に置く。
+// --portable では設定も作業フォルダも実行ファイルの隣に置く。
+// 出力先やキャッシュなどの相対パスは作業フォルダ（work_dir）から見た位置になる
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use crate::config::Config;
+
+const APP_DIR_NAME: &str = "sosu-seisei";
+pub const SETTINGS_FILE: &str = "settings.txt";
+pub const GUI_STATE_FILE: &str = "gui_state.json";
+
+struct Paths {
+    portable: bool,
+    app_dir: PathBuf,
+    // 起動したときのカレントディレクトリ
+    startup_dir: PathBuf,
+}
+
+static PATHS: OnceLock<Paths> = OnceLock::new();
+
+fn exe_dir() -> Option<PathBuf> {
+    env::current_exe().ok()?.parent().map(Path::to_path_buf)
+}
+
+fn resolve(portable: bool) -> Paths {
+    let startup_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let app_dir = if portable {
+        exe_dir()
+    } else {
+        dirs::config_dir().map(|dir| dir.join(APP_DIR_NAME))
+    };
+    // どちらも分からなければ従来どおりカレントディレクトリを使う
+    let app_dir = app_dir.unwrap_or_else(|| startup_dir.clone());
+    Paths { portable, app_dir, startup_dir }
+}
+
+fn paths() -> &'static Paths {
+    PATHS.get_or_init(|| resolve(false))
+}
+
+// 起動時に 1 回だけ呼ぶ（呼ばなければポータブルでない場所を使う）
+pub fn init(portable: bool) {
+    let _ = PATHS.set(resolve(portable));
+}
+
+pub fn is_portable() -> bool {
+    paths().portable
+}
+
+pub fn app_dir() -> &'static Path {
+    &paths().app_dir
+}
+
+pub fn settings_path() -> PathBuf {
+    app_dir().join(SETTINGS_FILE)
+}
+
+// ウィンドウの位置や入力欄など GUI の状態（gui_state.rs）
+pub fn gui_state_path() -> PathBuf {
+    app_dir().join(GUI_STATE_FILE)
+}
+
+// 以前の版がカレントディレクトリに書いた settings.txt（まだ移していなければ読み込む）
+pub fn legacy_settings_path() -> Option<PathBuf> {
+    let legacy = paths().startup_dir.join(SETTINGS_FILE);
+    (legacy != settings_path() && legacy.is_file() && !settings_path().exists()).then_some(legacy)
+}
+
+// 作業フォルダ。work_dir が空なら、ポータブルモードでは実行ファイルのフォルダ、そうでなければ起動したときのフォルダ
+pub fn work_dir(config: &Config) -> PathBuf {
+    let paths = paths();
+    match config.work_dir.trim() {
+        "" if paths.portable => paths.app_dir.clone(),
+        "" => paths.startup_dir.clone(),
+        dir => paths.startup_dir.join(dir),
+    }
+}
+
+// 作業フォルダを作ってカレントディレクトリにする
+pub fn enter_work_dir(config: &Config) -> io::Result<PathBuf> {
+    let dir = work_dir(config);
+    fs::create_dir_all(&dir)?;
+    env::set_current_dir(&dir)?;
+    Ok(dir)
+}
@@ -0,0 +1,202 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// π(x) と近似式の比較。区間の境界ごとに実際の π(x) を記録し、
+// li(x)、リーマンの R(x)、x / ln x との差（誤差項）を表にする
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub const REPORT_FILE_NAME: &str = "pi_report.txt";
+
+// 区間の指定がないときの行数
+const DEFAULT_ROWS: u64 = 100;
+
+// π(10^k)（k = 1, ..., 19）の既知の値
+pub const KNOWN_PI: [(u64, u64); 19] = [
+    (10, 4),
+    (100, 25),
+    (1_000, 168),
+    (10_000, 1_229),
+    (100_000, 9_592),
+    (1_000_000, 78_498),
+    (10_000_000, 664_579),
+    (100_000_000, 5_761_455),
+    (1_000_000_000, 50_847_534),
+    (10_000_000_000, 455_052_511),
+    (100_000_000_000, 4_118_054_813),
+    (1_000_000_000_000, 37_607_912_018),
+    (10_000_000_000_000, 346_065_536_839),
+    (100_000_000_000_000, 3_204_941_750_802),
+    (1_000_000_000_000_000, 29_844_570_422_669),
+    (10_000_000_000_000_000, 279_238_341_033_925),
+    (100_000_000_000_000_000, 2_623_557_157_654_233),
+    (1_000_000_000_000_000_000, 24_739_954_287_740_860),
+    (10_000_000_000_000_000_000, 234_057_667_276_344_607),
+];
+
+// オイラー定数
+const EULER_GAMMA: f64 = 0.577_215_664_901_532_9;
+
+// li(x) = Ei(ln x) = γ + ln t + Σ t^k / (k · k!)（t = ln x）。項はすべて正なので桁落ちしない
+pub fn li(x: f64) -> f64 {
+    if x <= 1.0 {
+        return f64::NEG_INFINITY;
+    }
+    let t = x.ln();
+    let mut term = 1.0;
+    let mut sum = 0.0;
+    for k in 1..1000 {
+        term *= t / k as f64;
+        let add = term / k as f64;
+        sum += add;
+        if add < sum * f64::EPSILON {
+            break;
+        }
+    }
+    EULER_GAMMA + t.ln() + sum
+}
+
+// ζ(s)（s ≥ 2）。20 項に Euler-Maclaurin の補正を足す
+fn zeta(s: f64) -> f64 {
+    const N: f64 = 20.0;
+    let head: f64 = (1..20).map(|n| (n as f64).powf(-s)).sum();
+    head + N.powf(1.0 - s) / (s - 1.0) + N.powf(-s) / 2.0 + s * N.powf(-s - 1.0) / 12.0
+}
+
+// リーマンの R(x) = 1 + Σ t^k / (k · k! · ζ(k + 1))（グラムの級数、t = ln x）
+pub fn riemann_r(x: f64) -> f64 {
+    if x < 1.0 {
+        return 0.0;
+    }
+    let t = x.ln();
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..1000 {
+        term *= t / k as f64;
+        let add = term / (k as f64 * zeta(k as f64 + 1.0));
+        sum += add;
+        if add < sum * f64::EPSILON {
+            break;
+        }
+    }
+    sum
+}
+
+// x 以下の素数の個数と各近似値
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiRow {
+    pub x: u64,
+    pub pi: u64,
+    pub li: f64,
+    pub r: f64,
+    pub x_over_ln: f64,
+}
+
+impl PiRow {
+    pub fn new(x: u64, pi: u64) -> Self {
+        let xf = x as f64;
+        let x_over_ln = if x >= 2 { xf / xf.ln() } else { 0.0 };
+        PiRow { x, pi, li: if x >= 2 { li(xf) } else { 0.0 }, r: riemann_r(xf), x_over_ln }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PiReport {
+    pub rows: Vec<PiRow>,
+}
+
+// 昇順のセグメントを受け取り、境界（step の倍数と prime_max）ごとに π(x) を記録する
+pub struct PiTracker {
+    step: u64,
+    prime_max: u64,
+    // 次に記録する x と、そこまでに数えた素数
+    next: u64,
+    pi: u64,
+    report: PiReport,
+}
+
+impl PiTracker {
+    // below は prime_min 未満の素数の個数。step = 0 なら範囲を DEFAULT_ROWS 等分する
+    pub fn new(prime_min: u64, prime_max: u64, step: u64, below: u64) -> Self {
+        let step = if step > 0 { step } else { (prime_max - prime_min + 1).div_ceil(DEFAULT_ROWS).max(1) };
+        let next = (prime_min / step).saturating_add(1).saturating_mul(step).min(prime_max);
+        PiTracker { step, prime_max, next, pi: below, report: PiReport::default() }
+    }
+
+    fn record(&mut self) {
+        self.report.rows.push(PiRow::new(self.next, self.pi));
+        self.next = if self.next >= self.prime_max { u64::MAX } else { self.next.saturating_add(self.step).min(self.prime_max) };
+    }
+
+    // high はこのセグメントの上端（そこまでは処理済み）
+    pub fn add_segment(&mut self, primes: &[u64], high: u64) {
+        for &p in primes {
+            while p > self.next {
+                self.record();
+            }
+            self.pi += 1;
+        }
+        while self.next <= high {
+            self.record();
+        }
+    }
+
+    pub fn finish(self) -> PiReport {
+        self.report
+    }
+}
+
+impl PiReport {
+    pub fn last(&self) -> Option<&PiRow> {
+        self.rows.last()
+    }
+
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        writeln!(w, "# pi(x) compared with li(x), R(x) and x/ln(x)")?;
+        writeln!(w, "x\tpi(x)\tli(x)\tpi-li\tR(x)\tpi-R\tx/ln(x)\tpi-x/ln(x)")?;
+        for row in &self.rows {
+            let pi = row.pi as f64;
+            writeln!(
+                w,
+                "{}\t{}\t{:.1}\t{:.1}\t{:.1}\t{:.1}\t{:.1}\t{:.1}",
+                row.x, row.pi, row.li, pi - row.li, row.r, pi - row.r, row.x_over_ln, pi - row.x_over_ln
+            )?;
+        }
+        w.flush()
+    }
+}
+
+// 2 以下から昇順に篩った素数を数え、10^k を過ぎるたびに KNOWN_PI と比べる
+#[derive(Debug, Default)]
+pub struct PiCheckpoints {
+    // 次に比べる KNOWN_PI の位置と、これまでに数えた素数
+    next: usize,
+    pi: u64,
+}
+
+impl PiCheckpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // high はこのセグメントの上端。通過した (x, π(x)) を返し、既知の値と違えばその (x, 数えた値, 既知の値)
+    pub fn add_segment(&mut self, primes: &[u64], high: u64) -> Result<Vec<(u64, u64)>, (u64, u64, u64)> {
+        let mut passed = Vec::new();
+        while let Some(&(x, expected)) = KNOWN_PI.get(self.next).filter(|&&(x, _)| x <= high) {
+            let pi = self.pi + primes.partition_point(|&p| p <= x) as u64;
+            if pi != expected {
+                return Err((x, pi, expected));
+            }
+            passed.push((x, pi));
+            self.next += 1;
+        }
+        self.pi += primes.len() as u64;
+        Ok(passed)
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 素数をほかのプロセスへそのまま流す sink（pipe_path）。受け取る側が先に作っておいた
+// Unix ドメインソケット（Windows では名前付きパイプ \\.\pipe\名前）に接続し、
+// 書き出す素数を 10 進で 1 行に 1 つずつ送る。中間ファイルを読み直さずに済む
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use crate::app::WorkerMessage;
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::i18n::tr;
+use crate::output::{push_line, PrimeSink};
+
+// これだけ溜まったら送る
+const PIPE_BUFFER_SIZE: usize = 64 * 1024;
+// 素数がゆっくりしか出ないときは、前に送ってからこれだけ経てば溜まった分を送る
+const PIPE_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+// 受け取る側への接続
+pub fn connect(path: &str) -> io::Result<Box<dyn Write + Send>> {
+    #[cfg(unix)]
+    {
+        Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?))
+    }
+    #[cfg(windows)]
+    {
+        // 名前付きパイプはサーバー側が作ったものをファイルとして開ける
+        Ok(Box::new(std::fs::OpenOptions::new().write(true).open(path)?))
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        Err(io::Error::new(io::ErrorKind::Unsupported, "pipe_path is not supported on this platform"))
+    }
+}
+
+struct Pipe {
+    path: String,
+    stream: Box<dyn Write + Send>,
+    buffer: Vec<u8>,
+    last_flush: Instant,
+}
+
+// 内側の sink に書きつつ、素数を pipe_path の受け取る側にも送る（pipe_path が空なら何もしない）。
+// 受け取る側が接続を閉じたら、ログに残して送るのをやめ、ファイルへの出力は続ける
+pub struct PipeSink<S> {
+    inner: S,
+    pipe: Option<Pipe>,
+    sender: mpsc::Sender<WorkerMessage>,
+}
+
+impl<S: PrimeSink> PipeSink<S> {
+    pub fn new(inner: S, config: &Config, sender: &mpsc::Sender<WorkerMessage>) -> Result<Self, SieveError> {
+        let path = config.pipe_path.trim();
+        let pipe = if path.is_empty() {
+            None
+        } else {
+            let stream = connect(path).map_err(|e| SieveError::Config(format!("pipe_path: {}: {}", path, e)))?;
+            Some(Pipe { path: path.to_string(), stream, buffer: Vec::with_capacity(PIPE_BUFFER_SIZE + 32), last_flush: Instant::now() })
+        };
+        Ok(PipeSink { inner, pipe, sender: sender.clone() })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.pipe.is_some()
+    }
+
+    // 溜まった素数を送る。受け取る側がいなくなっていたら送るのをやめる
+    fn flush_pipe(&mut self) {
+        let Some(pipe) = &mut self.pipe else {
+            return;
+        };
+        let result = pipe.stream.write_all(&pipe.buffer).and_then(|()| pipe.stream.flush());
+        pipe.buffer.clear();
+        pipe.last_flush = Instant::now();
+        if let Err(e) = result {
+            self.sender.send(WorkerMessage::Log(tr!("Stopped sending primes to {}: {}", pipe.path, e))).ok();
+            self.pipe = None;
+        }
+    }
+}
+
+impl<S: PrimeSink> PrimeSink for PipeSink<S> {
+    fn write_prime(&mut self, p: u64) -> io::Result<()> {
+        self.inner.write_prime(p)?;
+        let Some(pipe) = &mut self.pipe else {
+            return Ok(());
+        };
+        push_line(&mut pipe.buffer, p);
+        if pipe.buffer.len() >= PIPE_BUFFER_SIZE || pipe.last_flush.elapsed() >= PIPE_FLUSH_INTERVAL {
+            self.flush_pipe();
+        }
+        Ok(())
+    }
+
+    // 残りを送ってから接続を閉じ、受け取る側に終わりを知らせる
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()?;
+        self.flush_pipe();
+        self.pipe = None;
+        Ok(())
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.inner.current_path()
+    }
+
+    fn relocate(&mut self, dir: &Path) -> io::Result<()> {
+        self.inner.relocate(dir)
+    }
+
+    fn written_files(&self) -> Vec<PathBuf> {
+        self.inner.written_files()
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 計算中に OS がスリープ/休止状態へ移行しないよう抑止する。
+// ガードが drop されると抑止は解除される。
+pub struct KeepAwake {
+    #[cfg(not(windows))]
+    child: Option<std::process::Child>,
+}
+
+#[cfg(windows)]
+mod win {
+    pub const ES_CONTINUOUS: u32 = 0x8000_0000;
+    pub const ES_SYSTEM_REQUIRED: u32 = 0x0000_0001;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn SetThreadExecutionState(es_flags: u32) -> u32;
+    }
+}
+
+impl KeepAwake {
+    #[cfg(windows)]
+    pub fn acquire() -> Result<Self, String> {
+        // SetThreadExecutionState はスレッド単位なので、ガードを作成したスレッドで保持すること
+        let prev = unsafe { win::SetThreadExecutionState(win::ES_CONTINUOUS | win::ES_SYSTEM_REQUIRED) };
+        if prev == 0 {
+            return Err("SetThreadExecutionState failed".to_string());
+        }
+        Ok(KeepAwake {})
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn acquire() -> Result<Self, String> {
+        let pid = std::process::id().to_string();
+        let child = std::process::Command::new("caffeinate")
+            .args(["-i", "-w", &pid])
+            .spawn()
+            .map_err(|e| format!("Failed to start caffeinate: {}", e))?;
+        Ok(KeepAwake { child: Some(child) })
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn acquire() -> Result<Self, String> {
+        let child = std::process::Command::new("systemd-inhibit")
+            .args([
+                "--what=sleep:idle",
+                "--who=sosu-seisei",
+                "--why=Prime generation in progress",
+                "--mode=block",
+                "sleep",
+                "infinity",
+            ])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start systemd-inhibit: {}", e))?;
+        Ok(KeepAwake { child: Some(child) })
+    }
+}
+
+impl Drop for KeepAwake {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        unsafe {
+            win::SetThreadExecutionState(win::ES_CONTINUOUS);
+        }
+        #[cfg(not(windows))]
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+// 呼び出したスレッドの優先度を下げる（バックグラウンドモード用）
+#[cfg(windows)]
+pub fn lower_current_thread_priority() -> Result<(), String> {
+    use windows::Win32::System::Threading::{GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_LOWEST};
+    unsafe { SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_LOWEST) }.map_err(|e| e.to_string())
+}
+
+#[cfg(target_os = "macos")]
+pub fn lower_current_thread_priority() -> Result<(), String> {
+    let ret = unsafe { libc::pthread_set_qos_class_self_np(libc::qos_class_t::QOS_CLASS_UTILITY, 0) };
+    if ret != 0 {
+        return Err(format!("pthread_set_qos_class_self_np failed ({})", ret));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn lower_current_thread_priority() -> Result<(), String> {
+    // Linux ではスレッドIDを指定するとそのスレッドだけの nice 値が変わる
+    let ret = unsafe { libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, 10) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+pub fn lower_current_thread_priority() -> Result<(), String> {
+    Err("not supported on this platform".to_string())
+}
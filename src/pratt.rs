@@ -0,0 +1,89 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// Pratt の素数証明書。素数 p について、位数が p - 1 の元 a（証拠）と p - 1 の素因数分解を示し、
+// 各素因数も再帰的に証明する。検証はべき乗剰余だけでできる
+
+use crate::factor::{factorize, format_factors, Factorization};
+use crate::miller_rabin::{is_prime, pow_mod};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrattCertificate {
+    pub p: u64,
+    pub witness: u64,
+    // p - 1 の素因数分解
+    pub factors: Factorization,
+    // 2 より大きい各素因数の証明書
+    pub children: Vec<PrattCertificate>,
+}
+
+// 証拠の候補の上限（位数 p - 1 の元は小さい値でほぼ必ず見つかる）
+const MAX_WITNESS: u64 = 10_000;
+
+// p が素数でない場合は None
+pub fn pratt_certificate(p: u64) -> Option<PrattCertificate> {
+    if !is_prime(p) {
+        return None;
+    }
+    if p == 2 {
+        return Some(PrattCertificate { p, witness: 1, factors: Vec::new(), children: Vec::new() });
+    }
+    let factors = factorize(p - 1);
+    let witness = (2..MAX_WITNESS.min(p)).find(|&a| is_witness(p, a, &factors))?;
+    let children = factors.iter().filter(|&&(q, _)| q > 2).map(|&(q, _)| pratt_certificate(q)).collect::<Option<Vec<_>>>()?;
+    Some(PrattCertificate { p, witness, factors, children })
+}
+
+// a^(p-1) ≡ 1 かつ p - 1 の各素因数 q について a^((p-1)/q) ≢ 1 (mod p)
+fn is_witness(p: u64, a: u64, factors: &[(u64, u32)]) -> bool {
+    pow_mod(a, p - 1, p) == 1 && factors.iter().all(|&(q, _)| pow_mod(a, (p - 1) / q, p) != 1)
+}
+
+impl PrattCertificate {
+    // 素数判定を使わずに証明書を検証する
+    pub fn verify(&self) -> bool {
+        if self.p == 2 {
+            return true;
+        }
+        if self.p < 2 || self.factors.iter().map(|&(q, e)| q.checked_pow(e)).try_fold(1u64, |acc, f| acc.checked_mul(f?)) != Some(self.p - 1) {
+            return false;
+        }
+        if !is_witness(self.p, self.witness, &self.factors) {
+            return false;
+        }
+        // 2 以外の素因数にはそれぞれ正しい証明書が必要
+        self.factors.iter().all(|&(q, _)| {
+            q == 2 || self.children.iter().any(|c| c.p == q && c.verify())
+        })
+    }
+
+    // テキスト形式。小さい素数から順に 1 行ずつ「p: witness a, p - 1 = ...」を並べ、
+    // 各行の素因数 (2 以外) はそれより前の行で証明されている
+    pub fn to_text(&self) -> String {
+        let mut lines = Vec::new();
+        self.collect_lines(&mut lines);
+        lines.sort_by_key(|&(p, _)| p);
+        lines.dedup_by_key(|&mut (p, _)| p);
+        let mut text = format!(
+            "# Pratt certificate for {}\n# Each line \"p: witness a, p - 1 = q1^e1 * ...\" claims a^(p-1) = 1 (mod p) and a^((p-1)/q) != 1 (mod p) for every listed q.\n# Every q other than 2 is certified on an earlier line.\n",
+            self.p
+        );
+        for (_, line) in lines {
+            text.push_str(&line);
+            text.push('\n');
+        }
+        text
+    }
+
+    fn collect_lines(&self, lines: &mut Vec<(u64, String)>) {
+        if self.p == 2 {
+            return;
+        }
+        for child in &self.children {
+            child.collect_lines(lines);
+        }
+        lines.push((self.p, format!("{}: witness {}, p - 1 = {}", self.p, self.witness, format_factors(&self.factors))));
+    }
+}
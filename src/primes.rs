@@ -0,0 +1,94 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// ライブラリとして使うための素数列挙・計数。GUI の設定やファイル出力を介さずに
+// [min, max] の素数を篩う（Python バインディングなどから使う）
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use rayon::prelude::*;
+use crate::sieve::{integer_sqrt, segment_ranges, segmented_sieve, simple_sieve};
+
+// 1 回に篩う区間の長さ
+const SEGMENT_SIZE: u64 = 1 << 20;
+// 数えるときに並列で篩う区間の数。区間の一覧を先にすべて作らず、この数ずつ進める
+const COUNT_BATCH: u64 = 256;
+
+// [min, max] の素数を小さい順に返すイテレータ。区間ごとに必要になった時点で篩う
+pub struct Primes {
+    small_primes: Vec<u64>,
+    // 次に篩う区間の先頭（None なら篩い終わり）
+    next_low: Option<u64>,
+    max: u64,
+    buffered: std::vec::IntoIter<u64>,
+    stop_flag: Arc<AtomicBool>,
+}
+
+impl Primes {
+    pub fn new(min: u64, max: u64) -> Self {
+        let small_primes = if min <= max { simple_sieve(integer_sqrt(max) + 1) } else { Vec::new() };
+        Primes {
+            small_primes,
+            next_low: (min <= max).then_some(min),
+            max,
+            buffered: Vec::new().into_iter(),
+            stop_flag: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl Iterator for Primes {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        loop {
+            if let Some(p) = self.buffered.next() {
+                return Some(p);
+            }
+            let low = self.next_low?;
+            let high = low.saturating_add(SEGMENT_SIZE - 1).min(self.max);
+            self.next_low = (high < self.max).then(|| high + 1);
+            self.buffered = segmented_sieve(&self.small_primes, low, high, &self.stop_flag).into_iter();
+        }
+    }
+}
+
+pub fn is_prime(n: u64) -> bool {
+    crate::miller_rabin::is_prime(n)
+}
+
+// [min, max] の素数の個数（区間ごとに並列に篩う）
+pub fn count_primes(min: u64, max: u64) -> u64 {
+    count_primes_until(min, max, &Arc::new(AtomicBool::new(false))).unwrap_or(0)
+}
+
+// count_primes と同じだが、stop_flag が立ったら途中でやめて None を返す
+pub fn count_primes_until(min: u64, max: u64, stop_flag: &Arc<AtomicBool>) -> Option<u64> {
+    if min > max {
+        return Some(0);
+    }
+    if stop_flag.load(Ordering::SeqCst) {
+        return None;
+    }
+    let small_primes = simple_sieve(integer_sqrt(max) + 1);
+    let mut count = 0;
+    let mut low = min;
+    loop {
+        if stop_flag.load(Ordering::SeqCst) {
+            return None;
+        }
+        let high = low.saturating_add(SEGMENT_SIZE * COUNT_BATCH - 1).min(max);
+        count += segment_ranges(low, high, SEGMENT_SIZE)
+            .par_iter()
+            .map(|&(low, high)| segmented_sieve(&small_primes, low, high, stop_flag).len() as u64)
+            .sum::<u64>();
+        if high >= max {
+            break;
+        }
+        low = high + 1;
+    }
+    // 最後のまとまりの途中で止められた場合は数え終わっていない
+    (!stop_flag.load(Ordering::SeqCst)).then_some(count)
+}
@@ -0,0 +1,278 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// primesieve（https://github.com/kimwalisch/primesieve）との相互運用。
+// primesieve にはファイル形式がないので、`primesieve --print` のテキスト出力（1 行に 1 個）と、
+// generate_primes が返す uint64_t の配列を書き出したもの（Binary 形式と同じ、.bin）を読み書きする。
+// 取り込みは読んだ素数を現在の出力設定で書き直し、比較は自分の出力と primesieve の出力を
+// 重なっている範囲について突き合わせる
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use crate::app::WorkerMessage;
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::i18n::{format_number, tr};
+use crate::output::{existing_outputs, is_binary, open_content, NumberTooLarge, PrimeSink, TextNumbers};
+use crate::sieve::{open_output, parse_filters};
+
+// 不一致の例として報告する数
+const MAX_EXAMPLES: usize = 10;
+
+// 出力ファイルから素数を 1 個ずつ読む（scan_primes の引き出し版。2 つのファイルを並べて読むのに使う）
+pub struct PrimeStream {
+    reader: BufReader<Box<dyn Read>>,
+    binary: bool,
+    path: PathBuf,
+    numbers: TextNumbers,
+}
+
+impl PrimeStream {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(PrimeStream { reader: BufReader::new(open_content(path, 0)?), binary: is_binary(path), path: path.to_path_buf(), numbers: TextNumbers::default() })
+    }
+
+    fn invalid(&self, message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{} in {}", message, self.path.display()))
+    }
+
+    fn next_binary(&mut self) -> io::Result<Option<u64>> {
+        let mut record = [0u8; 8];
+        let mut filled = 0;
+        while filled < record.len() {
+            match self.reader.read(&mut record[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        match filled {
+            0 => Ok(None),
+            8 => Ok(Some(u64::from_le_bytes(record))),
+            _ => Err(self.invalid("truncated record")),
+        }
+    }
+
+    // 数字以外を区切りとして扱い、prime_index の番号は読み飛ばす（scan_primes と同じ）
+    fn next_text(&mut self) -> io::Result<Option<u64>> {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(self.numbers.finish().map(|(_, v)| v));
+            }
+            let mut used = 0;
+            let mut found = Ok(None);
+            for &b in buf {
+                used += 1;
+                found = self.numbers.push(b, 0);
+                if !matches!(found, Ok(None)) {
+                    break;
+                }
+            }
+            self.reader.consume(used);
+            match found {
+                Ok(Some((_, v))) => return Ok(Some(v)),
+                Ok(None) => {}
+                Err(NumberTooLarge) => return Err(self.invalid("number too large")),
+            }
+        }
+    }
+}
+
+impl Iterator for PrimeStream {
+    type Item = io::Result<u64>;
+
+    fn next(&mut self) -> Option<io::Result<u64>> {
+        let next = if self.binary { self.next_binary() } else { self.next_text() };
+        next.transpose()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Comparison {
+    // 両方が含む範囲（どちらかが空なら None）
+    pub range: Option<(u64, u64)>,
+    pub matched: u64,
+    // 範囲内で片方にしかない素数の数と、その最初のいくつか
+    pub only_ours: u64,
+    pub only_theirs: u64,
+    pub examples_ours: Vec<u64>,
+    pub examples_theirs: Vec<u64>,
+}
+
+impl Comparison {
+    pub fn is_match(&self) -> bool {
+        self.only_ours == 0 && self.only_theirs == 0
+    }
+}
+
+// 昇順の列を 1 個先読みしながら読む。昇順でなければエラー
+struct Sorted<I> {
+    iter: I,
+    peeked: Option<u64>,
+    name: &'static str,
+}
+
+impl<I: Iterator<Item = io::Result<u64>>> Sorted<I> {
+    fn new(mut iter: I, name: &'static str) -> io::Result<Self> {
+        let peeked = iter.next().transpose()?;
+        Ok(Sorted { iter, peeked, name })
+    }
+
+    fn advance(&mut self) -> io::Result<()> {
+        let next = self.iter.next().transpose()?;
+        if let (Some(prev), Some(next)) = (self.peeked, next) {
+            if next <= prev {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} primes are not in ascending order ({} after {})", self.name, next, prev)));
+            }
+        }
+        self.peeked = next;
+        Ok(())
+    }
+}
+
+// 昇順の 2 つの列を、両方の範囲が重なる部分について突き合わせる。
+// 範囲の終わりは先に尽きた方の最後の素数まで。stop_flag が立ったら None
+pub fn compare_primes(
+    ours: impl Iterator<Item = io::Result<u64>>,
+    theirs: impl Iterator<Item = io::Result<u64>>,
+    stop_flag: &AtomicBool,
+) -> io::Result<Option<Comparison>> {
+    let mut ours = Sorted::new(ours, "our")?;
+    let mut theirs = Sorted::new(theirs, "primesieve")?;
+    let mut result = Comparison::default();
+    let (Some(a), Some(b)) = (ours.peeked, theirs.peeked) else {
+        return Ok(Some(result));
+    };
+    let start = a.max(b);
+    while ours.peeked.is_some_and(|p| p < start) {
+        ours.advance()?;
+    }
+    while theirs.peeked.is_some_and(|p| p < start) {
+        theirs.advance()?;
+    }
+    let mut last = None;
+    let mut steps = 0u64;
+    while let (Some(a), Some(b)) = (ours.peeked, theirs.peeked) {
+        steps += 1;
+        if steps.is_multiple_of(1 << 20) && stop_flag.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        if a == b {
+            result.matched += 1;
+            ours.advance()?;
+            theirs.advance()?;
+        } else if a < b {
+            result.only_ours += 1;
+            if result.examples_ours.len() < MAX_EXAMPLES {
+                result.examples_ours.push(a);
+            }
+            ours.advance()?;
+        } else {
+            result.only_theirs += 1;
+            if result.examples_theirs.len() < MAX_EXAMPLES {
+                result.examples_theirs.push(b);
+            }
+            theirs.advance()?;
+        }
+        last = Some(a.min(b));
+    }
+    result.range = last.map(|last| (start, last));
+    Ok(Some(result))
+}
+
+fn format_examples(examples: &[u64], count: u64) -> String {
+    let list = examples.iter().map(|&p| format_number(p)).collect::<Vec<_>>().join(", ");
+    if count > examples.len() as u64 {
+        format!("{}, ...", list)
+    } else {
+        list
+    }
+}
+
+// 現在の出力設定（形式・圧縮・分割）の出力ファイルと primesieve の出力を比較する
+pub fn compare_with_primesieve(config: Config, path: PathBuf, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let dir = Path::new(&config.output_dir);
+    let files = existing_outputs(dir, &config.output_format, config.zstd_seekable, config.split_count);
+    if files.is_empty() {
+        return Err(SieveError::Config(format!("No {:?} output files found in {}", config.output_format, dir.display())));
+    }
+    if config.output_radix != 10 {
+        return Err(SieveError::Config("only decimal output can be compared".to_string()));
+    }
+    sender.send(WorkerMessage::Log(tr!("Comparing {} file(s) with {}", files.len(), path.display()))).ok();
+
+    let open_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| SieveError::OpenFile { path: path.clone(), source }
+    };
+    let theirs = PrimeStream::open(&path).map_err(open_error(&path))?;
+    // 分割ファイルは順に読む（開けなかったファイルはそこでエラーにする）
+    let ours = files.iter().flat_map(|file| match PrimeStream::open(file) {
+        Ok(stream) => Box::new(stream) as Box<dyn Iterator<Item = io::Result<u64>>>,
+        Err(e) => Box::new(std::iter::once(Err(io::Error::new(e.kind(), format!("{}: {}", file.display(), e))))),
+    });
+    let Some(result) = compare_primes(ours, theirs, &stop_flag).map_err(open_error(dir))? else {
+        sender.send(WorkerMessage::Stopped).ok();
+        return Ok(());
+    };
+
+    match result.range {
+        None => sender.send(WorkerMessage::Log(tr!("The outputs have no range in common").to_string())).ok(),
+        Some((low, high)) => sender.send(WorkerMessage::Log(tr!("Compared [{}, {}]: {} primes match", format_number(low), format_number(high), format_number(result.matched)))).ok(),
+    };
+    if result.is_match() {
+        if result.range.is_some() {
+            sender.send(WorkerMessage::Log(tr!("The outputs agree").to_string())).ok();
+        }
+    } else {
+        if result.only_ours > 0 {
+            sender.send(WorkerMessage::Log(tr!("{} primes only in our output: {}", format_number(result.only_ours), format_examples(&result.examples_ours, result.only_ours)))).ok();
+        }
+        if result.only_theirs > 0 {
+            sender.send(WorkerMessage::Log(tr!("{} primes only in the primesieve output: {}", format_number(result.only_theirs), format_examples(&result.examples_theirs, result.only_theirs)))).ok();
+        }
+        if config.residue_modulus != 0 || !config.digit_filter.trim().is_empty() || !config.filter_script.trim().is_empty() {
+            sender.send(WorkerMessage::Log(tr!("Note: the output filters may have removed primes from our output").to_string())).ok();
+        }
+    }
+    Ok(())
+}
+
+// primesieve の出力（テキストまたは .bin）を読み、現在の出力設定（フィルタを含む）で書き直す
+pub fn import_primesieve(config: Config, path: PathBuf, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let filters = parse_filters(&config)?;
+    if !config.output_dir.is_empty() {
+        std::fs::create_dir_all(&config.output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(&config.output_dir), source })?;
+    }
+    sender.send(WorkerMessage::Log(tr!("Importing {}", path.display()))).ok();
+    let mut out = open_output(&config, filters, &sender)?;
+    let stream = PrimeStream::open(&path).map_err(|source| SieveError::OpenFile { path: path.clone(), source })?;
+    let mut last = None;
+    let mut count = 0u64;
+    for p in stream {
+        let p = p.map_err(|source| SieveError::OpenFile { path: path.clone(), source })?;
+        if last.is_some_and(|last| p <= last) {
+            return Err(SieveError::Config(format!("{} is not in ascending order", path.display())));
+        }
+        last = Some(p);
+        let out_path = out.current_path().map(Path::to_path_buf).unwrap_or_default();
+        out.write_prime(p).map_err(SieveError::write(&out_path))?;
+        count += 1;
+        if count.is_multiple_of(1 << 20) && stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+    }
+    let out_path = out.current_path().map(Path::to_path_buf).unwrap_or_default();
+    out.finish().map_err(SieveError::write(&out_path))?;
+    let files = out.written_files().iter().map(|f| f.display().to_string()).collect::<Vec<_>>().join(", ");
+    sender.send(WorkerMessage::Log(tr!("Imported {} primes into {}", format_number(count), files))).ok();
+    Ok(())
+}
@@ -0,0 +1,147 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 強擬素数の探索モード。篩で合成数と分かっている奇数のうち、指定した底の
+// Miller-Rabin をすべて通過するもの（強擬素数）を探す。底ごとの「嘘つき」の数も数える
+
+use std::fs::{create_dir_all, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use rayon::prelude::*;
+use crate::app::{ProgressPhase, WorkerMessage};
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::miller_rabin::is_strong_probable_prime;
+use crate::sieve::{build_thread_pool, format_eta, integer_sqrt, parallel_segments, segment_ranges, segmented_sieve_in, simple_sieve};
+use crate::i18n::tr;
+
+pub const OUTPUT_FILE_NAME: &str = "pseudoprimes.txt";
+
+// 指定できる底の数の上限
+pub const MAX_BASES: usize = 16;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SegmentResult {
+    // すべての底を通過した合成数
+    pub pseudoprimes: Vec<u64>,
+    // 判定した奇数の合成数の個数
+    pub composites: u64,
+    // 底ごとに通過した（その底が合成数を見抜けなかった）個数
+    pub liars: Vec<u64>,
+}
+
+// [low, high] の強擬素数を探す。底以下の数は判定しない
+pub fn strong_pseudoprimes_in(small_primes: &[u64], low: u64, high: u64, bases: &[u64], stop_flag: &Arc<AtomicBool>) -> SegmentResult {
+    let mut composite = vec![0u8; (high - low + 1) as usize];
+    segmented_sieve_in(&mut composite, small_primes, low, high, stop_flag);
+    let max_base = bases.iter().copied().max().unwrap_or(0);
+    let mut result = SegmentResult { liars: vec![0; bases.len()], ..SegmentResult::default() };
+    for (i, &flag) in composite.iter().enumerate() {
+        let n = low + i as u64;
+        // 強擬素数は奇数の合成数として定義される
+        if flag == 0 || n.is_multiple_of(2) || n <= max_base.max(3) {
+            continue;
+        }
+        if i & 0xFFFF == 0 && stop_flag.load(Ordering::SeqCst) {
+            break;
+        }
+        result.composites += 1;
+        let mut passed_all = true;
+        for (b, &a) in bases.iter().enumerate() {
+            if is_strong_probable_prime(n, a) {
+                result.liars[b] += 1;
+            } else {
+                passed_all = false;
+            }
+        }
+        if passed_all {
+            result.pseudoprimes.push(n);
+        }
+    }
+    result
+}
+
+pub fn run_pseudoprime(config: Config, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let bases = config.pseudoprime_bases.clone();
+    if bases.is_empty() || bases.len() > MAX_BASES {
+        return Err(SieveError::Config(format!("between 1 and {} Miller-Rabin bases are required", MAX_BASES)));
+    }
+    if bases.iter().any(|&a| a < 2) {
+        return Err(SieveError::Config("Miller-Rabin bases must be at least 2".to_string()));
+    }
+    sender.send(WorkerMessage::Log(tr!("Searching strong pseudoprimes to bases {}", format!("{:?}", bases)))).ok();
+
+    let prime_min = config.prime_min.parse::<u64>()?;
+    let prime_max = config.prime_max.parse::<u64>()?;
+    if prime_min > prime_max {
+        return Err(SieveError::Config("prime_min must not exceed prime_max".to_string()));
+    }
+    if config.segment_size == 0 {
+        return Err(SieveError::Config("segment_size must be greater than 0".to_string()));
+    }
+    if !config.output_dir.is_empty() {
+        create_dir_all(&config.output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(&config.output_dir), source })?;
+    }
+    let path = Path::new(&config.output_dir).join(OUTPUT_FILE_NAME);
+    let file = File::create(&path).map_err(|source| SieveError::OpenFile { path: path.clone(), source })?;
+    let mut out = BufWriter::with_capacity(config.writer_buffer_size, file);
+
+    let pool = build_thread_pool(&config)?;
+    let small_primes = simple_sieve(integer_sqrt(prime_max) + 1);
+    let segments = segment_ranges(prime_min, prime_max, config.segment_size);
+    let total_range = prime_max - prime_min + 1;
+    let start_time = Instant::now();
+    let mut processed = 0u64;
+    let mut total = SegmentResult { liars: vec![0; bases.len()], ..SegmentResult::default() };
+
+    for batch in segments.chunks(parallel_segments(&config, &pool)) {
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        let results: Vec<SegmentResult> = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|&(low, high)| strong_pseudoprimes_in(&small_primes, low, high, &bases, &stop_flag))
+                .collect()
+        });
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+
+        for result in results {
+            for &n in &result.pseudoprimes {
+                writeln!(out, "{}", n).map_err(SieveError::write(&path))?;
+                sender.send(WorkerMessage::Log(tr!("Strong pseudoprime: {}", n))).ok();
+            }
+            total.pseudoprimes.extend(result.pseudoprimes);
+            total.composites += result.composites;
+            for (sum, n) in total.liars.iter_mut().zip(result.liars) {
+                *sum += n;
+            }
+        }
+
+        processed += batch.iter().map(|&(low, high)| high - low + 1).sum::<u64>();
+        sender.send(WorkerMessage::Progress { current: processed, total: total_range, phase: ProgressPhase::Testing }).ok();
+        sender.send(WorkerMessage::Eta(format_eta(start_time, processed as f64 / total_range as f64))).ok();
+    }
+    out.flush().map_err(SieveError::write(&path))?;
+
+    for (&a, &n) in bases.iter().zip(&total.liars) {
+        sender.send(WorkerMessage::Log(tr!(
+            "Base {}: strong liar for {} of {} odd composites", a, n, total.composites))).ok();
+    }
+    sender.send(WorkerMessage::Eta(tr!("0 hour 0 min 0 sec").to_string())).ok();
+    sender.send(WorkerMessage::Log(tr!(
+        "Finished strong pseudoprime search. {} pseudoprimes written to {}", total.pseudoprimes.len(), path.display()))).ok();
+    sender.send(WorkerMessage::Finished { found: total.pseudoprimes.len() as u64, files: vec![path] }).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
@@ -0,0 +1,80 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 素数競争（チェビシェフの偏り）。2 つの剰余類 a, b (mod m) の素数の個数を数え、
+// 差 π(x; m, a) - π(x; m, b) の推移と首位が入れ替わった位置を記録する
+
+use crate::app::WorkerMessage;
+use crate::i18n::tr;
+use std::sync::mpsc;
+
+// 首位交代をログに出す上限（それ以降は件数のみ数える）
+const MAX_LOGGED_LEAD_CHANGES: u64 = 100;
+
+pub struct PrimeRace {
+    modulus: u64,
+    a: u64,
+    b: u64,
+    count_a: u64,
+    count_b: u64,
+    // 直近の非ゼロの差の符号
+    leader: i8,
+    lead_changes: u64,
+    // a が首位（差 > 0）だった素数の個数
+    a_leading: u64,
+    total: u64,
+}
+
+impl PrimeRace {
+    pub fn new(modulus: u64, a: u64, b: u64) -> Self {
+        PrimeRace { modulus, a: a % modulus, b: b % modulus, count_a: 0, count_b: 0, leader: 0, lead_changes: 0, a_leading: 0, total: 0 }
+    }
+
+    pub fn difference(&self) -> i64 {
+        self.count_a as i64 - self.count_b as i64
+    }
+
+    // 昇順の素数列を渡す。high はこのセグメントの上端で、そこでの差を RaceSample として送る
+    pub fn add_segment(&mut self, primes: &[u64], high: u64, sender: &mpsc::Sender<WorkerMessage>) {
+        for &p in primes {
+            let r = p % self.modulus;
+            if r == self.a {
+                self.count_a += 1;
+            } else if r == self.b {
+                self.count_b += 1;
+            } else {
+                continue;
+            }
+            self.total += 1;
+            let diff = self.difference();
+            if diff > 0 {
+                self.a_leading += 1;
+            }
+            let sign = diff.signum() as i8;
+            if sign != 0 && sign != self.leader {
+                if self.leader != 0 {
+                    self.lead_changes += 1;
+                    // 交代点もグラフに含める
+                    sender.send(WorkerMessage::RaceSample { x: p, difference: diff }).ok();
+                    if self.lead_changes <= MAX_LOGGED_LEAD_CHANGES {
+                        let (leader, other) = if sign > 0 { (self.a, self.b) } else { (self.b, self.a) };
+                        sender.send(WorkerMessage::Log(tr!(
+                            "Prime race: {} mod {} takes the lead over {} mod {} at {}", leader, self.modulus, other, self.modulus, p))).ok();
+                    }
+                }
+                self.leader = sign;
+            }
+        }
+        sender.send(WorkerMessage::RaceSample { x: high, difference: self.difference() }).ok();
+    }
+
+    pub fn summary(&self) -> String {
+        let share = if self.total > 0 { self.a_leading as f64 * 100.0 / self.total as f64 } else { 0.0 };
+        tr!(
+            "Prime race mod {0}: {1} mod {0} = {2}, {3} mod {0} = {4}, lead changes: {5}, {1} mod {0} ahead for {6}% of the race",
+            self.modulus, self.a, self.count_a, self.b, self.count_b, self.lead_changes, format!("{:.2}", share),
+        )
+    }
+}
@@ -0,0 +1,463 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// S3 互換ストレージへのアップロード。書き終えた出力ファイル（分割出力では 1 ファイルずつ）を
+// 別スレッドでアップロードする。署名は AWS Signature Version 4、part_size を超えるファイルは
+// マルチパートアップロードにし、各リクエストは失敗しても間隔をあけて再試行する。
+// 認証情報は設定ファイルではなく環境変数 AWS_ACCESS_KEY_ID / AWS_SECRET_ACCESS_KEY
+// （/ AWS_SESSION_TOKEN）から読む
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use crate::app::WorkerMessage;
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::archive::ArchiveSink;
+use crate::output::PrimeSink;
+use crate::sftp::SftpClient;
+use crate::webdav::WebDavClient;
+
+// 1 リクエストあたりの試行回数と最初の待ち時間（以降は倍にする）
+pub(crate) const MAX_ATTEMPTS: u32 = 5;
+pub(crate) const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+// S3 のマルチパートの最小パートサイズ（最後のパートを除く）
+pub const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    pub fn from_env() -> io::Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key), Some(secret_key)) => Ok(Credentials { access_key, secret_key, session_token: var("AWS_SESSION_TOKEN") }),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set")),
+        }
+    }
+}
+
+// AWS の URI エンコード（encode_slash が false なら / はそのまま）
+pub(crate) fn uri_encode(text: &str, encode_slash: bool) -> String {
+    let mut encoded = String::new();
+    for b in text.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(b as char),
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+// ファイルの中身の SHA-256（16 進）。大きなファイルも少しずつ読む
+pub(crate) fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => return Ok(hex(&hasher.finalize())),
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+// UNIX 時間を (YYYYMMDD, YYYYMMDDTHHMMSSZ) にする
+pub fn amz_date(unix_secs: u64) -> (String, String) {
+    let days = (unix_secs / 86_400) as i64;
+    let secs = unix_secs % 86_400;
+    // 1970-01-01 からの日数を暦日に直す（Howard Hinnant の civil_from_days）
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let datetime = format!("{}T{:02}{:02}{:02}Z", date, secs / 3600, secs / 60 % 60, secs % 60);
+    (date, datetime)
+}
+
+// 署名するリクエスト。headers には host と x-amz-* を含め、名前は小文字
+pub struct SignedRequest<'a> {
+    pub method: &'a str,
+    // エンコード済みのパス
+    pub path: &'a str,
+    pub query: &'a [(String, String)],
+    pub headers: &'a [(String, String)],
+    pub payload_hash: &'a str,
+}
+
+// Authorization ヘッダーの値
+pub fn authorization(request: &SignedRequest, credentials: &Credentials, region: &str, datetime: &str) -> String {
+    let date = &datetime[..8];
+    let mut query: Vec<(String, String)> = request.query.iter().map(|(k, v)| (uri_encode(k, true), uri_encode(v, true))).collect();
+    query.sort();
+    let canonical_query = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&");
+    let mut headers: Vec<(String, String)> = request.headers.iter().map(|(k, v)| (k.to_lowercase(), v.trim().to_string())).collect();
+    headers.sort();
+    let canonical_headers: String = headers.iter().map(|(k, v)| format!("{}:{}\n", k, v)).collect();
+    let signed_headers = headers.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>().join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method, request.path, canonical_query, canonical_headers, signed_headers, request.payload_hash
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", datetime, scope, sha256_hex(canonical_request.as_bytes()));
+    let key = hmac(format!("AWS4{}", credentials.secret_key).as_bytes(), date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, "s3");
+    let key = hmac(&key, "aws4_request");
+    let signature = hex(&hmac(&key, &string_to_sign));
+    format!("AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}", credentials.access_key, scope, signed_headers, signature)
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Target {
+    // 例: https://s3.us-east-1.amazonaws.com、http://localhost:9000
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    // キーの前に付ける文字列（例: primes/run1/）
+    pub prefix: String,
+    // true ならパス形式（endpoint/bucket/key）、false なら仮想ホスト形式（bucket.host/key）
+    pub path_style: bool,
+    pub part_size: usize,
+}
+
+impl S3Target {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let bucket = config.s3_bucket.trim();
+        (!bucket.is_empty()).then(|| S3Target {
+            endpoint: config.s3_endpoint.trim().trim_end_matches('/').to_string(),
+            region: config.s3_region.trim().to_string(),
+            bucket: bucket.to_string(),
+            prefix: config.s3_prefix.trim().to_string(),
+            path_style: config.s3_path_style,
+            part_size: (config.s3_part_size_mb as usize * 1024 * 1024).max(MIN_PART_SIZE),
+        })
+    }
+
+    pub fn key_for(&self, path: &Path) -> String {
+        format!("{}{}", self.prefix, path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default())
+    }
+
+    // (URL の先頭, host, エンコード済みのパス)
+    fn locate(&self, key: &str) -> io::Result<(String, String, String)> {
+        let (scheme, host) = self.endpoint.split_once("://").ok_or_else(|| invalid_input(format!("invalid endpoint {}", self.endpoint)))?;
+        let key = uri_encode(key, false);
+        if self.path_style {
+            Ok((format!("{}://{}", scheme, host), host.to_string(), format!("/{}/{}", uri_encode(&self.bucket, true), key)))
+        } else {
+            let host = format!("{}.{}", self.bucket, host);
+            Ok((format!("{}://{}", scheme, host), host, format!("/{}", key)))
+        }
+    }
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+pub struct S3Client {
+    target: S3Target,
+    credentials: Credentials,
+    agent: ureq::Agent,
+}
+
+impl S3Client {
+    pub fn new(target: S3Target, credentials: Credentials) -> Self {
+        let agent = ureq::AgentBuilder::new().timeout_connect(Duration::from_secs(30)).timeout_read(Duration::from_secs(300)).build();
+        S3Client { target, credentials, agent }
+    }
+
+    // 署名付きリクエストを送る。接続エラー、5xx と 429 は再試行する。(ETag, 本文) を返す
+    fn send(&self, method: &str, key: &str, query: &[(String, String)], body: &[u8]) -> io::Result<(Option<String>, String)> {
+        let (base, host, path) = self.target.locate(key)?;
+        let payload_hash = sha256_hex(body);
+        let mut delay = FIRST_RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            let unix_secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            let (_, datetime) = amz_date(unix_secs);
+            let mut headers = vec![
+                ("host".to_string(), host.clone()),
+                ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+                ("x-amz-date".to_string(), datetime.clone()),
+            ];
+            if let Some(token) = &self.credentials.session_token {
+                headers.push(("x-amz-security-token".to_string(), token.clone()));
+            }
+            let request = SignedRequest { method, path: &path, query, headers: &headers, payload_hash: &payload_hash };
+            let auth = authorization(&request, &self.credentials, &self.target.region, &datetime);
+
+            let query_string = query.iter().map(|(k, v)| if v.is_empty() { uri_encode(k, true) } else { format!("{}={}", uri_encode(k, true), uri_encode(v, true)) }).collect::<Vec<_>>().join("&");
+            let url = if query_string.is_empty() { format!("{}{}", base, path) } else { format!("{}{}?{}", base, path, query_string) };
+            let mut call = self.agent.request(method, &url).set("Authorization", &auth);
+            for (name, value) in headers.iter().filter(|(name, _)| name != "host") {
+                call = call.set(name, value);
+            }
+            let error = match call.send_bytes(body) {
+                Ok(response) => {
+                    let etag = response.header("ETag").map(str::to_string);
+                    let text = response.into_string()?;
+                    return Ok((etag, text));
+                }
+                Err(ureq::Error::Status(code, response)) => {
+                    let text = response.into_string().unwrap_or_default();
+                    let error = io::Error::other(format!("HTTP {} for {} {}: {}", code, method, key, text.trim()));
+                    if !(code == 429 || code >= 500) {
+                        return Err(error);
+                    }
+                    error
+                }
+                Err(ureq::Error::Transport(transport)) => io::Error::other(transport.to_string()),
+            };
+            if attempt >= MAX_ATTEMPTS {
+                return Err(error);
+            }
+            std::thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+
+    // ファイルをアップロードする。part_size を超えるものはマルチパートにする
+    pub fn upload(&self, path: &Path) -> io::Result<String> {
+        let key = self.target.key_for(path);
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        if size <= self.target.part_size as u64 {
+            let mut body = Vec::with_capacity(size as usize);
+            file.read_to_end(&mut body)?;
+            self.send("PUT", &key, &[], &body)?;
+            return Ok(key);
+        }
+
+        let (_, response) = self.send("POST", &key, &[("uploads".to_string(), String::new())], &[])?;
+        let upload_id = xml_value(&response, "UploadId").ok_or_else(|| io::Error::other(format!("no UploadId in response: {}", response)))?;
+        let result = self.upload_parts(&key, &upload_id, &mut file);
+        if result.is_err() {
+            // 途中のパートが残って課金されないよう中止する（失敗しても元のエラーを返す）
+            let _ = self.send("DELETE", &key, &[("uploadId".to_string(), upload_id.clone())], &[]);
+        }
+        result.map(|()| key)
+    }
+
+    fn upload_parts(&self, key: &str, upload_id: &str, file: &mut File) -> io::Result<()> {
+        let mut parts = Vec::new();
+        let mut buffer = vec![0u8; self.target.part_size];
+        loop {
+            let mut filled = 0;
+            while filled < buffer.len() {
+                match file.read(&mut buffer[filled..])? {
+                    0 => break,
+                    n => filled += n,
+                }
+            }
+            if filled == 0 {
+                break;
+            }
+            let number = parts.len() + 1;
+            let query = [("partNumber".to_string(), number.to_string()), ("uploadId".to_string(), upload_id.to_string())];
+            let (etag, _) = self.send("PUT", key, &query, &buffer[..filled])?;
+            parts.push(etag.ok_or_else(|| io::Error::other(format!("no ETag for part {}", number)))?);
+            if filled < buffer.len() {
+                break;
+            }
+        }
+        let mut body = String::from("<CompleteMultipartUpload>");
+        for (i, etag) in parts.iter().enumerate() {
+            body.push_str(&format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", i + 1, etag));
+        }
+        body.push_str("</CompleteMultipartUpload>");
+        let (_, response) = self.send("POST", key, &[("uploadId".to_string(), upload_id.to_string())], body.as_bytes())?;
+        // 完了リクエストは 200 でも本文にエラーが返ることがある
+        if response.contains("<Error>") {
+            return Err(io::Error::other(format!("failed to complete the upload: {}", response)));
+        }
+        Ok(())
+    }
+
+    pub fn url_for(&self, key: &str) -> String {
+        format!("s3://{}/{}", self.target.bucket, key)
+    }
+}
+
+fn xml_value(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml[start..end].to_string())
+}
+
+// アップロードの結果（送り先の URL）。Skipped は同じ中身のファイルがすでにあって送らなかった
+#[derive(Debug, Clone, PartialEq)]
+pub enum Uploaded {
+    Sent(String),
+    Skipped(String),
+}
+
+// アップロード先（S3、WebDAV と SFTP）
+pub trait Destination: Send {
+    fn upload(&self, path: &Path) -> io::Result<Uploaded>;
+}
+
+impl Destination for S3Client {
+    fn upload(&self, path: &Path) -> io::Result<Uploaded> {
+        S3Client::upload(self, path).map(|key| Uploaded::Sent(self.url_for(&key)))
+    }
+}
+
+// 書き終えたファイルを順にすべてのアップロード先へ送るスレッド。delete_local なら
+// すべての送り先に送れたファイルを消す。送らずに飛ばした送り先があるファイルは、
+// サーバーにあるのが本当にこのファイルか送って確かめていないので残す
+struct Uploader {
+    files: Option<mpsc::Sender<PathBuf>>,
+    handle: Option<JoinHandle<Vec<String>>>,
+}
+
+impl Uploader {
+    fn start(destinations: Vec<Box<dyn Destination>>, delete_local: bool, sender: mpsc::Sender<WorkerMessage>) -> Self {
+        let (files, receiver) = mpsc::channel::<PathBuf>();
+        let handle = std::thread::spawn(move || {
+            let mut errors = Vec::new();
+            for path in receiver {
+                let mut sent = true;
+                for destination in &destinations {
+                    match destination.upload(&path) {
+                        Ok(Uploaded::Sent(url)) => sender.send(WorkerMessage::Log(tr!("Uploaded {} to {}", path.display(), url))).ok(),
+                        Ok(Uploaded::Skipped(url)) => {
+                            sent = false;
+                            sender.send(WorkerMessage::Log(tr!("{} is already at {}, skipped", path.display(), url))).ok()
+                        }
+                        Err(e) => {
+                            sent = false;
+                            errors.push(format!("{}: {}", path.display(), e));
+                            sender.send(WorkerMessage::Log(tr!("Failed to upload {}: {}", path.display(), e))).ok()
+                        }
+                    };
+                }
+                if sent && delete_local {
+                    match std::fs::remove_file(&path) {
+                        Ok(()) => sender.send(WorkerMessage::Log(tr!("Deleted the local copy of {}", path.display()))).ok(),
+                        Err(e) => sender.send(WorkerMessage::Log(tr!("Failed to delete {}: {}", path.display(), e))).ok(),
+                    };
+                }
+            }
+            errors
+        });
+        Uploader { files: Some(files), handle: Some(handle) }
+    }
+
+    fn queue(&self, path: PathBuf) {
+        if let Some(files) = &self.files {
+            files.send(path).ok();
+        }
+    }
+
+    // 残りのアップロードを待ち、失敗したファイルのエラーを返す
+    fn wait(&mut self) -> Vec<String> {
+        self.files = None;
+        self.handle.take().map(|h| h.join().unwrap_or_default()).unwrap_or_default()
+    }
+}
+
+// ArchiveSink を包み、書き終えたファイル（まとめる場合は圧縮ファイル）をアップロードする
+// （s3_bucket、webdav_url と sftp_url がどれも空なら何もしない）
+pub struct UploadSink {
+    inner: ArchiveSink,
+    uploader: Option<Uploader>,
+    // アップロードに回したファイルの数
+    queued: usize,
+}
+
+impl UploadSink {
+    pub fn new(inner: ArchiveSink, config: &Config, sender: &mpsc::Sender<WorkerMessage>) -> io::Result<Self> {
+        let mut destinations: Vec<Box<dyn Destination>> = Vec::new();
+        if let Some(target) = S3Target::from_config(config) {
+            let credentials = Credentials::from_env().map_err(|e| io::Error::new(e.kind(), format!("s3: {}", e)))?;
+            destinations.push(Box::new(S3Client::new(target, credentials)));
+        }
+        if let Some(client) = WebDavClient::from_config(config).map_err(|e| io::Error::new(e.kind(), format!("webdav: {}", e)))? {
+            destinations.push(Box::new(client));
+        }
+        if let Some(client) = SftpClient::from_config(config).map_err(|e| io::Error::new(e.kind(), format!("sftp: {}", e)))? {
+            destinations.push(Box::new(client));
+        }
+        let uploader = (!destinations.is_empty()).then(|| Uploader::start(destinations, config.upload_delete_local, sender.clone()));
+        Ok(UploadSink { inner, uploader, queued: 0 })
+    }
+
+    // 先頭から done 個のファイルをアップロードに回す
+    fn queue_completed(&mut self, done: usize) {
+        if let Some(uploader) = &self.uploader {
+            for path in &self.inner.files()[self.queued.min(done)..done] {
+                uploader.queue(path.clone());
+            }
+        }
+        self.queued = self.queued.max(done);
+    }
+}
+
+impl PrimeSink for UploadSink {
+    fn write_prime(&mut self, p: u64) -> io::Result<()> {
+        self.inner.write_prime(p)?;
+        let done = self.inner.completed();
+        if done > self.queued {
+            self.queue_completed(done);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()?;
+        self.queue_completed(self.inner.files().len());
+        let errors = self.uploader.as_mut().map(Uploader::wait).unwrap_or_default();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("upload failed: {}", errors.join("; "))))
+        }
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.inner.current_path()
+    }
+
+    fn relocate(&mut self, dir: &Path) -> io::Result<()> {
+        self.inner.relocate(dir)
+    }
+
+    fn written_files(&self) -> Vec<PathBuf> {
+        self.inner.written_files()
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// rhai スクリプトで出力を拡張する。
+// フィルタ: 素数ごとに評価する条件式。変数 p に素数が入り、true を返した素数だけを書き出す。
+//   例: p % 10 == 7 && digit_sum(p) % 3 == 0
+// 出力スクリプト: 書き出した素数を on_batch(primes) で一定数ずつ受け取る関数を定義したファイル。
+//   独自の形式のファイルを書いたり、HTTP で外部に送ったりできる
+// rhai の整数は i64 なので、2^63 以上の素数は扱えない
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, INT};
+use crate::app::WorkerMessage;
+use crate::config::Config;
+use crate::error::SieveError;
+use crate::miller_rabin::is_prime;
+use crate::output::PrimeSink;
+
+// スクリプトで扱える最大の素数
+pub const MAX_SCRIPT_PRIME: u64 = INT::MAX as u64;
+
+// 1 回の評価で実行できる演算の数（無限ループで止まらないようにする）
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+pub struct FilterScript {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+}
+
+fn digit_sum(n: INT) -> INT {
+    let mut n = n.unsigned_abs();
+    let mut sum = 0;
+    while n > 0 {
+        sum += (n % 10) as INT;
+        n /= 10;
+    }
+    sum
+}
+
+fn digits(n: INT) -> INT {
+    n.unsigned_abs().checked_ilog10().unwrap_or(0) as INT + 1
+}
+
+fn reverse_digits(n: INT) -> INT {
+    let mut n = n.unsigned_abs();
+    let mut reversed: u64 = 0;
+    while n > 0 {
+        reversed = reversed.saturating_mul(10).saturating_add(n % 10);
+        n /= 10;
+    }
+    reversed.min(INT::MAX as u64) as INT
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    // 未定義の変数はコンパイル時にエラーにする
+    engine.set_strict_variables(true);
+    engine.disable_symbol("eval");
+    // print / debug の出力で CLI の標準出力を汚さない
+    engine.on_print(|_| {});
+    engine.on_debug(|_, _, _| {});
+    engine.register_fn("digit_sum", digit_sum);
+    engine.register_fn("digits", digits);
+    engine.register_fn("reverse_digits", reverse_digits);
+    engine.register_fn("palindrome", |n: INT| reverse_digits(n) == n.abs());
+    engine.register_fn("is_prime", |n: INT| n > 0 && is_prime(n as u64));
+    engine
+}
+
+impl FilterScript {
+    // コンパイルして p = 2 で試しに評価する（型の誤りなどを実行前に見つける）
+    pub fn compile(source: &str) -> Result<Self, String> {
+        let mut engine = engine();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let mut scope = Scope::new();
+        scope.push("p", 2 as INT);
+        let ast = engine.compile_with_scope(&scope, source).map_err(|e| e.to_string())?;
+        let mut script = FilterScript { engine, ast, scope };
+        script.matches(2).map_err(|e| format!("{} (for p = 2)", e))?;
+        Ok(script)
+    }
+
+    pub fn matches(&mut self, p: u64) -> Result<bool, String> {
+        let p = INT::try_from(p).map_err(|_| format!("{} is too large for the script (maximum {})", p, MAX_SCRIPT_PRIME))?;
+        self.scope.set_value("p", p);
+        let result = self.engine.eval_ast_with_scope::<bool>(&mut self.scope, &self.ast);
+        // スクリプトが let で作った変数を消す（評価のたびに増えないように）
+        self.scope.rewind(1);
+        result.map_err(|e| e.to_string())
+    }
+}
+
+fn script_error(message: impl std::fmt::Display) -> io::Error {
+    io::Error::other(format!("sink_script: {}", message))
+}
+
+// 出力スクリプトから使う関数。相対パスは出力フォルダから見た位置
+fn register_sink_functions(engine: &mut Engine, output_dir: PathBuf, sender: mpsc::Sender<WorkerMessage>) {
+    engine.register_fn("append_file", move |path: &str, text: &str| -> Result<(), Box<EvalAltResult>> {
+        let path = output_dir.join(path);
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        file.write_all(text.as_bytes()).map_err(|e| format!("{}: {}", path.display(), e).into())
+    });
+    engine.register_fn("http_post", |url: &str, body: &str| -> Result<INT, Box<EvalAltResult>> {
+        match ureq::post(url).set("Content-Type", "text/plain; charset=utf-8").send_string(body) {
+            Ok(response) => Ok(response.status() as INT),
+            Err(ureq::Error::Status(status, _)) => Ok(status as INT),
+            Err(e) => Err(e.to_string().into()),
+        }
+    });
+    engine.register_fn("log", move |text: &str| {
+        sender.send(WorkerMessage::Log(text.to_string())).ok();
+    });
+}
+
+// 内側の sink に書きつつ、素数を sink_batch_size 個ずつ出力スクリプトの on_batch(primes) に渡す。
+// 最後に on_finish() があれば呼ぶ。関数の中では this（最初は空のマップ）に状態を持てる（sink_script が空なら何もしない）
+pub struct ScriptSink<S> {
+    inner: S,
+    script: Option<SinkScript>,
+    batch_size: usize,
+    batch: Array,
+}
+
+struct SinkScript {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    state: Dynamic,
+    has_finish: bool,
+    finished: bool,
+}
+
+impl SinkScript {
+    fn call(&mut self, name: &str, args: impl rhai::FuncArgs) -> io::Result<()> {
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+        self.engine.call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast, name, args).map(drop).map_err(script_error)
+    }
+}
+
+impl<S: PrimeSink> ScriptSink<S> {
+    // スクリプトを読み込んでコンパイルし、関数の外の文（初期化）を 1 回実行する
+    pub fn new(inner: S, config: &Config, sender: &mpsc::Sender<WorkerMessage>) -> Result<Self, SieveError> {
+        let batch_size = config.sink_batch_size.max(1);
+        let path = config.sink_script.trim();
+        if path.is_empty() {
+            return Ok(ScriptSink { inner, script: None, batch_size, batch: Array::new() });
+        }
+        let config_error = |e: &dyn std::fmt::Display| SieveError::Config(format!("sink_script: {}: {}", path, e));
+        let source = fs::read_to_string(path).map_err(|e| config_error(&e))?;
+        let mut engine = engine();
+        register_sink_functions(&mut engine, PathBuf::from(&config.output_dir), sender.clone());
+        let ast = engine.compile(&source).map_err(|e| config_error(&e))?;
+        let has_function = |name: &str, params: usize| ast.iter_functions().any(|f| f.name == name && f.params.len() == params);
+        if !has_function("on_batch", 1) {
+            return Err(config_error(&"the script must define fn on_batch(primes)"));
+        }
+        let has_finish = has_function("on_finish", 0);
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast).map_err(|e| config_error(&e))?;
+        let state = Dynamic::from_map(Map::new());
+        Ok(ScriptSink { inner, script: Some(SinkScript { engine, ast, scope, state, has_finish, finished: false }), batch_size, batch: Array::new() })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.script.is_some()
+    }
+
+    // 溜まった素数を渡す。失敗した場合はバッチを残すので、そのまま再試行できる
+    fn flush_batch(&mut self) -> io::Result<()> {
+        if let Some(script) = &mut self.script {
+            if !self.batch.is_empty() {
+                script.call("on_batch", (self.batch.clone(),))?;
+                self.batch.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: PrimeSink> PrimeSink for ScriptSink<S> {
+    fn write_prime(&mut self, p: u64) -> io::Result<()> {
+        // 満杯のバッチは次の素数の前に渡す（失敗しても p を二重に書かないように）
+        if self.batch.len() >= self.batch_size {
+            self.flush_batch()?;
+        }
+        if self.script.is_some() && p > MAX_SCRIPT_PRIME {
+            return Err(script_error(format!("{} is too large for the script (maximum {})", p, MAX_SCRIPT_PRIME)));
+        }
+        self.inner.write_prime(p)?;
+        if self.script.is_some() {
+            self.batch.push(Dynamic::from_int(p as INT));
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush_batch()?;
+        if let Some(script) = self.script.as_mut().filter(|s| s.has_finish && !s.finished) {
+            script.call("on_finish", ())?;
+            script.finished = true;
+        }
+        self.inner.finish()
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.inner.current_path()
+    }
+
+    fn relocate(&mut self, dir: &Path) -> io::Result<()> {
+        self.inner.relocate(dir)
+    }
+
+    fn written_files(&self) -> Vec<PathBuf> {
+        self.inner.written_files()
+    }
+}
@@ -0,0 +1,346 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// zstd seekable 形式（zstd の contrib/seekable_format）。圧縮前のデータを frame_size バイトごとに
+// 独立した zstd フレームにし、末尾のスキップ可能フレームに各フレームの圧縮後・圧縮前のサイズ
+// （シークテーブル）を置く。普通の zstd としてもそのまま展開でき、位置を指定して読むときは
+// シークテーブルからその位置を含むフレームだけを展開すればよい
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A5E;
+const SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+// フレーム数（4）、記述子（1）、マジックナンバー（4）
+const FOOTER_LEN: u64 = 9;
+const CHECKSUM_FLAG: u8 = 0x80;
+const RESERVED_BITS: u8 = 0x7C;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SeekTable {
+    // (圧縮後のサイズ, 圧縮前のサイズ)
+    frames: Vec<(u32, u32)>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl SeekTable {
+    pub fn frames(&self) -> &[(u32, u32)] {
+        &self.frames
+    }
+
+    // 展開後の全体の長さ
+    pub fn decompressed_len(&self) -> u64 {
+        self.frames.iter().map(|&(_, d)| d as u64).sum()
+    }
+
+    // スキップ可能フレームとしての長さ
+    fn encoded_len(&self) -> u64 {
+        8 + 8 * self.frames.len() as u64 + FOOTER_LEN
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&SKIPPABLE_MAGIC.to_le_bytes());
+        buf.extend_from_slice(&((self.encoded_len() - 8) as u32).to_le_bytes());
+        for &(compressed, decompressed) in &self.frames {
+            buf.extend_from_slice(&compressed.to_le_bytes());
+            buf.extend_from_slice(&decompressed.to_le_bytes());
+        }
+        buf.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        buf.push(0);
+        buf.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+    }
+
+    // ファイル末尾のシークテーブルを読む。末尾がシークテーブルでなければ None
+    pub fn read<R: Read + Seek>(reader: &mut R) -> io::Result<Option<SeekTable>> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        if len < 8 + FOOTER_LEN {
+            return Ok(None);
+        }
+        let mut footer = [0u8; FOOTER_LEN as usize];
+        reader.seek(SeekFrom::Start(len - FOOTER_LEN))?;
+        reader.read_exact(&mut footer)?;
+        if u32::from_le_bytes(footer[5..9].try_into().unwrap()) != SEEKABLE_MAGIC {
+            return Ok(None);
+        }
+        let descriptor = footer[4];
+        if descriptor & RESERVED_BITS != 0 {
+            return Err(invalid("reserved bits are set in the zstd seek table"));
+        }
+        let entry_len: u64 = if descriptor & CHECKSUM_FLAG != 0 { 12 } else { 8 };
+        let count = u32::from_le_bytes(footer[0..4].try_into().unwrap()) as u64;
+        let table_len = 8 + entry_len * count + FOOTER_LEN;
+        if table_len > len {
+            return Err(invalid("the zstd seek table is longer than the file"));
+        }
+        reader.seek(SeekFrom::Start(len - table_len))?;
+        let mut body = vec![0u8; (table_len - FOOTER_LEN) as usize];
+        reader.read_exact(&mut body)?;
+        let word = |at: usize| u32::from_le_bytes(body[at..at + 4].try_into().unwrap());
+        if word(0) != SKIPPABLE_MAGIC || word(4) as u64 != table_len - 8 {
+            return Err(invalid("the zstd seek table is not a skippable frame"));
+        }
+        let frames: Vec<(u32, u32)> = (0..count as usize)
+            .map(|i| {
+                let at = 8 + i * entry_len as usize;
+                (word(at), word(at + 4))
+            })
+            .collect();
+        if frames.iter().map(|&(c, _)| c as u64).sum::<u64>() != len - table_len {
+            return Err(invalid("the zstd seek table does not match the file"));
+        }
+        Ok(Some(SeekTable { frames }))
+    }
+
+    // 各フレームの先頭の (圧縮後の位置, 展開後の位置)
+    fn starts(&self) -> Vec<(u64, u64)> {
+        let mut at = (0u64, 0u64);
+        self.frames
+            .iter()
+            .map(|&(c, d)| {
+                let start = at;
+                at = (at.0 + c as u64, at.1 + d as u64);
+                start
+            })
+            .collect()
+    }
+}
+
+// seekable 形式で書く Write。write が受け取ったデータは flush までにフレームへまとめて書き、
+// flush のたびに末尾へシークテーブルを書く（次の write でその位置まで戻って上書きする）。
+// 失敗したときは書けなかった残りを持っておき、次の write / flush でその続きから書く
+pub struct SeekableWriter<W: Write + Seek> {
+    inner: W,
+    level: i32,
+    frame_size: usize,
+    // 次のフレームにする圧縮前のデータ
+    frame: Vec<u8>,
+    // 圧縮済みのフレーム（pending にあるものを含む）
+    table: SeekTable,
+    // 圧縮したがまだ書けていないバイト列
+    pending: Vec<u8>,
+    // ファイル末尾に書いたシークテーブルのバイト数
+    table_len: u64,
+}
+
+impl<W: Write + Seek> SeekableWriter<W> {
+    // 圧縮前で frame_size バイトごとに、level の圧縮レベルでフレームにする
+    pub fn new(inner: W, level: i32, frame_size: usize) -> Self {
+        SeekableWriter {
+            inner,
+            level,
+            frame_size: frame_size.clamp(1, u32::MAX as usize),
+            frame: Vec::new(),
+            table: SeekTable::default(),
+            pending: Vec::new(),
+            table_len: 0,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    // 末尾のフレームにする、まだ圧縮していないデータ
+    pub fn frame_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.frame
+    }
+
+    fn rewind_table(&mut self) -> io::Result<()> {
+        if self.table_len > 0 {
+            self.inner.seek(SeekFrom::Current(-(self.table_len as i64)))?;
+            self.table_len = 0;
+        }
+        Ok(())
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        if !self.frame.is_empty() {
+            let compressed = zstd::bulk::compress(&self.frame, self.level)?;
+            self.table.frames.push((compressed.len() as u32, self.frame.len() as u32));
+            self.pending.extend_from_slice(&compressed);
+            self.frame.clear();
+        }
+        Ok(())
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            match self.inner.write(&self.pending) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+                Ok(n) => {
+                    self.pending.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    // 途中まで書けた場合も table_len に数えておき、次に戻る位置を合わせる
+    fn write_table(&mut self) -> io::Result<()> {
+        let mut table = Vec::with_capacity(self.table.encoded_len() as usize);
+        self.table.encode(&mut table);
+        let mut written = 0;
+        while written < table.len() {
+            match self.inner.write(&table[written..]) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+                Ok(n) => {
+                    written += n;
+                    self.table_len = written as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SeekableWriter<File> {
+    // 既存のファイルへ追記する。末尾から合わせて min_tail バイト以上になるまでのフレームを展開して
+    // 未圧縮のフレーム（frame_mut）に戻し、ファイルをその直前まで切り詰める。
+    // 書き込み側と、ファイル全体の展開後の長さを返す。空のファイルはそのまま新しく書く
+    pub fn append(mut file: File, level: i32, frame_size: usize, min_tail: usize) -> io::Result<(Self, u64)> {
+        if file.metadata()?.len() == 0 {
+            return Ok((SeekableWriter::new(file, level, frame_size), 0));
+        }
+        let table = SeekTable::read(&mut file)?.ok_or_else(|| invalid("not a zstd seekable file"))?;
+        let total = table.decompressed_len();
+        let mut keep = table.frames.len();
+        let mut tail_len = 0u64;
+        while keep > 0 && tail_len < min_tail as u64 {
+            keep -= 1;
+            tail_len += table.frames[keep].1 as u64;
+        }
+        let offset = table.starts().get(keep).map_or(0, |&(c, _)| c);
+        file.seek(SeekFrom::Start(offset))?;
+        let mut frame = Vec::with_capacity(tail_len as usize);
+        for &(compressed, decompressed) in &table.frames[keep..] {
+            frame.extend_from_slice(&read_frame(&mut file, compressed, decompressed)?);
+        }
+        file.set_len(offset)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut writer = SeekableWriter::new(file, level, frame_size);
+        writer.frame = frame;
+        writer.table.frames = table.frames[..keep].to_vec();
+        Ok((writer, total))
+    }
+}
+
+impl<W: Write + Seek> Write for SeekableWriter<W> {
+    // エラーを返すときは buf を受け取っていない
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.rewind_table()?;
+        if self.frame.len() >= self.frame_size {
+            self.end_frame()?;
+        }
+        self.write_pending()?;
+        let n = buf.len().min(self.frame_size - self.frame.len());
+        self.frame.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    // ここまでのデータをフレームにして書き、シークテーブルを書く（この時点で完結したファイルになる）
+    fn flush(&mut self) -> io::Result<()> {
+        self.rewind_table()?;
+        self.end_frame()?;
+        self.write_pending()?;
+        self.write_table()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + Seek> Drop for SeekableWriter<W> {
+    // BufWriter と同じく、残りを書いてから閉じる（エラーは無視する）
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+fn read_frame<R: Read>(reader: &mut R, compressed: u32, decompressed: u32) -> io::Result<Vec<u8>> {
+    let mut data = vec![0u8; compressed as usize];
+    reader.read_exact(&mut data)?;
+    let frame = zstd::bulk::decompress(&data, decompressed as usize)?;
+    if frame.len() != decompressed as usize {
+        return Err(invalid("a zstd frame does not match the seek table"));
+    }
+    Ok(frame)
+}
+
+// seekable 形式のファイルを展開後の位置で読む。読む位置を含むフレームだけを展開する
+pub struct SeekableReader<R: Read + Seek> {
+    inner: R,
+    table: SeekTable,
+    starts: Vec<(u64, u64)>,
+    len: u64,
+    pos: u64,
+    // 展開済みのフレームの番号と中身
+    current: Option<(usize, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> SeekableReader<R> {
+    // 末尾にシークテーブルがなければ None
+    pub fn new(mut inner: R) -> io::Result<Option<Self>> {
+        let Some(table) = SeekTable::read(&mut inner)? else {
+            return Ok(None);
+        };
+        let starts = table.starts();
+        let len = table.decompressed_len();
+        Ok(Some(SeekableReader { inner, table, starts, len, pos: 0, current: None }))
+    }
+
+    pub fn table(&self) -> &SeekTable {
+        &self.table
+    }
+
+    // 展開後の長さ
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let index = self.starts.partition_point(|&(_, d)| d <= self.pos) - 1;
+        if self.current.as_ref().map(|(i, _)| *i) != Some(index) {
+            let (offset, _) = self.starts[index];
+            let (compressed, decompressed) = self.table.frames[index];
+            self.inner.seek(SeekFrom::Start(offset))?;
+            self.current = Some((index, read_frame(&mut self.inner, compressed, decompressed)?));
+        }
+        let (_, frame) = self.current.as_ref().unwrap();
+        let start = (self.pos - self.starts[index].1) as usize;
+        let n = buf.len().min(frame.len() - start);
+        buf[..n].copy_from_slice(&frame[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len.checked_add_signed(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        };
+        self.pos = target.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))?;
+        Ok(self.pos)
+    }
+}
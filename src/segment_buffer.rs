@@ -0,0 +1,219 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// セグメント用の合成数フラグ配列（0 = 素数候補, 1 = 合成数）。
+// いずれの確保方法でも初期値は 0 なので、明示的な初期化は不要。
+// HugePages は 2 MiB などの大きいページで確保して TLB ミスを減らす。使えなければ黙って
+// 通常のページ（Linux では透過的な大きいページを頼んだ匿名マップ、Windows ではヒープ）にする。
+
+use crate::config::SegmentBufferKind;
+use memmap2::{MmapMut, MmapOptions};
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// FileMmap の一時ファイルの通し番号。同じ出力フォルダーを使うほかのジョブやプロセスと名前が重ならないよう、
+// プロセス ID と合わせてファイル名に入れる
+static NEXT_TEMP_FILE: AtomicU64 = AtomicU64::new(0);
+
+pub enum SegmentBuffer {
+    Heap(Vec<u8>),
+    Mmap(MmapMut),
+    FileMmap(TempFileMap),
+    HugePages(HugePageMap),
+}
+
+// 一時ファイルは drop 時にアンマップしてから削除する
+pub struct TempFileMap {
+    map: Option<MmapMut>,
+    path: PathBuf,
+}
+
+impl SegmentBuffer {
+    pub fn new(kind: &SegmentBufferKind, size: usize, temp_dir: &Path, tag: u64) -> io::Result<Self> {
+        match kind {
+            SegmentBufferKind::Heap => Ok(SegmentBuffer::Heap(vec![0u8; size])),
+            SegmentBufferKind::Mmap => Ok(SegmentBuffer::Mmap(MmapOptions::new().len(size).map_anon()?)),
+            SegmentBufferKind::FileMmap => {
+                let id = NEXT_TEMP_FILE.fetch_add(1, Ordering::Relaxed);
+                let path = temp_dir.join(format!(".sosu_segment_{}_{}_{}.tmp", std::process::id(), id, tag));
+                // 万一同じ名前のファイルがあっても、切り詰めて共有せずに失敗する
+                let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+                file.set_len(size as u64)?;
+                let map = unsafe { MmapOptions::new().len(size).map_mut(&file) };
+                match map {
+                    Ok(map) => Ok(SegmentBuffer::FileMmap(TempFileMap { map: Some(map), path })),
+                    Err(e) => {
+                        let _ = fs::remove_file(&path);
+                        Err(e)
+                    }
+                }
+            }
+            SegmentBufferKind::HugePages => match HugePageMap::new(size) {
+                Some(map) => Ok(SegmentBuffer::HugePages(map)),
+                None => fallback_for_huge_pages(size),
+            },
+        }
+    }
+}
+
+// 大きいページで確保した領域。長さはページの倍数に切り上げて確保し、先頭の len バイトを使う
+#[cfg(target_os = "linux")]
+pub struct HugePageMap {
+    map: MmapMut,
+    len: usize,
+}
+
+#[cfg(target_os = "linux")]
+impl HugePageMap {
+    // 2 MiB のページ。予約された大きいページ（vm.nr_hugepages）がなければ None
+    fn new(size: usize) -> Option<Self> {
+        const PAGE_BITS: u8 = 21;
+        let map = MmapOptions::new().huge(Some(PAGE_BITS)).len(size.next_multiple_of(1 << PAGE_BITS)).map_anon().ok()?;
+        Some(HugePageMap { map, len: size })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.map[..self.len]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.map[..self.len]
+    }
+}
+
+// 予約がなくても、透過的な大きいページが有効ならカーネルが大きいページにまとめられる
+#[cfg(target_os = "linux")]
+fn fallback_for_huge_pages(size: usize) -> io::Result<SegmentBuffer> {
+    let map = MmapOptions::new().len(size).map_anon()?;
+    let _ = map.advise(memmap2::Advice::HugePage);
+    Ok(SegmentBuffer::Mmap(map))
+}
+
+#[cfg(windows)]
+pub struct HugePageMap {
+    ptr: std::ptr::NonNull<u8>,
+    len: usize,
+}
+
+// 確保した領域はこの値だけが持つ
+#[cfg(windows)]
+unsafe impl Send for HugePageMap {}
+
+#[cfg(windows)]
+impl HugePageMap {
+    // 大きいページには「メモリ内のページのロック」の権利（SeLockMemoryPrivilege）が要る。
+    // 権利を有効にできないか、連続した物理メモリが足りなければ None
+    fn new(size: usize) -> Option<Self> {
+        use windows::Win32::System::Memory::{GetLargePageMinimum, VirtualAlloc, MEM_COMMIT, MEM_LARGE_PAGES, MEM_RESERVE, PAGE_READWRITE};
+        static PRIVILEGE: std::sync::Once = std::sync::Once::new();
+        PRIVILEGE.call_once(enable_lock_memory_privilege);
+        let page = unsafe { GetLargePageMinimum() };
+        if page == 0 {
+            return None;
+        }
+        let ptr = unsafe { VirtualAlloc(None, size.next_multiple_of(page), MEM_COMMIT | MEM_RESERVE | MEM_LARGE_PAGES, PAGE_READWRITE) };
+        std::ptr::NonNull::new(ptr.cast::<u8>()).map(|ptr| HugePageMap { ptr, len: size })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for HugePageMap {
+    fn drop(&mut self) {
+        use windows::Win32::System::Memory::{VirtualFree, MEM_RELEASE};
+        let _ = unsafe { VirtualFree(self.ptr.as_ptr().cast(), 0, MEM_RELEASE) };
+    }
+}
+
+// アカウントが権利を持っていなければ何も変わらず、VirtualAlloc が失敗する
+#[cfg(windows)]
+fn enable_lock_memory_privilege() {
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::{CloseHandle, BOOL, HANDLE, LUID};
+    use windows::Win32::Security::{AdjustTokenPrivileges, LookupPrivilegeValueW, LUID_AND_ATTRIBUTES, SE_LOCK_MEMORY_NAME, SE_PRIVILEGE_ENABLED, TOKEN_ADJUST_PRIVILEGES, TOKEN_PRIVILEGES};
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+    unsafe {
+        let mut token = HANDLE::default();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_ADJUST_PRIVILEGES, &mut token).is_err() {
+            return;
+        }
+        let mut luid = LUID::default();
+        if LookupPrivilegeValueW(PCWSTR::null(), SE_LOCK_MEMORY_NAME, &mut luid).is_ok() {
+            let privileges = TOKEN_PRIVILEGES { PrivilegeCount: 1, Privileges: [LUID_AND_ATTRIBUTES { Luid: luid, Attributes: SE_PRIVILEGE_ENABLED }] };
+            let _ = AdjustTokenPrivileges(token, BOOL(0), Some(&privileges as *const TOKEN_PRIVILEGES), 0, None, None);
+        }
+        let _ = CloseHandle(token);
+    }
+}
+
+#[cfg(windows)]
+fn fallback_for_huge_pages(size: usize) -> io::Result<SegmentBuffer> {
+    Ok(SegmentBuffer::Heap(vec![0u8; size]))
+}
+
+// 大きいページを確保する手段がない OS では常にヒープを使う
+#[cfg(not(any(target_os = "linux", windows)))]
+pub enum HugePageMap {}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+impl HugePageMap {
+    fn new(_size: usize) -> Option<Self> {
+        None
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        match *self {}
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        match *self {}
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn fallback_for_huge_pages(size: usize) -> io::Result<SegmentBuffer> {
+    Ok(SegmentBuffer::Heap(vec![0u8; size]))
+}
+
+impl Deref for SegmentBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SegmentBuffer::Heap(v) => v,
+            SegmentBuffer::Mmap(m) => m,
+            SegmentBuffer::FileMmap(t) => t.map.as_deref().unwrap_or(&[]),
+            SegmentBuffer::HugePages(h) => h.as_slice(),
+        }
+    }
+}
+
+impl DerefMut for SegmentBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            SegmentBuffer::Heap(v) => v,
+            SegmentBuffer::Mmap(m) => m,
+            SegmentBuffer::FileMmap(t) => t.map.as_deref_mut().unwrap_or(&mut []),
+            SegmentBuffer::HugePages(h) => h.as_mut_slice(),
+        }
+    }
+}
+
+impl Drop for TempFileMap {
+    fn drop(&mut self) {
+        self.map = None;
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 自己診断。既知の π(x) と、篩・Miller-Rabin・出力の各経路の結果が一致するかを確かめる。
+// 性能の設定（セグメントの大きさ、並列数、バッファ、キャッシュ）は今の設定のまま使うので、
+// 設定を変えた後やソースからビルドした後の確認になる
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use num_bigint::BigUint;
+use num_traits::One;
+use crate::app::{run_worker, ProgressPhase, WorkerMessage};
+use crate::config::{Config, OutputFormat, OverwriteMode, RunMode};
+use crate::error::SieveError;
+use crate::i18n::{format_number, tr};
+use crate::miller_rabin::{check_prime_big, is_prime, verify_prime, BigPrimality, DETERMINISTIC_LIMIT};
+use crate::output::read_primes;
+use crate::pi_report::KNOWN_PI;
+use crate::primes::count_primes;
+use crate::sieve::{segmented_sieve, simple_sieve};
+
+// 数えて確かめる π(10^k) の範囲（10^7 まで）
+const KNOWN_PI_CHECKED: usize = 7;
+
+// 出力を比べる範囲 [1, OUTPUT_LIMIT]
+const OUTPUT_LIMIT: u64 = 1_000_000;
+
+// 篩と Miller-Rabin を比べる大きな数の近くの区間の幅
+const WINDOW: u64 = 100_000;
+
+// 設定、一時ディレクトリ、停止フラグを受け取り、失敗なら理由を返す
+type Check = fn(&Config, &Path, &Arc<AtomicBool>) -> Result<(), String>;
+
+fn checks() -> [(&'static str, Check); 6] {
+    [
+        (tr!("Known pi(x) up to 10^7"), known_counts),
+        (tr!("Simple sieve against Miller-Rabin"), small_primes),
+        (tr!("Segmented sieve against Miller-Rabin near 10^12 and 10^15"), windows),
+        (tr!("Generate with the current performance settings"), generate_output),
+        (tr!("Generate, descending and N-th prime outputs are identical"), output_paths),
+        (tr!("Primality tests above 64 bits"), big_numbers),
+    ]
+}
+
+pub fn run_self_test(config: Config, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_self_test_{}", std::process::id()));
+    let checks = checks();
+    let mut failed = Vec::new();
+    for (i, (name, check)) in checks.iter().enumerate() {
+        if stop_flag.load(Ordering::SeqCst) {
+            fs::remove_dir_all(&dir).ok();
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        let start = Instant::now();
+        let result = check(&config, &dir, &stop_flag);
+        fs::remove_dir_all(&dir).ok();
+        let seconds = format!("{:.2}", start.elapsed().as_secs_f64());
+        match result {
+            Ok(()) => sender.send(WorkerMessage::Log(tr!("PASS {} ({} s)", name, seconds))).ok(),
+            // 停止で途中になった確認は失敗に数えない
+            Err(_) if stop_flag.load(Ordering::SeqCst) => None,
+            Err(reason) => {
+                failed.push(*name);
+                sender.send(WorkerMessage::Log(tr!("FAIL {}: {}", name, reason))).ok()
+            }
+        };
+        sender.send(WorkerMessage::Progress { current: i as u64 + 1, total: checks.len() as u64, phase: ProgressPhase::Testing }).ok();
+    }
+    if stop_flag.load(Ordering::SeqCst) {
+        sender.send(WorkerMessage::Stopped).ok();
+        return Ok(());
+    }
+    if !failed.is_empty() {
+        return Err(SieveError::SelfTest(format!("{} of {} checks failed ({})", failed.len(), checks.len(), failed.join(", "))));
+    }
+    sender.send(WorkerMessage::Log(tr!("Self test passed: all {} checks", checks.len()))).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
+
+fn expect<T: PartialEq + std::fmt::Debug>(what: &str, actual: T, expected: T) -> Result<(), String> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("{}: got {:?}, expected {:?}", what, actual, expected))
+    }
+}
+
+// 素数の列を比べ、違えば個数と最初に違う位置を返す
+fn same_primes(what: &str, actual: &[u64], expected: &[u64]) -> Result<(), String> {
+    if actual == expected {
+        return Ok(());
+    }
+    let index = actual.iter().zip(expected).position(|(a, b)| a != b).unwrap_or(actual.len().min(expected.len()));
+    Err(format!(
+        "{}: {} primes instead of {}, first difference at index {} ({:?} instead of {:?})",
+        what,
+        format_number(actual.len() as u64),
+        format_number(expected.len() as u64),
+        index,
+        actual.get(index),
+        expected.get(index)
+    ))
+}
+
+fn known_counts(_: &Config, _: &Path, _: &Arc<AtomicBool>) -> Result<(), String> {
+    for &(x, pi) in &KNOWN_PI[..KNOWN_PI_CHECKED] {
+        expect(&format!("pi({})", x), count_primes(0, x), pi)?;
+    }
+    Ok(())
+}
+
+fn small_primes(_: &Config, _: &Path, _: &Arc<AtomicBool>) -> Result<(), String> {
+    let limit = KNOWN_PI[4].0;
+    let expected: Vec<u64> = (0..=limit).filter(|&n| is_prime(n)).collect();
+    same_primes(&format!("primes up to {}", limit), &simple_sieve(limit), &expected)
+}
+
+fn windows(_: &Config, _: &Path, stop_flag: &Arc<AtomicBool>) -> Result<(), String> {
+    let small_primes = simple_sieve(32_000_000);
+    for low in [1_000_000_000_000u64, 1_000_000_000_000_000] {
+        let high = low + WINDOW;
+        let sieved = segmented_sieve(&small_primes, low, high, stop_flag);
+        let tested: Vec<u64> = (low..=high).filter(|&n| is_prime(n)).collect();
+        same_primes(&format!("primes in [{}, {}]", low, high), &sieved, &tested)?;
+        if let Some(n) = (low..=high).find(|&n| verify_prime(n) != is_prime(n)) {
+            return Err(format!("verify_prime({}) disagrees with is_prime", n));
+        }
+    }
+    Ok(())
+}
+
+// 出力の比較に使う設定。性能に関わるもの以外は既定値にする
+fn output_config(config: &Config, dir: &Path) -> Config {
+    Config {
+        prime_min: "1".to_string(),
+        prime_max: OUTPUT_LIMIT.to_string(),
+        output_format: OutputFormat::Text,
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        segment_size: config.segment_size,
+        chunk_size: config.chunk_size,
+        writer_buffer_size: config.writer_buffer_size,
+        auto_tuning: config.auto_tuning,
+        parallel_segments: config.parallel_segments,
+        adaptive_segments: config.adaptive_segments,
+        async_writer: config.async_writer,
+        writer_queue_blocks: config.writer_queue_blocks,
+        writer_flush_interval_ms: config.writer_flush_interval_ms,
+        pin_threads: config.pin_threads,
+        segment_buffer: config.segment_buffer.clone(),
+        base_primes_file: config.base_primes_file.clone(),
+        cache_dir: config.cache_dir.clone(),
+        work_dir: config.work_dir.clone(),
+        ..Config::default()
+    }
+}
+
+// 1 回実行して、書いた素数の個数と出力ファイルを返す
+fn run(config: Config, stop_flag: &Arc<AtomicBool>) -> Result<(u64, PathBuf), String> {
+    let (sender, messages) = mpsc::channel();
+    // 指示を送る側がないので、ディスクがいっぱいになったら待たずにエラーになる
+    let (_, commands) = mpsc::channel();
+    run_worker(config, sender, commands, stop_flag.clone()).map_err(|e| e.to_string())?;
+    for message in messages.try_iter() {
+        match message {
+            WorkerMessage::Finished { found, files } => {
+                return files.into_iter().next().map(|file| (found, file)).ok_or_else(|| "no output file".to_string());
+            }
+            WorkerMessage::Error(e) | WorkerMessage::DiskFull(e) => return Err(e),
+            _ => {}
+        }
+    }
+    Err("the run did not finish".to_string())
+}
+
+fn read_all(path: &Path) -> Result<Vec<u64>, String> {
+    let mut primes = Vec::new();
+    read_primes(path, |p| primes.push(p)).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(primes)
+}
+
+fn generate_output(config: &Config, dir: &Path, stop_flag: &Arc<AtomicBool>) -> Result<(), String> {
+    let (found, path) = run(output_config(config, dir), stop_flag)?;
+    expect(&format!("primes written up to {}", OUTPUT_LIMIT), found, KNOWN_PI[5].1)?;
+    same_primes(&format!("primes in {}", path.display()), &read_all(&path)?, &simple_sieve(OUTPUT_LIMIT))
+}
+
+fn output_paths(config: &Config, dir: &Path, stop_flag: &Arc<AtomicBool>) -> Result<(), String> {
+    let ascending = output_config(config, &dir.join("ascending"));
+    let (_, ascending_path) = run(ascending.clone(), stop_flag)?;
+    let bytes = fs::read(&ascending_path).map_err(|e| e.to_string())?;
+
+    // N 番目の素数モードは同じ素数を同じ形式で書く
+    let nth = Config { mode: RunMode::NthPrime, nth_count: KNOWN_PI[5].1, output_dir: dir.join("nth").display().to_string(), ..ascending.clone() };
+    let (_, nth_path) = run(nth, stop_flag)?;
+    if fs::read(&nth_path).map_err(|e| e.to_string())? != bytes {
+        return Err(format!("{} differs from {}", nth_path.display(), ascending_path.display()));
+    }
+
+    // 降順は同じ素数を逆に並べたもの
+    let descending = Config { descending: true, output_dir: dir.join("descending").display().to_string(), ..ascending };
+    let (_, descending_path) = run(descending, stop_flag)?;
+    let mut primes = read_all(&descending_path)?;
+    primes.reverse();
+    same_primes(&format!("primes in {} reversed", descending_path.display()), &primes, &read_all(&ascending_path)?)
+}
+
+fn big_numbers(_: &Config, _: &Path, _: &Arc<AtomicBool>) -> Result<(), String> {
+    let one = BigUint::one();
+    let cases = [
+        // 2^64 + 13 は u64 を超える最小の素数
+        ((&one << 64u32) + 13u32, BigPrimality::Prime),
+        ((&one << 64u32) + 1u32, BigPrimality::Composite),
+        // 最初の 13 個の素数を底にした強擬素数と、最初の 12 個の素数を底にした強擬素数
+        (BigUint::from(DETERMINISTIC_LIMIT), BigPrimality::Composite),
+        (BigUint::from(318_665_857_834_031_151_167_461u128), BigPrimality::Composite),
+        ((&one << 89u32) - 1u32, BigPrimality::ProbablePrime),
+        ((&one << 521u32) - 1u32, BigPrimality::ProbablePrime),
+        (((&one << 89u32) - 1u32) * ((&one << 61u32) - 1u32), BigPrimality::Composite),
+    ];
+    for (n, expected) in cases {
+        expect(&n.to_string(), check_prime_big(&n, 2), expected)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// settings.txt が外部（テキストエディタなど）で書き換えられたことを検知する。
+// エディタは別名で書いてから置き換えることがあるので、ファイルではなくフォルダを監視して名前で絞る
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+pub struct SettingsWatcher {
+    _watcher: RecommendedWatcher,
+    changed: Arc<AtomicBool>,
+}
+
+impl SettingsWatcher {
+    // 変更があるたびに on_change を呼ぶ（監視スレッドから呼ばれる）
+    pub fn start(path: &Path, on_change: impl Fn() + Send + 'static) -> notify::Result<Self> {
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("."));
+        let name = path.file_name().map(|n| n.to_os_string());
+        let changed = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&changed);
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else { return };
+            let relevant = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                && event.paths.iter().any(|p| p.file_name().map(|n| n.to_os_string()) == name);
+            if relevant {
+                flag.store(true, Ordering::SeqCst);
+                on_change();
+            }
+        })?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+        Ok(SettingsWatcher { _watcher: watcher, changed })
+    }
+
+    // 前回の呼び出しから変更があったか
+    pub fn take_change(&self) -> bool {
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+}
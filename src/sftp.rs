@@ -0,0 +1,266 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// SFTP サーバーへのアップロード（sftp_url）。書き終えた出力ファイルを sftp://user@host:port/path の
+// フォルダーに送り、送り終えたら中身の SHA-256 を隣の「名前.sha256」に置く（WebDAV と同じく、
+// 大きさと SHA-256 が一致するときだけ送らずに飛ばす）。接続が切れたら間隔をあけてつなぎ直し、
+// サーバーにある分の続きから書く。サーバーのホスト鍵は known_hosts にあるものしか受け付けない。
+// パスワードや鍵は設定ファイルではなく環境変数 SFTP_PASSWORD / SFTP_PRIVATE_KEY から読む
+// （どちらもなければ ssh-agent を使う）
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use ssh2::{CheckResult, KnownHostFileKind, OpenFlags, OpenType, Session, Sftp};
+use crate::config::Config;
+use crate::s3::{sha256_file, Destination, Uploaded, FIRST_RETRY_DELAY, MAX_ATTEMPTS};
+use crate::webdav::HASH_SUFFIX;
+
+const DEFAULT_PORT: u16 = 22;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+// 応答を待つ時間（ミリ秒）
+const SESSION_TIMEOUT_MS: u32 = 300_000;
+
+// sftp_url を分けたもの
+#[derive(Debug, Clone, PartialEq)]
+pub struct SftpTarget {
+    pub user: String,
+    pub host: String,
+    pub port: u16,
+    // サーバー上のフォルダー（URL にパスがなければ "."、つまりログインしたフォルダー）
+    pub dir: String,
+}
+
+impl SftpTarget {
+    // sftp://user@host[:port][/path]。user は省けない
+    pub fn parse(url: &str) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid SFTP URL {}", url));
+        let rest = url.trim().strip_prefix("sftp://").ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+        let (user, host) = authority.rsplit_once('@').ok_or_else(invalid)?;
+        // IPv6 のアドレスは [::1]:22 のように括弧でくくる
+        let (host, port) = match host.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, after) = bracketed.split_once(']').ok_or_else(invalid)?;
+                (host, after.strip_prefix(':'))
+            }
+            None => match host.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port.parse().map_err(|_| invalid())?,
+            None => DEFAULT_PORT,
+        };
+        if user.is_empty() || host.is_empty() {
+            return Err(invalid());
+        }
+        let dir = path.trim_end_matches('/');
+        let dir = if dir.is_empty() { "." } else { dir };
+        Ok(SftpTarget { user: user.to_string(), host: host.to_string(), port, dir: dir.to_string() })
+    }
+
+    pub fn remote_path(&self, path: &Path) -> String {
+        format!("{}/{}", self.dir, path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default())
+    }
+
+    pub fn url_for(&self, path: &Path) -> String {
+        format!("sftp://{}@{}:{}/{}", self.user, self.host, self.port, self.remote_path(path).trim_start_matches('/'))
+    }
+}
+
+// 認証の方法
+enum Auth {
+    Password(String),
+    // 鍵ファイルとそのパスフレーズ
+    Key(PathBuf, Option<String>),
+    Agent,
+}
+
+pub struct SftpClient {
+    target: SftpTarget,
+    auth: Auth,
+    known_hosts: PathBuf,
+    // 前のファイルで使った接続（切れたら None に戻してつなぎ直す）
+    connection: Mutex<Option<(Session, Sftp)>>,
+}
+
+// 認証やホスト鍵の誤りは再試行しても直らない
+fn fatal(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, message)
+}
+
+impl SftpClient {
+    pub fn new(target: SftpTarget, password: Option<String>, private_key: Option<PathBuf>, known_hosts: PathBuf) -> Self {
+        let auth = match (private_key, password) {
+            (Some(key), passphrase) => Auth::Key(key, passphrase),
+            (None, Some(password)) => Auth::Password(password),
+            (None, None) => Auth::Agent,
+        };
+        SftpClient { target, auth, known_hosts, connection: Mutex::new(None) }
+    }
+
+    // sftp_url が空なら None。SFTP_PRIVATE_KEY があれば鍵（SFTP_PASSWORD はそのパスフレーズ）、
+    // なければ SFTP_PASSWORD、どちらもなければ ssh-agent で認証する。known_hosts は
+    // SFTP_KNOWN_HOSTS か ~/.ssh/known_hosts
+    pub fn from_config(config: &Config) -> io::Result<Option<Self>> {
+        let url = config.sftp_url.trim();
+        if url.is_empty() {
+            return Ok(None);
+        }
+        let target = SftpTarget::parse(url)?;
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let known_hosts = match var("SFTP_KNOWN_HOSTS") {
+            Some(path) => PathBuf::from(path),
+            None => dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts")).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home folder for ~/.ssh/known_hosts; set SFTP_KNOWN_HOSTS"))?,
+        };
+        Ok(Some(SftpClient::new(target, var("SFTP_PASSWORD"), var("SFTP_PRIVATE_KEY").map(PathBuf::from), known_hosts)))
+    }
+
+    // サーバーのホスト鍵が known_hosts にあって一致するか確かめる
+    fn check_host_key(&self, session: &Session) -> io::Result<()> {
+        let SftpTarget { host, port, .. } = &self.target;
+        let mut known = session.known_hosts()?;
+        known.read_file(&self.known_hosts, KnownHostFileKind::OpenSSH)
+            .map_err(|e| fatal(format!("cannot read {}: {}", self.known_hosts.display(), e)))?;
+        let (key, _) = session.host_key().ok_or_else(|| fatal(format!("{} sent no host key", host)))?;
+        match known.check_port(host, *port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::NotFound => Err(fatal(format!("the host key of {} is not in {}; connect once with ssh to add it", host, self.known_hosts.display()))),
+            CheckResult::Mismatch => Err(fatal(format!("the host key of {} does not match {}", host, self.known_hosts.display()))),
+            CheckResult::Failure => Err(fatal(format!("cannot check the host key of {}", host))),
+        }
+    }
+
+    fn connect(&self) -> io::Result<(Session, Sftp)> {
+        let SftpTarget { user, host, port, dir } = &self.target;
+        let address = (host.as_str(), *port).to_socket_addrs()?.next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", host)))?;
+        let tcp = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        let mut session = Session::new()?;
+        session.set_tcp_stream(tcp);
+        session.set_timeout(SESSION_TIMEOUT_MS);
+        session.handshake()?;
+        self.check_host_key(&session)?;
+        let result = match &self.auth {
+            Auth::Password(password) => session.userauth_password(user, password),
+            Auth::Key(key, passphrase) => session.userauth_pubkey_file(user, None, key, passphrase.as_deref()),
+            Auth::Agent => session.userauth_agent(user),
+        };
+        if let Err(e) = result {
+            return Err(fatal(format!("authentication as {} failed: {}", user, e)));
+        }
+        let sftp = session.sftp()?;
+        // フォルダーがなければ作る（親のフォルダーは作らない）
+        if sftp.stat(Path::new(dir)).is_err() {
+            sftp.mkdir(Path::new(dir), 0o755)?;
+        }
+        Ok((session, sftp))
+    }
+
+    // 接続して op を行う。接続が切れるなどして失敗したら、つなぎ直して op をやり直す
+    fn retry<T>(&self, mut op: impl FnMut(&Sftp) -> io::Result<T>) -> io::Result<T> {
+        let mut delay = FIRST_RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            let mut connection = self.connection.lock().unwrap_or_else(PoisonError::into_inner);
+            let result = match connection.as_ref() {
+                Some((_, sftp)) => op(sftp),
+                None => self.connect().and_then(|(session, sftp)| {
+                    let result = op(&sftp);
+                    *connection = Some((session, sftp));
+                    result
+                }),
+            };
+            let error = match result {
+                Ok(value) => return Ok(value),
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return Err(e),
+                Err(e) => e,
+            };
+            *connection = None;
+            drop(connection);
+            if attempt >= MAX_ATTEMPTS {
+                return Err(error);
+            }
+            std::thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+
+    // ファイルを書く。サーバーにすでにある分は送らず、その続きから書く
+    fn write_file(sftp: &Sftp, remote: &Path, path: &Path, len: u64) -> io::Result<()> {
+        let offset = sftp.stat(remote).ok().and_then(|stat| stat.size).filter(|&size| size <= len).unwrap_or(0);
+        let mut flags = OpenFlags::WRITE | OpenFlags::CREATE;
+        if offset == 0 {
+            flags |= OpenFlags::TRUNCATE;
+        }
+        let mut out = sftp.open_mode(remote, flags, 0o644, OpenType::File)?;
+        out.seek(SeekFrom::Start(offset))?;
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buffer = vec![0u8; 256 * 1024];
+        loop {
+            match file.read(&mut buffer)? {
+                0 => break,
+                n => out.write_all(&buffer[..n])?,
+            }
+        }
+        out.flush()?;
+        drop(out);
+        match sftp.stat(remote)?.size {
+            Some(size) if size == len => Ok(()),
+            size => Err(io::Error::other(format!("{} has {} bytes on the server, expected {}", remote.display(), size.unwrap_or(0), len))),
+        }
+    }
+
+    fn read_hash(sftp: &Sftp, remote: &Path) -> Option<String> {
+        let mut text = String::new();
+        sftp.open(remote).ok()?.read_to_string(&mut text).ok()?;
+        Some(text.trim().to_string())
+    }
+
+    // ファイルをアップロードする。大きさと SHA-256 が同じファイルがすでにあれば送らない。
+    // 送る前に古いファイルと SHA-256 を消すので、続きから書くときにサーバーにあるのは
+    // このアップロードで送った分だけになる
+    pub fn upload(&self, path: &Path) -> io::Result<Uploaded> {
+        let url = self.target.url_for(path);
+        let remote = PathBuf::from(self.target.remote_path(path));
+        let hash_remote = PathBuf::from(format!("{}{}", remote.display(), HASH_SUFFIX));
+        let len = std::fs::metadata(path)?.len();
+        let hash = sha256_file(path)?;
+        let same = self.retry(|sftp| {
+            let size = sftp.stat(&remote).ok().and_then(|stat| stat.size);
+            Ok(size == Some(len) && Self::read_hash(sftp, &hash_remote).as_deref() == Some(hash.as_str()))
+        })?;
+        if same {
+            return Ok(Uploaded::Skipped(url));
+        }
+        self.retry(|sftp| {
+            for old in [&hash_remote, &remote] {
+                if sftp.stat(old).is_ok() {
+                    sftp.unlink(old)?;
+                }
+            }
+            Ok(())
+        })?;
+        self.retry(|sftp| Self::write_file(sftp, &remote, path, len))?;
+        self.retry(|sftp| sftp.create(&hash_remote)?.write_all(hash.as_bytes()))?;
+        Ok(Uploaded::Sent(url))
+    }
+}
+
+impl Destination for SftpClient {
+    fn upload(&self, path: &Path) -> io::Result<Uploaded> {
+        SftpClient::upload(self, path)
+    }
+}
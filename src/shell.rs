@@ -0,0 +1,27 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// ファイルやフォルダを OS の既定のアプリ（エクスプローラー、Finder など）で開く
+
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+pub fn open_path(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display())));
+    }
+    let program = if cfg!(windows) {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    let mut child = Command::new(program).arg(path).spawn()?;
+    // 終了は待たずに回収だけ行う（explorer は成功しても 1 を返すことがある）
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
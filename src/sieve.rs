@@ -5,14 +5,51 @@
 
 use std::sync::{mpsc,Arc};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::io::{BufWriter, Write};
-use std::fs::{OpenOptions, create_dir_all};
-use std::path::Path;
-use std::time::Instant;
-use crate::config::{Config, OutputFormat};
-use crate::app::WorkerMessage;
-
-fn integer_sqrt(n: u64) -> u64 {
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::config::{Config, OverwriteMode, RunMode, SegmentBufferKind};
+use crate::segment_buffer::SegmentBuffer;
+use crate::error::SieveError;
+use crate::output::{existing_outputs, is_disk_full, read_primes, PrimeSink, PrimeWriter, ZstdOptions, MAX_RADIX, MIN_RADIX};
+use crate::gaps::{self, GapAnalyzer};
+use crate::filter::{DigitExpr, DigitFilter, ResidueFilter, ScriptFilter};
+use crate::script::{FilterScript, ScriptSink, MAX_SCRIPT_PRIME};
+use crate::race::PrimeRace;
+use crate::aggregates::{Aggregates, MAX_AGGREGATE_MODULUS};
+use crate::pi_report::{self, PiCheckpoints, PiTracker};
+use crate::primes::count_primes_until;
+use crate::archive::ArchiveSink;
+use crate::s3::UploadSink;
+use crate::pipe::PipeSink;
+use crate::database::{redact_url, DbSink};
+use crate::base_primes::load_small_primes;
+use crate::cache::DiskCache;
+use crate::metadata::{plan_output, ranges_to_sieve, record_output, require_decimal_text, Fingerprint, OutputPlan};
+use crate::app::{ProgressPhase, WorkerCommand, WorkerMessage};
+use crate::monitor::{TemperatureSensor, ThermalThrottle};
+use crate::power::lower_current_thread_priority;
+use crate::i18n::{format_number, tr};
+use rayon::prelude::*;
+
+// 剰余類フィルタの法の上限（剰余類ごとの集計表の大きさ）
+const MAX_RESIDUE_MODULUS: u64 = 1_000_000;
+
+// これより短い篩の時間は速さの測定結果として保存しない
+const MIN_CALIBRATION_SECS: f64 = 1.0;
+
+// セグメントの篩で停止フラグを確認する間隔（配列への書き込み・読み出しの回数）。
+// キャッシュに載らない書き込みばかりでも数十ミリ秒で、停止までの遅れを 200ms 未満に保つ
+const STOP_CHECK_WORK: u64 = 1 << 20;
+
+// セグメントの篩で小さい素数の印をまとめて付ける範囲（数の個数 = バイト数）。
+// L1 キャッシュに載る大きさにして、すべての小さい素数の印を付けてから次のチャンクへ進む
+const SIEVE_CHUNK: u64 = 1 << 15;
+
+// 書き込みの進捗を知らせる間隔（素数の個数）
+const WRITE_PROGRESS_INTERVAL: u64 = 1 << 16;
+
+pub(crate) fn integer_sqrt(n: u64) -> u64 {
     let mut low = 0u64;
     let mut high = n;
     while low <= high {
@@ -26,73 +63,218 @@ fn integer_sqrt(n: u64) -> u64 {
     high
 }
 
-pub fn run_program_old(config: Config, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(),Box<dyn std::error::Error>> {
-    sender.send(WorkerMessage::Log("Running old method (Sieve) with parallelization".to_string())).ok();
+pub fn run_program_old(config: Config, sender: mpsc::Sender<WorkerMessage>, commands: mpsc::Receiver<WorkerCommand>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    sender.send(WorkerMessage::Log(tr!("Running old method (Sieve) with parallelization").to_string())).ok();
 
     let prime_min = config.prime_min.parse::<u64>()?;
     let prime_max = config.prime_max.parse::<u64>()?;
+    if prime_min > prime_max {
+        return Err(SieveError::Config("prime_min must not exceed prime_max".to_string()));
+    }
+    if config.segment_size == 0 {
+        return Err(SieveError::Config("segment_size must be greater than 0".to_string()));
+    }
+    if config.residue_modulus > MAX_RESIDUE_MODULUS {
+        return Err(SieveError::Config(format!("residue_modulus must not exceed {}", MAX_RESIDUE_MODULUS)));
+    }
+    if config.residue_modulus > 0 && config.residues.is_empty() {
+        return Err(SieveError::Config("residues must not be empty when residue_modulus is set".to_string()));
+    }
+    let filters = parse_filters(&config)?;
+    if config.race_modulus > 0 && config.race_a % config.race_modulus == config.race_b % config.race_modulus {
+        return Err(SieveError::Config("race_a and race_b must be different residue classes".to_string()));
+    }
+    if config.aggregate_modulus > MAX_AGGREGATE_MODULUS {
+        return Err(SieveError::Config(format!("aggregate_modulus must not exceed {}", MAX_AGGREGATE_MODULUS)));
+    }
 
-    let root = integer_sqrt(prime_max) + 1;
-    let small_primes = simple_sieve(root);
-
-    let segment_size = config.segment_size as u64;
-    let mut segments = Vec::new();
-    {
-        let mut start = prime_min;
-        while start <= prime_max {
-            let end = (start + segment_size -1).min(prime_max);
-            segments.push((start, end));
-            start = end + 1;
+    // 追記モードでは既存の出力のメタデータを見て、篩い終えた範囲の続きから篩う
+    let plan = plan_output(&config, prime_min, prime_max, &sender);
+    let prime_min = match &plan {
+        OutputPlan::Continue { start, .. } => *start,
+        OutputPlan::Covered => {
+            sender.send(WorkerMessage::Finished { found: 0, files: Vec::new() }).ok();
+            sender.send(WorkerMessage::Done).ok();
+            return Ok(());
         }
+        OutputPlan::Fresh | OutputPlan::Untracked => prime_min,
+    };
+    // ほかの出力との重なりを警告し、skip_covered_ranges なら重なる部分を飛ばす
+    let ranges = ranges_to_sieve(&config, prime_min, prime_max, &sender);
+    let (Some(&(prime_min, _)), Some(&(_, prime_max))) = (ranges.first(), ranges.last()) else {
+        sender.send(WorkerMessage::Finished { found: 0, files: Vec::new() }).ok();
+        sender.send(WorkerMessage::Done).ok();
+        return Ok(());
+    };
+    // 区間ごとの統計とギャップは昇順の途切れない範囲を前提にするので、飛ばした範囲があるときと降順では行わない
+    let contiguous = ranges.len() == 1 && !config.descending;
+    if !contiguous && (config.stats_interval > 0 || config.gap_analysis || config.race_modulus > 0 || config.pi_report) {
+        let message = if config.descending {
+            tr!("Interval statistics, gap analysis, prime races and the pi(x) report are skipped in descending order")
+        } else {
+            tr!("Interval statistics, gap analysis, prime races and the pi(x) report are skipped because the range has gaps")
+        };
+        sender.send(WorkerMessage::Log(message.to_string())).ok();
+    }
+
+    if config.background_mode {
+        match lower_current_thread_priority() {
+            Ok(()) => sender.send(WorkerMessage::Log(tr!("Background mode: lowered worker priority (throttle {}%)", config.throttle_percent))).ok(),
+            Err(e) => sender.send(WorkerMessage::Log(tr!("Background mode: failed to lower priority: {}", e))).ok(),
+        };
     }
+    let throttle_percent = if config.background_mode { config.throttle_percent.min(90) } else { 0 };
+
+    let root = integer_sqrt(prime_max) + 1;
+    // base_primes_file の指定がなければキャッシュディレクトリの小さい素数を使う
+    let cache = DiskCache::open(&config);
+    let small_primes = match &cache {
+        Some(cache) if config.base_primes_file.trim().is_empty() => cache.base_primes(root, &sender),
+        _ => load_small_primes(&config.base_primes_file, root, &sender),
+    };
+
+    // 降順では上のセグメントから篩う
+    let mut scheduler = SegmentScheduler::new(&ranges, config.segment_size, config.descending, config.adaptive_segments);
 
-    let writer_buffer_size = config.writer_buffer_size;
     let start_time = Instant::now();
-    let total_range = prime_max - prime_min + 1;
-    let output_format = config.output_format.clone();
-    let split_count = config.split_count;
+    let total_range: u64 = ranges.iter().map(|&(low, high)| high - low + 1).sum();
 
     if !config.output_dir.is_empty() {
-        create_dir_all(&config.output_dir)?;
+        create_dir_all(&config.output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(&config.output_dir), source })?;
     }
 
-    // 全てのセグメントを逐次処理し、その都度進捗とETAを通知
+    let pool = build_thread_pool(&config)?;
+    if config.pin_threads {
+        sender.send(WorkerMessage::Log(tr!("Pinned {} worker threads to CPU cores", pool.current_num_threads()))).ok();
+    }
+    let batch_size = parallel_segments(&config, &pool);
+    // CPU が熱くなったら並列に篩うセグメントを減らす
+    let mut thermal = (config.thermal_limit > 0).then(TemperatureSensor::new).flatten().map(|sensor| (sensor, ThermalThrottle::new(config.thermal_limit, batch_size)));
+    if config.thermal_limit > 0 && thermal.is_none() {
+        sender.send(WorkerMessage::Log(tr!("CPU temperature is not available on this system; the temperature limit is ignored").to_string())).ok();
+    }
+    let mut thermal_throttled = false;
+    if let Some(calibration) = cache.as_ref().and_then(|c| c.calibration(pool.current_num_threads(), config.segment_size, prime_max)) {
+        let estimate = format_duration((total_range as f64 / calibration.numbers_per_sec).round() as u64);
+        sender.send(WorkerMessage::Log(tr!("Estimated sieving time from previous runs: {}", estimate))).ok();
+        sender.send(WorkerMessage::Eta(estimate)).ok();
+    }
+    let temp_dir = if config.output_dir.is_empty() { Path::new(".").to_path_buf() } else { Path::new(&config.output_dir).to_path_buf() };
+    if config.segment_buffer != SegmentBufferKind::Heap {
+        sender.send(WorkerMessage::Log(tr!("Segment buffers: {}", format!("{:?}", config.segment_buffer)))).ok();
+    }
+
+    // ワーカー数ぶんのセグメントをまとめて並列処理し、バッチ毎に進捗とETAを通知
     let mut all_primes: Vec<u64> = Vec::new();
     let mut processed = 0u64; // 処理済みレンジ数
-    for (low, high) in segments.into_iter() {
+    let mut interval_counter = (contiguous && config.stats_interval > 0).then(|| IntervalCounter::new(prime_min, prime_max, config.stats_interval));
+    let mut race = (contiguous && config.race_modulus > 0).then(|| PrimeRace::new(config.race_modulus, config.race_a, config.race_b));
+    // 和や個数は順序によらないので、降順や飛ばした範囲があっても篩った素数すべてで求める
+    let mut aggregates = config.aggregates.then(|| Aggregates::new(config.aggregate_modulus));
+    // π(x) には prime_min 未満の素数も要るので、先に数えておく。prime_min が大きいと時間がかかるので、
+    // 停止されたら数えるのをやめる（下のループがすぐに停止を扱う）
+    let mut pi_tracker = None;
+    if contiguous && config.pi_report {
+        if prime_min > 2 {
+            sender.send(WorkerMessage::Log(tr!("Counting the primes below {} for the pi(x) report", format_number(prime_min)))).ok();
+        }
+        pi_tracker = count_primes_until(0, prime_min.saturating_sub(1), &stop_flag)
+            .map(|below| PiTracker::new(prime_min, prime_max, config.stats_interval, below));
+    }
+    // 既知の π(10^k) と比べられるのは 2 以下から昇順に途切れず篩うときだけ
+    let mut checkpoints = (config.pi_checkpoints && contiguous && prime_min <= 2).then(PiCheckpoints::new);
+    if config.pi_checkpoints && checkpoints.is_none() {
+        sender.send(WorkerMessage::Log(tr!("Known pi(10^k) checks are skipped because the range does not start at 2 or below in ascending order").to_string())).ok();
+    }
+    // セグメントの総数。適応的なセグメントでは大きくなるたびに減る
+    let mut segments_done = 0u64;
+    let total_segments = scheduler.remaining();
+    sender.send(WorkerMessage::Log(tr!("Sieving in {} segments", format_number(total_segments)))).ok();
+    sender.send(WorkerMessage::Segments { done: 0, total: total_segments }).ok();
+    loop {
+        if let Some((sensor, throttle)) = thermal.as_mut() {
+            if let Some((temperature, count)) = sensor.read().and_then(|t| Some((t, throttle.update(t)?))) {
+                sender.send(WorkerMessage::Log(tr!("CPU at {} °C: sieving {} of {} segments at once", temperature.round(), count, batch_size))).ok();
+                thermal_throttled |= count < batch_size;
+            }
+        }
+        let batch = scheduler.next_batch(thermal.as_ref().map_or(batch_size, |(_, throttle)| throttle.current));
+        if batch.is_empty() {
+            break;
+        }
         if stop_flag.load(Ordering::SeqCst) {
             sender.send(WorkerMessage::Stopped).ok();
             return Ok(());
         }
-        let primes_in_segment = segmented_sieve(&small_primes, low, high, &stop_flag);
+        let batch_start = Instant::now();
+        // ビット配列は各ワーカー内で確保・初期化するため、ピン留め時はそのコアの NUMA ノードに配置される
+        let results: Result<Vec<Vec<u64>>, std::io::Error> = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|&(low, high)| {
+                    let mut buffer = SegmentBuffer::new(&config.segment_buffer, (high - low + 1) as usize, &temp_dir, low)?;
+                    Ok(segmented_sieve_in(&mut buffer, &small_primes, low, high, &stop_flag))
+                })
+                .collect()
+        });
+        let results = results.map_err(SieveError::SegmentBuffer)?;
+        if let Some(size) = scheduler.record(batch_start.elapsed()) {
+            sender.send(WorkerMessage::Log(tr!("Adaptive segments: segment size {}", format_number(size)))).ok();
+        }
+        if throttle_percent > 0 {
+            throttle_sleep(batch_start.elapsed(), throttle_percent, &stop_flag);
+        }
         if stop_flag.load(Ordering::SeqCst) {
             sender.send(WorkerMessage::Stopped).ok();
             return Ok(());
         }
 
-        // セグメント範囲ぶん処理完了
-        processed += high - low + 1;
+        // バッチ範囲ぶん処理完了
+        processed += batch.iter().map(|&(low, high)| high - low + 1).sum::<u64>();
 
         // 進捗とETA計算
-        let progress = processed as f64 / total_range as f64;
-        let elapsed = start_time.elapsed().as_secs_f64();
-        let eta = if progress > 0.0 {
-            let total_time = elapsed / progress;
-            let remaining = total_time - elapsed;
-            let remaining_sec = remaining.round() as u64;
-            let hours = remaining_sec / 3600;
-            let minutes = (remaining_sec % 3600) / 60;
-            let seconds = remaining_sec % 60;
-            format!("{} hour {} min {} sec", hours, minutes, seconds)
-        } else {
-            "Calculating...".to_string()
-        };
+        let eta = format_eta(start_time, processed as f64 / total_range as f64);
 
-        sender.send(WorkerMessage::Progress { current: processed, total: total_range}).ok();
+        sender.send(WorkerMessage::Progress { current: processed, total: total_range, phase: ProgressPhase::Sieving }).ok();
         sender.send(WorkerMessage::Eta(eta)).ok();
+        segments_done += batch.len() as u64;
+        sender.send(WorkerMessage::Segments { done: segments_done, total: segments_done + scheduler.remaining() }).ok();
 
         // 素数を蓄積
-        all_primes.extend(primes_in_segment);
+        for (&(low, high), primes_in_segment) in batch.iter().zip(results) {
+            sender.send(WorkerMessage::SegmentDone { low, high, primes: primes_in_segment.len() as u64 }).ok();
+            if let Some(counter) = interval_counter.as_mut() {
+                counter.add_segment(&primes_in_segment, high, &sender);
+            }
+            if let Some(race) = race.as_mut() {
+                race.add_segment(&primes_in_segment, high, &sender);
+            }
+            if let Some(aggregates) = aggregates.as_mut() {
+                aggregates.add_segment(&primes_in_segment);
+            }
+            if let Some(tracker) = pi_tracker.as_mut() {
+                tracker.add_segment(&primes_in_segment, high);
+            }
+            if let Some(checkpoints) = checkpoints.as_mut() {
+                match checkpoints.add_segment(&primes_in_segment, high) {
+                    Ok(passed) => {
+                        for (x, pi) in passed {
+                            sender.send(WorkerMessage::Log(tr!("pi({}) = {} matches the known value", format_number(x), format_number(pi)))).ok();
+                        }
+                    }
+                    Err((x, pi, expected)) => {
+                        return Err(SieveError::Miscount(tr!("pi({}) = {}, but the known value is {}", format_number(x), format_number(pi), format_number(expected))));
+                    }
+                }
+            }
+            // セグメントは出力の順に篩うので、降順ではセグメントの中だけ逆にすれば全体を並べ替えずに済む
+            let mut primes_in_segment = primes_in_segment;
+            if config.descending {
+                primes_in_segment.reverse();
+            }
+            all_primes.extend(primes_in_segment);
+        }
     }
 
     if stop_flag.load(Ordering::SeqCst) {
@@ -100,100 +282,529 @@ pub fn run_program_old(config: Config, sender: mpsc::Sender<WorkerMessage>, stop
         return Ok(());
     }
 
-    // 全素数をソート
-    all_primes.sort_unstable();
+    if let Some(counter) = interval_counter.as_mut() {
+        counter.flush(&sender);
+    }
+    if let Some(race) = &race {
+        sender.send(WorkerMessage::Log(race.summary())).ok();
+    }
+    if let Some(tracker) = pi_tracker {
+        finish_pi_report(tracker, Path::new(&config.output_dir), &sender)?;
+    }
+
+    // 篩の速さを記録する（短すぎる実行や間引き中・温度で並列数を減らした実行は測定にならないので除く）
+    let sieve_time = start_time.elapsed().as_secs_f64();
+    if let Some(cache) = cache.as_ref().filter(|_| sieve_time >= MIN_CALIBRATION_SECS && throttle_percent == 0 && !thermal_throttled) {
+        if let Err(e) = cache.store_calibration(pool.current_num_threads(), config.segment_size, prime_max, total_range as f64 / sieve_time) {
+            sender.send(WorkerMessage::Log(tr!("Failed to save calibration data: {}", e))).ok();
+        }
+    }
 
     // 書き込み開始
     let mut found_count = 0u64;
-    let mut current_prime_count_in_file = 0u64;
-    let mut file_index = 1;
-
-    let open_file = |index: usize| {
-        let base_name = match output_format {
-            OutputFormat::Text => "primes",
-            OutputFormat::CSV  => "primes",
-            OutputFormat::JSON => "primes",
-        };
-        let file_ext = match output_format {
-            OutputFormat::Text => "txt",
-            OutputFormat::CSV  => "csv",
-            OutputFormat::JSON => "json",
-        };
+    let mut fingerprint = Fingerprint::default();
+    let mut out = open_output(&config, filters, &sender)?;
 
-        let file_name = if split_count > 0 {
-            format!("{}_{}.{}", base_name, index, file_ext)
-        } else {
-            format!("{}.{}", base_name, file_ext)
-        };
+    let mut gap_analyzer = (contiguous && config.gap_analysis).then(|| GapAnalyzer::new(prime_min, config.stats_interval));
 
-        let full_path = Path::new(&config.output_dir).join(file_name);
-        let file = OpenOptions::new().create(true).truncate(true).write(true).open(&full_path).unwrap();
-        BufWriter::with_capacity(writer_buffer_size, file)
-    };
+    // 全書き込み処理。篩の進捗は 100% のままなので、書き込みの進捗を別に知らせる
+    let total_primes = all_primes.len() as u64;
+    sender.send(WorkerMessage::Log(tr!("Writing {} primes", format_number(total_primes)))).ok();
+    sender.send(WorkerMessage::Progress { current: 0, total: total_primes, phase: ProgressPhase::Writing }).ok();
+    for (i, &p) in all_primes.iter().enumerate() {
+        if stop_flag.load(Ordering::SeqCst) {
+            keep_stopped_output(&mut out, plan, &config, &ranges, &all_primes[..i], &sender);
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+
+        if !retry_on_disk_full(&mut out, &sender, &commands, &stop_flag, |out| out.write_prime(p))? {
+            return Ok(());
+        }
+
+        found_count += 1;
+        fingerprint.add(p);
+        sender.send(WorkerMessage::FoundPrimeIndex(p, found_count)).ok();
+        if found_count.is_multiple_of(WRITE_PROGRESS_INTERVAL) {
+            sender.send(WorkerMessage::Progress { current: found_count, total: total_primes, phase: ProgressPhase::Writing }).ok();
+        }
+        if let Some(analyzer) = gap_analyzer.as_mut() {
+            analyzer.push(p);
+        }
+    }
+    sender.send(WorkerMessage::Progress { current: found_count, total: total_primes, phase: ProgressPhase::Writing }).ok();
+
+    if !retry_on_disk_full(&mut out, &sender, &commands, &stop_flag, |out| out.finish())? {
+        return Ok(());
+    }
+
+    record_output(plan, &config, &ranges, found_count, fingerprint, &out.written_files(), &sender);
+
+    if let Some(analyzer) = gap_analyzer {
+        finish_gap_report(analyzer, Path::new(&config.output_dir), &sender)?;
+    }
+
+    // 処理完了メッセージ
+    sender.send(WorkerMessage::Eta(tr!("0 hour 0 min 0 sec").to_string())).ok();
+
+    let script_filter = out.get_ref();
+    let residue_filter = script_filter.get_ref();
+    if residue_filter.is_active() {
+        sender.send(WorkerMessage::Log(residue_filter.summary())).ok();
+    }
+    if residue_filter.is_active() || script_filter.is_active() || out.is_active() {
+        sender.send(WorkerMessage::Log(tr!("Primes written after filters: {}", format_number(residue_filter.written())))).ok();
+    }
+    if let Some(aggregates) = aggregates {
+        for line in aggregates.summary_lines() {
+            sender.send(WorkerMessage::Log(line)).ok();
+        }
+        sender.send(WorkerMessage::Aggregates(aggregates)).ok();
+    }
+    sender.send(WorkerMessage::Log(tr!("Finished old method. Total primes found: {}", format_number(found_count)))).ok();
+    sender.send(WorkerMessage::Finished { found: found_count, files: out.written_files() }).ok();
+    sender.send(WorkerMessage::Done).ok();
 
-    let mut writer = open_file(file_index);
-    let mut first_item = true;
-    if let OutputFormat::JSON = output_format {
-        write!(writer, "[").unwrap();
+    Ok(())
+}
+
+// 既存の出力ファイル（現在の出力形式・分割設定のもの）を読み込んでギャップを解析する
+pub fn analyze_existing_output(config: Config, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let dir = Path::new(&config.output_dir);
+    let files = existing_outputs(dir, &config.output_format, config.zstd_seekable, config.split_count);
+    if files.is_empty() {
+        return Err(SieveError::Config(format!("No {:?} output files found in {}", config.output_format, dir.display())));
+    }
+    for path in &files {
+        require_decimal_text(path, Some((&config.output_format, config.output_radix)))?;
     }
+    sender.send(WorkerMessage::Log(tr!("Analyzing gaps in {} file(s)", files.len()))).ok();
 
-    // 全書き込み処理
-    for &p in &all_primes {
+    let prime_min = config.prime_min.parse::<u64>().unwrap_or(0);
+    let mut analyzer = GapAnalyzer::new(prime_min, config.stats_interval);
+    let mut last = None;
+    for (i, path) in files.iter().enumerate() {
         if stop_flag.load(Ordering::SeqCst) {
             sender.send(WorkerMessage::Stopped).ok();
             return Ok(());
         }
+        let mut unsorted = false;
+        read_primes(path, |p| {
+            unsorted |= last.is_some_and(|last| p <= last);
+            last = Some(p);
+            analyzer.push(p);
+        })
+        .map_err(|source| SieveError::OpenFile { path: path.clone(), source })?;
+        if unsorted {
+            return Err(SieveError::Config(format!("{} is not in ascending order", path.display())));
+        }
+        sender.send(WorkerMessage::Progress { current: i as u64 + 1, total: files.len() as u64, phase: ProgressPhase::Reading }).ok();
+    }
 
-        match output_format {
-            OutputFormat::Text => {
-                writeln!(writer,"{}",p).unwrap();
-            },
-            OutputFormat::CSV => {
-                write!(writer,"{},",p).unwrap();
-            },
-            OutputFormat::JSON => {
-                if !first_item {
-                    write!(writer,",{}", p).unwrap();
-                } else {
-                    write!(writer,"{}", p).unwrap();
-                    first_item = false;
-                }
-            },
+    finish_gap_report(analyzer, dir, &sender)?;
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
+
+fn finish_gap_report(analyzer: GapAnalyzer, dir: &Path, sender: &mpsc::Sender<WorkerMessage>) -> Result<(), SieveError> {
+    let report = analyzer.finish();
+    let path = dir.join(gaps::REPORT_FILE_NAME);
+    report.write_to(&path).map_err(SieveError::write(&path))?;
+    match report.max_gap() {
+        Some(max) => sender.send(WorkerMessage::Log(tr!(
+            "Gap report written to {}. Max gap: {} after {}", path.display(), max.gap, max.after))).ok(),
+        None => sender.send(WorkerMessage::Log(tr!("Gap report written to {}", path.display()))).ok(),
+    };
+    sender.send(WorkerMessage::GapReport(report)).ok();
+    Ok(())
+}
+
+// 書き込み中に停止したときは、書いたところまでで出力ファイルを閉じる。昇順なら書いた範囲を
+// メタデータに記録して、Append で続きから再開できるようにする（再開の目印）
+fn keep_stopped_output(out: &mut OutputSink, plan: OutputPlan, config: &Config, ranges: &[(u64, u64)], written_primes: &[u64], sender: &mpsc::Sender<WorkerMessage>) {
+    if !close_stopped_output(out, sender) {
+        return;
+    }
+    let Some(&last) = written_primes.last() else {
+        return;
+    };
+    let written = written_primes.len() as u64;
+    if config.descending {
+        sender.send(WorkerMessage::Log(tr!("Stopped after writing {} primes. Descending output cannot be continued.", format_number(written)))).ok();
+        return;
+    }
+    // メタデータのない既存の出力に追記していた場合は記録できない
+    if !matches!(plan, OutputPlan::Fresh | OutputPlan::Continue { .. }) {
+        sender.send(WorkerMessage::Log(tr!("Stopped after writing {} primes up to {}.", format_number(written), format_number(last)))).ok();
+        return;
+    }
+    let written_ranges: Vec<(u64, u64)> = ranges.iter().filter(|&&(low, _)| low <= last).map(|&(low, high)| (low, high.min(last))).collect();
+    record_output(plan, config, &written_ranges, written, Fingerprint::of(written_primes), &out.written_files(), sender);
+    sender.send(WorkerMessage::Log(tr!(
+        "Stopped after writing {} primes up to {}. Run again with Append to continue from {}.",
+        format_number(written), format_number(last), format_number(last + 1)
+    ))).ok();
+}
+
+// 停止時に出力ファイルを閉じる（JSON の ']' などを書いて、書いたところまでを有効なファイルにする）
+pub(crate) fn close_stopped_output(out: &mut OutputSink, sender: &mpsc::Sender<WorkerMessage>) -> bool {
+    match out.finish() {
+        Ok(()) => true,
+        Err(e) => {
+            sender.send(WorkerMessage::Log(tr!("Failed to close the output after stopping: {}", e))).ok();
+            false
         }
+    }
+}
 
-        found_count += 1;
-        current_prime_count_in_file += 1;
-        sender.send(WorkerMessage::FoundPrimeIndex(p, found_count)).ok();
+fn finish_pi_report(tracker: PiTracker, dir: &Path, sender: &mpsc::Sender<WorkerMessage>) -> Result<(), SieveError> {
+    let report = tracker.finish();
+    let path = dir.join(pi_report::REPORT_FILE_NAME);
+    report.write_to(&path).map_err(SieveError::write(&path))?;
+    if let Some(row) = report.last() {
+        let pi = row.pi as f64;
+        sender.send(WorkerMessage::Log(tr!(
+            "pi(x) report written to {}. pi({}) = {}, pi - li = {}, pi - R = {}, pi - x/ln x = {}",
+            path.display(), format_number(row.x), format_number(row.pi),
+            format!("{:.1}", pi - row.li), format!("{:.1}", pi - row.r), format!("{:.1}", pi - row.x_over_ln)
+        ))).ok();
+    }
+    sender.send(WorkerMessage::PiReport(report)).ok();
+    Ok(())
+}
+
+// 区間 [start, end] ごとの素数の個数を数え、区間が埋まるたびに IntervalCount を送る。
+// セグメントは昇順に渡されること
+struct IntervalCounter {
+    interval: u64,
+    prime_max: u64,
+    start: u64,
+    end: u64,
+    count: u64,
+    done: bool,
+}
+
+impl IntervalCounter {
+    fn new(prime_min: u64, prime_max: u64, interval: u64) -> Self {
+        IntervalCounter {
+            interval,
+            prime_max,
+            start: prime_min,
+            end: prime_min.saturating_add(interval - 1).min(prime_max),
+            count: 0,
+            done: false,
+        }
+    }
+
+    fn emit(&mut self, sender: &mpsc::Sender<WorkerMessage>) {
+        sender.send(WorkerMessage::IntervalCount { start: self.start, end: self.end, count: self.count }).ok();
+        if self.end >= self.prime_max {
+            self.done = true;
+            return;
+        }
+        self.start = self.end + 1;
+        self.end = self.start.saturating_add(self.interval - 1).min(self.prime_max);
+        self.count = 0;
+    }
 
-        if split_count > 0 && current_prime_count_in_file >= split_count {
-            writer.flush().unwrap();
-            if let OutputFormat::JSON = output_format {
-                write!(writer, "]").unwrap();
-                writer.flush().unwrap();
+    // high はこのセグメントの上端（そこまでは処理済み）
+    fn add_segment(&mut self, primes: &[u64], high: u64, sender: &mpsc::Sender<WorkerMessage>) {
+        for &p in primes {
+            while !self.done && p > self.end {
+                self.emit(sender);
             }
-            file_index += 1;
-            writer = open_file(file_index);
-            current_prime_count_in_file = 0;
-            if let OutputFormat::JSON = output_format {
-                write!(writer, "[").unwrap();
-                first_item = true;
+            self.count += 1;
+        }
+        while !self.done && self.end <= high {
+            self.emit(sender);
+        }
+    }
+
+    fn flush(&mut self, sender: &mpsc::Sender<WorkerMessage>) {
+        if !self.done {
+            self.emit(sender);
+        }
+    }
+}
+
+// 出力の手前に挟む条件式（桁フィルタとスクリプト）。実行前に解析して誤りを報告する
+pub(crate) struct OutputFilters {
+    digit: Option<DigitExpr>,
+    script: Option<FilterScript>,
+}
+
+pub(crate) fn parse_filters(config: &Config) -> Result<OutputFilters, SieveError> {
+    let digit = match config.digit_filter.trim() {
+        "" => None,
+        expr => Some(DigitExpr::parse(expr).map_err(|e| SieveError::Config(format!("digit_filter: {}", e)))?),
+    };
+    let script = match config.filter_script.trim() {
+        "" => None,
+        source => {
+            if config.mode == RunMode::Generate && config.prime_max.parse::<u64>().is_ok_and(|max| max > MAX_SCRIPT_PRIME) {
+                return Err(SieveError::Config(format!("filter_script can only be used up to {}", MAX_SCRIPT_PRIME)));
             }
+            Some(FilterScript::compile(source).map_err(|e| SieveError::Config(format!("filter_script: {}", e)))?)
         }
+    };
+    Ok(OutputFilters { digit, script })
+}
+
+// open_output が返す sink（外側から順に素数を通す）
+pub(crate) type OutputSink = DigitFilter<ScriptFilter<ResidueFilter<PipeSink<ScriptSink<DbSink<UploadSink>>>>>>;
+
+// 出力ファイルを開き、書き終えたファイルの圧縮、S3、WebDAV と SFTP へのアップロード、データベースへの登録、出力スクリプト、パイプへの送信、剰余類フィルタ、フィルタスクリプトと桁フィルタを重ねる
+pub(crate) fn open_output(config: &Config, filters: OutputFilters, sender: &mpsc::Sender<WorkerMessage>) -> Result<OutputSink, SieveError> {
+    if !(MIN_RADIX..=MAX_RADIX).contains(&config.output_radix) {
+        return Err(SieveError::Config(format!("output_radix must be between {} and {}", MIN_RADIX, MAX_RADIX)));
+    }
+    if config.zstd_seekable && config.zstd_frame_size == 0 {
+        return Err(SieveError::Config("zstd_frame_size must be greater than 0".to_string()));
     }
+    // 追記では既存の出力の番号から続けられないので併用しない
+    if config.prime_index && config.overwrite_mode == OverwriteMode::Append {
+        return Err(SieveError::Config("prime_index cannot be used with overwrite_mode = Append".to_string()));
+    }
+    // まとめたファイルには追記できず、seekable 形式をさらに圧縮しても小さくならない
+    if config.archive_outputs && config.overwrite_mode == OverwriteMode::Append {
+        return Err(SieveError::Config("archive_outputs cannot be used with overwrite_mode = Append".to_string()));
+    }
+    if config.archive_outputs && config.zstd_seekable {
+        return Err(SieveError::Config("archive_outputs cannot be used with zstd_seekable".to_string()));
+    }
+    let out = PrimeWriter::create(config.output_format.clone(), ZstdOptions::from_config(config), Path::new(&config.output_dir), config.split_count, config.writer_buffer_size, &config.overwrite_mode)
+        .map_err(|source| SieveError::OpenFile { path: PathBuf::from(&config.output_dir), source })?
+        .with_radix(config.output_radix)
+        .with_flush_interval(Duration::from_millis(config.writer_flush_interval_ms));
+    let out = if config.prime_index {
+        out.with_prime_index().map_err(|source| SieveError::OpenFile { path: PathBuf::from(&config.output_dir), source })?
+    } else {
+        out
+    };
+    let out = if config.async_writer {
+        out.with_io_thread(config.writer_queue_blocks)
+            .map_err(|source| SieveError::OpenFile { path: PathBuf::from(&config.output_dir), source })?
+    } else {
+        out
+    };
+    let out = ArchiveSink::new(out, config, sender);
+    let out = UploadSink::new(out, config, sender).map_err(|e| SieveError::Config(e.to_string()))?;
+    let out = DbSink::new(out, config)?;
+    if out.is_active() {
+        sender.send(WorkerMessage::Log(tr!("Inserting primes into {} ({})", config.db_table.trim(), redact_url(config.db_url.trim())))).ok();
+    }
+    let out = ScriptSink::new(out, config, sender)?;
+    if out.is_active() {
+        sender.send(WorkerMessage::Log(tr!("Passing primes to {}", config.sink_script.trim()))).ok();
+    }
+    let out = PipeSink::new(out, config, sender)?;
+    if out.is_active() {
+        sender.send(WorkerMessage::Log(tr!("Sending primes to {}", config.pipe_path.trim()))).ok();
+    }
+    let out = ResidueFilter::new(out, config.residue_modulus, &config.residues);
+    let out = ScriptFilter::new(out, filters.script);
+    Ok(DigitFilter::new(out, filters.digit))
+}
+
+// ディスク容量不足の場合は一時停止して GUI に通知し、再開指示を待ってから op を再試行する。
+// 停止された場合は Stopped を送って false を返す
+pub(crate) fn retry_on_disk_full<S: PrimeSink + ?Sized>(
+    out: &mut S,
+    sender: &mpsc::Sender<WorkerMessage>,
+    commands: &mpsc::Receiver<WorkerCommand>,
+    stop_flag: &Arc<AtomicBool>,
+    mut op: impl FnMut(&mut S) -> std::io::Result<()>,
+) -> Result<bool, SieveError> {
+    let path_of = |out: &S| out.current_path().map(Path::to_path_buf).unwrap_or_default();
+    loop {
+        let e = match op(out) {
+            Ok(()) => return Ok(true),
+            Err(e) if is_disk_full(&e) => e,
+            Err(source) => return Err(SieveError::Write { path: path_of(out), source }),
+        };
+        sender.send(WorkerMessage::DiskFull(path_of(out).display().to_string())).ok();
+        sender.send(WorkerMessage::Log(tr!("Paused: {} ({})", e, path_of(out).display()))).ok();
 
-    if let OutputFormat::JSON = output_format {
-        write!(writer, "]").unwrap();
+        // 古い指示は捨てる
+        while commands.try_recv().is_ok() {}
+        loop {
+            if stop_flag.load(Ordering::SeqCst) {
+                sender.send(WorkerMessage::Stopped).ok();
+                return Ok(false);
+            }
+            match commands.recv_timeout(Duration::from_millis(200)) {
+                Ok(WorkerCommand::Resume) => break,
+                Ok(WorkerCommand::ResumeIn(dir)) => {
+                    let old_dir = path_of(out).parent().map(|d| d.display().to_string()).unwrap_or_default();
+                    match out.relocate(Path::new(&dir)) {
+                        Ok(()) => {
+                            sender.send(WorkerMessage::Log(tr!(
+                                "Output moved to {}. Files already completed remain in {}", dir, old_dir))).ok();
+                            break;
+                        }
+                        Err(e) => {
+                            sender.send(WorkerMessage::Log(tr!("Failed to switch output directory: {}", e))).ok();
+                            sender.send(WorkerMessage::DiskFull(path_of(out).display().to_string())).ok();
+                        }
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                // GUI 側が終了した
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(SieveError::Write { path: path_of(out), source: e });
+                }
+            }
+        }
+        sender.send(WorkerMessage::Log(tr!("Resuming...").to_string())).ok();
     }
-    writer.flush().unwrap();
+}
 
-    // 処理完了メッセージ
-    sender.send(WorkerMessage::Progress { current: total_range, total: total_range}).ok();
-    sender.send(WorkerMessage::Eta("0 hour 0 min 0 sec".to_string())).ok();
+// 経過時間と進捗率（0〜1）から残り時間の表示を作る
+pub(crate) fn format_eta(start_time: Instant, progress: f64) -> String {
+    if progress <= 0.0 {
+        return tr!("Calculating...").to_string();
+    }
+    let elapsed = start_time.elapsed().as_secs_f64();
+    let total_time = elapsed / progress;
+    let remaining = total_time - elapsed;
+    format_duration(remaining.round() as u64)
+}
 
-    sender.send(WorkerMessage::Log(format!("Finished old method. Total primes found: {}", found_count))).ok();
-    sender.send(WorkerMessage::Done).ok();
+// 秒数を「時間 分 秒」の表示にする
+pub(crate) fn format_duration(total_sec: u64) -> String {
+    let hours = total_sec / 3600;
+    let minutes = (total_sec % 3600) / 60;
+    let seconds = total_sec % 60;
+    tr!("{} hour {} min {} sec", hours, minutes, seconds)
+}
 
-    Ok(())
+// [min, max] を segment_size ごとの区間に分ける
+pub(crate) fn segment_ranges(min: u64, max: u64, segment_size: u64) -> Vec<(u64, u64)> {
+    let mut segments = Vec::new();
+    let mut start = min;
+    while start <= max {
+        let end = start.saturating_add(segment_size - 1).min(max);
+        segments.push((start, end));
+        if end == u64::MAX {
+            break;
+        }
+        start = end + 1;
+    }
+    segments
+}
+
+// 適応的なセグメントは segment_size のこの倍数まで大きくする
+pub const MAX_SEGMENT_GROWTH: u64 = 8;
+
+// 篩う範囲を前から（降順では後ろから）セグメントに切り出す。adaptive なら、上の方ほど素数が疎らで
+// 書き出しの手間が減るぶん、バッチの時間が最初のバッチと同じくらいになるようセグメントを大きくしていく
+pub struct SegmentScheduler {
+    // 残りの範囲（昇順）
+    ranges: std::collections::VecDeque<(u64, u64)>,
+    descending: bool,
+    size: u64,
+    min_size: u64,
+    adaptive: bool,
+    // 最初のバッチの時間（秒）
+    target: Option<f64>,
+}
+
+impl SegmentScheduler {
+    pub fn new(ranges: &[(u64, u64)], segment_size: u64, descending: bool, adaptive: bool) -> Self {
+        SegmentScheduler { ranges: ranges.iter().copied().collect(), descending, size: segment_size, min_size: segment_size, adaptive, target: None }
+    }
+
+    // 最大 count 個のセグメント。残りがなければ空
+    pub fn next_batch(&mut self, count: usize) -> Vec<(u64, u64)> {
+        let mut batch = Vec::with_capacity(count);
+        while batch.len() < count {
+            let segment = if self.descending {
+                let Some((low, high)) = self.ranges.pop_back() else { break };
+                let start = high.saturating_sub(self.size - 1).max(low);
+                if start > low {
+                    self.ranges.push_back((low, start - 1));
+                }
+                (start, high)
+            } else {
+                let Some((low, high)) = self.ranges.pop_front() else { break };
+                let end = low.saturating_add(self.size - 1).min(high);
+                if end < high {
+                    self.ranges.push_front((end + 1, high));
+                }
+                (low, end)
+            };
+            batch.push(segment);
+        }
+        batch
+    }
+
+    // 今の大きさで切り出すと残りがいくつのセグメントになるか
+    pub fn remaining(&self) -> u64 {
+        self.ranges.iter().map(|&(low, high)| (high - low) / self.size + 1).sum()
+    }
+
+    // バッチにかかった時間を渡す。セグメントの大きさを変えたらその大きさを返す
+    pub fn record(&mut self, elapsed: Duration) -> Option<u64> {
+        if !self.adaptive {
+            return None;
+        }
+        let secs = elapsed.as_secs_f64().max(1e-6);
+        let Some(target) = self.target else {
+            self.target = Some(secs);
+            return None;
+        };
+        // 1 回に 2 倍までにして、測定のぶれで大きく変わらないようにする
+        let scaled = (self.size as f64 * (target / secs).clamp(0.5, 2.0)) as u64;
+        let size = scaled.clamp(self.min_size, self.min_size.saturating_mul(MAX_SEGMENT_GROWTH));
+        // 1 割未満の変化は無視する
+        if size.abs_diff(self.size) * 10 < self.size {
+            return None;
+        }
+        self.size = size;
+        Some(size)
+    }
+}
+
+// 1 バッチで並列に篩うセグメントの数
+pub(crate) fn parallel_segments(config: &Config, pool: &rayon::ThreadPool) -> usize {
+    match config.parallel_segments {
+        0 => pool.current_num_threads().max(1),
+        n => n,
+    }
+}
+
+// セグメント処理用のスレッドプール。pin_threads 時は各ワーカーを CPU コアに固定する
+pub(crate) fn build_thread_pool(config: &Config) -> Result<rayon::ThreadPool, SieveError> {
+    let core_ids = if config.pin_threads { core_affinity::get_core_ids().unwrap_or_default() } else { Vec::new() };
+    let background_mode = config.background_mode;
+
+    let mut builder = rayon::ThreadPoolBuilder::new();
+    if !core_ids.is_empty() {
+        builder = builder.num_threads(core_ids.len());
+    }
+    let pool = builder
+        .start_handler(move |index| {
+            if !core_ids.is_empty() {
+                core_affinity::set_for_current(core_ids[index % core_ids.len()]);
+            }
+            if background_mode {
+                let _ = lower_current_thread_priority();
+            }
+        })
+        .build()?;
+    Ok(pool)
+}
+
+// 稼働時間 busy に対して percent% がアイドルになるよう休止する（停止フラグは 100ms 毎に確認）
+fn throttle_sleep(busy: Duration, percent: u8, stop_flag: &Arc<AtomicBool>) {
+    let percent = percent as u32;
+    let mut remaining = busy * percent / (100 - percent);
+    let slice = Duration::from_millis(100);
+    while remaining > Duration::ZERO && !stop_flag.load(Ordering::SeqCst) {
+        let d = remaining.min(slice);
+        std::thread::sleep(d);
+        remaining -= d;
+    }
 }
 
 pub fn simple_sieve(limit:u64)->Vec<u64>{
@@ -215,60 +826,118 @@ pub fn simple_sieve(limit:u64)->Vec<u64>{
         }
     }
     let mut primes=Vec::new();
-    for i in 2..=limit as usize {
-        if is_prime[i] {
+    for (i, &flag) in is_prime.iter().enumerate().skip(2) {
+        if flag {
             primes.push(i as u64);
         }
     }
     primes
 }
 
+// low 以上で p² 以上の p の最初の倍数
+fn first_multiple(p: u64, low: u64) -> u64 {
+    let start = if low.is_multiple_of(p) { low } else { low + (p - low % p) };
+    start.max(p * p)
+}
+
+// composite[start], composite[start + step], ... のうち末尾までのものに印を付け、次の添字を返す。
+// 添字は直前に長さと比べているので、書き込みごとの範囲の確認は省く
+#[inline]
+fn mark_multiples(composite: &mut [u8], start: usize, step: usize) -> usize {
+    let len = composite.len();
+    let mut i = start;
+    while i < len {
+        // SAFETY: i < len
+        unsafe { *composite.get_unchecked_mut(i) = 1 };
+        i += step;
+    }
+    i
+}
+
+// [low, high] の素数を小さい順に返す。範囲はどのモードでも両端を含み、0 と 1 は素数に数えない。
+// small_primes は √high 以下の素数をすべて含むこと（low より小さい素数も倍数を消すのに使う）
 pub fn segmented_sieve(small_primes:&[u64], low:u64, high:u64, stop_flag: &Arc<AtomicBool>)->Vec<u64> {
     let size=(high - low +1) as usize;
-    let mut is_prime = vec![true; size];
+    let mut composite = vec![0u8; size];
+    segmented_sieve_in(&mut composite, small_primes, low, high, stop_flag)
+}
+
+// composite は長さ high-low+1 の 0 埋めされた配列（1 = 合成数）。確保方法は呼び出し側が選ぶ
+pub fn segmented_sieve_in(composite:&mut [u8], small_primes:&[u64], low:u64, high:u64, stop_flag: &Arc<AtomicBool>)->Vec<u64> {
+    let size=(high - low +1) as usize;
 
     if low == 0 {
         if size > 0 {
-            is_prime[0] = false;
+            composite[0] = 1;
         }
         if size > 1 {
-            is_prime[1] = false;
+            composite[1] = 1;
         }
     } else if low == 1 {
-        is_prime[0] = false;
+        composite[0] = 1;
     }
 
-    for &p in small_primes {
-        if stop_flag.load(Ordering::SeqCst) {
-            return Vec::new();
-        }
-
-        if p*p>high {
-            break;
-        }
-
-        let mut start=if low%p==0 {low} else {low+(p-(low%p))};
-        if start<p*p {
-            start=p*p;
+    // 篩に使うのは √high 以下の素数。SIEVE_CHUNK 以下の素数はチャンクごと、それより大きい素数は
+    // セグメント全体を 1 つずつ印を付ける（大きい素数は 1 つのチャンクにほとんど倍数がない）
+    let sieving = &small_primes[..small_primes.partition_point(|&p| p.saturating_mul(p) <= high)];
+    let (chunked, large) = sieving.split_at(sieving.partition_point(|&p| p <= SIEVE_CHUNK));
+
+    // 停止フラグは書き込みのたびではなく、STOP_CHECK_WORK 回ぶんの書き込みごとに確認する。
+    // 大きいセグメントでは 1 つの素数の倍数だけでもこれを超えるので、その途中でも確認する
+    let mut work = STOP_CHECK_WORK;
+
+    // 小さい素数の次の倍数の添字（low からの距離）。剰余の計算はここでの 1 回だけで、あとはチャンクをまたいで持ち越す
+    let mut next: Vec<usize> = chunked.iter().map(|&p| (first_multiple(p, low) - low) as usize).collect();
+    let mut chunk_end = 0;
+    while chunk_end < size {
+        chunk_end = size.min(chunk_end + SIEVE_CHUNK as usize);
+        let chunk = &mut composite[..chunk_end];
+        for (&p, j) in chunked.iter().zip(next.iter_mut()) {
+            if work >= STOP_CHECK_WORK {
+                if stop_flag.load(Ordering::SeqCst) {
+                    return Vec::new();
+                }
+                work = 0;
+            }
+            // 1 つのチャンクに印は高々 SIEVE_CHUNK / p + 1 個なので、確認の間隔を大きく超えることはない
+            let marked = mark_multiples(chunk, *j, p as usize);
+            work += ((marked - *j) / p as usize) as u64 + 1;
+            *j = marked;
         }
+    }
 
-        let mut j=start;
-        while j<=high {
-            if stop_flag.load(Ordering::SeqCst) {
-                return Vec::new();
+    for &p in large {
+        // 倍数が範囲にない素数も剰余の計算の分を 1 回と数える
+        work += 1;
+        let step = p as usize;
+        let mut j = (first_multiple(p, low) - low) as usize;
+        loop {
+            if work >= STOP_CHECK_WORK {
+                if stop_flag.load(Ordering::SeqCst) {
+                    return Vec::new();
+                }
+                work = 0;
             }
-            is_prime[(j - low) as usize] = false;
-            j+=p;
+            if j >= size {
+                break;
+            }
+            // 次の確認までの回数ぶんだけ印を付ける（32 ビット環境では step が大きいと掛け算があふれるので飽和させる）
+            let end = size.min(j.saturating_add(((STOP_CHECK_WORK - work) as usize).saturating_mul(step)));
+            work += (end - j).div_ceil(step) as u64;
+            j = mark_multiples(&mut composite[..end], j, step);
         }
     }
 
     let mut primes=Vec::new();
-    for i in 0..size {
+    for (c, chunk) in composite[..size].chunks(STOP_CHECK_WORK as usize).enumerate() {
         if stop_flag.load(Ordering::SeqCst) {
             return primes;
         }
-        if is_prime[i] {
-            primes.push(low+i as u64);
+        let chunk_low = low + c as u64 * STOP_CHECK_WORK;
+        for (i, &flag) in chunk.iter().enumerate() {
+            if flag == 0 {
+                primes.push(chunk_low+i as u64);
+            }
         }
     }
     primes
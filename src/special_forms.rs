@@ -0,0 +1,257 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 特殊形の数の素数判定ツール。GUI の Special Forms タブから SpecialFormJob を
+// 1 つ選んで実行し、結果をログと special_forms.txt（追記）に出力する
+
+use std::fs::{create_dir_all, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use crate::app::{ProgressPhase, WorkerMessage};
+use crate::error::SieveError;
+use crate::miller_rabin::{check_prime_big, is_prime, jacobi, mul_mod, BigPrimality};
+use crate::number_check::probabilistic_warning;
+use crate::sieve::simple_sieve;
+use crate::i18n::tr;
+
+pub const OUTPUT_FILE_NAME: &str = "special_forms.txt";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpecialFormJob {
+    // k·2^n + 1（k は奇数で k < 2^n）を n_min..=n_max について判定する
+    Proth { k: u64, n_min: u64, n_max: u64 },
+    // フェルマー数 F_m = 2^(2^m) + 1 の因数 k·2^(m+2) + 1 を k <= max_k の範囲で探す
+    FermatFactors { m_min: u32, m_max: u32, max_k: u64 },
+    // p# ± 1 を素数 p <= max_p について BPSW で判定する
+    Primorial { max_p: u64 },
+    // n! ± 1 を n <= max_n について BPSW で判定する
+    Factorial { max_n: u64 },
+}
+
+// 素数階乗・階乗の上限（これ以上は 1 件の判定に時間がかかりすぎる）
+const MAX_PRIMORIAL_P: u64 = 100_000;
+const MAX_FACTORIAL_N: u64 = 20_000;
+
+// 10 進の桁数の概算（大きな数を文字列にしないため）
+fn approx_digits(n: &BigUint) -> u64 {
+    (n.bits() as f64 * std::f64::consts::LOG10_2).ceil() as u64
+}
+
+// base ± 1 を判定して素数ならその形と、確率的な判定かどうかを返す（name は "p#" や "n!"）。停止された場合は None
+fn test_plus_minus_one(base: &BigUint, name: &str, random_rounds: u32, stop_flag: &AtomicBool) -> Option<Vec<(String, bool)>> {
+    let mut found = Vec::new();
+    for (sign, candidate) in [("+", base + 1u32), ("-", base - 1u32)] {
+        if stop_flag.load(Ordering::SeqCst) {
+            return None;
+        }
+        match (check_prime_big(&candidate, random_rounds), candidate.to_u64()) {
+            (BigPrimality::Composite, _) => {}
+            (_, Some(small)) => found.push((format!("{}{}1 = {} is prime", name, sign, small), false)),
+            (BigPrimality::Prime, None) => found.push((format!("{}{}1 is prime (deterministic Miller-Rabin, {} digits)", name, sign, approx_digits(&candidate)), false)),
+            (BigPrimality::ProbablePrime, None) => found.push((
+                format!("{}{}1 is a probable prime (BPSW + {} random Miller-Rabin rounds, not proven, {} digits)", name, sign, random_rounds, approx_digits(&candidate)),
+                true,
+            )),
+        }
+    }
+    Some(found)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProthResult {
+    Prime,
+    Composite,
+    // 平方非剰余が見つからなかった（N が平方数の場合など）
+    Undetermined,
+}
+
+// 平方非剰余を探す底の上限
+const MAX_PROTH_BASE: u64 = 1000;
+
+// プロスの定理: ある a について a^((N-1)/2) ≡ -1 (mod N) なら N は素数。
+// a を平方非剰余に選べば逆も成り立つので判定は決定的になる
+pub fn proth_test(k: u64, n: u64, stop_flag: &AtomicBool) -> Option<ProthResult> {
+    let big_n = (BigUint::from(k) << n) + 1u32;
+    if let Some(small) = u64::try_from(&big_n).ok().filter(|&v| v < 1 << 32) {
+        return Some(if is_prime(small) { ProthResult::Prime } else { ProthResult::Composite });
+    }
+    let mut base = None;
+    for a in 3..=MAX_PROTH_BASE {
+        match jacobi(a, &big_n) {
+            -1 => {
+                base = Some(a);
+                break;
+            }
+            // a と N が共通因数を持つ
+            0 => return Some(ProthResult::Composite),
+            _ => {}
+        }
+    }
+    let Some(a) = base else {
+        return Some(ProthResult::Undetermined);
+    };
+    if stop_flag.load(Ordering::SeqCst) {
+        return None;
+    }
+    let n_minus_one = &big_n - 1u32;
+    let x = BigUint::from(a).modpow(&(&n_minus_one >> 1), &big_n);
+    Some(if x == n_minus_one { ProthResult::Prime } else { ProthResult::Composite })
+}
+
+// F_m の因数 q = k·2^(m+2) + 1 (k <= max_k) を探す。q は u64 に収まる範囲に限る
+pub fn fermat_factors(m: u32, max_k: u64, stop_flag: &AtomicBool) -> Option<Vec<u64>> {
+    let mut factors = Vec::new();
+    let shift = m + 2;
+    if shift >= 63 {
+        return Some(factors);
+    }
+    for k in 1..=max_k {
+        if k & 0xFFFF == 0 && stop_flag.load(Ordering::SeqCst) {
+            return None;
+        }
+        let Some(q) = k.checked_mul(1 << shift).and_then(|v| v.checked_add(1)).filter(|&q| q < 1 << 63) else {
+            break;
+        };
+        // 2^(2^m) mod q を m 回の 2 乗で求める
+        let mut x = 2 % q;
+        for _ in 0..m {
+            x = mul_mod(x, x, q);
+        }
+        if x == q - 1 {
+            factors.push(q);
+        }
+    }
+    Some(factors)
+}
+
+pub fn run_special_form(job: SpecialFormJob, random_rounds: u32, output_dir: &str, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    if !output_dir.is_empty() {
+        create_dir_all(output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(output_dir), source })?;
+    }
+    let path = Path::new(output_dir).join(OUTPUT_FILE_NAME);
+    let file = OpenOptions::new().create(true).append(true).open(&path)
+        .map_err(|source| SieveError::OpenFile { path: path.clone(), source })?;
+    let mut out = BufWriter::new(file);
+    // ログと出力ファイルの両方に書く
+    let mut report = |line: String| -> Result<(), SieveError> {
+        writeln!(out, "{}", line).and_then(|_| out.flush()).map_err(SieveError::write(&path))?;
+        sender.send(WorkerMessage::Log(line)).ok();
+        Ok(())
+    };
+    let start_time = Instant::now();
+    // 確率的な判定で素数としたものがあったか（最後に 1 度だけ警告する）
+    let mut probabilistic = false;
+
+    match job {
+        SpecialFormJob::Proth { k, n_min, n_max } => {
+            if k % 2 == 0 || n_min == 0 || n_min > n_max {
+                return Err(SieveError::Config("Proth test requires an odd k and 1 <= n_min <= n_max".to_string()));
+            }
+            report(format!("# Proth test: {}*2^n+1 for n = {}..{}", k, n_min, n_max))?;
+            let total = n_max - n_min + 1;
+            let mut primes = 0u64;
+            for n in n_min..=n_max {
+                // k < 2^n のときだけプロスの定理が使える
+                if n < 64 && k >= 1 << n {
+                    continue;
+                }
+                let result = match proth_test(k, n, &stop_flag) {
+                    Some(result) => result,
+                    None => {
+                        sender.send(WorkerMessage::Stopped).ok();
+                        return Ok(());
+                    }
+                };
+                match result {
+                    ProthResult::Prime => {
+                        primes += 1;
+                        report(format!("{}*2^{}+1 is prime", k, n))?;
+                    }
+                    ProthResult::Undetermined => report(format!("{}*2^{}+1: no quadratic non-residue found, undetermined", k, n))?,
+                    ProthResult::Composite => {}
+                }
+                sender.send(WorkerMessage::Progress { current: n - n_min + 1, total, phase: ProgressPhase::Testing }).ok();
+            }
+            report(format!("# {} Proth primes found ({:.2} s)", primes, start_time.elapsed().as_secs_f64()))?;
+        }
+        SpecialFormJob::FermatFactors { m_min, m_max, max_k } => {
+            if m_min > m_max {
+                return Err(SieveError::Config("m_min must not exceed m_max".to_string()));
+            }
+            report(format!("# Fermat number factors k*2^(m+2)+1 for m = {}..{}, k <= {}", m_min, m_max, max_k))?;
+            let total = (m_max - m_min + 1) as u64;
+            for m in m_min..=m_max {
+                let factors = match fermat_factors(m, max_k, &stop_flag) {
+                    Some(factors) => factors,
+                    None => {
+                        sender.send(WorkerMessage::Stopped).ok();
+                        return Ok(());
+                    }
+                };
+                for q in factors {
+                    let k = (q - 1) >> (m + 2);
+                    let kind = if is_prime(q) { "prime" } else { "composite" };
+                    report(format!("F{} has factor {} = {}*2^{}+1 ({})", m, q, k, m + 2, kind))?;
+                }
+                sender.send(WorkerMessage::Progress { current: (m - m_min + 1) as u64, total, phase: ProgressPhase::Testing }).ok();
+            }
+            report(format!("# Fermat factor screening finished ({:.2} s)", start_time.elapsed().as_secs_f64()))?;
+        }
+        SpecialFormJob::Primorial { max_p } => {
+            if max_p > MAX_PRIMORIAL_P {
+                return Err(SieveError::Config(format!("the primorial bound must not exceed {}", MAX_PRIMORIAL_P)));
+            }
+            report(format!("# Primorial primes p#+1 / p#-1 for p <= {}", max_p))?;
+            let primes = simple_sieve(max_p);
+            let mut primorial = BigUint::from(1u32);
+            for (i, &p) in primes.iter().enumerate() {
+                primorial *= p;
+                let Some(found) = test_plus_minus_one(&primorial, &format!("{}#", p), random_rounds, &stop_flag) else {
+                    sender.send(WorkerMessage::Stopped).ok();
+                    return Ok(());
+                };
+                for (line, probable) in found {
+                    probabilistic |= probable;
+                    report(line)?;
+                }
+                sender.send(WorkerMessage::Progress { current: i as u64 + 1, total: primes.len() as u64, phase: ProgressPhase::Testing }).ok();
+            }
+            report(format!("# Primorial search finished ({:.2} s)", start_time.elapsed().as_secs_f64()))?;
+        }
+        SpecialFormJob::Factorial { max_n } => {
+            if max_n > MAX_FACTORIAL_N {
+                return Err(SieveError::Config(format!("the factorial bound must not exceed {}", MAX_FACTORIAL_N)));
+            }
+            report(format!("# Factorial primes n!+1 / n!-1 for n <= {}", max_n))?;
+            let mut factorial = BigUint::from(1u32);
+            for n in 1..=max_n {
+                factorial *= n;
+                let Some(found) = test_plus_minus_one(&factorial, &format!("{}!", n), random_rounds, &stop_flag) else {
+                    sender.send(WorkerMessage::Stopped).ok();
+                    return Ok(());
+                };
+                for (line, probable) in found {
+                    probabilistic |= probable;
+                    report(line)?;
+                }
+                sender.send(WorkerMessage::Progress { current: n, total: max_n, phase: ProgressPhase::Testing }).ok();
+            }
+            report(format!("# Factorial search finished ({:.2} s)", start_time.elapsed().as_secs_f64()))?;
+        }
+    }
+
+    if probabilistic {
+        sender.send(WorkerMessage::Warning(probabilistic_warning(random_rounds))).ok();
+    }
+    sender.send(WorkerMessage::Log(tr!("Results appended to {}", path.display()))).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
@@ -0,0 +1,116 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// ウラムの螺旋とサックスの螺旋の座標計算と、表示する範囲の素数の読み込み
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use crate::app::WorkerMessage;
+use crate::error::SieveError;
+use crate::metadata::require_decimal_text;
+use crate::output::read_primes;
+use crate::sieve::{integer_sqrt, segmented_sieve, simple_sieve};
+use crate::i18n::tr;
+
+// ウラムの螺旋の一辺の上限（表示する数は一辺の 2 乗個）
+pub const MAX_SIDE: u64 = 2001;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpiralSource {
+    // 表示する範囲をその場で篩う
+    Sieve,
+    // 生成済みの出力ファイルから読む
+    File(PathBuf),
+}
+
+// 螺旋の k 番目（中心が 0）の座標。1 は右、2 は右上と反時計回りに進む（y は上向き）
+pub fn ulam_position(k: u64) -> (i64, i64) {
+    if k == 0 {
+        return (0, 0);
+    }
+    // k は r 周目 ((2r-1)^2 <= k < (2r+1)^2) にあり、各周は (r, -r+1) から始まる
+    let r = integer_sqrt(k).div_ceil(2);
+    let t = (k - (2 * r - 1) * (2 * r - 1)) as i64;
+    let r = r as i64;
+    match t / (2 * r) {
+        0 => (r, -r + 1 + t),
+        1 => (r - 1 - (t - 2 * r), r),
+        2 => (-r, r - 1 - (t - 4 * r)),
+        _ => (-r + 1 + (t - 6 * r), -r),
+    }
+}
+
+// ulam_position の逆
+pub fn ulam_index(x: i64, y: i64) -> u64 {
+    let r = x.abs().max(y.abs());
+    if r == 0 {
+        return 0;
+    }
+    let t = if x == r && y > -r {
+        y + r - 1
+    } else if y == r {
+        2 * r + (r - 1 - x)
+    } else if x == -r {
+        4 * r + (r - 1 - y)
+    } else {
+        6 * r + (x + r - 1)
+    };
+    ((2 * r - 1) * (2 * r - 1) + t) as u64
+}
+
+// 一辺 side（奇数）の正方形で、中心を start とした螺旋上の素数の位置。
+// 添字は row * side + col（row は上から）
+pub fn ulam_bitmap(start: u64, side: u64, primes: &[u64]) -> Vec<bool> {
+    let mut cells = vec![false; (side * side) as usize];
+    let center = (side / 2) as i64;
+    let end = start + side * side;
+    for &p in primes.iter().filter(|&&p| p >= start && p < end) {
+        let (x, y) = ulam_position(p - start);
+        cells[((center - y) * side as i64 + center + x) as usize] = true;
+    }
+    cells
+}
+
+// サックスの螺旋上の n の位置。半径 √n、角度 2π√n（平方数は正の x 軸上に並ぶ）
+pub fn sacks_position(n: u64) -> (f64, f64) {
+    let r = (n as f64).sqrt();
+    let theta = std::f64::consts::TAU * r;
+    (r * theta.cos(), r * theta.sin())
+}
+
+// [start, start + count) の素数を読み込んで SpiralPrimes で送る
+pub fn load_spiral_primes(source: SpiralSource, start: u64, count: u64, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let end = start.checked_add(count).filter(|&end| end > start)
+        .ok_or_else(|| SieveError::Config("spiral range overflows u64".to_string()))?;
+    let primes = match source {
+        SpiralSource::Sieve => {
+            let small_primes = simple_sieve(integer_sqrt(end) + 1);
+            segmented_sieve(&small_primes, start, end - 1, &stop_flag)
+        }
+        SpiralSource::File(path) => {
+            require_decimal_text(&path, None)?;
+            sender.send(WorkerMessage::Log(tr!("Reading primes from {}", path.display()))).ok();
+            let mut primes = Vec::new();
+            read_primes(&path, |p| {
+                if p >= start && p < end {
+                    primes.push(p);
+                }
+            })
+            .map_err(|source| SieveError::OpenFile { path: path.clone(), source })?;
+            primes.sort_unstable();
+            primes.dedup();
+            primes
+        }
+    };
+    if stop_flag.load(Ordering::SeqCst) {
+        sender.send(WorkerMessage::Stopped).ok();
+        return Ok(());
+    }
+    sender.send(WorkerMessage::Log(tr!("{} primes in [{}, {})", primes.len(), start, end))).ok();
+    sender.send(WorkerMessage::SpiralPrimes { start, end, primes }).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
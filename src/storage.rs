@@ -0,0 +1,100 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 出力先の種類の推定（storage_tuning）。実行開始時に出力フォルダへ小さい書き込みの同期と
+// まとまった書き込みを試し、かかった時間から SSD・HDD・ネットワーク共有のどれらしいかを決めて、
+// 書き込みバッファの大きさ、I/O スレッドに先に渡すブロック数、書き出す間隔を選ぶ
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Instant;
+use crate::config::Config;
+
+const MIB: usize = 1024 * 1024;
+
+// 同期の遅さを測る小さい書き込み（回数, バイト数）
+const SYNC_WRITES: usize = 16;
+const SYNC_WRITE_SIZE: usize = 4096;
+
+// 速さを測るまとまった書き込み
+const BULK_WRITE_SIZE: usize = 16 * MIB;
+
+// 1 回の同期がこれより速ければ SSD
+const SSD_MAX_SYNC_MS: f64 = 2.0;
+// 同期がこれより遅いか、書き込みがこれより遅ければネットワーク共有
+const NETWORK_MIN_SYNC_MS: f64 = 30.0;
+const NETWORK_MAX_MIB_PER_SEC: f64 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageKind {
+    Ssd,
+    Hdd,
+    Network,
+}
+
+// 測った値
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StorageProbe {
+    // 小さい書き込み 1 回と同期にかかった平均時間
+    pub sync_ms: f64,
+    // まとまった書き込み（同期まで）の速さ
+    pub mib_per_sec: f64,
+}
+
+impl StorageProbe {
+    // 回転待ちのない SSD は同期が速い。遅い同期や遅い書き込みは、HDD では説明しにくいほどならネットワークとみなす
+    pub fn kind(&self) -> StorageKind {
+        if self.sync_ms < SSD_MAX_SYNC_MS {
+            StorageKind::Ssd
+        } else if self.sync_ms >= NETWORK_MIN_SYNC_MS || self.mib_per_sec < NETWORK_MAX_MIB_PER_SEC {
+            StorageKind::Network
+        } else {
+            StorageKind::Hdd
+        }
+    }
+}
+
+// dir に一時ファイルを書いて測る（終わったら消す）
+pub fn probe_storage(dir: &Path) -> io::Result<StorageProbe> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!(".sosu_storage_probe_{}.tmp", std::process::id()));
+    let result = probe_file(&path);
+    let _ = fs::remove_file(&path);
+    result
+}
+
+fn probe_file(path: &Path) -> io::Result<StorageProbe> {
+    let mut file = File::create(path)?;
+    let block = vec![b'7'; SYNC_WRITE_SIZE];
+    let start = Instant::now();
+    for _ in 0..SYNC_WRITES {
+        file.write_all(&block)?;
+        file.sync_data()?;
+    }
+    let sync_ms = start.elapsed().as_secs_f64() * 1000.0 / SYNC_WRITES as f64;
+
+    let bulk = vec![b'7'; BULK_WRITE_SIZE];
+    let start = Instant::now();
+    file.write_all(&bulk)?;
+    file.sync_data()?;
+    let mib_per_sec = (BULK_WRITE_SIZE / MIB) as f64 / start.elapsed().as_secs_f64().max(f64::EPSILON);
+    Ok(StorageProbe { sync_ms, mib_per_sec })
+}
+
+// ネットワーク共有へ書き出す間隔
+const NETWORK_FLUSH_INTERVAL_MS: u64 = 1000;
+
+// 種類ごとの書き込みバッファ、先に渡すブロック数、書き出す間隔。HDD は大きく書いてシークを減らす。
+// ネットワーク共有は往復の待ちを隠すため I/O スレッドにより多く渡し、接続が切れたときに
+// 失う分が少なく、ほかのマシンからも進み具合が見えるよう、バッファが埋まるのを待たずにこまめに書き出す
+pub fn storage_tuned(config: &Config, kind: StorageKind) -> Config {
+    let (writer_buffer_size, writer_queue_blocks, writer_flush_interval_ms) = match kind {
+        StorageKind::Ssd => (8 * MIB, 4, 0),
+        StorageKind::Hdd => (32 * MIB, 4, 0),
+        StorageKind::Network => (16 * MIB, 16, NETWORK_FLUSH_INTERVAL_MS),
+    };
+    Config { writer_buffer_size, writer_queue_blocks, writer_flush_interval_ms, ..config.clone() }
+}
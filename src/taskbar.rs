@@ -0,0 +1,181 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// Windows タスクバーへの進捗表示とシステムトレイへの最小化。
+// Windows 以外では何もしない。
+
+use eframe::egui;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Default)]
+pub struct TaskbarProgress {
+    #[cfg(windows)]
+    list: Option<windows::Win32::UI::Shell::ITaskbarList3>,
+    #[cfg(windows)]
+    hwnd: Option<windows::Win32::Foundation::HWND>,
+    initialized: bool,
+    last: Option<(u64, u64)>,
+}
+
+impl TaskbarProgress {
+    // 実行中は current/total を、停止中は進捗なしを表示する（変化があった時のみ反映）
+    pub fn update(&mut self, frame: &eframe::Frame, running: bool, current: u64, total: u64) {
+        let state = if running && total > 0 { Some((current, total)) } else { None };
+        if self.initialized && self.last == state {
+            return;
+        }
+        if !self.initialized {
+            self.initialized = true;
+            self.init(frame);
+        }
+        self.last = state;
+        self.apply(state);
+    }
+
+    #[cfg(windows)]
+    fn init(&mut self, frame: &eframe::Frame) {
+        use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+        use windows::Win32::Foundation::HWND;
+        use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED};
+        use windows::Win32::UI::Shell::{ITaskbarList3, TaskbarList};
+
+        if let Ok(handle) = frame.window_handle() {
+            if let RawWindowHandle::Win32(h) = handle.as_raw() {
+                self.hwnd = Some(HWND(h.hwnd.get() as *mut core::ffi::c_void));
+            }
+        }
+        unsafe {
+            // winit が既に COM を初期化している場合は S_FALSE 等が返るが問題ない
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            if let Ok(list) = CoCreateInstance::<_, ITaskbarList3>(&TaskbarList, None, CLSCTX_INPROC_SERVER) {
+                if list.HrInit().is_ok() {
+                    self.list = Some(list);
+                }
+            }
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn init(&mut self, _frame: &eframe::Frame) {}
+
+    #[cfg(windows)]
+    fn apply(&self, state: Option<(u64, u64)>) {
+        use windows::Win32::UI::Shell::{TBPF_NOPROGRESS, TBPF_NORMAL};
+
+        if let (Some(list), Some(hwnd)) = (&self.list, self.hwnd) {
+            unsafe {
+                match state {
+                    Some((current, total)) => {
+                        let _ = list.SetProgressState(hwnd, TBPF_NORMAL);
+                        let _ = list.SetProgressValue(hwnd, current, total);
+                    }
+                    None => {
+                        let _ = list.SetProgressState(hwnd, TBPF_NOPROGRESS);
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn apply(&self, _state: Option<(u64, u64)>) {}
+}
+
+#[derive(Default)]
+pub struct Tray {
+    #[cfg(windows)]
+    icon: Option<tray_icon::TrayIcon>,
+    restore_requested: Arc<AtomicBool>,
+    tooltip: String,
+}
+
+impl Tray {
+    pub fn is_supported() -> bool {
+        cfg!(windows)
+    }
+
+    pub fn is_shown(&self) -> bool {
+        #[cfg(windows)]
+        {
+            self.icon.is_some()
+        }
+        #[cfg(not(windows))]
+        {
+            false
+        }
+    }
+
+    // トレイアイコンのクリックでウィンドウが復元されたかどうか（一度だけ true を返す）
+    pub fn take_restore_request(&self) -> bool {
+        self.restore_requested.swap(false, Ordering::SeqCst)
+    }
+
+    #[cfg(windows)]
+    pub fn show(&mut self, ctx: &egui::Context, tooltip: &str) -> Result<(), String> {
+        use tray_icon::{Icon, MouseButton, TrayIconBuilder, TrayIconEvent};
+
+        if self.icon.is_some() {
+            return Ok(());
+        }
+
+        // 16x16 の単色アイコン
+        let size = 16u32;
+        let mut rgba = Vec::with_capacity((size * size * 4) as usize);
+        for _ in 0..size * size {
+            rgba.extend_from_slice(&[0x2e, 0x7d, 0x32, 0xff]);
+        }
+        let icon = Icon::from_rgba(rgba, size, size).map_err(|e| e.to_string())?;
+
+        // ウィンドウ非表示中は update() が呼ばれないため、イベントハンドラから直接復元する
+        let ctx = ctx.clone();
+        let restore_requested = self.restore_requested.clone();
+        TrayIconEvent::set_event_handler(Some(move |event: TrayIconEvent| {
+            let clicked = matches!(
+                event,
+                TrayIconEvent::Click { button: MouseButton::Left, .. } | TrayIconEvent::DoubleClick { .. }
+            );
+            if clicked {
+                restore_requested.store(true, Ordering::SeqCst);
+                ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+                ctx.request_repaint();
+            }
+        }));
+
+        let tray = TrayIconBuilder::new()
+            .with_tooltip(tooltip)
+            .with_icon(icon)
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.icon = Some(tray);
+        self.tooltip = tooltip.to_string();
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    pub fn show(&mut self, _ctx: &egui::Context, _tooltip: &str) -> Result<(), String> {
+        Err("System tray is only supported on Windows".to_string())
+    }
+
+    pub fn set_tooltip(&mut self, tooltip: &str) {
+        if self.tooltip == tooltip {
+            return;
+        }
+        self.tooltip = tooltip.to_string();
+        #[cfg(windows)]
+        if let Some(icon) = &self.icon {
+            let _ = icon.set_tooltip(Some(tooltip));
+        }
+    }
+
+    pub fn hide(&mut self) {
+        #[cfg(windows)]
+        {
+            self.icon = None;
+        }
+    }
+}
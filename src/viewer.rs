@@ -0,0 +1,217 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 出力ファイルのページ表示用の索引。stride 個ごとに素数の先頭のバイト位置と値を記録し、
+// 出力ファイルの隣に .idx ファイル（サイドカー）として保存する。
+// 添字での移動は索引から、値での検索は索引の二分探索から、どちらも 1 区間だけ読めば済む。
+// zstd seekable 形式の出力ではバイト位置は展開後のもので、その区間を含むフレームだけを展開する。
+// レコードの長さがそろったファイル（Binary、FixedWidth）は索引を作らず、ファイルを直接二分探索する
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use serde::{Deserialize, Serialize};
+use crate::app::{ProgressPhase, WorkerMessage};
+use crate::error::SieveError;
+use crate::output::{content_len, fixed_record_len, scan_primes};
+use crate::i18n::{format_number, tr};
+
+pub const INDEX_STRIDE: u64 = 4096;
+
+const INDEX_MAGIC: &[u8; 8] = b"SSPIDX01";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PrimeIndex {
+    pub path: PathBuf,
+    pub stride: u64,
+    // ファイル内の素数の個数
+    pub count: u64,
+    // 索引を作ったときのファイルの長さ（変わっていたら作り直す）
+    file_len: u64,
+    // (バイト位置, 値)。i 番目は添字 i * stride の素数
+    entries: Vec<(u64, u64)>,
+    // レコードの長さがそろったファイルならその長さ（entries は空で、添字 i の素数は i * record_len バイト目）
+    record_len: Option<u64>,
+}
+
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".idx");
+    PathBuf::from(name)
+}
+
+impl PrimeIndex {
+    // 停止された場合は None。progress(読んだバイト数, 全体の長さ)。圧縮されたファイルではどちらも展開後のバイト数
+    pub fn build(path: &Path, stride: u64, stop_flag: &AtomicBool, mut progress: impl FnMut(u64, u64)) -> io::Result<Option<PrimeIndex>> {
+        let file_len = std::fs::metadata(path)?.len();
+        let content_len = content_len(path)?;
+        let mut entries = Vec::new();
+        let mut count = 0u64;
+        let mut stopped = false;
+        scan_primes(path, 0, |offset, p| {
+            if count.is_multiple_of(stride) {
+                entries.push((offset, p));
+                if entries.len() % 256 == 0 {
+                    if stop_flag.load(Ordering::SeqCst) {
+                        stopped = true;
+                        return false;
+                    }
+                    progress(offset, content_len);
+                }
+            }
+            count += 1;
+            true
+        })?;
+        Ok((!stopped).then(|| PrimeIndex { path: path.to_path_buf(), stride, count, file_len, entries, record_len: None }))
+    }
+
+    // レコードの長さがそろったファイルなら、読まずに作れる索引
+    pub fn fixed(path: &Path) -> io::Result<Option<PrimeIndex>> {
+        let Some(record_len) = fixed_record_len(path)? else {
+            return Ok(None);
+        };
+        let file_len = std::fs::metadata(path)?.len();
+        let count = content_len(path)? / record_len;
+        Ok(Some(PrimeIndex { path: path.to_path_buf(), stride: 1, count, file_len, entries: Vec::new(), record_len: Some(record_len) }))
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.record_len.is_some()
+    }
+
+    // サイドカーがない、または出力ファイルの長さが変わっている場合は None
+    pub fn load(path: &Path) -> io::Result<Option<PrimeIndex>> {
+        let file_len = std::fs::metadata(path)?.len();
+        let mut reader = match File::open(sidecar_path(path)) {
+            Ok(file) => BufReader::new(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Ok(None);
+        }
+        let mut read_u64 = || -> io::Result<u64> {
+            let mut buf = [0u8; 8];
+            reader.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf))
+        };
+        let stride = read_u64()?;
+        let count = read_u64()?;
+        let indexed_len = read_u64()?;
+        if indexed_len != file_len || stride == 0 {
+            return Ok(None);
+        }
+        let entries = (0..count.div_ceil(stride)).map(|_| Ok((read_u64()?, read_u64()?))).collect::<io::Result<Vec<_>>>()?;
+        Ok(Some(PrimeIndex { path: path.to_path_buf(), stride, count, file_len, entries, record_len: None }))
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(sidecar_path(&self.path))?);
+        out.write_all(INDEX_MAGIC)?;
+        for v in [self.stride, self.count, self.file_len] {
+            out.write_all(&v.to_le_bytes())?;
+        }
+        for &(offset, value) in &self.entries {
+            out.write_all(&offset.to_le_bytes())?;
+            out.write_all(&value.to_le_bytes())?;
+        }
+        out.flush()
+    }
+
+    // 添字 first から最大 len 個
+    pub fn read_page(&self, first: u64, len: usize) -> io::Result<Vec<u64>> {
+        let mut page = Vec::with_capacity(len);
+        if let Some(record_len) = self.record_len {
+            if first < self.count {
+                scan_primes(&self.path, first * record_len, |_, p| {
+                    page.push(p);
+                    page.len() < len
+                })?;
+            }
+            return Ok(page);
+        }
+        let Some(&(offset, _)) = self.entries.get((first / self.stride) as usize) else {
+            return Ok(page);
+        };
+        let mut skip = first % self.stride;
+        scan_primes(&self.path, offset, |_, p| {
+            if skip > 0 {
+                skip -= 1;
+            } else {
+                page.push(p);
+            }
+            page.len() < len
+        })?;
+        Ok(page)
+    }
+
+    // value 以上の最初の素数の添字（すべて value 未満なら count）
+    pub fn position_of(&self, value: u64) -> io::Result<u64> {
+        if self.record_len.is_some() {
+            let (mut low, mut high) = (0, self.count);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                if self.read_page(mid, 1)?.first().is_some_and(|&p| p < value) {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            return Ok(low);
+        }
+        let block = self.entries.partition_point(|&(_, p)| p <= value).saturating_sub(1);
+        let Some(&(offset, _)) = self.entries.get(block) else {
+            return Ok(0);
+        };
+        let mut index = block as u64 * self.stride;
+        let mut found = false;
+        scan_primes(&self.path, offset, |_, p| {
+            if p >= value {
+                found = true;
+                return false;
+            }
+            index += 1;
+            true
+        })?;
+        Ok(if found { index } else { self.count })
+    }
+}
+
+// サイドカーを読み込む（なければ作って保存する）
+pub fn open_index(path: PathBuf, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let open_error = |source| SieveError::OpenFile { path: path.clone(), source };
+    let fixed = PrimeIndex::fixed(&path).map_err(open_error)?;
+    let index = match fixed.map_or_else(|| PrimeIndex::load(&path), |index| Ok(Some(index))).map_err(open_error)? {
+        Some(index) => index,
+        None => {
+            sender.send(WorkerMessage::Log(tr!("Building index for {}", path.display()))).ok();
+            let built = PrimeIndex::build(&path, INDEX_STRIDE, &stop_flag, |current, total| {
+                sender.send(WorkerMessage::Progress { current, total, phase: ProgressPhase::Reading }).ok();
+            })
+            .map_err(open_error)?;
+            let Some(index) = built else {
+                sender.send(WorkerMessage::Stopped).ok();
+                return Ok(());
+            };
+            let sidecar = sidecar_path(&path);
+            match index.save() {
+                Ok(()) => sender.send(WorkerMessage::Log(tr!("Index saved to {}", sidecar.display()))).ok(),
+                Err(e) => sender.send(WorkerMessage::Log(tr!("Failed to save index {}: {}", sidecar.display(), e))).ok(),
+            };
+            index
+        }
+    };
+    if index.is_fixed() {
+        sender.send(WorkerMessage::Log(tr!("Fixed-width records in {}; searching the file directly", path.display()))).ok();
+    }
+    sender.send(WorkerMessage::Log(tr!("{} primes in {}", format_number(index.count), path.display()))).ok();
+    sender.send(WorkerMessage::PrimeIndex(index)).ok();
+    sender.send(WorkerMessage::Done).ok();
+    Ok(())
+}
@@ -0,0 +1,212 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// WebDAV サーバーへのアップロード。書き終えた出力ファイルを webdav_url のコレクションに PUT し、
+// 送り終えたら中身の SHA-256 を隣の「名前.sha256」に置く。出力ファイルの名前はほかの実行と
+// 同じになりうるので、大きさと SHA-256 の両方が一致するときだけ送らずに飛ばす。
+// 各リクエストは失敗しても間隔をあけて再試行し、ファイルの送信が途中で切れたときは、サーバーが
+// Content-Range 付きの PUT を受け付ける限りサーバーにある分の続きから送る。
+// 認証情報は設定ファイルではなく環境変数 WEBDAV_USERNAME / WEBDAV_PASSWORD から読む
+
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use base64::Engine;
+use crate::config::Config;
+use crate::s3::{sha256_file, uri_encode, Destination, Uploaded, FIRST_RETRY_DELAY, MAX_ATTEMPTS};
+
+// アップロードしたファイルの SHA-256（16 進）を置くファイルの接尾辞
+pub const HASH_SUFFIX: &str = ".sha256";
+
+pub struct WebDavClient {
+    // コレクションの URL（/ で終わる）
+    base: String,
+    authorization: Option<String>,
+    agent: ureq::Agent,
+    // コレクションを作ったか確かめたか
+    collection_ready: AtomicBool,
+    // Content-Range 付きの PUT で続きから送れるか（断られたら false にする）
+    partial_put: AtomicBool,
+}
+
+// 応答を分ける。accept の状態コードは成功として返し、エラーには再試行できるか（接続エラー、5xx と 429）を添える
+fn check(result: Result<ureq::Response, ureq::Error>, method: &str, url: &str, accept: &[u16]) -> Result<ureq::Response, (io::Error, bool)> {
+    match result {
+        Ok(response) => Ok(response),
+        Err(ureq::Error::Status(code, response)) if accept.contains(&code) => Ok(response),
+        Err(ureq::Error::Status(code, response)) => {
+            let text = response.into_string().unwrap_or_default();
+            let error = io::Error::other(format!("HTTP {} for {} {}: {}", code, method, url, text.trim()));
+            Err((error, code == 429 || code >= 500))
+        }
+        Err(ureq::Error::Transport(transport)) => Err((io::Error::other(transport.to_string()), true)),
+    }
+}
+
+impl WebDavClient {
+    pub fn new(url: &str, credentials: Option<(String, String)>) -> io::Result<Self> {
+        let url = url.trim();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid WebDAV URL {}", url)));
+        }
+        let base = if url.ends_with('/') { url.to_string() } else { format!("{}/", url) };
+        let authorization = credentials.map(|(user, password)| {
+            format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, password)))
+        });
+        let agent = ureq::AgentBuilder::new().timeout_connect(Duration::from_secs(30)).timeout_read(Duration::from_secs(300)).build();
+        Ok(WebDavClient { base, authorization, agent, collection_ready: AtomicBool::new(false), partial_put: AtomicBool::new(true) })
+    }
+
+    // webdav_url が空なら None
+    pub fn from_config(config: &Config) -> io::Result<Option<Self>> {
+        let url = config.webdav_url.trim();
+        if url.is_empty() {
+            return Ok(None);
+        }
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let credentials = var("WEBDAV_USERNAME").map(|user| (user, var("WEBDAV_PASSWORD").unwrap_or_default()));
+        WebDavClient::new(url, credentials).map(Some)
+    }
+
+    pub fn url_for(&self, path: &Path) -> String {
+        format!("{}{}", self.base, uri_encode(&path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default(), true))
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let call = self.agent.request(method, url);
+        match &self.authorization {
+            Some(auth) => call.set("Authorization", auth),
+            None => call,
+        }
+    }
+
+    // リクエストを送る。body があればそれを本文にする。accept の状態コードは成功として返し、
+    // 接続エラー、5xx と 429 は再試行する
+    fn send(&self, method: &str, url: &str, body: Option<&[u8]>, accept: &[u16]) -> io::Result<ureq::Response> {
+        let mut delay = FIRST_RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            let call = self.request(method, url);
+            let result = match body {
+                Some(body) => call.send_bytes(body),
+                None => call.call(),
+            };
+            let error = match check(result, method, url, accept) {
+                Ok(response) => return Ok(response),
+                Err((error, false)) => return Err(error),
+                Err((error, true)) => error,
+            };
+            if attempt >= MAX_ATTEMPTS {
+                return Err(error);
+            }
+            std::thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+
+    // ファイルを PUT する。失敗したら再試行し、サーバーに途中までの分が残っていればその続きから
+    // Content-Range 付きで送る。範囲付きの PUT を断るサーバー（400、501 など）や、受け付けても
+    // 大きさが合わなかったサーバーでは、以後は先頭から送り直す
+    fn put_file(&self, url: &str, path: &Path) -> io::Result<()> {
+        let len = std::fs::metadata(path)?.len();
+        let mut offset = 0;
+        let mut delay = FIRST_RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            let mut file = File::open(path)?;
+            file.seek(SeekFrom::Start(offset))?;
+            let mut call = self.request("PUT", url).set("Content-Length", &(len - offset).to_string());
+            if offset > 0 {
+                call = call.set("Content-Range", &format!("bytes {}-{}/{}", offset, len - 1, len));
+            }
+            let error = match call.send(file) {
+                Err(ureq::Error::Status(code, _)) if offset > 0 && ((code < 500 && code != 429) || code == 501) => {
+                    self.partial_put.store(false, Ordering::Relaxed);
+                    offset = 0;
+                    continue;
+                }
+                result => match check(result, "PUT", url, &[]) {
+                    Ok(_) if offset > 0 && self.remote_size(url)? != Some(len) => {
+                        self.partial_put.store(false, Ordering::Relaxed);
+                        offset = 0;
+                        continue;
+                    }
+                    Ok(_) => return Ok(()),
+                    Err((error, false)) => return Err(error),
+                    Err((error, true)) => error,
+                },
+            };
+            if attempt >= MAX_ATTEMPTS {
+                return Err(error);
+            }
+            std::thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+            offset = 0;
+            if self.partial_put.load(Ordering::Relaxed) {
+                if let Ok(Some(size)) = self.remote_size(url) {
+                    if size < len {
+                        offset = size;
+                    }
+                }
+            }
+        }
+    }
+
+    // コレクションがなければ作る（すでにあれば 405 が返る）。親のコレクションは作らない
+    fn ensure_collection(&self) -> io::Result<()> {
+        if !self.collection_ready.load(Ordering::Relaxed) {
+            self.send("MKCOL", &self.base, None, &[405])?;
+            self.collection_ready.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    // サーバーにあるファイルの大きさ（なければ None）
+    pub fn remote_size(&self, url: &str) -> io::Result<Option<u64>> {
+        let response = self.send("HEAD", url, None, &[404])?;
+        if response.status() == 404 {
+            return Ok(None);
+        }
+        Ok(response.header("Content-Length").and_then(|v| v.trim().parse().ok()))
+    }
+
+    // 前にアップロードしたときに置いた SHA-256（なければ None）
+    fn remote_hash(&self, url: &str) -> io::Result<Option<String>> {
+        let response = self.send("GET", &format!("{}{}", url, HASH_SUFFIX), None, &[404])?;
+        if response.status() == 404 {
+            return Ok(None);
+        }
+        Ok(Some(response.into_string()?.trim().to_string()))
+    }
+
+    // ファイルをアップロードする。大きさと SHA-256 が同じファイルがすでにあれば送らない。
+    // 送る前に古いファイルと SHA-256 を消すので、途中で止まっても古い SHA-256 が新しい中身に残らず、
+    // 続きから送るときにサーバーに残っているのはこのアップロードで送った分だけになる
+    pub fn upload(&self, path: &Path) -> io::Result<Uploaded> {
+        let url = self.url_for(path);
+        let size = std::fs::metadata(path)?.len();
+        let hash = sha256_file(path)?;
+        self.ensure_collection()?;
+        if self.remote_size(&url)? == Some(size) && self.remote_hash(&url)?.as_deref() == Some(hash.as_str()) {
+            return Ok(Uploaded::Skipped(url));
+        }
+        let hash_url = format!("{}{}", url, HASH_SUFFIX);
+        self.send("DELETE", &hash_url, None, &[404])?;
+        self.send("DELETE", &url, None, &[404])?;
+        self.put_file(&url, path)?;
+        self.send("PUT", &hash_url, Some(hash.as_bytes()), &[])?;
+        Ok(Uploaded::Sent(url))
+    }
+}
+
+impl Destination for WebDavClient {
+    fn upload(&self, path: &Path) -> io::Result<Uploaded> {
+        WebDavClient::upload(self, path)
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 実行の進捗と見つかった素数を WebSocket で配信する。各メッセージは WorkerMessage の JSON
+// （{"Progress":{"current":..,"total":..,"phase":..}}、"Done" など）で、素数は {"Primes":[..]} にまとめて送る。
+// 送信は専用スレッドで行い、遅いクライアントが GUI を止めないようにする
+
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+use serde::Serialize;
+use tungstenite::{Message, WebSocket};
+use crate::app::WorkerMessage;
+
+// 1 つの {"Primes":[..]} にまとめる素数の上限
+pub const PRIME_BATCH_SIZE: usize = 10_000;
+
+// この時間内に送れないクライアントは切断する
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+enum Batch<'a> {
+    Primes(&'a [u64]),
+}
+
+pub struct WsServer {
+    addr: SocketAddr,
+    clients: Arc<Mutex<Vec<WebSocket<TcpStream>>>>,
+    sender: mpsc::Sender<String>,
+    // 配信待ちの素数
+    primes: Vec<u64>,
+}
+
+impl WsServer {
+    pub fn start(bind: &str, port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((bind, port))?;
+        let addr = listener.local_addr()?;
+        let clients: Arc<Mutex<Vec<WebSocket<TcpStream>>>> = Arc::new(Mutex::new(Vec::new()));
+
+        let accepted = Arc::clone(&clients);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let accepted = Arc::clone(&accepted);
+                // ハンドシェイクは接続ごとのスレッドで行う
+                std::thread::spawn(move || {
+                    if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+                        return;
+                    }
+                    if let Ok(socket) = tungstenite::accept(stream) {
+                        accepted.lock().unwrap().push(socket);
+                    }
+                });
+            }
+        });
+
+        let (sender, receiver) = mpsc::channel::<String>();
+        let connected = Arc::clone(&clients);
+        std::thread::spawn(move || {
+            for text in receiver {
+                let mut clients = connected.lock().unwrap();
+                clients.retain_mut(|socket| socket.send(Message::text(text.clone())).is_ok());
+            }
+        });
+
+        Ok(WsServer { addr, clients, sender, primes: Vec::new() })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    // ワーカーからのメッセージを配信する（素数はまとめて、GUI 専用の大きなデータは送らない）
+    pub fn forward(&mut self, message: &WorkerMessage) {
+        match message {
+            WorkerMessage::FoundPrimeIndex(p, _) => {
+                self.primes.push(*p);
+                if self.primes.len() >= PRIME_BATCH_SIZE {
+                    self.flush();
+                }
+            }
+            WorkerMessage::SpiralPrimes { .. } | WorkerMessage::PrimeIndex(_) => {}
+            _ => {
+                self.flush();
+                self.send(message);
+            }
+        }
+    }
+
+    // 配信待ちの素数を送る
+    pub fn flush(&mut self) {
+        if !self.primes.is_empty() {
+            let primes = std::mem::take(&mut self.primes);
+            self.send(&Batch::Primes(&primes));
+        }
+    }
+
+    fn send<T: Serialize>(&self, value: &T) {
+        if let Ok(text) = serde_json::to_string(value) {
+            self.sender.send(text).ok();
+        }
+    }
+}
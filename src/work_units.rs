@@ -0,0 +1,322 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// ファイルのやり取りによるオフラインの分散生成。コーディネーターは作業単位の記述ファイル
+// （HMAC-SHA256 で署名）を書き出し、他のマシンはそれを篩って結果ファイルを作る。
+// 結果ファイルを集めたら、署名・範囲の網羅・素数の並びとハッシュを確かめてから順番に出力する。
+// 鍵は project.json にだけ保存し、作業単位と一緒には配らない
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use hmac::{Hmac, Mac};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::app::{ProgressPhase, WorkerMessage};
+use crate::config::{Config, OverwriteMode};
+use crate::error::SieveError;
+use crate::i18n::{format_number, tr};
+use crate::miller_rabin::verify_prime;
+use crate::output::{push_line, PrimeSink};
+use crate::sieve::{build_thread_pool, integer_sqrt, open_output, parse_filters, segment_ranges, segmented_sieve, simple_sieve};
+
+pub const PROJECT_FILE: &str = "project.json";
+const UNIT_EXTENSION: &str = "unit.json";
+const RESULT_EXTENSION: &str = "result.txt";
+
+// 結果の素数のうち verify_prime で抜き取り確認する間隔
+pub(crate) const SPOT_CHECK_INTERVAL: usize = 1000;
+
+// コーディネーター側だけが持つプロジェクトの情報
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Project {
+    pub project: String,
+    pub prime_min: u64,
+    pub prime_max: u64,
+    pub work_unit_size: u64,
+    // HMAC の鍵（16 進）
+    pub key: String,
+}
+
+impl Project {
+    pub fn units(&self) -> Vec<(u64, u64)> {
+        segment_ranges(self.prime_min, self.prime_max, self.work_unit_size)
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::<Sha256>::new_from_slice(self.key.as_bytes()).expect("HMAC accepts keys of any length")
+    }
+
+    pub fn sign(&self, id: u64, low: u64, high: u64) -> String {
+        let mut mac = self.mac();
+        mac.update(format!("{}:{}:{}:{}", self.project, id, low, high).as_bytes());
+        to_hex(&mac.finalize().into_bytes())
+    }
+
+    pub fn verify(&self, unit: &WorkUnit) -> bool {
+        let mut mac = self.mac();
+        mac.update(format!("{}:{}:{}:{}", unit.project, unit.id, unit.low, unit.high).as_bytes());
+        from_hex(&unit.signature).is_some_and(|signature| mac.verify_slice(&signature).is_ok())
+    }
+}
+
+// 作業単位の記述ファイルの中身
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkUnit {
+    pub project: String,
+    pub id: u64,
+    pub low: u64,
+    pub high: u64,
+    pub signature: String,
+}
+
+// 結果ファイルの 1 行目。2 行目以降は素数を 1 行に 1 つ
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResultHeader {
+    pub unit: WorkUnit,
+    pub count: u64,
+    // 素数の行（改行を含む）の SHA-256
+    pub sha256: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn invalid(path: &Path, message: String) -> SieveError {
+    SieveError::WorkUnit(format!("{}: {}", path.display(), message))
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, SieveError> {
+    let file = File::open(path).map_err(|source| SieveError::OpenFile { path: path.to_path_buf(), source })?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| invalid(path, e.to_string()))
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), SieveError> {
+    let text = serde_json::to_string_pretty(value).map_err(|e| invalid(path, e.to_string()))?;
+    fs::write(path, text).map_err(SieveError::write(path))
+}
+
+// 拡張子が suffix のファイル（名前順）
+fn files_with_suffix(dir: &Path, suffix: &str) -> Result<Vec<PathBuf>, SieveError> {
+    let entries = fs::read_dir(dir).map_err(|source| SieveError::OpenFile { path: dir.to_path_buf(), source })?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(suffix)))
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(SieveError::WorkUnit(tr!("No *.{} files in {}", suffix, dir.display())));
+    }
+    Ok(files)
+}
+
+fn unit_file_name(id: u64, extension: &str) -> String {
+    format!("unit_{:06}.{}", id, extension)
+}
+
+// 作業単位の記述ファイルを <output_dir>/work_units_<project>/ に書き出す
+pub fn export_work_units(config: Config, sender: mpsc::Sender<WorkerMessage>, _stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let prime_min = config.prime_min.parse::<u64>()?;
+    let prime_max = config.prime_max.parse::<u64>()?;
+    if prime_min > prime_max {
+        return Err(SieveError::Config("prime_min must not exceed prime_max".to_string()));
+    }
+    if config.work_unit_size == 0 {
+        return Err(SieveError::Config("work_unit_size must be greater than 0".to_string()));
+    }
+    let mut random = [0u8; 32];
+    getrandom::getrandom(&mut random).map_err(|e| SieveError::WorkUnit(e.to_string()))?;
+    let project = Project {
+        project: to_hex(&random[..8]),
+        prime_min,
+        prime_max,
+        work_unit_size: config.work_unit_size,
+        key: to_hex(&random[8..]),
+    };
+    let dir = Path::new(&config.output_dir).join(format!("work_units_{}", project.project));
+    fs::create_dir_all(&dir).map_err(|source| SieveError::CreateDir { path: dir.clone(), source })?;
+    write_json(&dir.join(PROJECT_FILE), &project)?;
+    let units = project.units();
+    for (id, &(low, high)) in units.iter().enumerate() {
+        let id = id as u64;
+        let unit = WorkUnit { project: project.project.clone(), id, low, high, signature: project.sign(id, low, high) };
+        write_json(&dir.join(unit_file_name(id, UNIT_EXTENSION)), &unit)?;
+    }
+    sender.send(WorkerMessage::Log(tr!("Exported {} work unit files to {}", format_number(units.len() as u64), dir.display()))).ok();
+    sender.send(WorkerMessage::Log(tr!("Hand out the unit_*.unit.json files and keep {} private.", PROJECT_FILE).to_string())).ok();
+    Ok(())
+}
+
+// dir の作業単位のうち結果ファイルがまだないものを篩い、結果を同じディレクトリに書く
+pub fn process_work_units(config: Config, dir: PathBuf, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    if config.segment_size == 0 {
+        return Err(SieveError::Config("segment_size must be greater than 0".to_string()));
+    }
+    let units = files_with_suffix(&dir, UNIT_EXTENSION)?;
+    let pool = build_thread_pool(&config)?;
+    let mut small_primes: Vec<u64> = Vec::new();
+    let mut sieve_limit = 0u64;
+    let mut done = 0u64;
+    for (index, path) in units.iter().enumerate() {
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        let unit: WorkUnit = read_json(path)?;
+        let result_path = dir.join(unit_file_name(unit.id, RESULT_EXTENSION));
+        if result_path.exists() || unit.low > unit.high {
+            continue;
+        }
+        let root = integer_sqrt(unit.high) + 1;
+        if root > sieve_limit {
+            small_primes = simple_sieve(root);
+            sieve_limit = root;
+        }
+        let segments = segment_ranges(unit.low, unit.high, config.segment_size);
+        let primes: Vec<u64> = pool.install(|| {
+            segments
+                .par_iter()
+                .map(|&(low, high)| segmented_sieve(&small_primes, low, high, &stop_flag))
+                .collect::<Vec<_>>()
+                .concat()
+        });
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+
+        let mut body = Vec::new();
+        for &p in &primes {
+            push_line(&mut body, p);
+        }
+        let header = ResultHeader { unit: unit.clone(), count: primes.len() as u64, sha256: to_hex(&Sha256::digest(&body)) };
+        // 途中で止まっても完全な結果ファイルだけが残るよう一時ファイルから名前を変える
+        let temp_path = result_path.with_extension("tmp");
+        let file = File::create(&temp_path).map_err(|source| SieveError::OpenFile { path: temp_path.clone(), source })?;
+        let mut out = BufWriter::new(file);
+        let header = serde_json::to_string(&header).map_err(|e| invalid(&result_path, e.to_string()))?;
+        writeln!(out, "{}", header).map_err(SieveError::write(&temp_path))?;
+        out.write_all(&body).map_err(SieveError::write(&temp_path))?;
+        out.flush().map_err(SieveError::write(&temp_path))?;
+        drop(out);
+        fs::rename(&temp_path, &result_path).map_err(SieveError::write(&result_path))?;
+
+        done += 1;
+        sender.send(WorkerMessage::Log(tr!("Work unit {} [{}, {}]: {} primes", unit.id, format_number(unit.low), format_number(unit.high), format_number(primes.len() as u64)))).ok();
+        sender.send(WorkerMessage::Progress { current: index as u64 + 1, total: units.len() as u64, phase: ProgressPhase::Sieving }).ok();
+    }
+    sender.send(WorkerMessage::Log(tr!("Processed {} work units. Send the unit_*.result.txt files back to the coordinator.", format_number(done)))).ok();
+    Ok(())
+}
+
+// 結果ファイル 1 つを読みながら確かめる。f には素数が順に渡る
+fn scan_result(path: &Path, project: &Project, units: &[(u64, u64)], mut f: impl FnMut(u64) -> Result<(), SieveError>) -> Result<ResultHeader, SieveError> {
+    let file = File::open(path).map_err(|source| SieveError::OpenFile { path: path.to_path_buf(), source })?;
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|source| SieveError::OpenFile { path: path.to_path_buf(), source })?;
+    let header: ResultHeader = serde_json::from_str(&line).map_err(|e| invalid(path, e.to_string()))?;
+    let unit = &header.unit;
+    if unit.project != project.project {
+        return Err(invalid(path, tr!("belongs to project {}", unit.project)));
+    }
+    if units.get(unit.id as usize) != Some(&(unit.low, unit.high)) || !project.verify(unit) {
+        return Err(invalid(path, tr!("invalid signature for work unit {}", unit.id)));
+    }
+
+    let mut hasher = Sha256::new();
+    let mut count = 0u64;
+    let mut previous = None;
+    for line in reader.lines() {
+        let line = line.map_err(|source| SieveError::OpenFile { path: path.to_path_buf(), source })?;
+        hasher.update(line.as_bytes());
+        hasher.update(b"\n");
+        let p: u64 = line.trim().parse().map_err(|_| invalid(path, tr!("invalid number {}", format!("{:?}", line))))?;
+        if p < unit.low || p > unit.high || previous.is_some_and(|q| q >= p) {
+            return Err(invalid(path, tr!("{} is out of order or outside [{}, {}]", p, unit.low, unit.high)));
+        }
+        if count.is_multiple_of(SPOT_CHECK_INTERVAL as u64) && !verify_prime(p) {
+            return Err(invalid(path, tr!("{} is not prime", p)));
+        }
+        previous = Some(p);
+        count += 1;
+        f(p)?;
+    }
+    if count != header.count || to_hex(&hasher.finalize()) != header.sha256 {
+        return Err(invalid(path, tr!("the primes do not match the count and hash in the header").to_string()));
+    }
+    Ok(header)
+}
+
+// dir（project.json と結果ファイル）を確かめ、すべての作業単位がそろっていれば出力ファイルに順に書く
+pub fn stitch_results(config: Config, dir: PathBuf, sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>) -> Result<(), SieveError> {
+    let project: Project = read_json(&dir.join(PROJECT_FILE))?;
+    let units = project.units();
+    let results = files_with_suffix(&dir, RESULT_EXTENSION)?;
+
+    // 1 回目: すべての結果ファイルを確かめて作業単位ごとに割り当てる
+    let mut by_unit: Vec<Option<PathBuf>> = vec![None; units.len()];
+    for (index, path) in results.iter().enumerate() {
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        let header = scan_result(path, &project, &units, |_| Ok(()))?;
+        let slot = &mut by_unit[header.unit.id as usize];
+        if let Some(first) = slot {
+            sender.send(WorkerMessage::Log(tr!("Work unit {} appears in both {} and {}; using the first", header.unit.id, first.display(), path.display()))).ok();
+        } else {
+            *slot = Some(path.clone());
+        }
+        sender.send(WorkerMessage::Progress { current: index as u64 + 1, total: results.len() as u64 * 2, phase: ProgressPhase::Verifying }).ok();
+    }
+    let missing: Vec<String> = by_unit.iter().enumerate().filter(|(_, p)| p.is_none()).map(|(id, _)| id.to_string()).collect();
+    if !missing.is_empty() {
+        let shown = missing.iter().take(20).cloned().collect::<Vec<_>>().join(", ");
+        let more = if missing.len() > 20 { ", ..." } else { "" };
+        return Err(SieveError::WorkUnit(tr!("{} of {} work units are missing: {}{}", missing.len(), units.len(), shown, more)));
+    }
+
+    // 2 回目: 作業単位の順に出力する（Ask は確認ダイアログを出せないので別名で書く）
+    let mut config = config;
+    config.prime_min = project.prime_min.to_string();
+    config.prime_max = project.prime_max.to_string();
+    if config.overwrite_mode == OverwriteMode::Ask {
+        config.overwrite_mode = OverwriteMode::Version;
+    }
+    if !config.output_dir.is_empty() {
+        fs::create_dir_all(&config.output_dir)
+            .map_err(|source| SieveError::CreateDir { path: PathBuf::from(&config.output_dir), source })?;
+    }
+    let filters = parse_filters(&config)?;
+    let mut out = open_output(&config, filters, &sender)?;
+    let mut found_count = 0u64;
+    for (index, path) in by_unit.iter().flatten().enumerate() {
+        if stop_flag.load(Ordering::SeqCst) {
+            sender.send(WorkerMessage::Stopped).ok();
+            return Ok(());
+        }
+        scan_result(path, &project, &units, |p| {
+            found_count += 1;
+            out.write_prime(p).map_err(|source| SieveError::Write { path: out.current_path().map(Path::to_path_buf).unwrap_or_default(), source })
+        })?;
+        sender.send(WorkerMessage::Progress { current: (results.len() + index + 1) as u64, total: results.len() as u64 * 2, phase: ProgressPhase::Writing }).ok();
+    }
+    out.finish().map_err(|source| SieveError::Write { path: out.current_path().map(Path::to_path_buf).unwrap_or_default(), source })?;
+    sender.send(WorkerMessage::Log(tr!("Stitched {} work units [{}, {}]. Total primes: {}", units.len(), format_number(project.prime_min), format_number(project.prime_max), format_number(found_count)))).ok();
+    sender.send(WorkerMessage::Finished { found: found_count, files: out.written_files() }).ok();
+    Ok(())
+}
@@ -0,0 +1,69 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use sosu_seisei_sieve::aggregates::Aggregates;
+use sosu_seisei_sieve::app::WorkerMessage;
+use sosu_seisei_sieve::config::Config;
+use sosu_seisei_sieve::i18n::format_u128;
+use sosu_seisei_sieve::sieve::{run_program_old, simple_sieve};
+
+#[test]
+fn sums_and_counts_segments_in_any_order() {
+    let primes = simple_sieve(1_000_000);
+    let mut aggregates = Aggregates::new(4);
+    for chunk in primes.chunks(1000).rev() {
+        aggregates.add_segment(chunk);
+    }
+    assert_eq!(aggregates.count, 78_498);
+    assert_eq!(aggregates.sum, 37_550_402_023);
+    // 小さい項から足した値と比べる
+    let expected: f64 = primes.iter().rev().map(|&p| 1.0 / p as f64).sum();
+    assert!((aggregates.reciprocal_sum() - expected).abs() < 1e-12, "{} {}", aggregates.reciprocal_sum(), expected);
+    assert_eq!(aggregates.nonzero_residues().collect::<Vec<_>>(), [(1, 39_175), (2, 1), (3, 39_322)]);
+
+    // u64 に収まらない和
+    let mut large = Aggregates::new(0);
+    large.add_segment(&[u64::MAX, u64::MAX]);
+    assert_eq!(large.sum, 2 * u64::MAX as u128);
+    assert_eq!(format_u128(large.sum), "36,893,488,147,419,103,230");
+    assert_eq!(large.summary_lines().len(), 2);
+}
+
+#[test]
+fn generate_reports_aggregates_before_finishing() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_aggregates_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_min: "1000".to_string(),
+        prime_max: "100000".to_string(),
+        segment_size: 7919,
+        descending: true,
+        aggregates: true,
+        aggregate_modulus: 3,
+        output_dir: dir.to_string_lossy().into_owned(),
+        ..Config::default()
+    };
+    let (sender, messages) = mpsc::channel();
+    let (_commands_sender, commands) = mpsc::channel();
+    run_program_old(config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap();
+
+    let messages: Vec<WorkerMessage> = messages.try_iter().collect();
+    let position = |f: fn(&WorkerMessage) -> bool| messages.iter().position(f).unwrap();
+    let aggregates = messages.iter().find_map(|m| match m {
+        WorkerMessage::Aggregates(a) => Some(a.clone()),
+        _ => None,
+    });
+    let aggregates = aggregates.unwrap();
+    assert!(position(|m| matches!(m, WorkerMessage::Aggregates(_))) < position(|m| matches!(m, WorkerMessage::Finished { .. })));
+    let primes: Vec<u64> = simple_sieve(100_000).into_iter().filter(|&p| p >= 1000).collect();
+    assert_eq!(aggregates.count, primes.len() as u64);
+    assert_eq!(aggregates.sum, primes.iter().map(|&p| p as u128).sum::<u128>());
+    assert_eq!(aggregates.nonzero_residues().map(|(_, n)| n).sum::<u64>(), primes.len() as u64);
+    assert_eq!(aggregates.residue_counts[0], 0);
+    fs::remove_dir_all(&dir).ok();
+}
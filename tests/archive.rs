@@ -0,0 +1,76 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use sosu_seisei_sieve::archive::{archive_file, archive_path, ArchiveSink};
+use sosu_seisei_sieve::config::{Config, OutputFormat, OverwriteMode};
+use sosu_seisei_sieve::output::{PrimeSink, PrimeWriter};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_archive_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// 1 つのファイルだけを含む tar を展開して (名前, 中身) を返す
+fn extract(archive: &Path) -> (String, Vec<u8>) {
+    let tar = zstd::decode_all(fs::File::open(archive).unwrap()).unwrap();
+    assert_eq!(tar.len() % 512, 0);
+    let header = &tar[..512];
+    assert_eq!(&header[257..263], b"ustar\0");
+    let stored: u64 = u64::from_str_radix(std::str::from_utf8(&header[148..154]).unwrap(), 8).unwrap();
+    let sum: u64 = header.iter().enumerate().map(|(i, &b)| if (148..156).contains(&i) { b' ' as u64 } else { b as u64 }).sum();
+    assert_eq!(stored, sum);
+    let name = String::from_utf8(header[..100].iter().copied().take_while(|&b| b != 0).collect()).unwrap();
+    let size = u64::from_str_radix(std::str::from_utf8(&header[124..135]).unwrap(), 8).unwrap() as usize;
+    assert!(tar[512 + size..].iter().all(|&b| b == 0));
+    assert!(tar.len() >= 512 + size + 1024);
+    (name, tar[512..512 + size].to_vec())
+}
+
+#[test]
+fn archives_a_file_and_removes_it() {
+    let dir = temp_dir("file");
+    let path = dir.join("primes.txt");
+    let text = (0..1000).map(|i| format!("{}\n", i)).collect::<String>();
+    fs::write(&path, &text).unwrap();
+    let archive = archive_file(&path, 3).unwrap();
+    assert_eq!(archive, dir.join("primes.txt.tar.zst"));
+    assert!(!path.exists());
+    assert_eq!(extract(&archive), ("primes.txt".to_string(), text.into_bytes()));
+    // 元のファイルがなければ何も残さない
+    assert!(archive_file(&dir.join("missing.txt"), 3).is_err());
+    assert!(!archive_path(&dir.join("missing.txt")).exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn archives_each_split_file_when_it_is_finished() {
+    let dir = temp_dir("split");
+    let writer = PrimeWriter::create(OutputFormat::Text, None, &dir, 2, 16, &OverwriteMode::Overwrite).unwrap();
+    let config = Config { archive_outputs: true, ..Config::default() };
+    let (sender, receiver) = mpsc::channel();
+    let mut out = ArchiveSink::new(writer, &config, &sender);
+    for p in [2, 3, 5] {
+        out.write_prime(p).unwrap();
+    }
+    assert_eq!(out.completed(), 0);
+    // 2 つ目のファイルに移ったあと、次の素数を書く前に 1 つ目をまとめる
+    out.write_prime(7).unwrap();
+    assert_eq!(out.completed(), 1);
+    out.write_prime(11).unwrap();
+    assert!(!dir.join("primes_1.txt").exists());
+    assert!(dir.join("primes_2.txt").exists());
+    out.finish().unwrap();
+    let expected: Vec<PathBuf> = (1..=3).map(|i| dir.join(format!("primes_{}.txt.tar.zst", i))).collect();
+    assert_eq!(out.written_files(), expected);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+    assert_eq!(extract(&expected[1]), ("primes_2.txt".to_string(), b"5\n7\n".to_vec()));
+    assert_eq!(receiver.try_iter().count(), 3);
+    fs::remove_dir_all(&dir).unwrap();
+}
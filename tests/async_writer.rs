@@ -0,0 +1,69 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use sosu_seisei_sieve::async_writer::AsyncWriter;
+
+#[test]
+fn writes_blocks_in_order() {
+    let mut writer = AsyncWriter::new(Vec::new(), 7, 2);
+    let mut expected = Vec::new();
+    for i in 0..1000u32 {
+        let line = format!("{}\n", i);
+        writer.write_all(line.as_bytes()).unwrap();
+        expected.extend_from_slice(line.as_bytes());
+    }
+    writer.flush().unwrap();
+    let (written, unwritten) = writer.into_parts();
+    assert_eq!(written, expected);
+    assert!(unwritten.is_empty());
+}
+
+// 最初の failures 回の書き込みで失敗する（ディスク容量不足のかわり）
+struct Flaky {
+    data: Vec<u8>,
+    failures: Arc<AtomicUsize>,
+}
+
+impl Write for Flaky {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.failures.load(Ordering::SeqCst) > 0 {
+            self.failures.fetch_sub(1, Ordering::SeqCst);
+            return Err(io::Error::other("disk full"));
+        }
+        // 一度に少しずつしか書けない
+        let n = buf.len().min(5);
+        self.data.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn reports_failures_and_rewrites_the_pending_data() {
+    let failures = Arc::new(AtomicUsize::new(0));
+    let mut writer = AsyncWriter::new(Flaky { data: Vec::new(), failures: Arc::clone(&failures) }, 8, 1);
+    writer.write_all(b"0123456789").unwrap();
+    writer.flush().unwrap();
+
+    failures.store(2, Ordering::SeqCst);
+    writer.write_all(b"abcdefghij").unwrap();
+    // 失敗は flush で返り、その後に受け取ったデータも順に溜まる
+    let error = writer.flush().unwrap_err();
+    assert_eq!(error.to_string(), "disk full");
+    // 書き直しで 2 回目の失敗が返る
+    assert!(writer.flush().is_err());
+    assert!(writer.write_all(b"KLMNOP").is_ok());
+    writer.flush().unwrap();
+
+    let (flaky, unwritten) = writer.into_parts();
+    assert_eq!(flaky.data, b"0123456789abcdefghijKLMNOP");
+    assert!(unwritten.is_empty());
+}
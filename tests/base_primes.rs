@@ -0,0 +1,73 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use sosu_seisei_sieve::base_primes::{load_small_primes, read_base_primes, write_base_primes};
+use sosu_seisei_sieve::sieve::simple_sieve;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn round_trips_and_detects_tampering() {
+    let dir = temp_dir("base_primes_round_trip");
+    let path = dir.join("base.txt");
+    let primes = simple_sieve(1000);
+    write_base_primes(&path, 1000, &primes).unwrap();
+    let base = read_base_primes(&path).unwrap();
+    assert_eq!((base.limit, base.has_header), (1000, true));
+    assert_eq!(base.primes, primes);
+
+    // 素数を 1 つ書き換えるとハッシュが合わない
+    let text = std::fs::read_to_string(&path).unwrap().replacen("\n997\n", "\n991\n", 1);
+    std::fs::write(&path, text).unwrap();
+    assert!(read_base_primes(&path).is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn imports_plain_prime_files() {
+    let dir = temp_dir("base_primes_plain");
+    let path = dir.join("primes.csv");
+    std::fs::write(&path, "2,3,5,7,11,13,").unwrap();
+    let base = read_base_primes(&path).unwrap();
+    assert_eq!((base.limit, base.has_header), (13, false));
+    assert_eq!(base.primes, vec![2, 3, 5, 7, 11, 13]);
+
+    for bad in ["3\n5\n7\n", "2\n5\n3\n", "2\n3\n9\n"] {
+        std::fs::write(&path, bad).unwrap();
+        assert!(read_base_primes(&path).is_err(), "{:?} was accepted", bad);
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn caches_and_extends_base_primes() {
+    let dir = temp_dir("base_primes_cache");
+    let path = dir.join("base.txt");
+    let file = path.display().to_string();
+    let (sender, _receiver) = mpsc::channel();
+
+    assert_eq!(load_small_primes(&file, 100, &sender), simple_sieve(100));
+    assert_eq!(read_base_primes(&path).unwrap().limit, 100);
+    // 範囲が小さければ読み込んだ素数を切り詰める
+    assert_eq!(load_small_primes(&file, 50, &sender), simple_sieve(50));
+    assert_eq!(read_base_primes(&path).unwrap().limit, 100);
+    // 足りなければ篩い直して保存し直す
+    assert_eq!(load_small_primes(&file, 500, &sender), simple_sieve(500));
+    assert_eq!(read_base_primes(&path).unwrap().limit, 500);
+
+    // ヘッダーのないファイルは足りなくても上書きしない
+    let plain = dir.join("plain.txt");
+    std::fs::write(&plain, "2\n3\n5\n7\n").unwrap();
+    assert_eq!(load_small_primes(&plain.display().to_string(), 30, &sender), simple_sieve(30));
+    assert_eq!(std::fs::read_to_string(&plain).unwrap(), "2\n3\n5\n7\n");
+    let _ = std::fs::remove_dir_all(&dir);
+}
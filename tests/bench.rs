@@ -0,0 +1,81 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use sosu_seisei_sieve::bench::{bench_cases, compare, first_difference, load_history, previous_report, run_bench, run_compare, save_history, BenchReport, BenchResult};
+use sosu_seisei_sieve::config::Config;
+
+fn report(version: &str, quick: bool, primes: u64, numbers_per_sec: f64) -> BenchReport {
+    BenchReport {
+        version: version.to_string(),
+        quick,
+        threads: 8,
+        segment_size: 1 << 20,
+        results: vec![BenchResult { name: "pi(1e7)".to_string(), low: 0, high: 10_000_000, primes, secs: 0.1, numbers_per_sec }],
+    }
+}
+
+#[test]
+fn names_the_canonical_ranges() {
+    let names: Vec<String> = bench_cases(false).into_iter().map(|(name, ..)| name).collect();
+    assert_eq!(names, ["pi(1e9)", "1e10 at 1e12", "1e9 at 1e15"]);
+    assert_eq!(bench_cases(true)[1], ("1e8 at 1e12".to_string(), 1_000_000_000_000, 1_000_100_000_000));
+}
+
+#[test]
+fn compares_with_the_previous_run() {
+    let current = report("0.2.0", true, 664_579, 1.1e9);
+    let (lines, mismatch) = compare(&current, None);
+    assert_eq!((lines, mismatch), (vec!["No previous results to compare with".to_string()], false));
+    let (lines, mismatch) = compare(&current, Some(&report("0.1.0", true, 664_579, 1e9)));
+    assert_eq!((lines, mismatch), (vec!["pi(1e7): +10.0% against 0.1.0 (1,000,000,000/s)".to_string()], false));
+
+    // 既知の値とも前回とも合わない個数
+    let (lines, mismatch) = compare(&report("0.2.0", true, 664_578, 1e9), Some(&report("0.1.0", true, 664_579, 1e9)));
+    assert!(mismatch);
+    assert_eq!(lines[0], "pi(1e7): counted 664,578 primes, but the known value is 664,579");
+    assert_eq!(lines[1], "pi(1e7): counted 664,578 primes, but the previous run counted 664,579");
+}
+
+#[test]
+fn keeps_the_history_per_kind() {
+    let path = std::env::temp_dir().join(format!("sosu_seisei_test_bench_{}", std::process::id())).join("bench_history.json");
+    assert!(load_history(&path).is_empty());
+    let history = vec![report("0.1.0", false, 664_579, 1e9), report("0.1.0", true, 664_579, 2e9), report("0.1.1", false, 664_579, 3e9)];
+    save_history(&path, &history).unwrap();
+    let history = load_history(&path);
+    assert_eq!(previous_report(&history, false).unwrap().version, "0.1.1");
+    assert_eq!(previous_report(&history, true).unwrap().results[0].numbers_per_sec, 2e9);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn stops_when_asked() {
+    let mut out = Vec::new();
+    assert_eq!(run_bench(&Config::default(), true, &Arc::new(AtomicBool::new(true)), &mut out).unwrap(), None);
+    assert!(String::from_utf8(out).unwrap().contains(" bench: "));
+}
+
+#[test]
+fn compares_the_sieve_with_miller_rabin() {
+    let config = Config { prime_min: "999999000000".to_string(), prime_max: "1000000100000".to_string(), segment_size: 100_000, ..Config::default() };
+    let mut out = Vec::new();
+    let report = run_compare(&config, &Arc::new(AtomicBool::new(false)), &mut out).unwrap().unwrap();
+    let methods: Vec<&str> = report.results.iter().map(|r| r.method.as_str()).collect();
+    assert_eq!(methods, ["sieve", "miller-rabin"]);
+    assert_eq!(report.results[0].primes, report.results[1].primes);
+    assert!(report.identical);
+    assert!(String::from_utf8(out).unwrap().contains("Identical output"));
+
+    let too_long = Config { prime_min: "1".to_string(), prime_max: "2000000000".to_string(), ..Config::default() };
+    assert!(run_compare(&too_long, &Arc::new(AtomicBool::new(false)), &mut Vec::new()).is_err());
+    assert_eq!(run_compare(&config, &Arc::new(AtomicBool::new(true)), &mut Vec::new()).unwrap(), None);
+
+    assert_eq!(first_difference(&[2, 3, 5], &[2, 3, 5]), None);
+    assert_eq!(first_difference(&[2, 3, 5, 7], &[2, 3, 7]), Some(5));
+    assert_eq!(first_difference(&[2, 3], &[2, 3, 5]), Some(5));
+}
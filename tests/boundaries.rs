@@ -0,0 +1,108 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 範囲の端の扱い: どの経路でも [min, max] の両端を含み、0 と 1 は素数ではない。
+// 篩の各経路が Miller-Rabin で 1 つずつ判定した結果と同じ集合を返すことを確かめる
+
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use proptest::prelude::*;
+use sosu_seisei_sieve::config::{Config, RunMode};
+use sosu_seisei_sieve::miller_rabin::is_prime;
+use sosu_seisei_sieve::nth_prime::run_nth_prime;
+use sosu_seisei_sieve::output::read_primes;
+use sosu_seisei_sieve::primes::{count_primes, Primes};
+use sosu_seisei_sieve::sieve::{run_program_old, segmented_sieve, simple_sieve};
+
+fn by_miller_rabin(min: u64, max: u64) -> Vec<u64> {
+    (min..=max).filter(|&n| is_prime(n)).collect()
+}
+
+// 小さい数の近くと、大きい数の近く
+fn range() -> impl Strategy<Value = (u64, u64)> {
+    prop_oneof![
+        (0u64..2000, 0u64..2000),
+        (0u64..1_000_000_000_000, 0u64..3000).prop_map(|(min, width)| (min, min + width)),
+    ]
+}
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("sosu_seisei_test_boundaries_{}_{}", name, std::process::id()))
+}
+
+fn written(dir: &std::path::Path) -> Vec<u64> {
+    let mut primes = Vec::new();
+    read_primes(&dir.join("primes.txt"), |p| primes.push(p)).unwrap();
+    primes
+}
+
+#[test]
+fn small_ranges_include_both_ends() {
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let small_primes = simple_sieve(100);
+    for (min, max, expected) in [(0, 1, vec![]), (1, 2, vec![2]), (2, 2, vec![2]), (0, 3, vec![2, 3]), (4, 4, vec![]), (7, 11, vec![7, 11])] {
+        assert_eq!(segmented_sieve(&small_primes, min, max, &stop_flag), expected, "[{}, {}]", min, max);
+        assert_eq!(Primes::new(min, max).collect::<Vec<_>>(), expected, "[{}, {}]", min, max);
+        assert_eq!(count_primes(min, max), expected.len() as u64, "[{}, {}]", min, max);
+    }
+}
+
+proptest! {
+    #[test]
+    fn library_paths_agree_with_miller_rabin((a, b) in range()) {
+        let (min, max) = (a.min(b), a.max(b));
+        let expected = by_miller_rabin(min, max);
+        prop_assert_eq!(Primes::new(min, max).collect::<Vec<_>>(), expected.clone());
+        prop_assert_eq!(count_primes(min, max), expected.len() as u64);
+        // 素数そのものが篩に使う小さい素数に入っていても消さない
+        let small_primes = simple_sieve((max as f64).sqrt() as u64 + 1);
+        prop_assert_eq!(segmented_sieve(&small_primes, min, max, &Arc::new(AtomicBool::new(false))), expected);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(24))]
+
+    #[test]
+    fn generate_and_nth_prime_agree_with_miller_rabin((a, b) in range(), segment_size in 1u64..500, descending: bool, adaptive_segments: bool) {
+        let (min, max) = (a.min(b), a.max(b));
+        let expected = by_miller_rabin(min, max);
+        let dir = temp_dir("generate");
+        let _ = fs::remove_dir_all(&dir);
+        let config = Config {
+            prime_min: min.to_string(),
+            prime_max: max.to_string(),
+            segment_size,
+            adaptive_segments,
+            output_dir: dir.to_string_lossy().into_owned(),
+            ..Config::default()
+        };
+        let run = |config: Config| {
+            let (sender, _messages) = mpsc::channel();
+            let (_commands_sender, commands) = mpsc::channel();
+            if config.mode == RunMode::NthPrime {
+                run_nth_prime(config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap();
+            } else {
+                run_program_old(config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap();
+            }
+        };
+
+        run(Config { descending, ..config.clone() });
+        let mut primes = written(&dir);
+        if descending {
+            primes.reverse();
+        }
+        prop_assert_eq!(&primes, &expected);
+        fs::remove_dir_all(&dir).unwrap();
+
+        // N 番目の素数モードは prime_min 自身から数える
+        if !expected.is_empty() {
+            run(Config { mode: RunMode::NthPrime, nth_count: expected.len() as u64, ..config });
+            prop_assert_eq!(written(&dir), expected);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use sosu_seisei_sieve::base_primes::read_base_primes;
+use sosu_seisei_sieve::cache::DiskCache;
+use sosu_seisei_sieve::config::Config;
+use sosu_seisei_sieve::sieve::simple_sieve;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn open(dir: &Path) -> DiskCache {
+    DiskCache::open(&Config { cache_dir: dir.display().to_string(), ..Config::default() }).unwrap()
+}
+
+#[test]
+fn is_disabled_without_a_directory() {
+    assert!(DiskCache::open(&Config::default()).is_none());
+}
+
+#[test]
+fn stores_base_primes_by_power_of_two_limit() {
+    let dir = temp_dir("cache_base_primes");
+    let cache = open(&dir);
+    let (sender, _receiver) = mpsc::channel();
+
+    assert_eq!(cache.base_primes(100_000, &sender), simple_sieve(100_000));
+    assert!(dir.join("base_primes_131072.txt").exists());
+    // 上限以下の root では同じファイルを使う
+    assert_eq!(cache.base_primes(70_000, &sender), simple_sieve(70_000));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    // 壊れたファイルは消して作り直す
+    std::fs::write(dir.join("base_primes_131072.txt"), "{\"limit\":131072,\"count\":1,\"sha256\":\"00\"}\n2\n").unwrap();
+    assert_eq!(cache.base_primes(100_000, &sender), simple_sieve(100_000));
+    assert_eq!(read_base_primes(&dir.join("base_primes_131072.txt")).unwrap().limit, 131_072);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn keeps_one_calibration_per_setting() {
+    let dir = temp_dir("cache_calibration");
+    let cache = open(&dir);
+    assert!(cache.calibration(8, 1_000_000, 1_000_000_000).is_none());
+    cache.store_calibration(8, 1_000_000, 1_000_000_000, 1.0e9).unwrap();
+    cache.store_calibration(8, 1_000_000, 1_000_000_000, 2.0e9).unwrap();
+    cache.store_calibration(4, 1_000_000, 1_000_000_000, 5.0e8).unwrap();
+    assert_eq!(cache.calibration(8, 1_000_000, 1_000_000_000).unwrap().numbers_per_sec, 2.0e9);
+    // 同じ桁数なら同じ測定結果を使う
+    assert_eq!(cache.calibration(4, 1_000_000, 9_999_999_999).unwrap().numbers_per_sec, 5.0e8);
+    assert!(cache.calibration(4, 1_000_000, 10_000_000_000).is_none());
+    assert!(cache.calibration(8, 2_000_000, 1_000_000_000).is_none());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,88 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use sosu_seisei_sieve::carmichael::carmichael_numbers_in;
+use sosu_seisei_sieve::factor::{ecm, factorize, factorize_big, format_factors};
+use sosu_seisei_sieve::pratt::pratt_certificate;
+use sosu_seisei_sieve::sieve::simple_sieve;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use num_bigint::BigUint;
+use num_traits::One;
+
+#[test]
+fn factorization() {
+    assert_eq!(factorize(1), vec![]);
+    assert_eq!(factorize(360), vec![(2, 3), (3, 2), (5, 1)]);
+    assert_eq!(factorize(999_983), vec![(999_983, 1)]);
+    // 試し割りの上限を超える素因数
+    assert_eq!(factorize(1_000_003 * 1_000_033), vec![(1_000_003, 1), (1_000_033, 1)]);
+    assert_eq!(factorize(4_294_967_291 * 4_294_967_291), vec![(4_294_967_291, 2)]);
+    assert_eq!(factorize(u64::MAX), vec![(3, 1), (5, 1), (17, 1), (257, 1), (641, 1), (65537, 1), (6_700_417, 1)]);
+    for n in 2..20_000u64 {
+        let factors = factorize(n);
+        assert_eq!(factors.iter().map(|&(p, e)| p.pow(e)).product::<u64>(), n);
+    }
+    assert_eq!(format_factors(&factorize(561)), "3 * 11 * 17");
+    assert_eq!(format_factors(&factorize(40)), "2^3 * 5");
+}
+
+#[test]
+fn carmichael_numbers() {
+    let stop = Arc::new(AtomicBool::new(false));
+    let small_primes = simple_sieve(1000);
+    let found: Vec<u64> = carmichael_numbers_in(&small_primes, 1, 100_000, &stop).into_iter().map(|(n, _)| n).collect();
+    // OEIS A002997
+    assert_eq!(found, vec![561, 1105, 1729, 2465, 2821, 6601, 8911, 10585, 15841, 29341, 41041, 46657, 52633, 62745, 63973, 75361]);
+    // 1e6 未満には 43 個
+    assert_eq!(carmichael_numbers_in(&small_primes, 1, 1_000_000, &stop).len(), 43);
+}
+
+#[test]
+fn pratt_certificates() {
+    let cert = pratt_certificate(1_000_003).unwrap();
+    assert!(cert.verify());
+    assert_eq!(cert.factors, factorize(1_000_002));
+    assert!(pratt_certificate(561).is_none());
+    for p in [2, 3, 5, 65537, 4_294_967_291, 18_446_744_073_709_551_557] {
+        assert!(pratt_certificate(p).unwrap().verify(), "{}", p);
+    }
+
+    // 証拠が位数 p - 1 でない場合は検証に失敗する
+    let mut bad = pratt_certificate(101).unwrap();
+    bad.witness = 5;
+    assert!(!bad.verify());
+    let mut bad = pratt_certificate(1_000_003).unwrap();
+    bad.children.clear();
+    assert!(!bad.verify());
+
+    let text = pratt_certificate(103).unwrap().to_text();
+    let lines: Vec<&str> = text.lines().filter(|l| !l.starts_with('#')).collect();
+    assert_eq!(lines, vec!["3: witness 2, p - 1 = 2", "17: witness 3, p - 1 = 2^4", "103: witness 5, p - 1 = 2 * 3 * 17"]);
+}
+
+#[test]
+fn big_factorization() {
+    let stop = AtomicBool::new(false);
+    // 2^64 + 1 = 274177 * 67280421310721
+    let n = (BigUint::one() << 64u32) + 1u32;
+    let factors = factorize_big(&n, &stop, |_| {}).unwrap();
+    let values: Vec<(String, u32, bool)> = factors.iter().map(|f| (f.value.to_string(), f.exponent, f.prime)).collect();
+    assert_eq!(values, vec![("274177".to_string(), 1, true), ("67280421310721".to_string(), 1, true)]);
+
+    let n = BigUint::from(3u32).pow(5) * BigUint::from(u64::MAX - 58).pow(2);
+    let factors = factorize_big(&n, &stop, |_| {}).unwrap();
+    assert_eq!(factors.iter().map(|f| f.exponent).collect::<Vec<_>>(), vec![5, 2]);
+
+    // u128 に収まる数は rho 法で分解できる（10 桁の素数と 2^89 - 1 の積）
+    let mersenne = (BigUint::one() << 89u32) - 1u32;
+    let n = BigUint::from(1_000_000_007u32) * &mersenne;
+    let factors = factorize_big(&n, &stop, |_| {}).unwrap();
+    assert_eq!(factors.iter().map(|f| (f.value.clone(), f.prime)).collect::<Vec<_>>(), vec![(BigUint::from(1_000_000_007u32), true), (mersenne, true)]);
+
+    // 12 桁と 15 桁の素数の積を ECM で分解する
+    let n: BigUint = "10000000000303100000000093".parse().unwrap();
+    assert_eq!(ecm(&n, 2000, 30, &stop), Some(BigUint::from(100_000_000_003u64)));
+}
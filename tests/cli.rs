@@ -0,0 +1,150 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use sosu_seisei_sieve::app::{ProgressPhase, WorkerMessage};
+use sosu_seisei_sieve::cli::{layer_overrides, parse_args, progress_event, run_headless, Args, BenchOptions, CliOptions, RunReport, EXIT_INTERRUPTED, EXIT_INVALID_CONFIG, EXIT_IO_ERROR, EXIT_SUCCESS};
+use sosu_seisei_sieve::config::{Config, OverwriteMode};
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn parses_command_line_options() {
+    let cli = |options| Ok(Args { cli: Some(options), ..Args::default() });
+    assert_eq!(parse_args(args(&[])), Ok(Args::default()));
+    assert_eq!(parse_args(args(&["--portable"])), Ok(Args { portable: true, ..Args::default() }));
+    assert_eq!(parse_args(args(&["--cli"])), cli(CliOptions::default()));
+    assert_eq!(parse_args(args(&["--portable", "--cli"])), Ok(Args { portable: true, cli: Some(CliOptions::default()), ..Args::default() }));
+    assert_eq!(parse_args(args(&["--progress-json"])), cli(CliOptions { progress_json: true, ..CliOptions::default() }));
+    let summary = CliOptions { summary_json: Some(PathBuf::from("out.json")), ..CliOptions::default() };
+    assert_eq!(parse_args(args(&["--summary-json", "out.json"])), cli(summary.clone()));
+    assert_eq!(parse_args(args(&["--summary-json=out.json"])), cli(summary));
+    let overrides = CliOptions { overrides: vec![("prime_max".to_string(), "1000".to_string()), ("output_dir".to_string(), "a=b".to_string())], ..CliOptions::default() };
+    assert_eq!(parse_args(args(&["--set", "prime_max=1000", "--set=output_dir=a=b"])), cli(overrides));
+    assert!(parse_args(args(&["--set", "prime_max"])).is_err());
+    assert!(parse_args(args(&["--summary-json"])).is_err());
+    assert!(parse_args(args(&["--bogus"])).is_err());
+    assert!(parse_args(args(&["--help"])).is_err());
+
+    // bench の後ろは bench のオプション
+    let bench = |options| Ok(Args { portable: true, bench: Some(options), ..Args::default() });
+    assert_eq!(parse_args(args(&["--portable", "bench"])), bench(BenchOptions::default()));
+    let options = BenchOptions { quick: true, report: Some(PathBuf::from("b.json")), overrides: vec![("segment_size".to_string(), "65536".to_string())], ..BenchOptions::default() };
+    assert_eq!(parse_args(args(&["--portable", "bench", "--quick", "--report", "b.json", "--set", "segment_size=65536"])), bench(options.clone()));
+    assert_eq!(parse_args(args(&["--portable", "bench", "--quick", "--report=b.json", "--set=segment_size=65536"])), bench(options));
+    assert_eq!(parse_args(args(&["--portable", "bench", "--compare"])), bench(BenchOptions { compare: true, ..BenchOptions::default() }));
+    assert!(parse_args(args(&["bench", "--compare", "--quick"])).is_err());
+    assert!(parse_args(args(&["bench", "--cli"])).is_err());
+    assert!(parse_args(args(&["bench", "--report"])).is_err());
+    assert!(parse_args(args(&["--cli", "bench"])).is_err());
+}
+
+#[test]
+fn set_options_take_precedence_over_the_environment() {
+    let env = vec![("SOSU_PRIME_MAX".to_string(), "500".to_string()), ("SOSU_SPLIT_COUNT".to_string(), "10".to_string())];
+    let options = CliOptions { overrides: vec![("prime_max".to_string(), "700".to_string())], ..CliOptions::default() };
+    let config = layer_overrides(&Config::default(), env, &options).unwrap();
+    assert_eq!(config.prime_max, "700");
+    assert_eq!(config.split_count, 10);
+    let options = CliOptions { overrides: vec![("split_count".to_string(), "x".to_string())], ..CliOptions::default() };
+    assert!(layer_overrides(&Config::default(), Vec::new(), &options).unwrap_err().starts_with("--set split_count"));
+}
+
+#[test]
+fn formats_progress_events_as_json() {
+    let event = progress_event(&WorkerMessage::Progress { current: 5, total: 10, phase: ProgressPhase::Writing }).unwrap();
+    assert_eq!(event, r#"{"type":"progress","current":5,"total":10,"phase":"writing"}"#);
+    let event = progress_event(&WorkerMessage::SegmentDone { low: 1, high: 100, primes: 25 }).unwrap();
+    assert_eq!(event, r#"{"type":"segment_done","low":1,"high":100,"primes":25}"#);
+    assert_eq!(progress_event(&WorkerMessage::Done).unwrap(), r#"{"type":"done"}"#);
+    assert_eq!(progress_event(&WorkerMessage::FoundPrimeIndex(2, 1)), None);
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn config(dir: &std::path::Path, prime_max: &str) -> Config {
+    Config {
+        prime_max: prime_max.to_string(),
+        segment_size: 10_000,
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        ..Config::default()
+    }
+}
+
+fn run(config: Config, options: &CliOptions, stopped: bool) -> (RunReport, String) {
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let report = run_headless(config, options, Arc::new(AtomicBool::new(stopped)), &mut out, &mut err).unwrap();
+    if options.progress_json {
+        assert!(out.is_empty());
+    }
+    (report, String::from_utf8(err).unwrap())
+}
+
+#[test]
+fn headless_run_streams_ndjson() {
+    let dir = temp_dir("cli");
+    let (report, err) = run(config(&dir, "100000"), &CliOptions { progress_json: true, ..CliOptions::default() }, false);
+    let events: Vec<serde_json::Value> = err.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    let count = |kind: &str| events.iter().filter(|e| e["type"] == kind).count();
+    assert_eq!(count("segment_done"), 10);
+    // セグメントの総数は最初に分かる
+    let segments: Vec<(u64, u64)> = events.iter().filter(|e| e["type"] == "segments").map(|e| (e["done"].as_u64().unwrap(), e["total"].as_u64().unwrap())).collect();
+    assert_eq!(segments.first(), Some(&(0, 10)));
+    assert_eq!(segments.last(), Some(&(10, 10)));
+    // 書き込みは篩い終えてから始まり、進捗は素数の個数で数える
+    let progress = |phase: &str| -> Vec<(u64, u64)> {
+        events.iter().filter(|e| e["type"] == "progress" && e["phase"] == phase).map(|e| (e["current"].as_u64().unwrap(), e["total"].as_u64().unwrap())).collect()
+    };
+    assert_eq!(progress("sieving").last(), Some(&(100_000, 100_000)));
+    let writing = progress("writing");
+    assert_eq!(writing.first(), Some(&(0, 9592)));
+    assert_eq!(writing.last(), Some(&(9592, 9592)));
+    let position = |f: &dyn Fn(&serde_json::Value) -> bool| events.iter().position(f).unwrap();
+    assert!(events.iter().rposition(|e| e["type"] == "segment_done").unwrap() < position(&|e| e["phase"] == "writing"));
+    assert!(position(&|e| e["type"] == "finished") > events.iter().rposition(|e| e["phase"] == "writing").unwrap());
+    assert!(count("progress") > 0);
+    let finished = events.iter().find(|e| e["type"] == "finished").unwrap();
+    assert_eq!(finished["found"], 9592);
+    assert_eq!(events.last().unwrap()["type"], "done");
+
+    assert_eq!(report.exit_code, EXIT_SUCCESS);
+    assert_eq!(report.found, Some(9592));
+    assert_eq!(report.files, vec![dir.join("primes.txt").display().to_string()]);
+    assert_eq!(report.error, None);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn exit_codes_distinguish_failures() {
+    let dir = temp_dir("cli_codes");
+    let options = CliOptions::default();
+    assert_eq!(run(config(&dir, "abc"), &options, false).0.exit_code, EXIT_INVALID_CONFIG);
+    // GUI と同じ検証で、篩う前に止める
+    let (report, err) = run(Config { residue_modulus: 4, residues: vec![5], ..config(&dir, "1000") }, &options, false);
+    assert_eq!(report.exit_code, EXIT_INVALID_CONFIG);
+    assert_eq!(report.error.as_deref(), Some("each residue must be less than the residue modulus."));
+    assert!(err.starts_with("Error: each residue"), "{}", err);
+    assert!(!dir.exists());
+    assert_eq!(run(config(&dir, "100000"), &options, true).0.exit_code, EXIT_INTERRUPTED);
+
+    // 出力先がファイルなのでディレクトリを作れない
+    fs::create_dir_all(&dir).unwrap();
+    let blocked = dir.join("file");
+    fs::write(&blocked, "").unwrap();
+    let (report, _) = run(config(&blocked, "1000"), &options, false);
+    assert_eq!(report.exit_code, EXIT_IO_ERROR);
+    assert!(report.error.is_some());
+    let _ = fs::remove_dir_all(&dir);
+}
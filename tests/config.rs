@@ -0,0 +1,78 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use sosu_seisei_sieve::config::{apply_env_overrides, apply_override, auto_tuned, validate_range, Config, OutputFormat, RangeErrors, MAX_PRIME_MAX};
+
+#[test]
+fn valid_range_inputs() {
+    assert_eq!(validate_range("1", "100", "0"), Ok((1, 100, 0)));
+    assert_eq!(validate_range(" 2 ", "999999999999999999", "1000"), Ok((2, MAX_PRIME_MAX, 1000)));
+    // 桁区切りは無視する
+    assert_eq!(validate_range("1_000", "1,000,000,000", "10 000"), Ok((1000, 1_000_000_000, 10_000)));
+}
+
+#[test]
+fn invalid_range_inputs() {
+    let errors = |min, max, split| validate_range(min, max, split).unwrap_err();
+
+    let e = errors("", "100", "0");
+    assert!(e.prime_min.is_some() && e.prime_max.is_none() && e.split_count.is_none());
+    let e = errors("1", "-5", "x");
+    assert!(e.prime_min.is_none() && e.prime_max.is_some() && e.split_count.is_some());
+    // 上限とオーバーフロー
+    assert!(errors("1", "1000000000000000000", "0").prime_max.is_some());
+    let e = errors("1", "18446744073709551616", "0");
+    assert!(e.prime_max.unwrap().contains("18446744073709551615"));
+    assert!(errors("1", "100", "99999999999999999999").split_count.is_some());
+    // prime_min > prime_max は prime_min の誤り（等しければ 1 つの数だけの範囲）
+    assert_eq!(validate_range("100", "100", "0"), Ok((100, 100, 0)));
+    let e = errors("101", "100", "0");
+    assert_eq!(e, RangeErrors { prime_min: e.prime_min.clone(), ..RangeErrors::default() });
+    assert!(e.prime_min.is_some());
+    assert_eq!(e.messages().len(), 1);
+    assert!(e.messages()[0].starts_with("prime_min: "));
+}
+
+#[test]
+fn overrides_settings_by_name() {
+    let config = Config::default();
+    let config = apply_override(&config, "prime_max", "5000").unwrap();
+    assert_eq!(config.prime_max, "5000");
+    assert_eq!(apply_override(&config, "segment_size", "1_000").unwrap().segment_size, 1000);
+    assert!(apply_override(&config, "async_writer", "true").unwrap().async_writer);
+    assert_eq!(apply_override(&config, "residues", "[1, 3]").unwrap().residues, vec![1, 3]);
+    assert_eq!(apply_override(&config, "output_format", "CSV").unwrap().output_format, OutputFormat::CSV);
+
+    assert!(apply_override(&config, "no_such_setting", "1").is_err());
+    assert!(apply_override(&config, "segment_size", "many").is_err());
+    assert!(apply_override(&config, "segment_size", "-1").is_err());
+    assert!(apply_override(&config, "output_format", "Excel").is_err());
+}
+
+#[test]
+fn overrides_settings_from_the_environment() {
+    let vars = |list: &[(&str, &str)]| list.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<Vec<_>>();
+    let config = apply_env_overrides(&Config::default(), vars(&[("SOSU_PRIME_MAX", "12345"), ("SOSU_OUTPUT_DIR", "/data/primes"), ("PATH", "/bin")])).unwrap();
+    assert_eq!(config.prime_max, "12345");
+    assert_eq!(config.output_dir, "/data/primes");
+
+    let error = apply_env_overrides(&Config::default(), vars(&[("SOSU_PRIME_MAXX", "1")])).unwrap_err();
+    assert!(error.starts_with("SOSU_PRIME_MAXX"), "{}", error);
+}
+
+#[test]
+fn auto_tuning_fits_the_free_memory() {
+    const GIB: u64 = 1024 * 1024 * 1024;
+    let config = Config { segment_size: 123, writer_buffer_size: 456, prime_max: "1000".to_string(), ..Config::default() };
+    // メモリが十分なら既定値
+    let tuned = auto_tuned(&config, 64 * GIB, 16);
+    assert_eq!((tuned.segment_size, tuned.chunk_size, tuned.writer_buffer_size), (10_000_000, 16_384, 8 * 1024 * 1024));
+    assert_eq!(tuned.prime_max, "1000");
+    // 少なければ小さくする（下限あり）
+    let tuned = auto_tuned(&config, GIB, 32);
+    assert_eq!((tuned.segment_size, tuned.writer_buffer_size), (2_000_000, 4 * 1024 * 1024));
+    let tuned = auto_tuned(&config, 0, 0);
+    assert_eq!((tuned.segment_size, tuned.writer_buffer_size), (1_000_000, 1024 * 1024));
+}
@@ -0,0 +1,194 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::sleep;
+use std::time::Duration;
+use sosu_seisei_sieve::app::{ProgressPhase, WorkerMessage};
+use sosu_seisei_sieve::config::{Config, OutputFormat, OverwriteMode, RunMode};
+use sosu_seisei_sieve::controller::{Controller, RunInputs, SHUTDOWN_TIMEOUT};
+use sosu_seisei_sieve::metadata::read_meta;
+
+#[test]
+fn validates_and_normalizes_inputs() {
+    let config = Config::default();
+    assert_eq!(Controller::validate_inputs(&RunInputs::from_config(&config), config.clone()).unwrap(), config);
+
+    let inputs = RunInputs {
+        prime_min: "1,000".to_string(),
+        prime_max: "2_000_000".to_string(),
+        residue_modulus: "10".to_string(),
+        residues: " 1, 3 ,7,9".to_string(),
+        ..RunInputs::from_config(&config)
+    };
+    let validated = Controller::validate_inputs(&inputs, Config { s3_bucket: " bucket ".to_string(), ..config.clone() }).unwrap();
+    assert_eq!((validated.prime_min.as_str(), validated.prime_max.as_str()), ("1000", "2000000"));
+    assert_eq!(validated.residues, vec![1, 3, 7, 9]);
+    assert_eq!(validated.s3_bucket, "bucket");
+
+    // N 番目の素数モードでは prime_max を読まない
+    let nth = RunInputs { prime_max: "abc".to_string(), nth_count: "10".to_string(), ..RunInputs::from_config(&config) };
+    let validated = Controller::validate_inputs(&nth, Config { mode: RunMode::NthPrime, ..config.clone() }).unwrap();
+    assert_eq!((validated.prime_max, validated.nth_count), (config.prime_max.clone(), 10));
+}
+
+#[test]
+fn reports_range_errors_before_the_others() {
+    let config = Config::default();
+    let inputs = RunInputs { prime_min: "10".to_string(), prime_max: "5".to_string(), residues: "x".to_string(), ..RunInputs::from_config(&config) };
+    assert_eq!(Controller::validate_inputs(&inputs, config.clone()).unwrap_err(), vec!["prime_min: Must not exceed prime_max.".to_string()]);
+
+    let inputs = RunInputs { residue_modulus: "4".to_string(), residues: "1, 5".to_string(), digit_filter: "(".to_string(), ..RunInputs::from_config(&config) };
+    let errors = Controller::validate_inputs(&inputs, Config { prime_index: true, overwrite_mode: OverwriteMode::Append, ..config }).unwrap_err();
+    assert_eq!(errors, vec![
+        "numbering primes cannot be combined with appending to existing files.".to_string(),
+        "each residue must be less than the residue modulus.".to_string(),
+        "digit filter expression is invalid.".to_string(),
+    ]);
+}
+
+#[test]
+fn builds_the_summary_from_messages() {
+    let config = Config { prime_min: "1".to_string(), prime_max: "100".to_string(), ..Config::default() };
+    let mut controller = Controller::new(&config);
+    // メッセージは登録したジョブのものとして受け取る（ワーカーのスレッドは起動しない）
+    let (job, _ends) = controller.register("N/A");
+    controller.handle_message(job, WorkerMessage::Progress { current: 25, total: 100, phase: ProgressPhase::Sieving });
+    let progress = controller.progress();
+    assert_eq!((progress.fraction, progress.current, progress.total), (0.25, 25, 100));
+    assert_eq!(progress.phase.unwrap().phase, ProgressPhase::Sieving);
+    controller.handle_message(job, WorkerMessage::Log("Sieving".to_string()));
+    controller.handle_message(job, WorkerMessage::Warning("careful".to_string()));
+    assert_eq!(controller.log, "Sieving\nWarning: careful\n");
+    controller.handle_message(job, WorkerMessage::CpuUsage(vec![100.0, 0.0]));
+    controller.handle_message(job, WorkerMessage::CpuUsage(vec![50.0, 50.0]));
+    assert_eq!(controller.cpu_history.iter().map(|(_, loads)| loads.clone()).collect::<Vec<_>>(), vec![vec![100.0, 0.0], vec![50.0, 50.0]]);
+    // 画面の部品向けのメッセージはそのまま返す
+    assert!(matches!(controller.handle_message(job, WorkerMessage::SpiralPrimes { start: 0, end: 10, primes: vec![2, 3, 5, 7] }), Some(WorkerMessage::SpiralPrimes { .. })));
+
+    assert!(controller.is_running());
+    controller.handle_message(job, WorkerMessage::Finished { found: 25, files: Vec::new() });
+    assert!(controller.handle_message(job, WorkerMessage::Done).is_none());
+    assert!(!controller.is_running());
+    let summary = controller.summary.as_ref().unwrap();
+    assert_eq!((summary.prime_min, summary.prime_max, summary.found), (1, 100, 25));
+    // 終わったジョブの進捗は次のジョブまで残る
+    assert_eq!(controller.progress().current, 25);
+}
+
+#[test]
+fn keeps_jobs_apart() {
+    let mut controller = Controller::new(&Config::default());
+    // 止められるまで進捗を送り続けるツール
+    let tool = |total: u64| {
+        move |sender: mpsc::Sender<WorkerMessage>, stop_flag: Arc<AtomicBool>| {
+            while !stop_flag.load(Ordering::SeqCst) {
+                sender.send(WorkerMessage::Progress { current: 1, total, phase: ProgressPhase::Testing }).ok();
+                sleep(Duration::from_millis(5));
+            }
+            sender.send(WorkerMessage::Stopped).ok();
+            Ok(())
+        }
+    };
+    let first = controller.spawn_tool(tool(10));
+    let second = controller.spawn_tool(tool(20));
+    assert_ne!(first, second);
+    assert_eq!(controller.foreground, Some(second));
+    let receive = |controller: &mut Controller| {
+        for (job, message) in controller.pending_messages() {
+            controller.handle_message(job, message);
+        }
+    };
+    while controller.workers.values().any(|w| w.progress.total == 0) {
+        receive(&mut controller);
+        sleep(Duration::from_millis(5));
+    }
+    assert_eq!(controller.workers[&first].progress.total, 10);
+    assert_eq!(controller.progress().total, 20);
+
+    // 止めるのは表示しているジョブだけ
+    controller.stop();
+    while controller.workers[&second].is_running() {
+        receive(&mut controller);
+        sleep(Duration::from_millis(5));
+    }
+    assert!(controller.is_running());
+    controller.workers[&first].stop_flag.store(true, Ordering::SeqCst);
+    while controller.is_running() {
+        receive(&mut controller);
+        sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn runs_a_worker_to_completion() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_controller_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_min: "1".to_string(),
+        prime_max: "100000".to_string(),
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        ..Config::default()
+    };
+    let mut controller = Controller::new(&config);
+    let job = controller.start_run(config);
+    assert!(controller.is_running() && controller.monitor.is_running());
+    while controller.is_running() {
+        for (job, message) in controller.pending_messages() {
+            controller.handle_message(job, message);
+        }
+        sleep(Duration::from_millis(10));
+    }
+    assert_eq!(controller.error, None);
+    let summary = controller.summary.take().unwrap();
+    assert_eq!((summary.found, summary.files), (9592, vec![dir.join("primes.txt")]));
+    assert_eq!(controller.written_primes, 9592);
+    assert!(controller.workers[&job].receiver.is_none());
+    // 実行が終わればメモリの監視も止まる
+    assert!(!controller.monitor.is_running());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn shutdown_stops_and_finalizes_running_jobs() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_controller_shutdown_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_min: "0".to_string(),
+        prime_max: "20000000".to_string(),
+        output_dir: dir.display().to_string(),
+        output_format: OutputFormat::JSON,
+        overwrite_mode: OverwriteMode::Overwrite,
+        ..Config::default()
+    };
+    let mut controller = Controller::new(&config);
+    controller.start_run(config);
+    while controller.written_primes == 0 && controller.is_running() {
+        for (job, message) in controller.pending_messages() {
+            controller.handle_message(job, message);
+        }
+        sleep(Duration::from_millis(1));
+    }
+    assert_eq!(controller.error, None);
+    assert_eq!(controller.shutdown(SHUTDOWN_TIMEOUT), 0);
+    assert!(!controller.is_running());
+    assert!(controller.log.contains("Process stopped by user."), "{}", controller.log);
+    // 止めた出力は閉じてあり、書いた範囲がメタデータにある
+    let content = fs::read_to_string(dir.join("primes.json")).unwrap();
+    assert!(content.ends_with(']'));
+    assert!(read_meta(&dir.join("primes.meta.json")).unwrap().primes_found > 0);
+    let _ = fs::remove_dir_all(&dir);
+
+    // 停止を見ないスレッドは待ちきれずに切り離す
+    let mut controller = Controller::new(&Config::default());
+    controller.spawn_tool(|_, _| {
+        sleep(Duration::from_millis(500));
+        Ok(())
+    });
+    assert_eq!(controller.shutdown(Duration::from_millis(20)), 1);
+}
@@ -0,0 +1,41 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use sosu_seisei_sieve::cunningham::{chain_from, ChainKind};
+use sosu_seisei_sieve::miller_rabin::is_prime;
+use sosu_seisei_sieve::sieve::simple_sieve;
+
+#[test]
+fn miller_rabin_matches_sieve() {
+    let primes = simple_sieve(100_000);
+    let mut iter = primes.iter().peekable();
+    for n in 0..=100_000u64 {
+        let expected = iter.next_if(|&&p| p == n).is_some();
+        assert_eq!(is_prime(n), expected, "{}", n);
+    }
+    assert!(is_prime((1 << 61) - 1));
+    assert!(is_prime(18_446_744_073_709_551_557));
+    // 底 2, 3, 5, 7 に対する強擬素数
+    assert!(!is_prime(3_215_031_751));
+    assert!(!is_prime(3_825_123_056_546_413_051));
+}
+
+#[test]
+fn known_chains() {
+    let first = chain_from(89, ChainKind::First, 2).unwrap();
+    assert_eq!(first.members, vec![89, 179, 359, 719, 1439, 2879]);
+    assert_eq!(chain_from(2, ChainKind::First, 2).unwrap().members, vec![2, 5, 11, 23, 47]);
+    // 5 は 2 から始まる鎖の途中
+    assert_eq!(chain_from(5, ChainKind::First, 2), None);
+
+    let second = chain_from(16651, ChainKind::Second, 2).unwrap();
+    assert_eq!(second.members, vec![16651, 33301, 66601, 133201, 266401, 532801, 1065601]);
+    assert_eq!(chain_from(19, ChainKind::Second, 4), None);
+
+    // u64 を超える項は打ち切る
+    let last = chain_from(18_446_744_073_709_551_557, ChainKind::First, 1).unwrap();
+    assert!(last.truncated);
+    assert_eq!(last.members.len(), 1);
+}
@@ -0,0 +1,184 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use sosu_seisei_sieve::app::WorkerMessage;
+use sosu_seisei_sieve::config::{Config, OverwriteMode};
+use sosu_seisei_sieve::distributed::{run_coordinator, run_distributed_worker, ToWorker};
+use sosu_seisei_sieve::error::SieveError;
+use sosu_seisei_sieve::output::read_primes;
+use sosu_seisei_sieve::primes::Primes;
+
+#[test]
+fn coordinator_collects_units_in_order() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_distributed_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let address = format!("127.0.0.1:{}", port);
+    let config = Config {
+        prime_min: "1000".to_string(),
+        prime_max: "200000".to_string(),
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        coordinator_listen: address.clone(),
+        coordinator_address: address.clone(),
+        work_unit_size: 7_000,
+        segment_size: 1_000,
+        ..Config::default()
+    };
+
+    let (sender, messages) = mpsc::channel();
+    let (_commands_sender, commands) = mpsc::channel();
+    let coordinator_config = config.clone();
+    let coordinator = std::thread::spawn(move || {
+        run_coordinator(coordinator_config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap()
+    });
+    let connect = || loop {
+        if let Ok(stream) = TcpStream::connect(&address) {
+            return stream;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+
+    // 作業単位を受け取ったまま切断するワーカー（その作業単位は配り直される）
+    let stream = connect();
+    let mut input = BufReader::new(stream.try_clone().unwrap());
+    writeln!(&stream, r#"{{"Ready":{{"threads":1}}}}"#).unwrap();
+    let mut line = String::new();
+    input.read_line(&mut line).unwrap();
+    assert!(matches!(serde_json::from_str(&line).unwrap(), ToWorker::Unit { id: 0, low: 1000, high: 7999 }));
+    drop((input, stream));
+
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            let config = config.clone();
+            let (sender, _messages) = mpsc::channel();
+            std::thread::spawn(move || run_distributed_worker(config, sender, Arc::new(AtomicBool::new(false))).unwrap())
+        })
+        .collect();
+    coordinator.join().unwrap();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    let files = messages
+        .try_iter()
+        .find_map(|m| match m {
+            WorkerMessage::Finished { files, .. } => Some(files),
+            _ => None,
+        })
+        .unwrap();
+    let mut written = Vec::new();
+    for file in &files {
+        read_primes(file, |p| written.push(p)).unwrap();
+    }
+    assert_eq!(written, Primes::new(1000, 200_000).collect::<Vec<_>>());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn coordinator_rejects_bad_workers() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_distributed_bad_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let address = format!("127.0.0.1:{}", port);
+    let config = Config {
+        prime_min: "1000".to_string(),
+        prime_max: "50000".to_string(),
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        coordinator_listen: address.clone(),
+        coordinator_address: address.clone(),
+        coordinator_token: "s3cret".to_string(),
+        work_unit_size: 7_000,
+        segment_size: 1_000,
+        ..Config::default()
+    };
+
+    let (sender, messages) = mpsc::channel();
+    let (_commands_sender, commands) = mpsc::channel();
+    let coordinator_config = config.clone();
+    let coordinator = std::thread::spawn(move || {
+        run_coordinator(coordinator_config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap()
+    });
+    // 接続し、送った Ready に作業単位が返れば (接続, その作業単位の範囲)
+    let ready = |token: &str| {
+        let stream = loop {
+            if let Ok(stream) = TcpStream::connect(&address) {
+                break stream;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        };
+        let mut input = BufReader::new(stream.try_clone().unwrap());
+        writeln!(&stream, r#"{{"Ready":{{"threads":1,"token":"{}"}}}}"#, token).unwrap();
+        let mut line = String::new();
+        input.read_line(&mut line).unwrap();
+        let unit = serde_json::from_str(&line).ok().map(|unit| match unit {
+            ToWorker::Unit { id, low, high } => (id, low, high),
+            ToWorker::Finish => panic!("finished too early"),
+        });
+        (stream, input, unit)
+    };
+    // 送ったあと切断されたか
+    let closed = |mut input: BufReader<TcpStream>| {
+        let mut line = String::new();
+        input.read_line(&mut line).unwrap_or(0) == 0
+    };
+
+    // 合言葉が違えば何も渡さずに切断する
+    let (_stream, _input, unit) = ready("wrong");
+    assert_eq!(unit, None);
+
+    // 素数でない数、順番の乱れ、範囲外の数を返したワーカーは切断し、作業単位は配り直す
+    let bad_results = [vec![1001, 1009], vec![1013, 1009], vec![1009, 9001]];
+    for primes in bad_results {
+        let (stream, input, unit) = ready("s3cret");
+        let (id, low, _) = unit.unwrap();
+        assert_eq!((id, low), (0, 1000));
+        writeln!(&stream, r#"{{"Done":{{"id":0,"primes":{:?}}}}}"#, primes).unwrap();
+        assert!(closed(input));
+    }
+
+    let worker_config = config.clone();
+    let worker = std::thread::spawn(move || {
+        let (sender, _messages) = mpsc::channel();
+        run_distributed_worker(worker_config, sender, Arc::new(AtomicBool::new(false))).unwrap()
+    });
+    coordinator.join().unwrap();
+    worker.join().unwrap();
+
+    let files = messages
+        .try_iter()
+        .find_map(|m| match m {
+            WorkerMessage::Finished { files, .. } => Some(files),
+            _ => None,
+        })
+        .unwrap();
+    let mut written = Vec::new();
+    for file in &files {
+        read_primes(file, |p| written.push(p)).unwrap();
+    }
+    assert_eq!(written, Primes::new(1000, 50_000).collect::<Vec<_>>());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn coordinator_needs_a_token_off_loopback() {
+    let config = Config {
+        prime_min: "1000".to_string(),
+        prime_max: "50000".to_string(),
+        coordinator_listen: "0.0.0.0:0".to_string(),
+        ..Config::default()
+    };
+    let (sender, _messages) = mpsc::channel();
+    let (_commands_sender, commands) = mpsc::channel();
+    let result = run_coordinator(config, sender, commands, Arc::new(AtomicBool::new(false)));
+    assert!(matches!(result, Err(SieveError::Config(_))));
+}
@@ -0,0 +1,88 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use sosu_seisei_sieve::config::Config;
+use sosu_seisei_sieve::error::SieveError;
+use sosu_seisei_sieve::filter::DigitExpr;
+use sosu_seisei_sieve::output::read_primes;
+use sosu_seisei_sieve::script::FilterScript;
+use sosu_seisei_sieve::sieve::{run_program_old, simple_sieve};
+
+fn select(expr: &str, limit: u64) -> Vec<u64> {
+    let expr = DigitExpr::parse(expr).unwrap();
+    simple_sieve(limit).into_iter().filter(|&p| expr.matches(p)).collect()
+}
+
+#[test]
+fn palindromic_primes() {
+    // OEIS A002385
+    assert_eq!(select("palindrome", 400), vec![2, 3, 5, 7, 11, 101, 131, 151, 181, 191, 313, 353, 373, 383]);
+    assert_eq!(select("palindrome & digits == 3 & digit_sum % 2 == 1", 200), vec![131, 151, 191]);
+}
+
+#[test]
+fn repunit_and_near_repdigit() {
+    assert_eq!(select("repunit", 100_000), vec![11]);
+    assert!(DigitExpr::parse("near_repdigit").unwrap().matches(1_111_111_211));
+    assert!(!DigitExpr::parse("near_repdigit").unwrap().matches(1_111_111_111));
+    assert!(!DigitExpr::parse("near_repdigit").unwrap().matches(1_211_111_211));
+    assert_eq!(select("near_repdigit & digits == 3 & !(digit_sum > 10)", 200), vec![101, 113, 131, 151, 181]);
+}
+
+#[test]
+fn invalid_expressions() {
+    for expr in ["", "palindrome &", "digit_sum", "digit_sum % 0 == 1", "(repunit", "prime", "digits = 3"] {
+        assert!(DigitExpr::parse(expr).is_err(), "{:?} should be rejected", expr);
+    }
+    assert!(DigitExpr::parse("palindrome | repunit & digits >= 2").is_ok());
+}
+
+fn select_script(source: &str, limit: u64) -> Vec<u64> {
+    let mut script = FilterScript::compile(source).unwrap();
+    simple_sieve(limit).into_iter().filter(|&p| script.matches(p).unwrap()).collect()
+}
+
+#[test]
+fn script_filters() {
+    // 桁の和が 3 の倍数なら 3 の倍数なので、末尾が 7 の素数は残らない
+    assert!(select_script("p % 10 == 7 && digit_sum(p) % 3 == 0", 200).is_empty());
+    assert_eq!(select_script("p % 10 == 7 && digit_sum(p) % 3 == 1", 200), vec![7, 37, 67, 97, 127, 157]);
+    assert_eq!(select_script("palindrome(p) && digits(p) == 3", 200), vec![101, 131, 151, 181, 191]);
+    // エマープ（OEIS A006567）
+    assert_eq!(select_script("let r = reverse_digits(p); r != p && is_prime(r)", 100), vec![13, 17, 31, 37, 71, 73, 79, 97]);
+    assert!(FilterScript::compile("true").unwrap().matches(1 << 63).is_err());
+}
+
+#[test]
+fn invalid_scripts() {
+    for source in ["p +", "q > 1", "p + 1", "unknown(p)", "p / (p - 2) > 0", "loop {}"] {
+        assert!(FilterScript::compile(source).is_err(), "{:?} should be rejected", source);
+    }
+}
+
+#[test]
+fn invalid_script_is_reported_before_the_run() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_filter_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config { prime_max: "1000".to_string(), output_dir: dir.to_string_lossy().into_owned(), ..Config::default() };
+    let run = |config: Config| {
+        let (sender, _messages) = mpsc::channel();
+        let (_commands_sender, commands) = mpsc::channel();
+        run_program_old(config, sender, commands, Arc::new(AtomicBool::new(false)))
+    };
+
+    let error = run(Config { filter_script: "p % 10 ==".to_string(), ..config.clone() }).unwrap_err();
+    assert!(matches!(error, SieveError::Config(ref e) if e.starts_with("filter_script")), "{}", error);
+    assert!(!dir.exists());
+
+    run(Config { filter_script: "p % 100 == 1".to_string(), ..config }).unwrap();
+    let mut written = Vec::new();
+    read_primes(&dir.join("primes.txt"), |p| written.push(p)).unwrap();
+    assert_eq!(written, vec![101, 401, 601, 701]);
+    fs::remove_dir_all(&dir).unwrap();
+}
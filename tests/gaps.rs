@@ -0,0 +1,36 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use sosu_seisei_sieve::gaps::{GapAnalyzer, RecordGap};
+use sosu_seisei_sieve::sieve::simple_sieve;
+
+#[test]
+fn record_gaps_below_1000() {
+    let mut analyzer = GapAnalyzer::new(0, 0);
+    for p in simple_sieve(1000) {
+        analyzer.push(p);
+    }
+    let report = analyzer.finish();
+    let records: Vec<(u64, u64)> = report.records.iter().map(|r| (r.gap, r.after)).collect();
+    // OEIS A002386 / A005250
+    assert_eq!(records, vec![(1, 2), (2, 3), (4, 7), (6, 23), (8, 89), (14, 113), (18, 523), (20, 887)]);
+    assert_eq!(report.max_gap(), Some(&RecordGap { gap: 20, after: 887 }));
+    assert_eq!(report.prime_count, 168);
+    assert_eq!(report.intervals.len(), 1);
+    assert_eq!(report.intervals[0].count, 167);
+}
+
+#[test]
+fn interval_statistics() {
+    let mut analyzer = GapAnalyzer::new(0, 10);
+    for p in [2, 3, 5, 7, 11, 13, 17, 19, 23, 29] {
+        analyzer.push(p);
+    }
+    let report = analyzer.finish();
+    // 上側の素数が属する区間に数える: [0,9]: 1,2,2 / [10,19]: 4,2,4,2 / [20,29]: 4,6
+    let summary: Vec<(u64, u64, u64, u64)> = report.intervals.iter().map(|i| (i.start, i.count, i.median, i.max)).collect();
+    assert_eq!(summary, vec![(0, 3, 2, 2), (10, 4, 2, 4), (20, 2, 4, 6)]);
+    assert_eq!(report.intervals[1].mean, 3.0);
+}
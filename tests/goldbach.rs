@@ -0,0 +1,26 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use sosu_seisei_sieve::goldbach::PrimeBits;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+#[test]
+fn minimal_partitions_and_counts() {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    // セグメント境界をまたぐよう小さいセグメントで作る
+    let bits = PrimeBits::sieve(1000, 97, &pool, &Arc::new(AtomicBool::new(false))).unwrap();
+    let primes: Vec<u64> = (0..=1000).filter(|&n| bits.is_prime(n)).collect();
+    assert_eq!(primes.len(), 168);
+
+    assert_eq!(bits.min_partition(4), Some(2));
+    assert_eq!(bits.min_partition(98), Some(19));
+    assert_eq!(bits.min_partition(992), Some(73));
+    assert!((4..=1000).step_by(2).all(|n| bits.min_partition(n).is_some()));
+
+    assert_eq!(bits.partition_count(4), 1);
+    assert_eq!(bits.partition_count(10), 2);
+    assert_eq!(bits.partition_count(100), 6);
+}
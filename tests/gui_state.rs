@@ -0,0 +1,50 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use eframe::egui;
+use sosu_seisei_sieve::app::Tab;
+use sosu_seisei_sieve::config::{Config, RunMode};
+use sosu_seisei_sieve::gui_state::{GuiState, WindowGeometry};
+
+#[test]
+fn state_round_trips_and_tolerates_old_or_broken_files() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_gui_state_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let path = dir.join("gui_state.json");
+    assert_eq!(GuiState::load_from(&path), GuiState::default());
+
+    let state = GuiState {
+        window: Some(WindowGeometry { position: Some([-1200.0, 40.0]), size: [900.0, 700.0], maximized: true }),
+        tab: Tab::Viewer,
+        show_log: false,
+        // 検証前の入力もそのまま残す
+        inputs: Some(Config { prime_max: "1e9".to_string(), mode: RunMode::NthPrime, ..Config::default() }),
+        check_input: "2^61-1".to_string(),
+        viewer_file: "primes.txt".to_string(),
+        ..GuiState::default()
+    };
+    state.save_to(&path).unwrap();
+    assert_eq!(GuiState::load_from(&path), state);
+
+    // 以前の版の（項目の足りない）ファイルと壊れたファイル
+    fs::write(&path, r#"{"tab":"Spiral"}"#).unwrap();
+    assert_eq!(GuiState::load_from(&path), GuiState { tab: Tab::Spiral, ..GuiState::default() });
+    fs::write(&path, "{").unwrap();
+    assert_eq!(GuiState::load_from(&path), GuiState::default());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn window_geometry_ignores_broken_sizes() {
+    let builder = WindowGeometry { position: Some([10.0, 20.0]), size: [800.0, 600.0], maximized: false }.apply(egui::ViewportBuilder::default());
+    assert_eq!(builder.inner_size, Some(egui::vec2(800.0, 600.0)));
+    assert_eq!(builder.position, Some(egui::pos2(10.0, 20.0)));
+
+    let builder = WindowGeometry { position: Some([f32::NAN, 0.0]), size: [0.0, 600.0], maximized: true }.apply(egui::ViewportBuilder::default());
+    assert_eq!(builder.inner_size, None);
+    assert_eq!(builder.position, None);
+    assert_eq!(builder.maximized, Some(true));
+}
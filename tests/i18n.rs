@@ -0,0 +1,96 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use sosu_seisei_sieve::config::Language;
+use sosu_seisei_sieve::i18n::{format_number, format_template, japanese, set_language, strip_separators, tr_format, translate};
+
+// ソース中の tr!("...") の文字列リテラル（エスケープを戻したもの）
+fn tr_literals() -> Vec<(String, String)> {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut found = Vec::new();
+    for entry in fs::read_dir(src).unwrap() {
+        let path = entry.unwrap().path();
+        // i18n.rs 自身はコメント中の例しかない
+        if path.ends_with("i18n.rs") {
+            continue;
+        }
+        let text = fs::read_to_string(&path).unwrap();
+        let mut rest = text.as_str();
+        while let Some(pos) = rest.find("tr!(") {
+            rest = rest[pos + 4..].trim_start();
+            let Some(body) = rest.strip_prefix('"') else {
+                continue;
+            };
+            let mut literal = String::new();
+            let mut chars = body.char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => match chars.next() {
+                        Some((_, 'n')) => literal.push('\n'),
+                        Some((_, other)) => literal.push(other),
+                        None => break,
+                    },
+                    '"' => {
+                        rest = &body[i + 1..];
+                        break;
+                    }
+                    _ => literal.push(c),
+                }
+            }
+            found.push((path.display().to_string(), literal));
+        }
+    }
+    found
+}
+
+// テンプレートが参照する引数の番号
+fn placeholders(template: &str) -> BTreeSet<usize> {
+    let args: Vec<String> = (0..16).map(|i| format!("<{}>", i)).collect();
+    let refs: Vec<&dyn std::fmt::Display> = args.iter().map(|a| a as &dyn std::fmt::Display).collect();
+    let formatted = format_template(template, &refs);
+    (0..16).filter(|i| formatted.contains(&args[*i])).collect()
+}
+
+#[test]
+fn every_ui_string_is_translated() {
+    let literals = tr_literals();
+    assert!(literals.len() > 100);
+    for (file, text) in literals {
+        let ja = japanese(&text).unwrap_or_else(|| panic!("{}: no Japanese text for {:?}", file, text));
+        assert_eq!(placeholders(&text), placeholders(ja), "{}: {:?}", file, text);
+    }
+}
+
+#[test]
+fn templates() {
+    assert_eq!(format_template("{} + {} = {}", &[&1, &2, &3]), "1 + 2 = 3");
+    assert_eq!(format_template("{1} < {0}", &[&"b", &"a"]), "a < b");
+    assert_eq!(format_template("{0} mod {0}: {1}", &[&7, &3]), "7 mod 7: 3");
+    // 波括弧が引数でない場合はそのまま
+    assert_eq!(format_template("[{}, {}) {x}", &[&1, &2]), "[1, 2) {x}");
+
+    set_language(Language::Japanese);
+    assert_eq!(translate("Settings"), "設定");
+    assert_eq!(translate("not in the table"), "not in the table");
+    assert_eq!(tr_format("{} primes in {}", &[&5, &"a.txt"]), "a.txt の素数: 5 個");
+    set_language(Language::English);
+    assert_eq!(translate("Settings"), "Settings");
+    assert_eq!(tr_format("{} primes in {}", &[&5, &"a.txt"]), "5 primes in a.txt");
+}
+
+#[test]
+fn number_formatting() {
+    assert_eq!(format_number(0), "0");
+    assert_eq!(format_number(999), "999");
+    assert_eq!(format_number(1000), "1,000");
+    assert_eq!(format_number(123_456_789_012), "123,456,789,012");
+    assert_eq!(format_number(u64::MAX), "18,446,744,073,709,551,615");
+    assert_eq!(strip_separators(" 1_000_000 "), "1000000");
+    assert_eq!(strip_separators("1,000,000"), "1000000");
+    assert_eq!(strip_separators("1'000 000"), "1000000");
+}
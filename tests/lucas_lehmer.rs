@@ -0,0 +1,27 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use sosu_seisei_sieve::lucas_lehmer::{lucas_lehmer, small_factor};
+use sosu_seisei_sieve::sieve::simple_sieve;
+use std::sync::atomic::AtomicBool;
+
+#[test]
+fn mersenne_exponents_below_700() {
+    let stop = AtomicBool::new(false);
+    let found: Vec<u64> = simple_sieve(700)
+        .into_iter()
+        .filter(|&p| small_factor(p, 1000).is_none() && lucas_lehmer(p, &stop, |_| {}).unwrap())
+        .collect();
+    // OEIS A000043
+    assert_eq!(found, vec![2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127, 521, 607]);
+}
+
+#[test]
+fn trial_factors() {
+    assert_eq!(small_factor(11, 100), Some(23));
+    assert_eq!(small_factor(29, 100), Some(233));
+    assert_eq!(small_factor(7, 100), None);
+    assert_eq!(small_factor(31, 100), None);
+}
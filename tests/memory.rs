@@ -0,0 +1,70 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use sosu_seisei_sieve::config::{Config, RunMode};
+use sosu_seisei_sieve::memory::{estimate_peak_memory, MemoryPreset};
+use sosu_seisei_sieve::output::read_primes;
+use sosu_seisei_sieve::sieve::{run_program_old, simple_sieve};
+
+#[test]
+fn presets_set_the_sizes_together() {
+    // 既定値は Medium
+    assert_eq!(MemoryPreset::matching(&Config::default()), Some(MemoryPreset::Medium));
+    for preset in MemoryPreset::ALL {
+        let config = preset.apply(&Config { prime_max: "1000".to_string(), ..Config::default() });
+        assert_eq!(MemoryPreset::matching(&config), Some(preset));
+        assert_eq!(config.prime_max, "1000");
+    }
+    let low = MemoryPreset::Low.apply(&Config::default());
+    assert_eq!((low.segment_size, low.writer_buffer_size, low.parallel_segments), (1_000_000, 1024 * 1024, 2));
+    assert_eq!(MemoryPreset::matching(&Config { segment_size: 12345, ..low }), None);
+}
+
+#[test]
+fn estimate_grows_with_the_presets() {
+    let estimates: Vec<u64> = MemoryPreset::ALL
+        .into_iter()
+        .map(|preset| estimate_peak_memory(&preset.apply(&Config::default()), 1, 10_000_000_000, 8).total())
+        .collect();
+    assert!(estimates.windows(2).all(|w| w[0] < w[1]), "{:?}", estimates);
+
+    let config = MemoryPreset::Low.apply(&Config::default());
+    let estimate = estimate_peak_memory(&config, 1, 10_000_000_000, 8);
+    // π(10^10) = 455,052,511 個を容量 2^29 の Vec に保持する
+    assert_eq!(estimate.primes, (1 << 29) * 8);
+    // 並列に篩うのは 2 セグメント
+    assert!(estimate.sieving > 2 * 1_000_000 && estimate.sieving < 4 * 1_000_000, "{:?}", estimate);
+    assert_eq!(estimate.writer, 1024 * 1024);
+    let estimate = estimate_peak_memory(&Config { async_writer: true, ..config.clone() }, 1, 10_000_000_000, 8);
+    assert_eq!(estimate.writer, 5 * 1024 * 1024);
+
+    // 素数を保持しないモードと、セグメントより狭い範囲
+    let estimate = estimate_peak_memory(&Config { mode: RunMode::Cunningham, ..config }, 1, 100, 8);
+    assert_eq!(estimate.primes, 0);
+    assert!(estimate.sieving < 1000, "{:?}", estimate);
+}
+
+#[test]
+fn fewer_parallel_segments_write_the_same_primes() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_memory_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_max: "100000".to_string(),
+        segment_size: 1000,
+        parallel_segments: 3,
+        output_dir: dir.to_string_lossy().into_owned(),
+        ..Config::default()
+    };
+    let (sender, _messages) = mpsc::channel();
+    let (_commands_sender, commands) = mpsc::channel();
+    run_program_old(config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap();
+    let mut written = Vec::new();
+    read_primes(&dir.join("primes.txt"), |p| written.push(p)).unwrap();
+    assert_eq!(written, simple_sieve(100_000));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,289 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use sosu_seisei_sieve::app::WorkerMessage;
+use sosu_seisei_sieve::config::{Config, OutputFormat, OverwriteMode, RunMode};
+use sosu_seisei_sieve::metadata::{find_overlaps, meta_path, read_meta, require_decimal_text, uncovered_ranges, Fingerprint, OutputMeta, Overlap};
+use sosu_seisei_sieve::output::read_primes;
+use sosu_seisei_sieve::primes::Primes;
+use sosu_seisei_sieve::nth_prime::run_nth_prime;
+use sosu_seisei_sieve::sieve::{analyze_existing_output, run_program_old};
+use sosu_seisei_sieve::spiral::{load_spiral_primes, SpiralSource};
+
+#[test]
+fn names_the_sidecar_after_the_file_set() {
+    assert_eq!(meta_path(Path::new("out/primes.txt"), 0), Path::new("out/primes.meta.json"));
+    assert_eq!(meta_path(Path::new("out/primes_12.csv"), 100), Path::new("out/primes.meta.json"));
+    assert_eq!(meta_path(Path::new("out/primes_from_idx_1001.txt"), 1000), Path::new("out/primes.meta.json"));
+    assert_eq!(meta_path(Path::new("out/primes (2)_3.txt"), 100), Path::new("out/primes (2).meta.json"));
+}
+
+#[test]
+fn merges_ranges_and_plans_continuations() {
+    let mut meta = OutputMeta::new(&Config::default());
+    assert_eq!(meta.continuation(5), Some(5));
+    meta.add_range(1, 100);
+    meta.add_range(201, 300);
+    meta.add_range(101, 150);
+    assert_eq!(meta.ranges, vec![(1, 150), (201, 300)]);
+    meta.add_range(140, 210);
+    assert_eq!(meta.ranges, vec![(1, 300)]);
+
+    assert_eq!(meta.continuation(1), Some(301));
+    assert_eq!(meta.continuation(301), Some(301));
+    assert_eq!(meta.continuation(500), Some(500));
+    meta.add_range(500, 600);
+    assert_eq!(meta.continuation(100), None);
+}
+
+#[test]
+fn only_continues_output_with_the_same_settings() {
+    let config = Config { residue_modulus: 4, residues: vec![1], ..Config::default() };
+    let meta = OutputMeta::new(&config);
+    assert!(meta.matches(&config));
+    assert!(!meta.matches(&Config { residues: vec![3], ..config.clone() }));
+    assert!(!meta.matches(&Config { split_count: 10, ..config.clone() }));
+    assert!(!meta.matches(&Config { digit_filter: "palindrome".to_string(), ..config.clone() }));
+    assert!(!meta.matches(&Config { filter_script: "p % 10 == 7".to_string(), ..config }));
+}
+
+fn run(config: &Config) {
+    let (sender, _messages) = mpsc::channel();
+    let (_commands_sender, commands) = mpsc::channel();
+    run_program_old(config.clone(), sender, commands, Arc::new(AtomicBool::new(false))).unwrap();
+}
+
+#[test]
+fn appending_continues_after_the_covered_range() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_metadata_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_min: "1".to_string(),
+        prime_max: "10000".to_string(),
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        segment_size: 1_000,
+        ..Config::default()
+    };
+    run(&config);
+    let meta = read_meta(&dir.join("primes.meta.json")).unwrap();
+    assert_eq!(meta.ranges, vec![(1, 10000)]);
+    assert_eq!(meta.primes_found, 1229);
+
+    // [1, 25000] を頼むと 10001 から篩って追記する
+    let config = Config { prime_max: "25000".to_string(), overwrite_mode: OverwriteMode::Append, ..config };
+    run(&config);
+    // 範囲をすでに含む場合は何も書かない
+    run(&Config { prime_max: "20000".to_string(), ..config });
+
+    let mut written = Vec::new();
+    read_primes(&dir.join("primes.txt"), |p| written.push(p)).unwrap();
+    assert_eq!(written, Primes::new(1, 25000).collect::<Vec<_>>());
+    let meta = read_meta(&dir.join("primes.meta.json")).unwrap();
+    assert_eq!(meta.ranges, vec![(1, 25000)]);
+    assert_eq!(meta.primes_found, written.len() as u64);
+    assert_eq!(meta.files, vec!["primes.txt".to_string()]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn fingerprints_depend_only_on_the_primes() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_metadata_fingerprint_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_min: "1".to_string(),
+        prime_max: "100000".to_string(),
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        ..Config::default()
+    };
+    let fingerprint = |config: Config| {
+        let (sender, _messages) = mpsc::channel();
+        let (_commands_sender, commands) = mpsc::channel();
+        if config.mode == RunMode::NthPrime {
+            run_nth_prime(config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap();
+        } else {
+            run_program_old(config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap();
+        }
+        read_meta(&dir.join("primes.meta.json")).unwrap().fingerprint.unwrap()
+    };
+    let expected = Fingerprint::of(&Primes::new(1, 100_000).collect::<Vec<_>>()).hex();
+    assert_eq!(fingerprint(config.clone()), expected);
+    // 分け方・並列数・形式・順序が違っても同じ
+    assert_eq!(fingerprint(Config { segment_size: 777, parallel_segments: 3, output_format: OutputFormat::Binary, ..config.clone() }), expected);
+    assert_eq!(fingerprint(Config { descending: true, ..config.clone() }), expected);
+    assert_eq!(fingerprint(Config { mode: RunMode::NthPrime, nth_count: 9592, ..config.clone() }), expected);
+    // 範囲が違えば違う
+    assert_ne!(fingerprint(Config { prime_max: "99990".to_string(), ..config }), expected);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn stopping_while_writing_keeps_a_checkpoint() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_metadata_stop_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_min: "0".to_string(),
+        prime_max: "20000000".to_string(),
+        output_dir: dir.display().to_string(),
+        output_format: OutputFormat::JSON,
+        overwrite_mode: OverwriteMode::Overwrite,
+        ..Config::default()
+    };
+    // 最初の素数を書いたと知らされたら止める
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let (sender, messages) = mpsc::channel();
+    let (_commands_sender, commands) = mpsc::channel();
+    let worker = {
+        let (config, stop_flag) = (config.clone(), Arc::clone(&stop_flag));
+        std::thread::spawn(move || run_program_old(config, sender, commands, stop_flag))
+    };
+    let mut stopped = false;
+    for message in messages {
+        match message {
+            WorkerMessage::FoundPrimeIndex(..) => stop_flag.store(true, Ordering::SeqCst),
+            WorkerMessage::Stopped => stopped = true,
+            _ => {}
+        }
+    }
+    worker.join().unwrap().unwrap();
+    assert!(stopped);
+
+    // 閉じた JSON 配列で、書いた範囲がメタデータにある
+    let content = fs::read_to_string(dir.join("primes.json")).unwrap();
+    assert!(content.starts_with("[2") && content.ends_with(']'), "{}", &content[content.len().saturating_sub(20)..]);
+    let meta = read_meta(&dir.join("primes.meta.json")).unwrap();
+    let mut written = Vec::new();
+    read_primes(&dir.join("primes.json"), |p| written.push(p)).unwrap();
+    assert!(written.len() < 1_270_607);
+    assert_eq!(meta.ranges, vec![(0, *written.last().unwrap())]);
+    assert_eq!(meta.primes_found, written.len() as u64);
+
+    // Append で続きから書く
+    run(&Config { overwrite_mode: OverwriteMode::Append, ..config });
+    let mut written = Vec::new();
+    read_primes(&dir.join("primes.json"), |p| written.push(p)).unwrap();
+    assert_eq!(written.len(), 1_270_607);
+    assert!(written.windows(2).all(|w| w[0] < w[1]));
+    // 止めて続けた出力の指紋は、書いた素数すべての指紋
+    let meta = read_meta(&dir.join("primes.meta.json")).unwrap();
+    assert_eq!(meta.fingerprint, Some(Fingerprint::of(&written).hex()));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+fn overlap(ranges: Vec<(u64, u64)>, same_filters: bool) -> Overlap {
+    Overlap { meta_path: "primes (2).meta.json".into(), ranges, same_filters, missing_files: Vec::new() }
+}
+
+#[test]
+fn computes_the_ranges_left_to_sieve() {
+    assert_eq!(uncovered_ranges(1, 100, &[]), vec![(1, 100)]);
+    let overlaps = [overlap(vec![(1, 10), (51, 60)], true), overlap(vec![(55, 70)], true), overlap(vec![(80, 90)], false)];
+    assert_eq!(uncovered_ranges(1, 100, &overlaps), vec![(11, 50), (71, 100)]);
+    assert_eq!(uncovered_ranges(1, 100, &[overlap(vec![(1, 100)], true)]), vec![]);
+    assert_eq!(uncovered_ranges(0, u64::MAX, &[overlap(vec![(5, u64::MAX)], true)]), vec![(0, 4)]);
+}
+
+#[test]
+fn skips_ranges_covered_by_other_outputs() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_overlaps_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_min: "1".to_string(),
+        prime_max: "10000".to_string(),
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        segment_size: 1_000,
+        ..Config::default()
+    };
+    run(&config);
+    // 上書きする組は重なりとして扱わない
+    assert!(find_overlaps(&config, 1, 10000).is_empty());
+
+    // 新しい組に書くときは [5001, 10000] を飛ばす
+    let config = Config { prime_min: "5001".to_string(), prime_max: "20000".to_string(), overwrite_mode: OverwriteMode::Version, skip_covered_ranges: true, ..config };
+    let overlaps = find_overlaps(&config, 5001, 20000);
+    assert_eq!(overlaps.len(), 1);
+    assert_eq!(overlaps[0].ranges, vec![(5001, 10000)]);
+    assert!(overlaps[0].is_reusable());
+    run(&config);
+
+    let mut written = Vec::new();
+    read_primes(&dir.join("primes (2).txt"), |p| written.push(p)).unwrap();
+    assert_eq!(written, Primes::new(10001, 20000).collect::<Vec<_>>());
+    assert_eq!(read_meta(&dir.join("primes (2).meta.json")).unwrap().ranges, vec![(10001, 20000)]);
+
+    let overlaps = find_overlaps(&config, 1, 30000);
+    assert_eq!(uncovered_ranges(1, 30000, &overlaps), vec![(20001, 30000)]);
+    // ファイルの欠けた組と、別のフィルタで書いた組は飛ばさない
+    fs::remove_file(dir.join("primes.txt")).unwrap();
+    let overlaps = find_overlaps(&config, 1, 30000);
+    let missing: Vec<&Vec<String>> = overlaps.iter().map(|o| &o.missing_files).collect();
+    assert_eq!(missing, vec![&vec![], &vec!["primes.txt".to_string()]]);
+    assert_eq!(uncovered_ranges(1, 30000, &overlaps), vec![(1, 10000), (20001, 30000)]);
+    let filtered = Config { digit_filter: "palindrome".to_string(), ..config };
+    assert_eq!(uncovered_ranges(1, 30000, &find_overlaps(&filtered, 1, 30000)), vec![(1, 30000)]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn writes_descending_output_that_is_not_continued() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_descending_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_min: "1".to_string(),
+        prime_max: "5000".to_string(),
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        segment_size: 700,
+        descending: true,
+        ..Config::default()
+    };
+    run(&config);
+    let mut written = Vec::new();
+    read_primes(&dir.join("primes.txt"), |p| written.push(p)).unwrap();
+    let mut expected: Vec<u64> = Primes::new(1, 5000).collect();
+    expected.reverse();
+    assert_eq!(written, expected);
+    let meta = read_meta(&dir.join("primes.meta.json")).unwrap();
+    assert!(meta.descending);
+    assert!(!meta.matches(&Config { descending: false, ..config.clone() }));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn only_decimal_text_output_is_read_back() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_metadata_decimal_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_min: "1".to_string(),
+        prime_max: "1000".to_string(),
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        split_count: 100,
+        ..Config::default()
+    };
+    run(&config);
+    assert!(require_decimal_text(&dir.join("primes_1.txt"), None).is_ok());
+
+    // 16 進の出力は、読む側の設定が 10 進でもメタデータを見て断る
+    run(&Config { output_radix: 16, split_count: 0, ..config.clone() });
+    assert!(require_decimal_text(&dir.join("primes.txt"), None).is_err());
+    assert!(require_decimal_text(&dir.join("primes.txt"), Some((&OutputFormat::Text, 10))).is_err());
+    let (sender, _messages) = mpsc::channel();
+    assert!(analyze_existing_output(Config { split_count: 0, ..config.clone() }, sender.clone(), Arc::new(AtomicBool::new(false))).is_err());
+    let source = SpiralSource::File(dir.join("primes.txt"));
+    assert!(load_spiral_primes(source, 1, 100, sender, Arc::new(AtomicBool::new(false))).is_err());
+
+    run(&Config { output_format: OutputFormat::FixedWidth, split_count: 0, ..config.clone() });
+    assert!(require_decimal_text(&dir.join("primes.txt"), None).is_err());
+    run(&Config { output_format: OutputFormat::Binary, split_count: 0, ..config });
+    assert!(require_decimal_text(&dir.join("primes.bin"), None).is_err());
+    let _ = fs::remove_dir_all(&dir);
+}
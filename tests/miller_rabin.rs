@@ -0,0 +1,106 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use num_bigint::BigUint;
+use proptest::prelude::*;
+use sosu_seisei_sieve::miller_rabin::{
+    check_prime_u128, find_non_prime, is_bpsw_probable_prime_u128, is_prime, is_probable_prime_u128, mul_mod, pow_mod, pow_mod_u128, verify_prime, BigPrimality,
+    DETERMINISTIC_LIMIT,
+};
+
+// u128 の剰余だけで計算した値
+fn reference(base: u64, exp: u64, m: u64) -> u64 {
+    let mut result = 1 % m;
+    let mut base = base % m;
+    let mut exp = exp;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+#[test]
+fn pow_mod_handles_edge_moduli() {
+    for m in [1, 2, 3, 4, 1 << 63, (1 << 63) + 1, u64::MAX - 1, u64::MAX] {
+        for (base, exp) in [(0, 0), (0, 5), (1, 0), (u64::MAX, 1), (u64::MAX, u64::MAX), (12345, 678)] {
+            assert_eq!(pow_mod(base, exp, m), reference(base, exp, m), "{}^{} mod {}", base, exp, m);
+        }
+    }
+    // 2^64 未満の最大の素数と、強擬素数 3215031751 = 151·751·28351
+    assert!(is_prime(18_446_744_073_709_551_557));
+    assert!(!is_prime(18_446_744_073_709_551_559));
+    assert!(!is_prime(3_215_031_751));
+}
+
+#[test]
+fn verify_prime_agrees_with_is_prime() {
+    for n in 0..10_000 {
+        assert_eq!(verify_prime(n), is_prime(n), "{}", n);
+    }
+    // 底を減らす境目の強擬素数と、その前後
+    for n in [2_047u64, 1_373_653, 25_326_001, 3_215_031_751, 2_152_302_898_747, 3_474_749_660_383, 341_550_071_728_321, 3_825_123_056_546_413_051] {
+        assert!(!verify_prime(n), "{}", n);
+        for m in n - 100..n + 100 {
+            assert_eq!(verify_prime(m), is_prime(m), "{}", m);
+        }
+    }
+    assert!(verify_prime(18_446_744_073_709_551_557));
+    // 53 の倍数と、53 より大きい素数の平方
+    assert!(!verify_prime(53 * 1_000_003));
+    assert!(!verify_prime(59 * 59));
+
+    let mut numbers: Vec<u64> = (1_000_000..1_100_000).filter(|&n| is_prime(n)).collect();
+    assert_eq!(find_non_prime(&numbers, 1), None);
+    numbers[500] = 1_000_001;
+    assert_eq!(find_non_prime(&numbers, 1), Some(1_000_001));
+    // 抜き取り確認では間の数は見ない
+    assert_eq!(find_non_prime(&numbers, 1000), None);
+}
+
+#[test]
+fn u128_tests_agree_with_u64() {
+    // 2047 や 3277 など底 2 の強擬素数は Lucas 判定で除かれる
+    for n in 0..100_000u64 {
+        assert_eq!(is_bpsw_probable_prime_u128(n as u128), is_prime(n), "{}", n);
+        assert_eq!(is_probable_prime_u128(n as u128), is_prime(n), "{}", n);
+    }
+    let mersenne = |p: u32| (1u128 << p) - 1;
+    assert_eq!(check_prime_u128((1 << 64) + 13, 0), BigPrimality::Prime);
+    assert_eq!(check_prime_u128(DETERMINISTIC_LIMIT, 0), BigPrimality::Composite);
+    // 最初の 12 個の素数を底にした最小の強擬素数（上限より小さい）
+    assert_ne!(check_prime_u128(318_665_857_834_031_151_167_461, 0), BigPrimality::Prime);
+    assert!(!is_probable_prime_u128(318_665_857_834_031_151_167_461));
+    assert_eq!(check_prime_u128(mersenne(127), 5), BigPrimality::ProbablePrime);
+    assert_eq!(check_prime_u128(mersenne(61) * mersenne(61), 5), BigPrimality::Composite);
+    assert_eq!(check_prime_u128(mersenne(61) * mersenne(67), 5), BigPrimality::Composite);
+    assert_eq!(check_prime_u128(u128::MAX, 5), BigPrimality::Composite);
+}
+
+proptest! {
+    #[test]
+    fn pow_mod_agrees_with_u128(base: u64, exp: u64, m in prop_oneof![1u64..1000, any::<u64>().prop_map(|m| m.max(1)), (u64::MAX - 1000)..=u64::MAX]) {
+        prop_assert_eq!(pow_mod(base, exp, m), reference(base, exp, m));
+    }
+
+    #[test]
+    fn pow_mod_u128_agrees_with_big_uint(base: u128, exp: u128, m in prop_oneof![any::<u128>().prop_map(|m| m | 1), (u128::MAX - 1000)..=u128::MAX, 1u128..1000]) {
+        let expected = BigUint::from(base).modpow(&BigUint::from(exp), &BigUint::from(m));
+        prop_assert_eq!(BigUint::from(pow_mod_u128(base, exp, m)), expected);
+    }
+
+    #[test]
+    fn bpsw_u128_agrees_with_is_prime(n: u64) {
+        prop_assert_eq!(is_bpsw_probable_prime_u128(n as u128), is_prime(n));
+    }
+
+    #[test]
+    fn verify_prime_agrees_with_is_prime_everywhere(n: u64) {
+        prop_assert_eq!(verify_prime(n), is_prime(n));
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::sync::mpsc;
+use std::time::Duration;
+use sosu_seisei_sieve::app::WorkerMessage;
+use sosu_seisei_sieve::monitor::{is_cpu_sensor, ResourceMonitor, ThermalThrottle};
+
+#[test]
+fn runs_only_while_subscribed() {
+    let mut monitor = ResourceMonitor::new();
+    assert!(!monitor.is_running());
+    let (first, first_messages) = mpsc::channel();
+    let (second, second_messages) = mpsc::channel();
+    monitor.subscribe(1, first);
+    monitor.subscribe(2, second);
+    assert!(monitor.is_running());
+    for messages in [&first_messages, &second_messages] {
+        assert!(matches!(messages.recv_timeout(Duration::from_secs(5)), Ok(WorkerMessage::MemUsage(usage)) if usage > 0));
+        // コアごとの使用率も送る
+        match messages.recv_timeout(Duration::from_secs(5)) {
+            Ok(WorkerMessage::CpuUsage(loads)) => assert!(!loads.is_empty() && loads.iter().all(|&load| load >= 0.0)),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    // 1 つ外しても残りの購読には送り続ける
+    monitor.unsubscribe(1);
+    assert!(monitor.is_running());
+    assert!(second_messages.recv_timeout(Duration::from_secs(5)).is_ok());
+    monitor.unsubscribe(2);
+    assert!(!monitor.is_running());
+    // スレッドは止まっていて、送り側はすべて捨てられている
+    assert!(first_messages.iter().all(|m| matches!(m, WorkerMessage::MemUsage(_) | WorkerMessage::CpuUsage(_))));
+    assert!(second_messages.iter().all(|m| matches!(m, WorkerMessage::MemUsage(_) | WorkerMessage::CpuUsage(_))));
+}
+
+#[test]
+fn throttles_segments_by_temperature() {
+    let mut throttle = ThermalThrottle::new(80, 8);
+    assert_eq!(throttle.update(70.0), None);
+    // 上限を超えるたびに半分にし、1 より減らさない
+    assert_eq!(throttle.update(85.0), Some(4));
+    assert_eq!(throttle.update(90.0), Some(2));
+    assert_eq!(throttle.update(90.0), Some(1));
+    assert_eq!(throttle.update(90.0), None);
+    // 上限の少し下では戻さず、十分に下がったら倍ずつ戻す
+    assert_eq!(throttle.update(78.0), None);
+    assert_eq!(throttle.update(75.0), Some(2));
+    assert_eq!(throttle.update(60.0), Some(4));
+    assert_eq!(throttle.update(60.0), Some(8));
+    assert_eq!(throttle.update(60.0), None);
+    assert_eq!(throttle.current, 8);
+}
+
+#[test]
+fn recognizes_cpu_sensors() {
+    for label in ["coretemp Package id 0", "k10temp Tctl", "CPU Temperature", "Core 3"] {
+        assert!(is_cpu_sensor(label), "{}", label);
+    }
+    for label in ["nvme Composite", "acpitz", "amdgpu edge"] {
+        assert!(!is_cpu_sensor(label), "{}", label);
+    }
+}
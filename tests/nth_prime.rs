@@ -0,0 +1,59 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use sosu_seisei_sieve::app::WorkerMessage;
+use sosu_seisei_sieve::config::{Config, OverwriteMode, RunMode};
+use sosu_seisei_sieve::nth_prime::{next_segments, run_nth_prime};
+use sosu_seisei_sieve::output::read_primes;
+use sosu_seisei_sieve::primes::Primes;
+
+#[test]
+fn extends_segments_up_to_the_end_of_u64() {
+    assert_eq!(next_segments(1, 10, 3), vec![(1, 10), (11, 20), (21, 30)]);
+    assert_eq!(next_segments(u64::MAX - 14, 10, 3), vec![(u64::MAX - 14, u64::MAX - 5), (u64::MAX - 4, u64::MAX)]);
+}
+
+// N 個の素数を探し、書き出した素数と報告された N 番目の素数を返す
+fn run(name: &str, prime_min: u64, n: u64) -> (Vec<u64>, Option<(u64, u64)>) {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        mode: RunMode::NthPrime,
+        prime_min: prime_min.to_string(),
+        nth_count: n,
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        segment_size: 500,
+        ..Config::default()
+    };
+    let (sender, messages) = mpsc::channel();
+    let (_commands_sender, commands) = mpsc::channel();
+    run_nth_prime(config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap();
+    let reported = messages.try_iter().find_map(|m| match m {
+        WorkerMessage::NthPrime { n, prime } => Some((n, prime)),
+        _ => None,
+    });
+    let mut written = Vec::new();
+    read_primes(&dir.join("primes.txt"), |p| written.push(p)).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    (written, reported)
+}
+
+#[test]
+fn finds_the_nth_prime() {
+    let (written, reported) = run("nth_first", 1, 1000);
+    assert_eq!(written, Primes::new(1, 7919).collect::<Vec<_>>());
+    assert_eq!(reported, Some((1000, 7919)));
+}
+
+#[test]
+fn counts_from_prime_min() {
+    let (written, reported) = run("nth_from", 100, 10);
+    assert_eq!(written, vec![101, 103, 107, 109, 113, 127, 131, 137, 139, 149]);
+    assert_eq!(reported, Some((10, 149)));
+}
@@ -0,0 +1,329 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use sosu_seisei_sieve::config::{OutputFormat, OverwriteMode};
+use sosu_seisei_sieve::filter::ResidueFilter;
+use sosu_seisei_sieve::output::{content_len, existing_outputs, fixed_record_len, push_line, push_radix, read_primes, PrimeSink, PrimeWriter, ZstdOptions, FLUSH_CHECK_RECORDS};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use sosu_seisei_sieve::sieve::simple_sieve;
+use sosu_seisei_sieve::viewer::PrimeIndex;
+
+const PRIMES: [u64; 7] = [2, 3, 5, 7, 11, 13, 17];
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_all(dir: &Path, format: OutputFormat, split_count: u64, mode: OverwriteMode, primes: &[u64]) {
+    let mut out = PrimeWriter::create(format, None, dir, split_count, 16, &mode).unwrap();
+    for &p in primes {
+        out.write_prime(p).unwrap();
+    }
+    out.finish().unwrap();
+}
+
+// 各ファイルが単独で有効な JSON 配列であることを確認して中身を返す
+fn parse_json_array(path: PathBuf) -> Vec<u64> {
+    let content = fs::read_to_string(&path).unwrap();
+    let body = content
+        .strip_prefix('[')
+        .and_then(|c| c.strip_suffix(']'))
+        .unwrap_or_else(|| panic!("{} is not a JSON array: {:?}", path.display(), content));
+    if body.is_empty() {
+        return Vec::new();
+    }
+    body.split(',').map(|v| v.parse().unwrap()).collect()
+}
+
+#[test]
+fn json_split_files_are_independently_valid() {
+    let dir = temp_dir("json_split");
+    write_all(&dir, OutputFormat::JSON, 3, OverwriteMode::Overwrite, &PRIMES);
+
+    assert_eq!(parse_json_array(dir.join("primes_1.json")), vec![2, 3, 5]);
+    assert_eq!(parse_json_array(dir.join("primes_2.json")), vec![7, 11, 13]);
+    assert_eq!(parse_json_array(dir.join("primes_3.json")), vec![17]);
+    assert!(!dir.join("primes_4.json").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn split_on_exact_boundary_leaves_no_empty_file() {
+    let dir = temp_dir("exact_boundary");
+    write_all(&dir, OutputFormat::JSON, 3, OverwriteMode::Overwrite, &PRIMES[..6]);
+    assert_eq!(parse_json_array(dir.join("primes_2.json")), vec![7, 11, 13]);
+    assert!(!dir.join("primes_3.json").exists());
+
+    write_all(&dir, OutputFormat::Text, 2, OverwriteMode::Overwrite, &PRIMES[..4]);
+    assert_eq!(fs::read_to_string(dir.join("primes_1.txt")).unwrap(), "2\n3\n");
+    assert_eq!(fs::read_to_string(dir.join("primes_2.txt")).unwrap(), "5\n7\n");
+    assert!(!dir.join("primes_3.txt").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn empty_output_is_valid_json() {
+    let dir = temp_dir("json_empty");
+    write_all(&dir, OutputFormat::JSON, 0, OverwriteMode::Overwrite, &[]);
+    assert_eq!(parse_json_array(dir.join("primes.json")), Vec::<u64>::new());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn csv_split_boundaries() {
+    let dir = temp_dir("csv_split");
+    write_all(&dir, OutputFormat::CSV, 4, OverwriteMode::Overwrite, &PRIMES);
+    assert_eq!(fs::read_to_string(dir.join("primes_1.csv")).unwrap(), "2,3,5,7,");
+    assert_eq!(fs::read_to_string(dir.join("primes_2.csv")).unwrap(), "11,13,17,");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn json_append_continues_the_array() {
+    let dir = temp_dir("json_append");
+    write_all(&dir, OutputFormat::JSON, 0, OverwriteMode::Overwrite, &PRIMES[..3]);
+    write_all(&dir, OutputFormat::JSON, 0, OverwriteMode::Append, &PRIMES[3..]);
+    assert_eq!(parse_json_array(dir.join("primes.json")), PRIMES.to_vec());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn residue_filter_writes_selected_classes() {
+    let dir = temp_dir("residue_filter");
+    let out = PrimeWriter::create(OutputFormat::Text, None, &dir, 0, 16, &OverwriteMode::Overwrite).unwrap();
+    let mut out = ResidueFilter::new(out, 4, &[1]);
+    for &p in &PRIMES {
+        out.write_prime(p).unwrap();
+    }
+    out.finish().unwrap();
+    assert_eq!(fs::read_to_string(dir.join("primes.txt")).unwrap(), "5\n13\n17\n");
+    assert_eq!(out.counts(), &[0, 3, 1, 3]);
+    assert_eq!(out.written(), 3);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn paged_index_over_output_file() {
+    let dir = temp_dir("viewer");
+    let primes = simple_sieve(10_000);
+    write_all(&dir, OutputFormat::JSON, 0, OverwriteMode::Overwrite, &primes);
+    let path = dir.join("primes.json");
+    let stop = AtomicBool::new(false);
+
+    assert_eq!(PrimeIndex::load(&path).unwrap(), None);
+    let index = PrimeIndex::build(&path, 7, &stop, |_, _| {}).unwrap().unwrap();
+    assert_eq!(index.count, primes.len() as u64);
+    for first in [0, 6, 7, 100, primes.len() as u64 - 3] {
+        let page = index.read_page(first, 5).unwrap();
+        let expected: Vec<u64> = primes.iter().skip(first as usize).take(5).copied().collect();
+        assert_eq!(page, expected, "{}", first);
+    }
+    assert_eq!(index.position_of(0).unwrap(), 0);
+    assert_eq!(index.position_of(7919).unwrap(), 999);
+    assert_eq!(index.position_of(7920).unwrap(), 1000);
+    assert_eq!(index.position_of(100_000).unwrap(), index.count);
+
+    // 保存した索引はファイルが変わるまで再利用される
+    index.save().unwrap();
+    assert_eq!(PrimeIndex::load(&path).unwrap(), Some(index));
+    write_all(&dir, OutputFormat::JSON, 0, OverwriteMode::Append, &[10_007]);
+    assert_eq!(PrimeIndex::load(&path).unwrap(), None);
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn fixed_width_records_are_searched_without_an_index() {
+    let dir = temp_dir("fixed_width");
+    let primes = simple_sieve(10_000);
+    write_all(&dir, OutputFormat::FixedWidth, 0, OverwriteMode::Overwrite, &primes);
+    let path = dir.join("primes.txt");
+    let content = fs::read_to_string(&path).unwrap();
+    assert!(content.starts_with("00000000000000000002\n00000000000000000003\n"), "{:?}", &content[..42]);
+    assert_eq!(content.len(), primes.len() * 21);
+    assert_eq!(fixed_record_len(&path).unwrap(), Some(21));
+
+    let index = PrimeIndex::fixed(&path).unwrap().unwrap();
+    assert_eq!(index.count, primes.len() as u64);
+    assert_eq!(index.read_page(1000, 3).unwrap(), primes[1000..1003]);
+    assert_eq!(index.position_of(0).unwrap(), 0);
+    assert_eq!(index.position_of(7919).unwrap(), 999);
+    assert_eq!(index.position_of(7920).unwrap(), 1000);
+    assert_eq!(index.position_of(100_000).unwrap(), index.count);
+
+    // 番号も同じ幅で埋めるので、番号付きでも同じ長さ
+    let mut out = PrimeWriter::create(OutputFormat::FixedWidth, None, &dir, 0, 16, &OverwriteMode::Overwrite).unwrap().with_prime_index().unwrap();
+    for &p in &PRIMES {
+        out.write_prime(p).unwrap();
+    }
+    out.finish().unwrap();
+    assert_eq!(fixed_record_len(&path).unwrap(), Some(42));
+    assert_eq!(PrimeIndex::fixed(&path).unwrap().unwrap().read_page(4, 10).unwrap(), [11, 13, 17]);
+
+    // ゼロ埋めされていない Text や、長さのそろわない Text は索引を作る
+    write_all(&dir, OutputFormat::Text, 0, OverwriteMode::Overwrite, &[2, 3, 5, 7]);
+    assert_eq!(fixed_record_len(&path).unwrap(), None);
+    fs::write(&path, "02\n03\n05\n07\n011\n").unwrap();
+    assert_eq!(fixed_record_len(&path).unwrap(), None);
+    write_all(&dir, OutputFormat::Binary, 0, OverwriteMode::Overwrite, &PRIMES);
+    assert_eq!(fixed_record_len(&dir.join("primes.bin")).unwrap(), Some(8));
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn formats_numbers_in_other_bases() {
+    let format = |p: u64, radix: u32| {
+        let mut buf = Vec::new();
+        push_radix(&mut buf, p, radix);
+        String::from_utf8(buf).unwrap()
+    };
+    assert_eq!(format(0, 16), "0");
+    assert_eq!(format(255, 16), "ff");
+    assert_eq!(format(35, 36), "z");
+    assert_eq!(format(13, 2), "1101");
+    assert_eq!(format(u64::MAX, 16), "ffffffffffffffff");
+    assert_eq!(format(u64::MAX, 2), "1".repeat(64));
+    assert_eq!(format(u64::MAX, 36), "3w5e11264sgsf");
+    assert_eq!(format(1_000_003, 10), "1000003");
+}
+
+#[test]
+fn formats_decimal_lines() {
+    let mut buf = Vec::new();
+    for p in [0, 7, 1_000_000_007, u64::MAX] {
+        push_line(&mut buf, p);
+    }
+    assert_eq!(String::from_utf8(buf).unwrap(), "0\n7\n1000000007\n18446744073709551615\n");
+}
+
+#[test]
+fn writes_hexadecimal_records() {
+    let dir = temp_dir("hex_output");
+    for format in [OutputFormat::Text, OutputFormat::JSON] {
+        let mut out = PrimeWriter::create(format, None, &dir, 0, 16, &OverwriteMode::Overwrite).unwrap().with_radix(16);
+        for p in [2, 11, 31, 257] {
+            out.write_prime(p).unwrap();
+        }
+        out.finish().unwrap();
+    }
+    assert_eq!(fs::read_to_string(dir.join("primes.txt")).unwrap(), "2\nb\n1f\n101\n");
+    // JSON の数値は 10 進なので文字列で書く
+    assert_eq!(fs::read_to_string(dir.join("primes.json")).unwrap(), r#"["2","b","1f","101"]"#);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn io_thread_writes_the_same_split_files() {
+    let dir = temp_dir("io_thread");
+    let mut out = PrimeWriter::create(OutputFormat::JSON, None, &dir, 3, 4, &OverwriteMode::Overwrite).unwrap().with_io_thread(2).unwrap();
+    for &p in &PRIMES {
+        out.write_prime(p).unwrap();
+    }
+    out.finish().unwrap();
+    drop(out);
+    assert_eq!(parse_json_array(dir.join("primes_1.json")), vec![2, 3, 5]);
+    assert_eq!(parse_json_array(dir.join("primes_2.json")), vec![7, 11, 13]);
+    assert_eq!(parse_json_array(dir.join("primes_3.json")), vec![17]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn flush_interval_writes_before_the_buffer_fills() {
+    let dir = temp_dir("flush_interval");
+    let mut out = PrimeWriter::create(OutputFormat::Text, None, &dir, 0, 1 << 20, &OverwriteMode::Overwrite).unwrap()
+        .with_flush_interval(Duration::from_millis(1));
+    let records = FLUSH_CHECK_RECORDS as u64;
+    for p in 1..=records {
+        out.write_prime(p).unwrap();
+    }
+    std::thread::sleep(Duration::from_millis(10));
+    // 書き出すのは次の 1 件を書く前で、その 1 件はまだバッファにある
+    out.write_prime(records + 1).unwrap();
+    let written: Vec<u64> = fs::read_to_string(dir.join("primes.txt")).unwrap().lines().map(|l| l.parse().unwrap()).collect();
+    assert_eq!(written, (1..=records).collect::<Vec<_>>());
+    out.finish().unwrap();
+    drop(out);
+    assert_eq!(fs::read_to_string(dir.join("primes.txt")).unwrap().lines().count() as u64, records + 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn zstd_output_is_seekable_and_appendable() {
+    let dir = temp_dir("zstd");
+    let primes = simple_sieve(20_000);
+    let zstd = Some(ZstdOptions { level: 3, frame_size: 1000 });
+    let (first, rest) = primes.split_at(1500);
+    for (part, mode) in [(first, OverwriteMode::Overwrite), (rest, OverwriteMode::Append)] {
+        let mut out = PrimeWriter::create(OutputFormat::JSON, zstd, &dir, 0, 64, &mode).unwrap();
+        for &p in part {
+            out.write_prime(p).unwrap();
+        }
+        out.finish().unwrap();
+    }
+    let path = dir.join("primes.json.zst");
+    let text = zstd::decode_all(fs::File::open(&path).unwrap()).unwrap();
+    fs::write(dir.join("primes.json"), text).unwrap();
+    assert_eq!(parse_json_array(dir.join("primes.json")), primes);
+
+    // 索引のバイト位置は展開後のもの
+    let index = PrimeIndex::build(&path, 100, &AtomicBool::new(false), |_, _| {}).unwrap().unwrap();
+    assert_eq!(index.count, primes.len() as u64);
+    for first in [0, 99, 1499, 1500, primes.len() as u64 - 2] {
+        let expected: Vec<u64> = primes.iter().skip(first as usize).take(5).copied().collect();
+        assert_eq!(index.read_page(first, 5).unwrap(), expected, "{}", first);
+    }
+    assert_eq!(index.position_of(7919).unwrap(), 999);
+    assert_eq!(content_len(&path).unwrap(), fs::metadata(dir.join("primes.json")).unwrap().len());
+    assert_eq!(existing_outputs(&dir, &OutputFormat::JSON, true, 0), vec![path]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn prime_index_numbers_records_across_split_files() {
+    let dir = temp_dir("prime_index");
+    let write = |format: OutputFormat, split_count: u64| {
+        let mut out = PrimeWriter::create(format, None, &dir, split_count, 16, &OverwriteMode::Overwrite).unwrap().with_prime_index().unwrap();
+        for &p in &PRIMES {
+            out.write_prime(p).unwrap();
+        }
+        out.finish().unwrap();
+        out.files().to_vec()
+    };
+
+    let files = write(OutputFormat::Text, 3);
+    let names: Vec<_> = files.iter().map(|f| f.file_name().unwrap().to_string_lossy().into_owned()).collect();
+    assert_eq!(names, ["primes_from_idx_1.txt", "primes_from_idx_4.txt", "primes_from_idx_7.txt"]);
+    assert_eq!(fs::read_to_string(&files[1]).unwrap(), "4:7\n5:11\n6:13\n");
+    assert!(!dir.join("primes_1.txt").exists());
+    assert_eq!(existing_outputs(&dir, &OutputFormat::Text, false, 3), files);
+
+    // 番号は読み飛ばして素数だけを読む
+    let mut read = Vec::new();
+    for file in &files {
+        read_primes(file, |p| read.push(p)).unwrap();
+    }
+    assert_eq!(read, PRIMES);
+
+    let files = write(OutputFormat::JSON, 0);
+    assert_eq!(files, [dir.join("primes.json")]);
+    let content = fs::read_to_string(&files[0]).unwrap();
+    assert_eq!(content, r#"{"1":2,"2":3,"3":5,"4":7,"5":11,"6":13,"7":17}"#);
+    let json: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&content).unwrap();
+    assert_eq!(json["7"], 17);
+    let mut read = Vec::new();
+    read_primes(&files[0], |p| read.push(p)).unwrap();
+    assert_eq!(read, PRIMES);
+
+    // Binary は位置から番号がわかるのでレコードを変えない
+    let files = write(OutputFormat::Binary, 4);
+    assert_eq!(files[1], dir.join("primes_from_idx_5.bin"));
+    assert_eq!(fs::read(&files[1]).unwrap().len(), 3 * 8);
+    fs::remove_dir_all(&dir).unwrap();
+}
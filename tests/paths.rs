@@ -0,0 +1,48 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+// 場所はプロセス全体で 1 回だけ決まり、カレントディレクトリも変えるので、1 つのテストにまとめる
+
+use std::env;
+use std::fs;
+use sosu_seisei_sieve::config::{load_or_create_config, save_config, Config};
+use sosu_seisei_sieve::paths;
+
+#[cfg(target_os = "linux")]
+#[test]
+fn settings_live_in_the_app_dir_and_runs_in_the_work_dir() {
+    let root = env::temp_dir().join(format!("sosu_seisei_test_paths_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let start = root.join("start");
+    fs::create_dir_all(&start).unwrap();
+    env::set_var("XDG_CONFIG_HOME", root.join("config"));
+    env::set_current_dir(&start).unwrap();
+    // 以前の版が書いた設定
+    let legacy = toml::to_string(&Config { prime_max: "12345".to_string(), ..Config::default() }).unwrap();
+    fs::write(start.join("settings.txt"), &legacy).unwrap();
+    paths::init(false);
+    assert!(!paths::is_portable());
+    assert_eq!(paths::settings_path(), root.join("config/sosu-seisei/settings.txt"));
+
+    // 読み込んだ設定は新しい場所に移す
+    let mut config = load_or_create_config().unwrap();
+    assert_eq!(config.prime_max, "12345");
+    assert!(paths::settings_path().is_file());
+    assert!(paths::legacy_settings_path().is_none());
+    config.prime_max = "999".to_string();
+    save_config(&config).unwrap();
+    assert_eq!(load_or_create_config().unwrap().prime_max, "999");
+    assert_eq!(fs::read_to_string(start.join("settings.txt")).unwrap(), legacy);
+
+    // 作業フォルダは起動したフォルダから見た位置
+    assert_eq!(paths::work_dir(&Config::default()), start);
+    let config = Config { work_dir: "work".to_string(), ..Config::default() };
+    assert_eq!(paths::enter_work_dir(&config).unwrap(), start.join("work"));
+    assert_eq!(env::current_dir().unwrap().canonicalize().unwrap(), start.join("work").canonicalize().unwrap());
+    assert_eq!(paths::work_dir(&config), start.join("work"));
+
+    env::set_current_dir(env::temp_dir()).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,117 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use sosu_seisei_sieve::app::WorkerMessage;
+use sosu_seisei_sieve::config::Config;
+use sosu_seisei_sieve::pi_report::{li, riemann_r, PiCheckpoints, PiReport, PiTracker, KNOWN_PI, REPORT_FILE_NAME};
+use sosu_seisei_sieve::sieve::{run_program_old, simple_sieve};
+
+#[test]
+fn approximations_match_known_values() {
+    assert!((li(2.0) - 1.045_163_780_117_492_7).abs() < 1e-12);
+    assert!((li(1e6) - 78_627.549_159_462_2).abs() < 1e-6);
+    // π(10^18) = 24739954287740860, π - li = -21949555
+    assert!((li(1e18) - 24_739_954_309_690_415.0).abs() < 1e3, "{}", li(1e18));
+    assert!((riemann_r(1e6) - 78_527.399_429_127_7).abs() < 1e-6, "{}", riemann_r(1e6));
+    assert!((riemann_r(1.0) - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn records_pi_at_every_boundary() {
+    let primes = simple_sieve(10_000);
+    let mut tracker = PiTracker::new(0, 10_000, 1000, 0);
+    for (i, segment) in primes.chunks(100).enumerate() {
+        // セグメントの上端は次のセグメントの最初の素数の手前
+        let high = primes.get((i + 1) * 100).map_or(10_000, |&p| p - 1);
+        tracker.add_segment(segment, high);
+    }
+    let report = tracker.finish();
+    let xs: Vec<u64> = report.rows.iter().map(|r| r.x).collect();
+    assert_eq!(xs, (1..=10).map(|k| k * 1000).collect::<Vec<_>>());
+    for row in &report.rows {
+        assert_eq!(row.pi, primes.iter().filter(|&&p| p <= row.x).count() as u64, "{}", row.x);
+    }
+}
+
+#[test]
+fn generate_writes_the_report_from_any_start() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_pi_report_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for prime_min in ["0", "500000"] {
+        let config = Config {
+            prime_min: prime_min.to_string(),
+            prime_max: "1000000".to_string(),
+            segment_size: 65_536,
+            stats_interval: 100_000,
+            pi_report: true,
+            output_dir: dir.to_string_lossy().into_owned(),
+            ..Config::default()
+        };
+        let (sender, messages) = mpsc::channel();
+        let (_commands_sender, commands) = mpsc::channel();
+        run_program_old(config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap();
+        let report: PiReport = messages.try_iter().find_map(|m| match m {
+            WorkerMessage::PiReport(report) => Some(report),
+            _ => None,
+        }).unwrap();
+        let last = report.last().unwrap();
+        assert_eq!((last.x, last.pi), (1_000_000, 78_498), "{}", prime_min);
+        assert_eq!(report.rows.first().unwrap().x, if prime_min == "0" { 100_000 } else { 600_000 });
+
+        let text = fs::read_to_string(dir.join(REPORT_FILE_NAME)).unwrap();
+        assert!(text.lines().any(|line| line.starts_with("1000000\t78498\t78627.5\t-129.5\t78527.4\t-29.4\t")), "{}", text);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn checkpoints_compare_counts_inside_segments() {
+    let primes = simple_sieve(100_000);
+    let mut checkpoints = PiCheckpoints::new();
+    let mut passed = Vec::new();
+    // 10^k がセグメントの途中にあっても数えられる
+    for (i, segment) in primes.chunks(333).enumerate() {
+        let high = primes.get((i + 1) * 333).map_or(100_000, |&p| p - 1);
+        passed.extend(checkpoints.add_segment(segment, high).unwrap());
+    }
+    assert_eq!(passed, KNOWN_PI[..5].to_vec());
+
+    // 素数が 1 つ欠けると、その後の最初の 10^k で止まる
+    let mut checkpoints = PiCheckpoints::new();
+    let missing: Vec<u64> = primes.iter().copied().filter(|&p| p != 101).collect();
+    assert_eq!(checkpoints.add_segment(&missing, 100_000), Err((1000, 167, 168)));
+}
+
+#[test]
+fn generate_checks_known_counts_from_the_start_only() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_pi_checkpoints_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let run = |prime_min: &str| {
+        let config = Config {
+            prime_min: prime_min.to_string(),
+            prime_max: "1000000".to_string(),
+            segment_size: 65_536,
+            pi_checkpoints: true,
+            output_dir: dir.to_string_lossy().into_owned(),
+            ..Config::default()
+        };
+        let (sender, messages) = mpsc::channel();
+        let (_commands_sender, commands) = mpsc::channel();
+        run_program_old(config, sender, commands, Arc::new(AtomicBool::new(false))).unwrap();
+        messages.try_iter().filter_map(|m| match m {
+            WorkerMessage::Log(line) => Some(line),
+            _ => None,
+        }).collect::<Vec<_>>()
+    };
+    let log = run("1");
+    assert_eq!(log.iter().filter(|line| line.ends_with("matches the known value")).count(), 6, "{:?}", log);
+    let log = run("100");
+    assert!(log.iter().any(|line| line.starts_with("Known pi(10^k) checks are skipped")));
+    assert!(!log.iter().any(|line| line.ends_with("matches the known value")));
+    fs::remove_dir_all(&dir).unwrap();
+}
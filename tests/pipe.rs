@@ -0,0 +1,95 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::io;
+use std::sync::mpsc;
+use sosu_seisei_sieve::config::Config;
+use sosu_seisei_sieve::output::PrimeSink;
+use sosu_seisei_sieve::pipe::PipeSink;
+
+#[derive(Default)]
+struct Collect(Vec<u64>);
+
+impl PrimeSink for Collect {
+    fn write_prime(&mut self, p: u64) -> io::Result<()> {
+        self.0.push(p);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn does_nothing_without_a_path() {
+    let (sender, _receiver) = mpsc::channel();
+    let mut out = PipeSink::new(Collect::default(), &Config::default(), &sender).unwrap();
+    assert!(!out.is_active());
+    out.write_prime(2).unwrap();
+    out.finish().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn streams_primes_to_a_unix_socket() {
+    use std::io::Read;
+    use std::os::unix::net::UnixListener;
+
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_pipe_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("primes.sock");
+    let config = Config { pipe_path: path.display().to_string(), ..Config::default() };
+    let (sender, _receiver) = mpsc::channel();
+
+    // 聞いている側がいなければ始める前に失敗する
+    assert!(PipeSink::new(Collect::default(), &config, &sender).is_err());
+
+    let listener = UnixListener::bind(&path).unwrap();
+    let reader = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut text = String::new();
+        stream.read_to_string(&mut text).unwrap();
+        text
+    });
+    let mut out = PipeSink::new(Collect::default(), &config, &sender).unwrap();
+    assert!(out.is_active());
+    let primes: Vec<u64> = (0..20_000).map(|i| 2 * i + 3).collect();
+    for &p in &primes {
+        out.write_prime(p).unwrap();
+    }
+    out.finish().unwrap();
+    assert!(!out.is_active());
+    // 終わったら接続を閉じるので、受け取る側は最後まで読める
+    let expected: String = primes.iter().map(|p| format!("{}\n", p)).collect();
+    assert_eq!(reader.join().unwrap(), expected);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn keeps_writing_when_the_reader_goes_away() {
+    use std::os::unix::net::UnixListener;
+
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_pipe_gone_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("primes.sock");
+    let config = Config { pipe_path: path.display().to_string(), ..Config::default() };
+    let (sender, receiver) = mpsc::channel();
+
+    let listener = UnixListener::bind(&path).unwrap();
+    let mut out = PipeSink::new(Collect::default(), &config, &sender).unwrap();
+    // 接続を受けてすぐ閉じる
+    drop(listener.accept().unwrap());
+    for p in 0..100_000 {
+        out.write_prime(p).unwrap();
+    }
+    assert!(!out.is_active());
+    assert_eq!(receiver.try_iter().count(), 1);
+    out.finish().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
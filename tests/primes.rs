@@ -0,0 +1,33 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use sosu_seisei_sieve::primes::{count_primes, count_primes_until, is_prime, Primes};
+
+#[test]
+fn iterate_and_count() {
+    let small: Vec<u64> = Primes::new(0, 30).collect();
+    assert_eq!(small, vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+    assert_eq!(Primes::new(24, 28).next(), None);
+    assert_eq!(Primes::new(10, 5).next(), None);
+
+    // 区間の境界をまたいでも欠けや重複がない
+    let primes: Vec<u64> = Primes::new(1_000_000, 3_200_000).collect();
+    assert_eq!(primes.len() as u64, count_primes(1_000_000, 3_200_000));
+    assert!(primes.windows(2).all(|w| w[0] < w[1]));
+    assert!(primes.iter().all(|&p| is_prime(p)));
+    assert_eq!(count_primes(0, 10_000_000), 664_579);
+    assert_eq!(count_primes(7, 7), 1);
+    assert_eq!(count_primes(10, 5), 0);
+}
+
+#[test]
+fn counting_stops_when_asked() {
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    assert_eq!(count_primes_until(0, 1_000_000, &stop_flag), Some(78_498));
+    stop_flag.store(true, Ordering::SeqCst);
+    assert_eq!(count_primes_until(0, 1_000_000_000_000_000_000, &stop_flag), None);
+}
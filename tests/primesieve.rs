@@ -0,0 +1,101 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use sosu_seisei_sieve::config::{Config, OutputFormat, OverwriteMode};
+use sosu_seisei_sieve::output::{read_primes, PrimeSink, PrimeWriter};
+use sosu_seisei_sieve::primesieve::{compare_primes, import_primesieve, PrimeStream};
+use sosu_seisei_sieve::sieve::simple_sieve;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn ok(values: &[u64]) -> impl Iterator<Item = io::Result<u64>> + '_ {
+    values.iter().map(|&p| Ok(p))
+}
+
+#[test]
+fn binary_output_is_a_little_endian_u64_array() {
+    let dir = temp_dir("primesieve_binary");
+    for (primes, mode) in [(&[2u64, 3, 5][..], OverwriteMode::Overwrite), (&[7, 1 << 40][..], OverwriteMode::Append)] {
+        let mut out = PrimeWriter::create(OutputFormat::Binary, None, &dir, 0, 16, &mode).unwrap();
+        for &p in primes {
+            out.write_prime(p).unwrap();
+        }
+        out.finish().unwrap();
+    }
+    let path = dir.join("primes.bin");
+    let bytes = fs::read(&path).unwrap();
+    assert_eq!(bytes.len(), 40);
+    assert_eq!(&bytes[..8], &2u64.to_le_bytes());
+    let mut read = Vec::new();
+    read_primes(&path, |p| read.push(p)).unwrap();
+    assert_eq!(read, vec![2, 3, 5, 7, 1 << 40]);
+
+    // 途中で切れたレコードはエラーになる
+    fs::write(&path, &bytes[..20]).unwrap();
+    assert!(PrimeStream::open(&path).unwrap().collect::<io::Result<Vec<_>>>().is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn reads_primesieve_print_output() {
+    let dir = temp_dir("primesieve_text");
+    let path = dir.join("primesieve.txt");
+    fs::write(&path, "2\n3\n5\n7\n11\r\n13").unwrap();
+    let primes = PrimeStream::open(&path).unwrap().collect::<io::Result<Vec<_>>>().unwrap();
+    assert_eq!(primes, vec![2, 3, 5, 7, 11, 13]);
+
+    // prime_index の番号は読み飛ばす
+    fs::write(&path, "1:2\n2:3\n").unwrap();
+    assert_eq!(PrimeStream::open(&path).unwrap().collect::<io::Result<Vec<_>>>().unwrap(), vec![2, 3]);
+    fs::write(&path, r#"{"9":23,"10":29}"#).unwrap();
+    assert_eq!(PrimeStream::open(&path).unwrap().collect::<io::Result<Vec<_>>>().unwrap(), vec![23, 29]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compares_the_overlapping_range() {
+    let stop = AtomicBool::new(false);
+    let result = compare_primes(ok(&[2, 3, 5, 7, 11, 13]), ok(&[5, 7, 11, 13, 17, 19]), &stop).unwrap().unwrap();
+    assert_eq!(result.range, Some((5, 13)));
+    assert_eq!(result.matched, 4);
+    assert!(result.is_match());
+
+    let result = compare_primes(ok(&[2, 3, 5, 9, 11]), ok(&[2, 3, 5, 7, 11, 13]), &stop).unwrap().unwrap();
+    assert_eq!((result.only_ours, result.only_theirs), (1, 1));
+    assert_eq!((result.examples_ours, result.examples_theirs), (vec![9], vec![7]));
+    assert_eq!(result.range, Some((2, 11)));
+
+    assert_eq!(compare_primes(ok(&[2, 3]), ok(&[]), &stop).unwrap().unwrap().range, None);
+    assert!(compare_primes(ok(&[2, 5, 3]), ok(&[2, 3, 5]), &stop).is_err());
+}
+
+#[test]
+fn imports_binary_primes_into_the_output_format() {
+    let dir = temp_dir("primesieve_import");
+    let primes = simple_sieve(10_000);
+    let source = dir.join("source.bin");
+    fs::write(&source, primes.iter().flat_map(|p| p.to_le_bytes()).collect::<Vec<u8>>()).unwrap();
+    let config = Config {
+        output_dir: dir.join("out").display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        ..Config::default()
+    };
+    let (sender, _messages) = mpsc::channel();
+    import_primesieve(config, source, sender, Arc::new(AtomicBool::new(false))).unwrap();
+    let mut written = Vec::new();
+    read_primes(&dir.join("out").join("primes.txt"), |p| written.push(p)).unwrap();
+    assert_eq!(written, primes);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,38 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use sosu_seisei_sieve::pseudoprime::strong_pseudoprimes_in;
+use sosu_seisei_sieve::sieve::simple_sieve;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+#[test]
+fn strong_pseudoprimes_to_base_two() {
+    let stop = Arc::new(AtomicBool::new(false));
+    let small_primes = simple_sieve(1000);
+    let result = strong_pseudoprimes_in(&small_primes, 1, 100_000, &[2], &stop);
+    // OEIS A001262
+    assert_eq!(&result.pseudoprimes[..8], &[2047, 3277, 4033, 4681, 8321, 15841, 29341, 42799]);
+    assert_eq!(result.liars, vec![result.pseudoprimes.len() as u64]);
+
+    // 区間の途中から始めても同じ結果になる
+    let tail = strong_pseudoprimes_in(&small_primes, 4000, 9000, &[2], &stop);
+    assert_eq!(tail.pseudoprimes, vec![4033, 4681, 8321]);
+}
+
+#[test]
+fn strong_pseudoprimes_to_several_bases() {
+    let stop = Arc::new(AtomicBool::new(false));
+    let small_primes = simple_sieve(2000);
+    // 底 2, 3 に対する最小の強擬素数は 1373653
+    let result = strong_pseudoprimes_in(&small_primes, 1, 1_400_000, &[2, 3], &stop);
+    assert_eq!(result.pseudoprimes, vec![1_373_653]);
+    // 底ごとの嘘つきの数はその底単独の強擬素数の個数に等しい
+    for (i, base) in [2, 3].into_iter().enumerate() {
+        let single = strong_pseudoprimes_in(&small_primes, 1, 1_400_000, &[base], &stop);
+        assert_eq!(single.pseudoprimes.len() as u64, result.liars[i]);
+        assert_eq!(single.composites, result.composites);
+    }
+}
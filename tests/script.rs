@@ -0,0 +1,83 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use sosu_seisei_sieve::app::WorkerMessage;
+use sosu_seisei_sieve::config::Config;
+use sosu_seisei_sieve::error::SieveError;
+use sosu_seisei_sieve::sieve::{run_program_old, simple_sieve};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_script_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run(config: Config) -> (Result<(), SieveError>, Vec<String>) {
+    let (sender, messages) = mpsc::channel();
+    let (_commands_sender, commands) = mpsc::channel();
+    let result = run_program_old(config, sender, commands, Arc::new(AtomicBool::new(false)));
+    let logs = messages.try_iter().filter_map(|m| match m {
+        WorkerMessage::Log(text) => Some(text),
+        _ => None,
+    });
+    (result, logs.collect())
+}
+
+fn config(dir: &Path, script: &str) -> Config {
+    let path = dir.join("sink.rhai");
+    fs::write(&path, script).unwrap();
+    Config {
+        prime_max: "1000".to_string(),
+        output_dir: dir.to_string_lossy().into_owned(),
+        sink_script: path.to_string_lossy().into_owned(),
+        sink_batch_size: 50,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn script_receives_the_written_primes_in_batches() {
+    let dir = temp_dir("batches");
+    let script = r#"
+        fn on_batch(primes) {
+            this.batches = (this.batches ?? 0) + 1;
+            let text = "";
+            for p in primes {
+                text += `${p};`;
+            }
+            append_file("custom.txt", text);
+        }
+
+        fn on_finish() {
+            log(`batches: ${this.batches}`);
+        }
+    "#;
+    let (result, logs) = run(Config { residue_modulus: 4, residues: vec![1], ..config(&dir, script) });
+    result.unwrap();
+
+    let expected: Vec<u64> = simple_sieve(1000).into_iter().filter(|p| p % 4 == 1).collect();
+    let written: Vec<u64> = fs::read_to_string(dir.join("custom.txt")).unwrap().split_terminator(';').map(|p| p.parse().unwrap()).collect();
+    assert_eq!(written, expected);
+    assert!(logs.contains(&format!("batches: {}", expected.len().div_ceil(50))), "{:?}", logs);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_scripts_are_rejected() {
+    let dir = temp_dir("invalid");
+    for script in ["fn on_finish() {}", "fn on_batch(primes) {", "fn on_batch(primes) {} throw \"setup failed\";"] {
+        let (result, _) = run(config(&dir, script));
+        assert!(matches!(result, Err(SieveError::Config(ref e)) if e.starts_with("sink_script")), "{:?}: {:?}", script, result);
+    }
+
+    let (result, _) = run(config(&dir, "fn on_batch(primes) { throw \"rejected\"; }"));
+    assert!(matches!(result, Err(SieveError::Write { .. })), "{:?}", result);
+    fs::remove_dir_all(&dir).unwrap();
+}
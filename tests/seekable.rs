@@ -0,0 +1,60 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use sosu_seisei_sieve::seekable::{SeekTable, SeekableReader, SeekableWriter};
+
+fn sample(len: usize) -> Vec<u8> {
+    (0..len).map(|i| b"0123456789\n"[i % 11]).collect()
+}
+
+// data を flush_at の位置ごとに区切って書き、flush のたびに完結していることを確認して圧縮後のバイト列を返す
+fn compress(data: &[u8], frame_size: usize, flush_at: &[usize]) -> Vec<u8> {
+    let mut writer = SeekableWriter::new(Cursor::new(Vec::new()), 3, frame_size);
+    let mut at = 0;
+    for &end in flush_at.iter().chain([data.len()].iter()) {
+        writer.write_all(&data[at..end]).unwrap();
+        writer.flush().unwrap();
+        at = end;
+        let written = writer.get_mut().get_ref().clone();
+        assert_eq!(zstd::decode_all(written.as_slice()).unwrap(), &data[..end]);
+    }
+    writer.get_mut().get_ref().clone()
+}
+
+#[test]
+fn frames_are_listed_in_the_seek_table() {
+    let data = sample(1000);
+    let compressed = compress(&data, 300, &[]);
+    let table = SeekTable::read(&mut Cursor::new(&compressed)).unwrap().unwrap();
+    assert_eq!(table.frames().iter().map(|&(_, d)| d).collect::<Vec<_>>(), vec![300, 300, 300, 100]);
+    assert_eq!(table.decompressed_len(), 1000);
+    assert_eq!(SeekTable::read(&mut Cursor::new(zstd::encode_all(data.as_slice(), 3).unwrap())).unwrap(), None);
+}
+
+#[test]
+fn rewrites_the_seek_table_after_each_flush() {
+    let data = sample(2500);
+    let compressed = compress(&data, 1000, &[10, 1500, 1501]);
+    let table = SeekTable::read(&mut Cursor::new(&compressed)).unwrap().unwrap();
+    assert_eq!(table.decompressed_len(), 2500);
+    // flush ごとにフレームが区切られる
+    assert_eq!(table.frames().iter().map(|&(_, d)| d).collect::<Vec<_>>(), vec![10, 1000, 490, 1, 999]);
+}
+
+#[test]
+fn reads_from_any_position() {
+    let data = sample(5000);
+    let compressed = compress(&data, 512, &[]);
+    let mut reader = SeekableReader::new(Cursor::new(compressed)).unwrap().unwrap();
+    assert_eq!(reader.len(), 5000);
+    for start in [0u64, 511, 512, 3000, 4990, 5000] {
+        reader.seek(SeekFrom::Start(start)).unwrap();
+        let mut buf = Vec::new();
+        reader.by_ref().take(700).read_to_end(&mut buf).unwrap();
+        let end = (start as usize + 700).min(data.len());
+        assert_eq!(buf, &data[start as usize..end], "{}", start);
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use sosu_seisei_sieve::config::SegmentBufferKind;
+use sosu_seisei_sieve::segment_buffer::SegmentBuffer;
+use sosu_seisei_sieve::sieve::{segmented_sieve, segmented_sieve_in, simple_sieve};
+
+#[test]
+fn huge_pages_fall_back_and_keep_the_requested_length() {
+    // 大きいページが予約されていない環境でも通常のページで確保できる
+    let size = 3 * 1024 * 1024 + 1;
+    let mut buffer = SegmentBuffer::new(&SegmentBufferKind::HugePages, size, &std::env::temp_dir(), 0).unwrap();
+    assert_eq!(buffer.len(), size);
+    assert!(buffer.iter().all(|&b| b == 0));
+
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let small_primes = simple_sieve(2_000);
+    let (low, high) = (1_000_000, 1_000_000 + size as u64 - 1);
+    assert_eq!(segmented_sieve_in(&mut buffer, &small_primes, low, high, &stop_flag), segmented_sieve(&small_primes, low, high, &stop_flag));
+}
+
+#[test]
+fn file_maps_with_the_same_tag_do_not_share_a_file() {
+    // 同じフォルダーで同じ範囲を篩う 2 つのジョブでも、それぞれの一時ファイルを使う
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_file_mmap_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut first = SegmentBuffer::new(&SegmentBufferKind::FileMmap, 4096, &dir, 1_000_000).unwrap();
+    let mut second = SegmentBuffer::new(&SegmentBufferKind::FileMmap, 4096, &dir, 1_000_000).unwrap();
+    first[0] = 1;
+    second[1] = 1;
+    assert_eq!((first[0], first[1]), (1, 0));
+    assert_eq!((second[0], second[1]), (0, 1));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+    drop((first, second));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,40 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use sosu_seisei_sieve::app::WorkerMessage;
+use sosu_seisei_sieve::config::Config;
+use sosu_seisei_sieve::primes::count_primes;
+use sosu_seisei_sieve::pi_report::KNOWN_PI;
+use sosu_seisei_sieve::self_test::run_self_test;
+
+#[test]
+fn known_counts_match_the_sieve() {
+    for &(x, pi) in &KNOWN_PI[..7] {
+        assert_eq!(count_primes(0, x), pi, "{}", x);
+    }
+}
+
+#[test]
+fn self_test_passes_with_small_segments() {
+    // セグメントの境目が多くなるように小さくする
+    let config = Config { segment_size: 4099, parallel_segments: 3, ..Config::default() };
+    let (sender, messages) = mpsc::channel();
+    run_self_test(config, sender, Arc::new(AtomicBool::new(false))).unwrap();
+    let log: Vec<String> = messages.try_iter().filter_map(|m| match m {
+        WorkerMessage::Log(line) => Some(line),
+        _ => None,
+    }).collect();
+    assert_eq!(log.iter().filter(|line| line.starts_with("PASS ")).count(), 6, "{:?}", log);
+    assert!(log.last().unwrap().starts_with("Self test passed"));
+}
+
+#[test]
+fn stopped_self_test_reports_no_failures() {
+    let (sender, messages) = mpsc::channel();
+    run_self_test(Config::default(), sender, Arc::new(AtomicBool::new(true))).unwrap();
+    assert!(matches!(messages.try_iter().last(), Some(WorkerMessage::Stopped)));
+}
@@ -0,0 +1,43 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::sync::mpsc;
+use std::time::Duration;
+use sosu_seisei_sieve::settings_watch::SettingsWatcher;
+
+#[test]
+fn detects_edits_and_replacements_of_the_settings_file() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_settings_watch_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("settings.txt");
+    fs::write(&path, "a").unwrap();
+    let (sender, changes) = mpsc::channel();
+    let watcher = SettingsWatcher::start(&path, move || {
+        sender.send(()).ok();
+    })
+    .unwrap();
+
+    // ほかのファイルの変更は無視する
+    fs::write(dir.join("other.txt"), "x").unwrap();
+    assert!(changes.recv_timeout(Duration::from_millis(500)).is_err());
+    assert!(!watcher.take_change());
+
+    fs::write(&path, "b").unwrap();
+    changes.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(watcher.take_change());
+
+    // エディタのように別名で書いてから置き換える
+    std::thread::sleep(Duration::from_millis(200));
+    while changes.try_recv().is_ok() {}
+    fs::write(dir.join("settings.txt.tmp"), "c").unwrap();
+    fs::rename(dir.join("settings.txt.tmp"), &path).unwrap();
+    changes.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(watcher.take_change());
+
+    drop(watcher);
+    fs::remove_dir_all(&dir).unwrap();
+}
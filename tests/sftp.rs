@@ -0,0 +1,32 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::path::Path;
+use sosu_seisei_sieve::config::Config;
+use sosu_seisei_sieve::sftp::{SftpClient, SftpTarget};
+
+#[test]
+fn parses_sftp_urls() {
+    let target = SftpTarget::parse("sftp://alice@example.com:2222/srv/primes/").unwrap();
+    assert_eq!(target, SftpTarget { user: "alice".to_string(), host: "example.com".to_string(), port: 2222, dir: "/srv/primes".to_string() });
+    assert_eq!(target.remote_path(Path::new("out/primes_1.txt")), "/srv/primes/primes_1.txt");
+    assert_eq!(target.url_for(Path::new("primes_1.txt")), "sftp://alice@example.com:2222/srv/primes/primes_1.txt");
+
+    // ポートとパスを省けば 22 番とログインしたフォルダー
+    let target = SftpTarget::parse("sftp://bob@host").unwrap();
+    assert_eq!((target.port, target.dir.as_str()), (22, "."));
+    assert_eq!(target.remote_path(Path::new("primes.bin")), "./primes.bin");
+    let target = SftpTarget::parse("sftp://bob@[::1]:2200/data").unwrap();
+    assert_eq!((target.host.as_str(), target.port, target.dir.as_str()), ("::1", 2200, "/data"));
+
+    for url in ["https://host/dav", "sftp://host/primes", "sftp://bob@host:ssh/primes", "sftp://@host"] {
+        assert!(SftpTarget::parse(url).is_err(), "{}", url);
+    }
+}
+
+#[test]
+fn is_off_without_a_url() {
+    assert!(SftpClient::from_config(&Config::default()).unwrap().is_none());
+}
@@ -0,0 +1,96 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sosu_seisei_sieve::miller_rabin::is_prime;
+use sosu_seisei_sieve::sieve::{segmented_sieve, simple_sieve, SegmentScheduler, MAX_SEGMENT_GROWTH};
+
+#[test]
+fn segments_larger_than_the_stop_check_interval() {
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let small_primes = simple_sieve(2000);
+    let expected: Vec<u64> = simple_sieve(3_500_000).into_iter().filter(|&p| p >= 3).collect();
+    assert_eq!(segmented_sieve(&small_primes, 3, 3_500_000, &stop_flag), expected);
+}
+
+#[test]
+fn marks_across_chunk_boundaries() {
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    // √high より小さい素数も、チャンク（32,768 個）より大きい素数も印を付ける範囲
+    let (low, high) = (10_000_000_007, 10_000_200_000);
+    let small_primes = simple_sieve(100_001);
+    let expected: Vec<u64> = (low..=high).filter(|&n| is_prime(n)).collect();
+    assert_eq!(segmented_sieve(&small_primes, low, high, &stop_flag), expected);
+    assert_eq!(segmented_sieve(&small_primes, 0, 100_000, &stop_flag), simple_sieve(100_000));
+}
+
+#[test]
+fn stops_promptly_in_a_large_segment() {
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let low = 1_000_000_000_000;
+    let small_primes = simple_sieve(1_000_001);
+    let started = Instant::now();
+    let flag = Arc::clone(&stop_flag);
+    let worker = std::thread::spawn(move || {
+        let primes = segmented_sieve(&small_primes, low, low + 200_000_000, &flag);
+        (primes, Instant::now())
+    });
+    std::thread::sleep(Duration::from_millis(100));
+    stop_flag.store(true, Ordering::SeqCst);
+    let stopped = Instant::now();
+    let (primes, finished) = worker.join().unwrap();
+    println!("stopped after {:?}, {:?} after the stop request", stopped - started, finished.saturating_duration_since(stopped));
+    assert!(primes.is_empty());
+    // 最適化なしのビルドや負荷の高い環境でも通るよう、目標の 200ms より余裕を持たせる
+    assert!(finished.saturating_duration_since(stopped) < Duration::from_secs(1));
+}
+
+#[test]
+fn scheduler_covers_the_ranges_in_order() {
+    let ranges = [(0, 24), (40, 49)];
+    let all = |descending: bool| {
+        let mut scheduler = SegmentScheduler::new(&ranges, 10, descending, false);
+        let mut segments = Vec::new();
+        loop {
+            let batch = scheduler.next_batch(2);
+            if batch.is_empty() {
+                return segments;
+            }
+            segments.extend(batch);
+        }
+    };
+    assert_eq!(SegmentScheduler::new(&ranges, 10, false, false).remaining(), 4);
+    assert_eq!(all(false), [(0, 9), (10, 19), (20, 24), (40, 49)]);
+    assert_eq!(all(true), [(40, 49), (15, 24), (5, 14), (0, 4)]);
+}
+
+#[test]
+fn adaptive_segments_grow_while_batches_get_faster() {
+    let mut scheduler = SegmentScheduler::new(&[(0, u64::MAX / 2)], 1000, false, true);
+    assert_eq!(scheduler.record(Duration::from_millis(100)), None);
+    // 速くなった分だけ大きく（1 回に 2 倍まで）
+    assert_eq!(scheduler.record(Duration::from_millis(80)), Some(1250));
+    assert_eq!(scheduler.next_batch(1), [(0, 1249)]);
+    assert_eq!(scheduler.record(Duration::from_millis(10)), Some(2500));
+    // 大きくしたぶん残りのセグメントは減る
+    assert_eq!(scheduler.remaining(), (u64::MAX / 2 - 1250) / 2500 + 1);
+    // 小さな揺れでは変えない
+    assert_eq!(scheduler.record(Duration::from_millis(97)), None);
+    for _ in 0..10 {
+        scheduler.record(Duration::from_millis(1));
+    }
+    assert_eq!(scheduler.next_batch(1), [(1250, 1250 + 1000 * MAX_SEGMENT_GROWTH - 1)]);
+    // 遅くなっても segment_size より小さくはしない
+    for _ in 0..10 {
+        scheduler.record(Duration::from_secs(10));
+    }
+    assert_eq!(scheduler.next_batch(1), [(1250 + 8000, 1250 + 8000 + 999)]);
+
+    let mut fixed = SegmentScheduler::new(&[(0, 99_999)], 1000, false, false);
+    fixed.record(Duration::from_millis(100));
+    assert_eq!(fixed.record(Duration::from_millis(1)), None);
+}
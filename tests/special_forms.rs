@@ -0,0 +1,108 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use sosu_seisei_sieve::app::WorkerMessage;
+use sosu_seisei_sieve::miller_rabin::{check_prime_big, is_bpsw_probable_prime, is_probable_prime_big, jacobi, BigPrimality, DETERMINISTIC_LIMIT};
+use sosu_seisei_sieve::number_check::run_number_check;
+use sosu_seisei_sieve::special_forms::{fermat_factors, proth_test, ProthResult};
+use num_bigint::BigUint;
+use num_traits::One;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+
+#[test]
+fn proth_primes_3_times_power_of_two() {
+    let stop = AtomicBool::new(false);
+    let found: Vec<u64> = (2..=600).filter(|&n| proth_test(3, n, &stop) == Some(ProthResult::Prime)).collect();
+    // OEIS A002253
+    assert_eq!(found, vec![2, 5, 6, 8, 12, 18, 30, 36, 41, 66, 189, 201, 209, 276, 353, 408, 438, 534]);
+    for n in [30, 66, 189] {
+        assert!(is_probable_prime_big(&((BigUint::from(3u32) << n) + 1u32)));
+    }
+}
+
+#[test]
+fn fermat_number_factors() {
+    let stop = AtomicBool::new(false);
+    assert_eq!(fermat_factors(5, 100, &stop), Some(vec![641]));
+    assert_eq!(fermat_factors(6, 2000, &stop), Some(vec![274177]));
+    assert_eq!(fermat_factors(12, 10, &stop), Some(vec![114689]));
+}
+
+#[test]
+fn jacobi_symbol() {
+    // (a / 15) for a = 1..15
+    let expected = [1, 1, 0, 1, 0, 0, -1, 1, 0, 0, -1, 0, -1, -1, 0];
+    for (a, &e) in (1..=15u64).zip(expected.iter()) {
+        assert_eq!(jacobi(a, &BigUint::from(15u32)), e, "({} / 15)", a);
+    }
+}
+
+#[test]
+fn bpsw_mersenne_numbers() {
+    let mersenne = |p: u32| (BigUint::one() << p) - 1u32;
+    assert!(is_bpsw_probable_prime(&mersenne(127)));
+    assert!(is_bpsw_probable_prime(&mersenne(89)));
+    assert!(!is_bpsw_probable_prime(&(mersenne(89) * mersenne(61))));
+    assert!(!is_bpsw_probable_prime(&((BigUint::one() << 128u32) + 1u32)));
+}
+
+#[test]
+fn results_above_the_deterministic_limit_are_probabilistic() {
+    let mersenne = |p: u32| (BigUint::one() << p) - 1u32;
+    // 2^64 + 13 は素数
+    assert_eq!(check_prime_big(&((BigUint::one() << 64u32) + 13u32), 0), BigPrimality::Prime);
+    // 上限そのものは最初の 13 個の素数を底にした強擬素数
+    let limit = BigUint::from(DETERMINISTIC_LIMIT);
+    assert!(is_probable_prime_big(&limit));
+    assert_eq!(check_prime_big(&limit, 0), BigPrimality::Composite);
+    // 上限より下にある、最初の 12 個の素数を底にした強擬素数も素数としない
+    assert_ne!(check_prime_big(&BigUint::from(318_665_857_834_031_151_167_461u128), 0), BigPrimality::Prime);
+    assert_eq!(check_prime_big(&mersenne(89), 20), BigPrimality::ProbablePrime);
+    assert_eq!(check_prime_big(&(mersenne(89) * mersenne(61)), 20), BigPrimality::Composite);
+
+    let warnings = |n: BigUint| {
+        let (sender, messages) = mpsc::channel();
+        run_number_check(n, false, 5, "", sender, Arc::new(AtomicBool::new(false))).unwrap();
+        messages.try_iter().filter(|m| matches!(m, WorkerMessage::Warning(_))).count()
+    };
+    assert_eq!(warnings(mersenne(89)), 1);
+    assert_eq!(warnings(mersenne(61)), 0);
+    assert_eq!(warnings((BigUint::one() << 64u32) + 13u32), 0);
+}
+
+#[test]
+fn primorial_and_factorial_primes() {
+    let small_primes: Vec<u32> = (2..400).filter(|&n| (2..n).all(|d| n % d != 0)).collect();
+    let (mut plus, mut minus) = (Vec::new(), Vec::new());
+    let mut primorial = BigUint::one();
+    for &p in &small_primes {
+        primorial *= p;
+        if is_bpsw_probable_prime(&(&primorial + 1u32)) {
+            plus.push(p);
+        }
+        if is_bpsw_probable_prime(&(&primorial - 1u32)) {
+            minus.push(p);
+        }
+    }
+    // OEIS A005234, A006794
+    assert_eq!(plus, vec![2, 3, 5, 7, 11, 31, 379]);
+    assert_eq!(minus, vec![3, 5, 11, 13, 41, 89, 317, 337]);
+
+    let (mut plus, mut minus) = (Vec::new(), Vec::new());
+    let mut factorial = BigUint::one();
+    for n in 1..=100u32 {
+        factorial *= n;
+        if is_bpsw_probable_prime(&(&factorial + 1u32)) {
+            plus.push(n);
+        }
+        if is_bpsw_probable_prime(&(&factorial - 1u32)) {
+            minus.push(n);
+        }
+    }
+    // OEIS A002981, A002982
+    assert_eq!(plus, vec![1, 2, 3, 11, 27, 37, 41, 73, 77]);
+    assert_eq!(minus, vec![3, 4, 6, 7, 12, 14, 30, 32, 33, 38, 94]);
+}
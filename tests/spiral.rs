@@ -0,0 +1,41 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use sosu_seisei_sieve::sieve::simple_sieve;
+use sosu_seisei_sieve::spiral::{sacks_position, ulam_bitmap, ulam_index, ulam_position};
+
+#[test]
+fn ulam_coordinates() {
+    let expected = [(0, 0), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1), (2, -1), (2, 0)];
+    for (k, &pos) in expected.iter().enumerate() {
+        assert_eq!(ulam_position(k as u64), pos, "{}", k);
+    }
+    for k in 0..100_000 {
+        let (x, y) = ulam_position(k);
+        assert_eq!(ulam_index(x, y), k);
+    }
+    // 一辺 2r+1 の正方形にちょうど収まる
+    for side in [1u64, 3, 5, 101] {
+        let r = (side / 2) as i64;
+        assert!((0..side * side).all(|k| {
+            let (x, y) = ulam_position(k);
+            x.abs() <= r && y.abs() <= r
+        }));
+    }
+}
+
+#[test]
+fn ulam_bitmap_marks_primes() {
+    let primes = simple_sieve(200);
+    let cells = ulam_bitmap(1, 11, &primes);
+    assert_eq!(cells.iter().filter(|&&c| c).count(), primes.iter().filter(|&&p| p < 122).count());
+    // 中心が 1、その右が 2、右上が 3
+    assert!(!cells[5 * 11 + 5]);
+    assert!(cells[5 * 11 + 6]);
+    assert!(cells[4 * 11 + 6]);
+
+    let (x, y) = sacks_position(49);
+    assert!((x - 7.0).abs() < 1e-9 && y.abs() < 1e-9);
+}
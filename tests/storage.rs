@@ -0,0 +1,41 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use sosu_seisei_sieve::config::Config;
+use sosu_seisei_sieve::storage::{probe_storage, storage_tuned, StorageKind, StorageProbe};
+
+const MIB: usize = 1024 * 1024;
+
+#[test]
+fn guesses_the_kind_from_the_timings() {
+    let kind = |sync_ms, mib_per_sec| StorageProbe { sync_ms, mib_per_sec }.kind();
+    assert_eq!(kind(0.2, 1500.0), StorageKind::Ssd);
+    assert_eq!(kind(8.0, 150.0), StorageKind::Hdd);
+    assert_eq!(kind(45.0, 150.0), StorageKind::Network);
+    assert_eq!(kind(5.0, 20.0), StorageKind::Network);
+}
+
+#[test]
+fn picks_the_writer_settings_per_kind() {
+    let config = Config { writer_buffer_size: 1234, writer_queue_blocks: 2, writer_flush_interval_ms: 5, prime_max: "1000".to_string(), ..Config::default() };
+    let sizes = |kind| {
+        let tuned = storage_tuned(&config, kind);
+        assert_eq!(tuned.prime_max, "1000");
+        (tuned.writer_buffer_size, tuned.writer_queue_blocks, tuned.writer_flush_interval_ms)
+    };
+    assert_eq!(sizes(StorageKind::Ssd), (8 * MIB, 4, 0));
+    assert_eq!(sizes(StorageKind::Hdd), (32 * MIB, 4, 0));
+    assert_eq!(sizes(StorageKind::Network), (16 * MIB, 16, 1000));
+}
+
+#[test]
+fn probes_and_removes_the_temporary_file() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_storage_{}", std::process::id()));
+    let probe = probe_storage(&dir).unwrap();
+    assert!(probe.sync_ms >= 0.0 && probe.mib_per_sec > 0.0);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
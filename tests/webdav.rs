@@ -0,0 +1,218 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use sosu_seisei_sieve::s3::{sha256_hex, Uploaded};
+use sosu_seisei_sieve::webdav::WebDavClient;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// 受け取ったリクエスト (メソッド, パス) とサーバーにあるファイル
+type Requests = Arc<Mutex<Vec<(String, String)>>>;
+type Files = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+#[derive(Clone, Copy, Default)]
+struct Faults {
+    // この回数目までのファイルの PUT には 503 を返す
+    fail_first: usize,
+    // 最初のファイルの PUT は半分だけ受け取って接続を切る
+    cut_first: bool,
+    // Content-Range 付きの PUT を受け付ける
+    partial_put: bool,
+}
+
+// 最小限の WebDAV もどき
+fn mock_server(faults: Faults) -> (String, Requests, Files) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/dav/primes", listener.local_addr().unwrap());
+    let requests: Requests = Arc::new(Mutex::new(Vec::new()));
+    let files: Files = Arc::new(Mutex::new(HashMap::new()));
+    let (log, store) = (Arc::clone(&requests), Arc::clone(&files));
+    // 前のクライアントが接続を開いたままでも応えられるよう、接続ごとにスレッドを分ける
+    let state = Arc::new(Mutex::new((0, false)));
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (log, store, state) = (Arc::clone(&log), Arc::clone(&store), Arc::clone(&state));
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut stream = stream;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        break;
+                    }
+                    let mut parts = line.split_whitespace();
+                    let method = parts.next().unwrap().to_string();
+                    let target = parts.next().unwrap().to_string();
+                    let mut headers = HashMap::new();
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        let header = header.trim_end();
+                        if header.is_empty() {
+                            break;
+                        }
+                        let (name, value) = header.split_once(':').unwrap();
+                        headers.insert(name.to_lowercase(), value.trim().to_string());
+                    }
+                    // user:secret
+                    assert_eq!(headers["authorization"], "Basic dXNlcjpzZWNyZXQ=");
+                    let len = headers.get("content-length").map(|v| v.parse().unwrap()).unwrap_or(0);
+                    let range = headers.get("content-range").cloned();
+                    log.lock().unwrap().push((if range.is_some() { format!("{} range", method) } else { method.clone() }, target.clone()));
+
+                    let mut state = state.lock().unwrap();
+                    let (puts, collection) = &mut *state;
+                    let data_put = method == "PUT" && !target.ends_with(".sha256");
+                    if data_put {
+                        *puts += 1;
+                    }
+                    if data_put && faults.cut_first && *puts == 1 {
+                        // 半分だけ受け取ったところで接続が切れる
+                        let mut body = vec![0u8; len / 2];
+                        reader.read_exact(&mut body).unwrap();
+                        store.lock().unwrap().insert(target, body);
+                        break;
+                    }
+                    let mut body = vec![0u8; len];
+                    reader.read_exact(&mut body).unwrap();
+
+                    let mut store = store.lock().unwrap();
+                    let (status, length, text) = match method.as_str() {
+                        "MKCOL" if *collection => ("405 Method Not Allowed", 0, Vec::new()),
+                        "MKCOL" => {
+                            *collection = true;
+                            ("201 Created", 0, Vec::new())
+                        }
+                        "HEAD" => match store.get(&target) {
+                            Some(data) => ("200 OK", data.len(), Vec::new()),
+                            None => ("404 Not Found", 0, Vec::new()),
+                        },
+                        "GET" => match store.get(&target) {
+                            Some(data) => ("200 OK", data.len(), data.clone()),
+                            None => ("404 Not Found", 0, Vec::new()),
+                        },
+                        "DELETE" => match store.remove(&target) {
+                            Some(_) => ("204 No Content", 0, Vec::new()),
+                            None => ("404 Not Found", 0, Vec::new()),
+                        },
+                        "PUT" if data_put && *puts <= faults.fail_first => ("503 Service Unavailable", 0, Vec::new()),
+                        "PUT" => match range {
+                            None => {
+                                store.insert(target, body);
+                                ("201 Created", 0, Vec::new())
+                            }
+                            Some(_) if !faults.partial_put => ("501 Not Implemented", 0, Vec::new()),
+                            Some(range) => {
+                                // bytes 開始-終了/全体。サーバーにある分の直後からだけ受け付ける
+                                let start: usize = range.trim_start_matches("bytes ").split('-').next().unwrap().parse().unwrap();
+                                let data = store.get_mut(&target).unwrap();
+                                assert_eq!(data.len(), start);
+                                data.extend_from_slice(&body);
+                                ("204 No Content", 0, Vec::new())
+                            }
+                        },
+                        _ => ("400 Bad Request", 0, Vec::new()),
+                    };
+                    // HEAD の応答には本文がないが、Content-Length はファイルの大きさを示す
+                    write!(stream, "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n", status, length).unwrap();
+                    stream.write_all(&text).unwrap();
+                }
+            });
+        }
+    });
+    (url, requests, files)
+}
+
+fn methods(requests: &Requests) -> Vec<(String, String)> {
+    requests.lock().unwrap().clone()
+}
+
+fn client(url: &str) -> WebDavClient {
+    WebDavClient::new(url, Some(("user".to_string(), "secret".to_string()))).unwrap()
+}
+
+#[test]
+fn uploads_files_and_retries() {
+    let dir = temp_dir("webdav_upload");
+    let path = dir.join("primes 1.txt");
+    std::fs::write(&path, "2\n3\n5\n7\n").unwrap();
+    // 最初の PUT は 1 回失敗させる
+    let (url, requests, files) = mock_server(Faults { fail_first: 1, ..Faults::default() });
+    let expected = format!("{}/primes%201.txt", url);
+    assert_eq!(client(&url).upload(&path).unwrap(), Uploaded::Sent(expected));
+
+    let summary = methods(&requests);
+    let summary: Vec<(&str, &str)> = summary.iter().map(|(m, t)| (m.as_str(), t.as_str())).collect();
+    assert_eq!(summary, vec![
+        ("MKCOL", "/dav/primes/"),
+        ("HEAD", "/dav/primes/primes%201.txt"),
+        ("DELETE", "/dav/primes/primes%201.txt.sha256"),
+        ("DELETE", "/dav/primes/primes%201.txt"),
+        ("PUT", "/dav/primes/primes%201.txt"),
+        ("HEAD", "/dav/primes/primes%201.txt"),
+        ("PUT", "/dav/primes/primes%201.txt"),
+        ("PUT", "/dav/primes/primes%201.txt.sha256"),
+    ]);
+    let files = files.lock().unwrap();
+    assert_eq!(files["/dav/primes/primes%201.txt"], b"2\n3\n5\n7\n");
+    assert_eq!(files["/dav/primes/primes%201.txt.sha256"], sha256_hex(b"2\n3\n5\n7\n").as_bytes());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn skips_only_files_with_the_same_content() {
+    let dir = temp_dir("webdav_resume");
+    let path = dir.join("primes_1.txt");
+    std::fs::write(&path, "2\n3\n5\n7\n").unwrap();
+    let (url, requests, _) = mock_server(Faults::default());
+    assert!(matches!(client(&url).upload(&path).unwrap(), Uploaded::Sent(_)));
+
+    // 同じ中身なら送り直さない（やり直した実行の新しいクライアントでも同じ）
+    let client = client(&url);
+    assert!(matches!(client.upload(&path).unwrap(), Uploaded::Skipped(_)));
+    // 大きさが同じでも、中身が違えば（前の別の実行の出力など）送り直す
+    std::fs::write(&path, "2\n3\n5\n9\n").unwrap();
+    assert!(matches!(client.upload(&path).unwrap(), Uploaded::Sent(_)));
+    // 大きさが違えば送り直す
+    std::fs::write(&path, "2\n3\n5\n7\n11\n").unwrap();
+    assert!(matches!(client.upload(&path).unwrap(), Uploaded::Sent(_)));
+
+    let puts = methods(&requests).iter().filter(|(m, t)| m == "PUT" && !t.ends_with(".sha256")).count();
+    assert_eq!(puts, 3);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn resumes_a_cut_upload_where_the_server_allows_it() {
+    let dir = temp_dir("webdav_partial");
+    let path = dir.join("primes_1.txt");
+    let content: String = (0..50_000).map(|i| format!("{}\n", 2 * i + 3)).collect();
+    std::fs::write(&path, &content).unwrap();
+
+    // Content-Range を受け付けるサーバーでは残りだけを送る
+    let (url, requests, files) = mock_server(Faults { cut_first: true, partial_put: true, ..Faults::default() });
+    assert!(matches!(client(&url).upload(&path).unwrap(), Uploaded::Sent(_)));
+    assert_eq!(files.lock().unwrap()["/dav/primes/primes_1.txt"], content.as_bytes());
+    let puts: Vec<String> = methods(&requests).into_iter().filter(|(m, t)| m.starts_with("PUT") && !t.ends_with(".sha256")).map(|(m, _)| m).collect();
+    assert_eq!(puts, vec!["PUT", "PUT range"]);
+
+    // 受け付けないサーバーでは先頭から送り直す
+    let (url, requests, files) = mock_server(Faults { cut_first: true, ..Faults::default() });
+    assert!(matches!(client(&url).upload(&path).unwrap(), Uploaded::Sent(_)));
+    assert_eq!(files.lock().unwrap()["/dav/primes/primes_1.txt"], content.as_bytes());
+    let puts: Vec<String> = methods(&requests).into_iter().filter(|(m, t)| m.starts_with("PUT") && !t.ends_with(".sha256")).map(|(m, _)| m).collect();
+    assert_eq!(puts, vec!["PUT", "PUT range", "PUT"]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
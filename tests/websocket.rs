@@ -0,0 +1,39 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::time::{Duration, Instant};
+use sosu_seisei_sieve::app::{ProgressPhase, WorkerMessage};
+use sosu_seisei_sieve::websocket::WsServer;
+
+#[test]
+fn streams_messages_and_prime_batches() {
+    let mut server = WsServer::start("127.0.0.1", 0).unwrap();
+    let (mut client, _) = tungstenite::connect(format!("ws://{}", server.local_addr())).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while server.client_count() == 0 {
+        assert!(Instant::now() < deadline, "client was not registered");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    server.forward(&WorkerMessage::Progress { current: 5, total: 10, phase: ProgressPhase::Sieving });
+    for (i, p) in [2u64, 3, 5].into_iter().enumerate() {
+        server.forward(&WorkerMessage::FoundPrimeIndex(p, i as u64 + 1));
+    }
+    // 素数以外のメッセージの前に溜まった素数を送る
+    server.forward(&WorkerMessage::Done);
+    server.forward(&WorkerMessage::FoundPrimeIndex(7, 4));
+    server.flush();
+
+    let mut received = Vec::new();
+    while received.len() < 4 {
+        received.push(client.read().unwrap().into_text().unwrap().to_string());
+    }
+    assert_eq!(received, vec![
+        r#"{"Progress":{"current":5,"total":10,"phase":"sieving"}}"#,
+        r#"{"Primes":[2,3,5]}"#,
+        r#""Done""#,
+        r#"{"Primes":[7]}"#,
+    ]);
+}
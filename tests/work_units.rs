@@ -0,0 +1,72 @@
+// Copyright (c) 2024 riragon
+//
+// This software is released under the MIT License.
+// See LICENSE file in the project root directory for more information.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use sosu_seisei_sieve::app::WorkerMessage;
+use sosu_seisei_sieve::config::{Config, OverwriteMode};
+use sosu_seisei_sieve::output::read_primes;
+use sosu_seisei_sieve::primes::Primes;
+use sosu_seisei_sieve::work_units::{export_work_units, process_work_units, stitch_results, PROJECT_FILE};
+
+fn stop_flag() -> Arc<AtomicBool> {
+    Arc::new(AtomicBool::new(false))
+}
+
+#[test]
+fn export_process_and_stitch() {
+    let dir = std::env::temp_dir().join(format!("sosu_seisei_test_work_units_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = Config {
+        prime_min: "1000".to_string(),
+        prime_max: "60000".to_string(),
+        output_dir: dir.display().to_string(),
+        overwrite_mode: OverwriteMode::Overwrite,
+        work_unit_size: 7_000,
+        segment_size: 1_000,
+        ..Config::default()
+    };
+    let (sender, _messages) = mpsc::channel();
+    export_work_units(config.clone(), sender.clone(), stop_flag()).unwrap();
+    let units_dir: PathBuf = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).find(|p| p.join(PROJECT_FILE).exists()).unwrap();
+    assert_eq!(fs::read_dir(&units_dir).unwrap().count(), 1 + 9);
+    process_work_units(config.clone(), units_dir.clone(), sender.clone(), stop_flag()).unwrap();
+
+    // 結果が欠けていれば結合しない
+    let last = units_dir.join("unit_000008.result.txt");
+    let saved = fs::read_to_string(&last).unwrap();
+    fs::remove_file(&last).unwrap();
+    let e = stitch_results(config.clone(), units_dir.clone(), sender.clone(), stop_flag()).unwrap_err();
+    assert!(e.to_string().contains("1 of 9 work units are missing: 8"), "{}", e);
+
+    // 範囲を書き換えると署名が合わない
+    fs::write(&last, saved.replacen("\"high\":60000", "\"high\":60001", 1)).unwrap();
+    let e = stitch_results(config.clone(), units_dir.clone(), sender.clone(), stop_flag()).unwrap_err();
+    assert!(e.to_string().contains("invalid signature for work unit 8"), "{}", e);
+
+    // 素数を抜くとハッシュが合わない
+    fs::write(&last, saved.replacen("59999\n", "", 1)).unwrap();
+    let e = stitch_results(config.clone(), units_dir.clone(), sender.clone(), stop_flag()).unwrap_err();
+    assert!(e.to_string().contains("do not match"), "{}", e);
+
+    fs::write(&last, &saved).unwrap();
+    let (sender, messages) = mpsc::channel();
+    stitch_results(config, units_dir, sender, stop_flag()).unwrap();
+    let files = messages
+        .try_iter()
+        .find_map(|m| match m {
+            WorkerMessage::Finished { files, .. } => Some(files),
+            _ => None,
+        })
+        .unwrap();
+    let mut written = Vec::new();
+    for file in &files {
+        read_primes(file, |p| written.push(p)).unwrap();
+    }
+    assert_eq!(written, Primes::new(1000, 60_000).collect::<Vec<_>>());
+    let _ = fs::remove_dir_all(&dir);
+}